@@ -0,0 +1,382 @@
+use crate::Message;
+use crate::Message::{AppError, DeviceViewEvent};
+use crate::device::DeviceEvent::RadioNotification;
+use crate::device::DeviceMessage::ConnectRequest;
+use crate::device::{DeviceEvent, DeviceIdentifier};
+use crate::device_list::RadioType;
+use crate::history::device_dir_name;
+use crate::timestamp::TimeStamp;
+use directories::ProjectDirs;
+use futures::SinkExt;
+use iced::Task;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::path::{Path, PathBuf};
+use tokio::fs::File;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, BufWriter, Lines};
+use tokio::time::{Duration, Instant, sleep_until};
+use tokio_stream::Stream;
+
+const CAPTURE_DIR: &str = "captures";
+const CAPTURE_EXTENSION: &str = "jsonl";
+
+/// The first line of a capture file, saying where the packets that follow it came from
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CaptureHeader {
+    pub radio_type: RadioType,
+    pub device: String,
+    pub started: TimeStamp,
+}
+
+/// A packet received from a radio, and when it was received, in ms since the capture started
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CaptureRecord<T> {
+    pub offset: u64,
+    pub packet: T,
+}
+
+/// A file that all the packets received from a radio are being recorded to, as JSON lines,
+/// so the session can be replayed later as if the radio was attached
+#[derive(Debug)]
+pub struct Capture {
+    writer: BufWriter<File>,
+    path: PathBuf,
+    started: TimeStamp,
+}
+
+/// Return the directory captures of radio traffic are stored in, if there is one
+fn capture_dir() -> Option<PathBuf> {
+    ProjectDirs::from("net", "Mackenzie Serres", "meshchat")
+        .map(|proj_dirs| proj_dirs.data_dir().join(CAPTURE_DIR))
+}
+
+impl Capture {
+    /// Create a new capture file in `dir` for the traffic from `device`, named so that it
+    /// doesn't clash with earlier captures from the same device
+    pub async fn create(
+        dir: &Path,
+        device: &DeviceIdentifier,
+        radio_type: RadioType,
+    ) -> io::Result<Self> {
+        tokio::fs::create_dir_all(dir).await?;
+        let file_name = format!(
+            "{}_{}.{CAPTURE_EXTENSION}",
+            device_dir_name(device),
+            chrono::Local::now().format("%Y%m%d-%H%M%S%.3f")
+        );
+        let path = dir.join(file_name);
+        let started = TimeStamp::now();
+
+        let mut capture = Capture {
+            writer: BufWriter::new(File::create(&path).await?),
+            path,
+            started,
+        };
+        capture
+            .write_line(&CaptureHeader {
+                radio_type,
+                device: String::from(device),
+                started,
+            })
+            .await?;
+
+        Ok(capture)
+    }
+
+    /// The path of the file being captured to
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Record a packet received from the radio now
+    pub async fn record<T: Serialize>(&mut self, packet: &T) -> io::Result<()> {
+        let offset = u64::from(TimeStamp::now()).saturating_sub(u64::from(self.started));
+        self.write_line(&CaptureRecord { offset, packet }).await
+    }
+
+    /// Write one JSON line and flush it, so nothing is lost if the app dies during a capture
+    async fn write_line<T: Serialize>(&mut self, value: &T) -> io::Result<()> {
+        let mut line = serde_json::to_vec(value).map_err(io::Error::other)?;
+        line.push(b'\n');
+        self.writer.write_all(&line).await?;
+        self.writer.flush().await
+    }
+}
+
+/// Use `start_capture` when connecting to `device` to start capturing its traffic to a new file
+/// in the app's data directory. The user is told where the file is, or why it couldn't be created.
+pub async fn start_capture(
+    device: &DeviceIdentifier,
+    radio_type: RadioType,
+    gui_sender: &mut futures_channel::mpsc::Sender<DeviceEvent>,
+) -> Option<Capture> {
+    let result = match capture_dir() {
+        Some(dir) => Capture::create(&dir, device, radio_type).await,
+        None => Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            "No data directory to capture to",
+        )),
+    };
+
+    let (capture, notification) = match result {
+        Ok(capture) => {
+            let notification = format!(
+                "Capturing radio traffic to '{}'",
+                capture.path().to_string_lossy()
+            );
+            (Some(capture), notification)
+        }
+        Err(e) => (None, format!("Could not capture radio traffic: {e}")),
+    };
+
+    gui_sender
+        .send(RadioNotification(notification, TimeStamp::now()))
+        .await
+        .unwrap_or_else(|e| eprintln!("Send error: {e}"));
+
+    capture
+}
+
+/// The lines of a capture file
+type CaptureLines = Lines<BufReader<File>>;
+
+/// Open the capture file at `path` to read its lines
+async fn open(path: &Path) -> io::Result<CaptureLines> {
+    Ok(BufReader::new(File::open(path).await?).lines())
+}
+
+/// Parse the [CaptureHeader] from the first of the `lines` of a capture file
+async fn parse_header(lines: &mut CaptureLines) -> io::Result<CaptureHeader> {
+    let header = lines
+        .next_line()
+        .await?
+        .ok_or_else(|| io::Error::new(io::ErrorKind::UnexpectedEof, "Empty capture file"))?;
+    serde_json::from_str(&header).map_err(io::Error::other)
+}
+
+/// Read the header of the capture file at `path`
+async fn load_header(path: &Path) -> io::Result<CaptureHeader> {
+    parse_header(&mut open(path).await?).await
+}
+
+/// Load the header and all the packets recorded in the capture file at `path`
+pub async fn load<T: DeserializeOwned>(
+    path: &Path,
+) -> io::Result<(CaptureHeader, Vec<CaptureRecord<T>>)> {
+    let mut lines = open(path).await?;
+    let header = parse_header(&mut lines).await?;
+
+    let mut records = vec![];
+    while let Some(line) = lines.next_line().await? {
+        if !line.trim().is_empty() {
+            records.push(serde_json::from_str(&line).map_err(io::Error::other)?);
+        }
+    }
+
+    Ok((header, records))
+}
+
+/// A stream of the packets in `records`, each one produced at the same time after the start of
+/// the stream as it was received after the start of the capture
+pub fn replay<T>(records: Vec<CaptureRecord<T>>) -> impl Stream<Item = T> {
+    async_stream::stream! {
+        let start = Instant::now();
+        for record in records {
+            sleep_until(start + Duration::from_millis(record.offset)).await;
+            yield record.packet;
+        }
+    }
+}
+
+/// Ask the user for a capture file to replay, and read its header to know what type of radio
+/// it was captured from
+async fn pick_capture() -> io::Result<Option<(PathBuf, CaptureHeader)>> {
+    let file_handle = rfd::AsyncFileDialog::new()
+        .set_title("Replay Capture")
+        .set_directory(capture_dir().unwrap_or_default())
+        .add_filter("Capture", &[CAPTURE_EXTENSION])
+        .pick_file()
+        .await;
+
+    match file_handle {
+        Some(file_handle) => {
+            let path = file_handle.path().to_path_buf();
+            let header = load_header(&path).await?;
+            Ok(Some((path, header)))
+        }
+        None => Ok(None),
+    }
+}
+
+/// Use `replay_capture` to let the user choose a capture file, then replay it as if the radio
+/// it was captured from was attached
+pub fn replay_capture() -> Task<Message> {
+    Task::perform(pick_capture(), |result| match result {
+        Ok(Some((path, header))) => DeviceViewEvent(ConnectRequest(
+            DeviceIdentifier::Replay {
+                path: path.to_string_lossy().to_string(),
+            },
+            header.radio_type,
+            None,
+        )),
+        Ok(None) => Message::None,
+        Err(e) => AppError(
+            "Error opening capture file".to_string(),
+            e.to_string(),
+            TimeStamp::now(),
+        ),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio_stream::StreamExt;
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    struct TestPacket {
+        id: u32,
+        text: String,
+    }
+
+    fn test_device() -> DeviceIdentifier {
+        DeviceIdentifier::from("AA:BB:CC:DD:EE:FF")
+    }
+
+    fn tempdir() -> tempfile::TempDir {
+        tempfile::Builder::new()
+            .prefix("meshchat")
+            .tempdir()
+            .expect("Could not create a temp dir")
+    }
+
+    #[tokio::test]
+    async fn capture_file_is_in_dir() {
+        let tempdir = tempdir();
+        let capture = Capture::create(tempdir.path(), &test_device(), RadioType::default())
+            .await
+            .expect("Could not create capture");
+        assert_eq!(capture.path().parent(), Some(tempdir.path()));
+        assert_eq!(
+            capture.path().extension().and_then(|ext| ext.to_str()),
+            Some(CAPTURE_EXTENSION)
+        );
+        assert!(capture.path().exists());
+    }
+
+    #[tokio::test]
+    async fn capture_and_load_roundtrip() {
+        let tempdir = tempdir();
+        let mut capture = Capture::create(tempdir.path(), &test_device(), RadioType::default())
+            .await
+            .expect("Could not create capture");
+        let packets = vec![
+            TestPacket {
+                id: 1,
+                text: "Hello".to_string(),
+            },
+            TestPacket {
+                id: 2,
+                text: "World".to_string(),
+            },
+        ];
+        for packet in &packets {
+            capture
+                .record(packet)
+                .await
+                .expect("Could not record packet");
+        }
+
+        let (header, records) = load::<TestPacket>(capture.path())
+            .await
+            .expect("Could not load capture");
+        assert_eq!(header.radio_type, RadioType::default());
+        assert_eq!(header.device, String::from(&test_device()));
+        assert_eq!(header.started, capture.started);
+        assert_eq!(
+            records
+                .iter()
+                .map(|record| record.packet.clone())
+                .collect::<Vec<_>>(),
+            packets
+        );
+        assert!(records[0].offset <= records[1].offset);
+    }
+
+    #[tokio::test]
+    async fn load_header_only() {
+        let tempdir = tempdir();
+        let capture = Capture::create(tempdir.path(), &test_device(), RadioType::default())
+            .await
+            .expect("Could not create capture");
+        let header = load_header(capture.path())
+            .await
+            .expect("Could not load header");
+        assert_eq!(header.device, String::from(&test_device()));
+
+        let (_, records) = load::<TestPacket>(capture.path())
+            .await
+            .expect("Could not load capture");
+        assert!(records.is_empty());
+    }
+
+    #[tokio::test]
+    async fn load_empty_file_errors() {
+        let tempdir = tempdir();
+        let path = tempdir.path().join("empty.jsonl");
+        File::create(&path).await.expect("Could not create file");
+        assert!(load::<TestPacket>(&path).await.is_err());
+        assert!(load_header(&path).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn load_corrupt_record_errors() {
+        let tempdir = tempdir();
+        let capture = Capture::create(tempdir.path(), &test_device(), RadioType::default())
+            .await
+            .expect("Could not create capture");
+        let mut file = tokio::fs::OpenOptions::new()
+            .append(true)
+            .open(capture.path())
+            .await
+            .expect("Could not open capture");
+        file.write_all(b"{ not json\n")
+            .await
+            .expect("Could not write capture");
+        assert!(load::<TestPacket>(capture.path()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn load_missing_file_errors() {
+        let tempdir = tempdir();
+        assert!(
+            load::<TestPacket>(&tempdir.path().join("missing.jsonl"))
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn replay_in_order_and_on_time() {
+        let records = vec![
+            CaptureRecord {
+                offset: 0,
+                packet: 1u32,
+            },
+            CaptureRecord {
+                offset: 20,
+                packet: 2u32,
+            },
+            CaptureRecord {
+                offset: 40,
+                packet: 3u32,
+            },
+        ];
+
+        let start = Instant::now();
+        let packets: Vec<u32> = replay(records).collect().await;
+        assert_eq!(packets, vec![1, 2, 3]);
+        assert!(start.elapsed() >= Duration::from_millis(40));
+    }
+}
@@ -0,0 +1,202 @@
+use crate::Message;
+use crate::Message::{CopyToClipBoard, DeviceViewEvent};
+use crate::device::DeviceMessage::{ChannelUrlInput, WriteChannels};
+use crate::meshchat::{MCChannelSet, MCChannelSettings};
+#[cfg(feature = "meshtastic")]
+use crate::mesht::channel_url::{channel_set_url, channels_in_url};
+use crate::styles::{
+    COLOR_RED, TIME_TEXT_COLOR, TIME_TEXT_SIZE, button_chip_style, text_input_style,
+};
+use crate::widgets::dialog::dialog;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use iced::widget::{Column, Row, Space, button, text, text_input};
+use iced::{Center, Element, Fill};
+
+/// Channel URLs are a Meshtastic format, so without it there is nothing to share
+#[cfg(not(feature = "meshtastic"))]
+fn channel_set_url(_channels: &[MCChannelSettings]) -> String {
+    String::new()
+}
+
+/// Channel URLs are a Meshtastic format, so without it none can be read
+#[cfg(not(feature = "meshtastic"))]
+fn channels_in_url(_url: &str, _in_use: usize) -> Result<MCChannelSet, String> {
+    Err("Channel URLs are only supported on Meshtastic radios".into())
+}
+
+/// A description of the kind of pre-shared key `psk` is
+pub fn psk_description(psk: &[u8]) -> String {
+    match psk {
+        [] | [0] => "No encryption".into(),
+        [1] => "Default key".into(),
+        [simple] => format!("Default key (simple {})", simple.saturating_sub(1)),
+        key if key.len() == 16 => "AES-128 key".into(),
+        key if key.len() == 32 => "AES-256 key".into(),
+        _ => "Invalid key".into(),
+    }
+}
+
+/// A dialog to share the channels on the radio as a URL, and to join the channels in a URL
+/// shared by someone else, replacing the ones on the radio or adding to them
+#[derive(Debug, Default)]
+pub struct ChannelSharing {
+    url_input: String,
+}
+
+impl ChannelSharing {
+    /// Set the URL of the channels to join, as entered by the user
+    pub fn set_url(&mut self, url: String) {
+        self.url_input = url;
+    }
+
+    /// The channels in the URL entered, or why they can't be joined on a radio with `in_use`
+    /// channels. None if no URL was entered.
+    pub fn channels(&self, in_use: usize) -> Option<Result<MCChannelSet, String>> {
+        (!self.url_input.trim().is_empty()).then(|| channels_in_url(&self.url_input, in_use))
+    }
+
+    /// The dialog, with the URL that shares the radio's `current` channels, primary first
+    pub fn view<'a>(&self, current: &[MCChannelSettings]) -> Element<'a, Message> {
+        let mut share = Column::new().spacing(8).push(text("Share").size(16));
+        if current.is_empty() {
+            share = share
+                .push(text("The channels on the radio are not known yet").color(TIME_TEXT_COLOR));
+        } else {
+            let url = channel_set_url(current);
+            share = share.push(Self::channel_list(current, false)).push(
+                Row::new()
+                    .spacing(8)
+                    .align_y(Center)
+                    .push(text(url.clone()).size(TIME_TEXT_SIZE).width(Fill))
+                    .push(
+                        button(text("Copy"))
+                            .style(button_chip_style)
+                            .on_press(CopyToClipBoard(url)),
+                    ),
+            );
+        }
+
+        let mut write_button = button(text("Write to radio")).style(button_chip_style);
+        let mut join = Column::new().spacing(8).push(text("Join").size(16)).push(
+            text_input("https://meshtastic.org/e/#…", &self.url_input)
+                .on_input(|url| DeviceViewEvent(ChannelUrlInput(url)))
+                .style(text_input_style),
+        );
+        match self.channels(current.len()) {
+            None => {}
+            Some(Ok(channel_set)) => {
+                write_button = write_button.on_press(DeviceViewEvent(WriteChannels));
+                let effect = if channel_set.add {
+                    text("These are added to the channels on the radio").color(TIME_TEXT_COLOR)
+                } else {
+                    text("This replaces all the channels on the radio").color(COLOR_RED)
+                };
+                join = join
+                    .push(Self::channel_list(&channel_set.channels, channel_set.add))
+                    .push(effect);
+            }
+            Some(Err(problem)) => join = join.push(text(problem).color(COLOR_RED)),
+        }
+        join = join.push(Row::new().push(Space::new().width(Fill)).push(write_button));
+
+        dialog(
+            "Channels".into(),
+            Column::new().padding(8).spacing(16).push(share).push(join),
+        )
+    }
+
+    /// The channels, one per row, with their role, name and key. The first is the primary
+    /// channel, unless they are all being `added` as secondary channels.
+    fn channel_list<'a>(channels: &[MCChannelSettings], added: bool) -> Element<'a, Message> {
+        channels
+            .iter()
+            .enumerate()
+            .fold(Column::new().spacing(4), |column, (index, channel)| {
+                let role = if index == 0 && !added {
+                    "Primary"
+                } else {
+                    "Secondary"
+                };
+                let name = if channel.name.is_empty() {
+                    "Default"
+                } else {
+                    &channel.name
+                };
+                column.push(
+                    Column::new()
+                        .push(text(format!("{index}: {name} ({role})")))
+                        .push(
+                            text(format!(
+                                "{} {}",
+                                psk_description(&channel.psk),
+                                STANDARD.encode(&channel.psk)
+                            ))
+                            .size(TIME_TEXT_SIZE)
+                            .color(TIME_TEXT_COLOR),
+                        ),
+                )
+            })
+            .into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_psk_description() {
+        assert_eq!(psk_description(&[]), "No encryption");
+        assert_eq!(psk_description(&[0]), "No encryption");
+        assert_eq!(psk_description(&[1]), "Default key");
+        assert_eq!(psk_description(&[3]), "Default key (simple 2)");
+        assert_eq!(psk_description(&[7; 16]), "AES-128 key");
+        assert_eq!(psk_description(&[7; 32]), "AES-256 key");
+        assert_eq!(psk_description(&[7; 5]), "Invalid key");
+    }
+
+    #[test]
+    fn test_no_url_entered() {
+        let mut sharing = ChannelSharing::default();
+        assert!(sharing.channels(1).is_none());
+        sharing.set_url("  ".into());
+        assert!(sharing.channels(1).is_none());
+    }
+
+    #[test]
+    fn test_bad_url_entered() {
+        let mut sharing = ChannelSharing::default();
+        sharing.set_url("https://example.com".into());
+        assert!(matches!(sharing.channels(1), Some(Err(_))));
+    }
+
+    #[cfg(feature = "meshtastic")]
+    #[test]
+    fn test_url_entered() {
+        let mut sharing = ChannelSharing::default();
+        sharing.set_url("https://meshtastic.org/e/#CgMSAQESCDgBQANIAVAe".into());
+        let channel_set = sharing.channels(1).and_then(Result::ok).expect("Channels");
+        assert_eq!(channel_set.channels.len(), 1);
+        assert_eq!(channel_set.channels[0].psk, vec![1]);
+        assert!(!channel_set.add);
+    }
+
+    #[test]
+    fn test_view() {
+        let mut sharing = ChannelSharing::default();
+        let _empty = sharing.view(&[]);
+        let current = vec![MCChannelSettings {
+            name: "Team".into(),
+            psk: vec![1],
+            ..Default::default()
+        }];
+        let _current = sharing.view(&current);
+        sharing.set_url("not a url".into());
+        let _problem = sharing.view(&current);
+        sharing.set_url("https://meshtastic.org/e/#CgMSAQESCDgBQANIAVAe".into());
+        let _preview = sharing.view(&current);
+        sharing.set_url("https://meshtastic.org/e/?add=true#CgMSAQESCDgBQANIAVAe".into());
+        let _added = sharing.view(&current);
+    }
+}
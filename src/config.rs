@@ -1,17 +1,22 @@
 use crate::Message;
 use crate::Message::{
-    HistoryLengthSelected, ToggleAutoReconnect, ToggleAutoUpdate, ToggleSaveWindowPosition,
-    ToggleSaveWindowSize, ToggleShowPositionUpdates, ToggleShowUserUpdates,
+    ChooseMapTilesFile, ChooseMapTilesFolder, HistoryLengthSelected, MapTilesSelected,
+    OfflineAfterSelected, OnlineWithinSelected, ToggleAutoReconnect, ToggleAutoResend,
+    ToggleAutoUpdate, ToggleCaptureTraffic, ToggleSaveWindowPosition, ToggleSaveWindowSize,
+    ToggleShowPositionUpdates, ToggleShowUserUpdates, UnitsSelected,
 };
 use crate::conversation_id::{ConversationId, NodeId};
+use crate::device::DeviceIdentifier;
 use crate::device_list::RadioType;
-use crate::styles::{picker_header_style, tooltip_style};
+use crate::styles::{
+    TIME_TEXT_COLOR, TIME_TEXT_SIZE, button_chip_style, picker_header_style, tooltip_style,
+};
 use crate::timestamp::TimeStamp;
 use directories::ProjectDirs;
 use iced::font::Weight;
-use iced::widget::{Column, container, pick_list, text, toggler};
+use iced::widget::{Column, Row, button, container, pick_list, text, toggler};
 use iced::{Center, Element, Fill, Font, Point, Size, Task};
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::collections::{HashMap, HashSet};
 use std::io;
 use std::path::PathBuf;
@@ -36,6 +41,9 @@ pub struct Config {
     pub aliases: HashMap<NodeId, String>, // node name aliases
     #[serde(default = "HashMap::new", skip_serializing_if = "HashMap::is_empty")]
     pub device_aliases: HashMap<String, String>, // device (as a string) to alias
+    /// Devices the user added by hand, as they are not discovered, with their radio type
+    #[serde(default = "HashMap::new", skip_serializing_if = "HashMap::is_empty")]
+    pub manual_devices: HashMap<String, RadioType>, // device (as a string) to radio type
     #[serde(
         default = "HistoryLength::default",
         skip_serializing_if = "HistoryLength::is_all"
@@ -60,6 +68,165 @@ pub struct Config {
     pub restore_window_size: bool,
     #[serde(default)]
     pub window_size: Option<WindowSize>,
+    /// Whether all the traffic from a radio is captured to a file, to be replayed later
+    #[serde(default)]
+    pub capture_traffic: bool,
+    /// Whether a message that is not acknowledged is sent again automatically, once
+    #[serde(default)]
+    pub auto_resend: bool,
+    /// How each conversation on each device (as a string) counts unread messages and notifies,
+    /// where not the default. Conversation ids are only unique on one device.
+    #[serde(
+        default = "HashMap::new",
+        skip_serializing_if = "HashMap::is_empty",
+        serialize_with = "save_conversation_settings",
+        deserialize_with = "load_conversation_settings"
+    )]
+    pub conversation_settings: HashMap<String, HashMap<ConversationId, ConversationSettings>>,
+    /// The order nodes are listed in
+    #[serde(default)]
+    pub node_sort: NodeSort,
+    /// A node last heard within this time is shown as online
+    #[serde(default = "default_online_within")]
+    pub online_within: HeardWithin,
+    /// A node not heard for this long is shown as offline, and as stale before that
+    #[serde(default = "default_offline_after")]
+    pub offline_after: HeardWithin,
+    /// The units distances to nodes are shown in
+    #[serde(default)]
+    pub units: Units,
+    /// The MBTiles file, or directory of tiles, the map of nodes is drawn on
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub map_tiles: Option<PathBuf>,
+}
+
+/// The order nodes are listed in
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum NodeSort {
+    /// Favourite nodes listed apart, before the others, each by name
+    #[default]
+    FavouritesFirst,
+    /// The most recently heard first
+    LastHeard,
+    Name,
+    /// The closest to my position first
+    Distance,
+    /// The fewest hops away first
+    HopsAway,
+}
+
+impl NodeSort {
+    pub const ALL: [NodeSort; 5] = [
+        NodeSort::FavouritesFirst,
+        NodeSort::LastHeard,
+        NodeSort::Name,
+        NodeSort::Distance,
+        NodeSort::HopsAway,
+    ];
+}
+
+impl std::fmt::Display for NodeSort {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NodeSort::FavouritesFirst => write!(f, "Favourites first"),
+            NodeSort::LastHeard => write!(f, "Last heard"),
+            NodeSort::Name => write!(f, "Name"),
+            NodeSort::Distance => write!(f, "Distance"),
+            NodeSort::HopsAway => write!(f, "Hops away"),
+        }
+    }
+}
+
+/// A time since a node was last heard, in minutes, used to tell if it is online
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+pub struct HeardWithin(u64);
+
+impl HeardWithin {
+    pub const ALL: [HeardWithin; 8] = [
+        HeardWithin(15),
+        HeardWithin(30),
+        HeardWithin(60),
+        HeardWithin(2 * 60),
+        HeardWithin(6 * 60),
+        HeardWithin(12 * 60),
+        HeardWithin(24 * 60),
+        HeardWithin(3 * 24 * 60),
+    ];
+
+    pub fn duration(&self) -> Duration {
+        Duration::from_secs(self.0.saturating_mul(60))
+    }
+}
+
+impl std::fmt::Display for HeardWithin {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.0 {
+            minutes if minutes < 60 => write!(f, "{minutes} minutes"),
+            60 => write!(f, "1 hour"),
+            minutes if minutes < 24 * 60 => write!(f, "{} hours", minutes / 60),
+            minutes if minutes == 24 * 60 => write!(f, "1 day"),
+            minutes => write!(f, "{} days", minutes / (24 * 60)),
+        }
+    }
+}
+
+/// The units distances are shown in
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Units {
+    /// Metres and kilometres
+    #[default]
+    Metric,
+    /// Feet and miles
+    Imperial,
+}
+
+impl Units {
+    pub const ALL: [Units; 2] = [Units::Metric, Units::Imperial];
+}
+
+impl std::fmt::Display for Units {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Units::Metric => write!(f, "Metric (km)"),
+            Units::Imperial => write!(f, "Imperial (mi)"),
+        }
+    }
+}
+
+/// How a conversation counts unread messages and notifies the user of new ones
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct ConversationSettings {
+    /// Don't count unread messages or notify of them at all
+    #[serde(default)]
+    pub muted: bool,
+    /// Whether unread messages count toward the device's unread messages and the window title
+    #[serde(default = "default_count_unread")]
+    pub count_unread: bool,
+    /// Only count unread messages that mention my node, and notify of them
+    #[serde(default)]
+    pub mentions_only: bool,
+}
+
+impl Default for ConversationSettings {
+    fn default() -> Self {
+        Self {
+            muted: false,
+            count_unread: default_count_unread(),
+            mentions_only: false,
+        }
+    }
+}
+
+/// The settings of one conversation as saved, as TOML only allows strings as table keys.
+/// Settings saved before they were kept per device have no device.
+#[derive(Serialize, Deserialize)]
+struct SavedConversationSettings {
+    #[serde(default)]
+    device: String,
+    conversation: ConversationId,
+    settings: ConversationSettings,
 }
 
 /// Struct we will use to serialize and deserialize window position
@@ -163,6 +330,7 @@ impl Default for Config {
             fav_nodes: HashSet::new(),
             aliases: HashMap::new(),
             device_aliases: HashMap::new(),
+            manual_devices: HashMap::new(),
             history_length: HistoryLength::default(),
             show_position_updates: default_show_position(),
             show_user_updates: default_show_user(),
@@ -172,11 +340,57 @@ impl Default for Config {
             window_position: None,
             restore_window_size: false,
             window_size: None,
+            capture_traffic: false,
+            auto_resend: false,
+            conversation_settings: HashMap::new(),
+            node_sort: NodeSort::default(),
+            online_within: default_online_within(),
+            offline_after: default_offline_after(),
+            units: Units::default(),
+            map_tiles: None,
         }
     }
 }
 
 impl Config {
+    /// The settings of a conversation on `device`, the default settings if they haven't been
+    /// changed
+    pub fn conversation_settings(
+        &self,
+        device: &DeviceIdentifier,
+        conversation_id: &ConversationId,
+    ) -> ConversationSettings {
+        self.conversation_settings
+            .get(&String::from(device))
+            .and_then(|conversations| conversations.get(conversation_id))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Change the settings of a conversation on `device`, only keeping them if they are not the
+    /// default
+    pub fn set_conversation_settings(
+        &mut self,
+        device: &DeviceIdentifier,
+        conversation_id: ConversationId,
+        settings: ConversationSettings,
+    ) {
+        let device = String::from(device);
+        if settings == ConversationSettings::default() {
+            if let Some(conversations) = self.conversation_settings.get_mut(&device) {
+                conversations.remove(&conversation_id);
+                if conversations.is_empty() {
+                    self.conversation_settings.remove(&device);
+                }
+            }
+        } else {
+            self.conversation_settings
+                .entry(device)
+                .or_default()
+                .insert(conversation_id, settings);
+        }
+    }
+
     /// Use `save_config` to save the config to disk from the UI
     pub fn save_config(&self) -> Task<Message> {
         if let Some(proj_dirs) = ProjectDirs::from("net", "Mackenzie Serres", "meshchat") {
@@ -208,10 +422,15 @@ impl Config {
             .push(self.show_position_in_chat_setting())
             .push(self.show_user_updates())
             .push(self.auto_reconnect())
+            .push(self.auto_resend())
             .push(self.history_length())
+            .push(self.node_status_thresholds())
+            .push(self.distance_units())
+            .push(self.map_tiles())
             .push(self.auto_update())
             .push(self.save_window_position())
-            .push(self.save_window_size());
+            .push(self.save_window_size())
+            .push(self.capture_traffic());
 
         let inner = Column::new()
             .spacing(8)
@@ -267,9 +486,83 @@ impl Config {
         .into()
     }
 
+    /// Settings view to modify how long since a node was heard it is shown online, or offline
+    fn node_status_thresholds<'a>(&self) -> Element<'a, Message> {
+        Column::new()
+            .spacing(4)
+            .push(
+                Row::new()
+                    .spacing(8)
+                    .align_y(Center)
+                    .push(text("Node is online if heard within").width(Fill))
+                    .push(pick_list(
+                        HeardWithin::ALL,
+                        Some(self.online_within),
+                        OnlineWithinSelected,
+                    )),
+            )
+            .push(
+                Row::new()
+                    .spacing(8)
+                    .align_y(Center)
+                    .push(text("Node is offline if not heard for").width(Fill))
+                    .push(pick_list(
+                        HeardWithin::ALL,
+                        Some(self.offline_after),
+                        OfflineAfterSelected,
+                    )),
+            )
+            .into()
+    }
+
+    fn distance_units<'a>(&self) -> Element<'a, Message> {
+        Row::new()
+            .spacing(8)
+            .align_y(Center)
+            .push(text("Show distances in").width(Fill))
+            .push(pick_list(Units::ALL, Some(self.units), UnitsSelected))
+            .into()
+    }
+
+    fn map_tiles<'a>(&self) -> Element<'a, Message> {
+        let chosen = self
+            .map_tiles
+            .as_ref()
+            .map_or("None, the map is a plain grid".to_string(), |path| {
+                path.display().to_string()
+            });
+        let mut row = Row::new()
+            .spacing(8)
+            .align_y(Center)
+            .push(
+                Column::new()
+                    .width(Fill)
+                    .push(text("Map tiles"))
+                    .push(text(chosen).size(TIME_TEXT_SIZE).color(TIME_TEXT_COLOR)),
+            )
+            .push(
+                button(text("MBTiles…"))
+                    .style(button_chip_style)
+                    .on_press(ChooseMapTilesFile),
+            )
+            .push(
+                button(text("Folder…"))
+                    .style(button_chip_style)
+                    .on_press(ChooseMapTilesFolder),
+            );
+        if self.map_tiles.is_some() {
+            row = row.push(
+                button(text("✕"))
+                    .style(button_chip_style)
+                    .on_press(MapTilesSelected(None)),
+            );
+        }
+        row.into()
+    }
+
     fn auto_reconnect<'a>(&self) -> Element<'a, Message> {
         toggler(self.auto_reconnect)
-            .label("Auto-reconnect at startup")
+            .label("Auto-reconnect at startup and when the connection drops")
             .on_toggle(Self::toggle_auto_reconnects)
             .into()
     }
@@ -299,6 +592,28 @@ impl Config {
             .into()
     }
 
+    fn auto_resend<'a>(&self) -> Element<'a, Message> {
+        toggler(self.auto_resend)
+            .label("Send messages that are not acknowledged again, once")
+            .on_toggle(Self::toggle_auto_resend)
+            .into()
+    }
+
+    fn toggle_auto_resend(_current_setting: bool) -> Message {
+        ToggleAutoResend
+    }
+
+    fn capture_traffic<'a>(&self) -> Element<'a, Message> {
+        toggler(self.capture_traffic)
+            .label("Capture radio traffic to a file, for bug reports")
+            .on_toggle(Self::toggle_capture_traffic)
+            .into()
+    }
+
+    fn toggle_capture_traffic(_current_setting: bool) -> Message {
+        ToggleCaptureTraffic
+    }
+
     fn toggle_save_window_position(_current_setting: bool) -> Message {
         ToggleSaveWindowPosition
     }
@@ -336,6 +651,60 @@ fn default_auto_reconnect() -> bool {
     true
 }
 
+/// If the online_within setting is missing in the config file, then default to two hours,
+/// as the Meshtastic apps do
+fn default_online_within() -> HeardWithin {
+    HeardWithin(2 * 60)
+}
+
+/// If the offline_after setting is missing in the config file, then default to one day
+fn default_offline_after() -> HeardWithin {
+    HeardWithin(24 * 60)
+}
+
+/// If the count_unread setting of a conversation is missing in the config file, then default
+/// to true, so its unread messages are counted
+fn default_count_unread() -> bool {
+    true
+}
+
+/// Save the settings of conversations as a list, as their ids can't be TOML table keys
+fn save_conversation_settings<S: Serializer>(
+    conversation_settings: &HashMap<String, HashMap<ConversationId, ConversationSettings>>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(
+        conversation_settings
+            .iter()
+            .flat_map(|(device, conversations)| {
+                conversations
+                    .iter()
+                    .map(|(conversation, settings)| SavedConversationSettings {
+                        device: device.clone(),
+                        conversation: *conversation,
+                        settings: *settings,
+                    })
+            }),
+    )
+}
+
+/// Load the list of the settings of conversations saved by [save_conversation_settings].
+/// Settings saved without a device can't be known to be for the right one, so are dropped.
+fn load_conversation_settings<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<HashMap<String, HashMap<ConversationId, ConversationSettings>>, D::Error> {
+    let saved = Vec::<SavedConversationSettings>::deserialize(deserializer)?;
+    let mut conversation_settings: HashMap<String, HashMap<ConversationId, ConversationSettings>> =
+        HashMap::new();
+    for saved in saved.into_iter().filter(|saved| !saved.device.is_empty()) {
+        conversation_settings
+            .entry(saved.device)
+            .or_default()
+            .insert(saved.conversation, saved.settings);
+    }
+    Ok(conversation_settings)
+}
+
 // Private methods for async reading and writing of config files
 async fn load(config_path: PathBuf) -> io::Result<Config> {
     let config_str = tokio::fs::read_to_string(config_path).await?;
@@ -402,7 +771,8 @@ pub fn load_config() -> Task<Message> {
 
 #[cfg(test)]
 mod tests {
-    use crate::config::{Config, HistoryLength, ONE_DAY_IN_SECONDS, load, save};
+    use crate::config::{Config, HistoryLength, ONE_DAY_IN_SECONDS, Units, load, save};
+    use crate::device::DeviceIdentifier;
     #[cfg(feature = "bluetooth")]
     use btleplug::api::BDAddr;
     use std::collections::{HashMap, HashSet};
@@ -417,6 +787,7 @@ mod tests {
         assert!(config.fav_nodes.is_empty());
         assert!(config.aliases.is_empty());
         assert!(config.device_aliases.is_empty());
+        assert!(config.manual_devices.is_empty());
     }
 
     #[test]
@@ -483,6 +854,33 @@ mod tests {
         );
     }
 
+    #[cfg(feature = "serial")]
+    #[tokio::test]
+    async fn serial_device_saved() {
+        use crate::device::DeviceIdentifier;
+
+        let device = DeviceIdentifier::Serial {
+            path: "/dev/ttyUSB0".to_string(),
+            baud: 115_200,
+        };
+        let config = Config {
+            ble_device: Some((String::from(&device), RadioType::Meshtastic)),
+            ..Default::default()
+        };
+
+        let tempfile = tempfile::Builder::new()
+            .prefix("meshchat")
+            .tempdir()
+            .expect("Could not create a temp file for test");
+        save(tempfile.path().join("config.toml"), config)
+            .await
+            .expect("Could not save config file");
+        let returned = load(tempfile.path().join("config.toml"))
+            .await
+            .expect("Could not load config file");
+        let (device_string, _) = returned.ble_device.expect("Serial device not saved");
+        assert_eq!(DeviceIdentifier::from(device_string), device);
+    }
     #[tokio::test]
     async fn history_length_default_saved() {
         let config = Config {
@@ -773,6 +1171,37 @@ mod tests {
         );
     }
 
+    #[cfg(feature = "tcp")]
+    #[tokio::test]
+    async fn test_manual_devices_saved() {
+        let mut manual_devices = HashMap::new();
+        manual_devices.insert("tcp://10.0.0.5:4403".to_string(), RadioType::default());
+
+        let config = Config {
+            manual_devices,
+            ..Default::default()
+        };
+
+        let tempfile = tempfile::Builder::new()
+            .prefix("meshchat")
+            .tempdir()
+            .expect("Could not create a temp file for test");
+
+        save(tempfile.path().join("config.toml"), config.clone())
+            .await
+            .expect("Could not save config file");
+
+        let returned = load(tempfile.path().join("config.toml"))
+            .await
+            .expect("Could not load config file");
+
+        assert_eq!(returned, config);
+        assert_eq!(
+            returned.manual_devices.get("tcp://10.0.0.5:4403"),
+            Some(&RadioType::default())
+        );
+    }
+
     #[tokio::test]
     async fn test_show_position_updates_saved() {
         let config = Config {
@@ -819,6 +1248,52 @@ mod tests {
         assert!(!returned.show_user_updates);
     }
 
+    #[tokio::test]
+    async fn test_auto_resend_saved() {
+        let config = Config {
+            auto_resend: true,
+            ..Default::default()
+        };
+
+        let tempfile = tempfile::Builder::new()
+            .prefix("meshchat")
+            .tempdir()
+            .expect("Could not create a temp file for test");
+
+        save(tempfile.path().join("config.toml"), config.clone())
+            .await
+            .expect("Could not save config file");
+
+        let returned = load(tempfile.path().join("config.toml"))
+            .await
+            .expect("Could not load config file");
+
+        assert!(returned.auto_resend);
+    }
+
+    #[tokio::test]
+    async fn test_capture_traffic_saved() {
+        let config = Config {
+            capture_traffic: true,
+            ..Default::default()
+        };
+
+        let tempfile = tempfile::Builder::new()
+            .prefix("meshchat")
+            .tempdir()
+            .expect("Could not create a temp file for test");
+
+        save(tempfile.path().join("config.toml"), config.clone())
+            .await
+            .expect("Could not save config file");
+
+        let returned = load(tempfile.path().join("config.toml"))
+            .await
+            .expect("Could not load config file");
+
+        assert!(returned.capture_traffic);
+    }
+
     #[tokio::test]
     async fn test_disable_auto_reconnect_saved() {
         let config = Config {
@@ -1261,6 +1736,21 @@ mod tests {
         assert!(matches!(msg, crate::Message::ToggleAutoUpdate));
     }
 
+    #[test]
+    fn test_toggle_auto_resend() {
+        let msg = Config::toggle_auto_resend(true);
+        assert!(matches!(msg, crate::Message::ToggleAutoResend));
+    }
+
+    #[test]
+    fn test_toggle_capture_traffic() {
+        let msg = Config::toggle_capture_traffic(true);
+        assert!(matches!(msg, crate::Message::ToggleCaptureTraffic));
+
+        let msg = Config::toggle_capture_traffic(false);
+        assert!(matches!(msg, crate::Message::ToggleCaptureTraffic));
+    }
+
     #[test]
     fn test_toggle_save_window_position() {
         let msg = Config::toggle_save_window_position(true);
@@ -1278,4 +1768,134 @@ mod tests {
         let msg = Config::toggle_save_window_size(false);
         assert!(matches!(msg, crate::Message::ToggleSaveWindowSize));
     }
+
+    #[tokio::test]
+    async fn test_conversation_settings_saved() {
+        use crate::config::ConversationSettings;
+        use crate::conversation_id::ConversationId;
+
+        let mut config = Config::default();
+        let device: DeviceIdentifier = "device1".into();
+        let muted = ConversationSettings {
+            muted: true,
+            ..Default::default()
+        };
+        let mentions = ConversationSettings {
+            count_unread: false,
+            mentions_only: true,
+            ..Default::default()
+        };
+        config.set_conversation_settings(&device, ConversationId::Channel(0.into()), muted);
+        config.set_conversation_settings(
+            &device,
+            ConversationId::Node(NodeId::from(42u64)),
+            mentions,
+        );
+
+        let tempfile = tempfile::Builder::new()
+            .prefix("meshchat")
+            .tempdir()
+            .expect("Could not create a temp file for test");
+        save(tempfile.path().join("config.toml"), config.clone())
+            .await
+            .expect("Could not save config file");
+        let returned = load(tempfile.path().join("config.toml"))
+            .await
+            .expect("Could not load config file");
+
+        assert_eq!(returned.conversation_settings, config.conversation_settings);
+        assert_eq!(
+            returned.conversation_settings(&device, &ConversationId::Channel(0.into())),
+            muted
+        );
+    }
+
+    #[test]
+    fn test_conversation_settings_default() {
+        use crate::config::ConversationSettings;
+        use crate::conversation_id::ConversationId;
+
+        let mut config = Config::default();
+        let device: DeviceIdentifier = "device1".into();
+        let conversation_id = ConversationId::Channel(1.into());
+        let settings = config.conversation_settings(&device, &conversation_id);
+        assert!(!settings.muted);
+        assert!(settings.count_unread);
+        assert!(!settings.mentions_only);
+
+        // Going back to the default settings forgets them
+        config.set_conversation_settings(
+            &device,
+            conversation_id,
+            ConversationSettings {
+                muted: true,
+                ..Default::default()
+            },
+        );
+        assert_eq!(config.conversation_settings.len(), 1);
+        config.set_conversation_settings(&device, conversation_id, ConversationSettings::default());
+        assert!(config.conversation_settings.is_empty());
+    }
+
+    #[test]
+    fn test_conversation_settings_per_device() {
+        use crate::config::ConversationSettings;
+        use crate::conversation_id::ConversationId;
+
+        let mut config = Config::default();
+        let device1: DeviceIdentifier = "device1".into();
+        let device2: DeviceIdentifier = "device2".into();
+        let conversation_id = ConversationId::Channel(0.into());
+        config.set_conversation_settings(
+            &device1,
+            conversation_id,
+            ConversationSettings {
+                muted: true,
+                ..Default::default()
+            },
+        );
+
+        assert!(
+            config
+                .conversation_settings(&device1, &conversation_id)
+                .muted
+        );
+        assert!(
+            !config
+                .conversation_settings(&device2, &conversation_id)
+                .muted
+        );
+    }
+
+    #[test]
+    fn test_conversation_settings_missing_count_unread() {
+        let config: Config = toml::from_str(
+            "[[conversation_settings]]\ndevice = \"device1\"\nconversation = { Channel = 2 }\nsettings = { muted = true }\n",
+        )
+        .expect("Could not parse config");
+        let settings = config.conversation_settings(
+            &"device1".into(),
+            &crate::conversation_id::ConversationId::Channel(2.into()),
+        );
+        assert!(settings.muted);
+        assert!(settings.count_unread);
+    }
+
+    #[test]
+    fn test_conversation_settings_without_device_dropped() {
+        let config: Config = toml::from_str(
+            "[[conversation_settings]]\nconversation = { Channel = 2 }\nsettings = { muted = true }\n",
+        )
+        .expect("Could not parse config");
+        assert!(config.conversation_settings.is_empty());
+    }
+
+    #[test]
+    fn test_units_default_to_metric() {
+        let config: Config = toml::from_str("").expect("Could not parse config");
+        assert_eq!(config.units, Units::Metric);
+        let config: Config =
+            toml::from_str("units = \"imperial\"\n").expect("Could not parse config");
+        assert_eq!(config.units, Units::Imperial);
+    }
 }
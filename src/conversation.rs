@@ -1,22 +1,28 @@
-use crate::Message::DeviceViewEvent;
-use crate::config::{Config, HistoryLength};
+use crate::Message::{DeviceViewEvent, ExportConversation};
+use crate::config::{Config, ConversationSettings, HistoryLength};
 use crate::conversation::ChannelViewMessage::{
     CancelPrepareReply, ClearMessage, EmojiPickerMsg, FocusMessageInput, MarkUnread, MessageInput,
-    MessageSeen, PickChannel, PrepareReply, ReplyWithEmoji, SendMessage, ShareMeshChat,
+    MessageSeen, PickChannel, PrepareReply, ReplyWithEmoji, ScrollToMessage, SendMessage,
+    ShareMeshChat,
 };
 use crate::conversation_id::{ConversationId, MessageId, NodeId};
 use crate::device::DeviceMessage::{
-    ChannelMsg, ForwardMessage, SendPositionMessage, SendSelfInfoMessage, ShowChannel,
-    StopForwardingMessage,
+    ChannelMsg, ComposeWaypoint, ForwardMessage, SendPositionMessage, SendSelfInfoMessage,
+    ShowChannel, StopForwardingMessage,
 };
 use crate::device::{Device, DeviceMessage};
-use crate::meshchat::MCNodeInfo;
+use crate::export::ExportFormat;
+use crate::meshchat::{MCNodeInfo, MCUser, MCWaypoint};
 use crate::message::MCContent::{
     AlertMessage, EmojiReply, NewTextMessage, PositionMessage, TextMessageReply, UserMessage,
+    Waypoint,
 };
+use crate::message::{DeliveryState, menu_button};
+use crate::outbox::QueuedMessage;
 use crate::styles::{
-    DAY_SEPARATOR_STYLE, button_chip_style, picker_header_style, reply_to_style, scrollbar_style,
-    text_input_button_style, text_input_container_style, text_input_style, tooltip_style,
+    DAY_SEPARATOR_STYLE, button_chip_style, highlight_style, menu_button_style,
+    picker_header_style, reply_to_style, scrollbar_style, text_input_button_style,
+    text_input_container_style, text_input_style, tooltip_style,
 };
 use crate::timestamp::TimeStamp;
 use crate::widgets::emoji_picker::{EmojiPicker, PickerMessage};
@@ -32,7 +38,9 @@ use iced::widget::{
     Button, Column, Container, Row, Space, button, container, row, scrollable, text, text_input,
 };
 use iced::widget::{Id, operation};
-use iced::{Center, Element, Fill, Font, Padding, Task};
+use iced::{Center, Element, Fill, Font, Padding, Renderer, Task, Theme};
+use iced_aw::menu::{Item, Menu};
+use iced_aw::{MenuBar, menu_bar};
 use ringmap::RingMap;
 use std::collections::{HashMap, HashSet};
 
@@ -57,6 +65,8 @@ pub enum ChannelViewMessage {
     /// MenuBar overlay before the focus operation traverses the widget tree,
     /// see https://github.com/iced-rs/iced_aw/issues/408.
     FocusMessageInput,
+    /// Scroll to and highlight a message, such as one found by searching all messages
+    ScrollToMessage(MessageId),
 }
 
 /// [Conversation] implements view and update methods for Iced for a set of
@@ -72,6 +82,8 @@ pub struct Conversation {
     last_seen_message: TimeStamp,
     /// Messages the user has manually marked as unread; the sensor will not re-mark them seen.
     manually_unread: HashSet<MessageId>,
+    /// A message to highlight, such as one found by searching all messages
+    highlighted_message: Option<MessageId>,
 }
 
 // jonesy:allow(unknown) async state machine artifact
@@ -88,14 +100,29 @@ impl Conversation {
         }
     }
 
-    /// Acknowledge the receipt of a message.
-    pub fn ack(&mut self, message_id: MessageId) {
+    /// Set how far the message with `message_id` has got in being delivered, returning true if
+    /// the message is in this conversation
+    pub fn set_delivery(&mut self, message_id: MessageId, delivery: DeliveryState) -> bool {
         // jonesy:allow(bounds) via ringmap::RingMap::get_mut
         if let Some(entry) = self.messages.get_mut(&message_id) {
-            entry.ack();
+            entry.set_delivery(delivery);
+            true
+        } else {
+            false
         }
     }
 
+    /// Get the message with `message_id`, if it is in this conversation
+    pub fn message(&self, message_id: &MessageId) -> Option<&MCMessage> {
+        // jonesy:allow(bounds) via ringmap::RingMap::get
+        self.messages.get(message_id)
+    }
+
+    /// Remove the message with `message_id`, such as one that failed and is being sent again
+    pub fn remove_message(&mut self, message_id: &MessageId) -> Option<MCMessage> {
+        self.messages.remove(message_id)
+    }
+
     /// Remove older messages according to the passed in history_length setting
     fn trim_history(&mut self, history_length: &HistoryLength) {
         // if there is an active config for the maximum length of history, then trim
@@ -134,6 +161,7 @@ impl Conversation {
             | NewTextMessage(_)
             | PositionMessage(_)
             | UserMessage(_)
+            | Waypoint(_)
             | TextMessageReply(_, _) => {
                 // Insert a new message, ordered by timestamp
                 self.messages.insert_sorted_by(
@@ -161,9 +189,56 @@ impl Conversation {
         }
     }
 
+    /// Merge messages from the saved history into the [Conversation], keeping any message
+    /// already received for the same [MessageId], then trim according to `history_length`
+    pub fn restore_history(&mut self, messages: Vec<MCMessage>, history_length: &HistoryLength) {
+        for message in messages {
+            if !self.messages.contains_key(&message.message_id()) {
+                self.messages.insert_sorted_by(
+                    message.message_id(),
+                    message,
+                    MCMessage::sort_by_timestamp,
+                );
+            }
+        }
+
+        // jonesy:allow(assert) via trim_history -> ringmap::RingMap::len
+        self.trim_history(history_length);
+    }
+
+    /// Return a copy of all the messages in the [Conversation], in timestamp order, to be saved
+    pub fn history(&self) -> Vec<MCMessage> {
+        self.messages.values().cloned().collect()
+    }
+
+    /// Return the waypoints shared in the [Conversation], with when each was shared
+    pub fn waypoints(&self) -> impl Iterator<Item = (TimeStamp, &MCWaypoint)> {
+        self.messages
+            .values()
+            .filter_map(|message| match message.message() {
+                Waypoint(waypoint) => Some((message.time(), waypoint)),
+                _ => None,
+            })
+    }
+
+    /// Return the messages with text that contains `pattern`, ignoring case, in timestamp order
+    pub fn search(&self, pattern: &str) -> Vec<&MCMessage> {
+        let pattern = pattern.to_lowercase();
+        self.messages
+            .values()
+            .filter(|message| {
+                message
+                    .message()
+                    .text()
+                    .is_some_and(|text| text.to_lowercase().contains(&pattern))
+            })
+            .collect()
+    }
+
     /// Cancel any interactive modes underway
     pub fn cancel_interactive(&mut self) {
         self.preparing_reply_to = None;
+        self.highlighted_message = None;
     }
 
     /// Update the [Conversation] state based on a [ChannelViewMessage]
@@ -211,6 +286,19 @@ impl Conversation {
                 }
             }
             FocusMessageInput => operation::focus(MESSAGE_INPUT_ID),
+            ScrollToMessage(message_id) => {
+                // jonesy:allow(bounds) via ringmap::RingMap::get_index_of
+                if let Some(index) = self.messages.get_index_of(&message_id) {
+                    self.highlighted_message = Some(message_id);
+                    // Messages are not all the same height, so this is approximate, but good
+                    // enough to bring the message into view
+                    let last = self.messages.len().saturating_sub(1).max(1);
+                    let y = index as f32 / last as f32;
+                    operation::snap_to(CHANNEL_VIEW_SCROLLABLE_ID, RelativeOffset { x: 0.0, y })
+                } else {
+                    Task::none()
+                }
+            }
             CancelPrepareReply => {
                 self.cancel_interactive();
                 Task::none()
@@ -289,11 +377,24 @@ impl Conversation {
         show_user_updates: bool,
     ) -> Element<'a, Message> {
         // jonesy:allow(assert,bounds) via channel_view -> ringmap::RingMap
+        let queued: Vec<&QueuedMessage> = device_view
+            .device()
+            .map(|device| {
+                device_view
+                    .outbox()
+                    .conversation(device, self.conversation_id)
+                    .collect()
+            })
+            .unwrap_or_default();
+        let hidden_nodes = device_view.hidden_nodes();
         let channel_view_content = self.channel_view(
             nodes,
             fav_nodes,
+            &hidden_nodes,
+            &queued,
             enable_position,
             enable_my_user,
+            device_view.can_send_waypoints(),
             show_position_updates,
             show_user_updates,
         );
@@ -306,12 +407,24 @@ impl Conversation {
     }
 
     /// Return the number of unread messages in the channel, not counting message types that
-    /// are currently not being shown
-    pub fn unread_count(&self, show_position_updates: bool, show_user_updates: bool) -> usize {
+    /// are currently not being shown. None are counted if the conversation is muted, and only
+    /// the ones that mention `me` if it is set to count mentions only.
+    pub fn unread_count(
+        &self,
+        show_position_updates: bool,
+        show_user_updates: bool,
+        settings: &ConversationSettings,
+        me: Option<&MCUser>,
+    ) -> usize {
+        if settings.muted {
+            return 0;
+        }
+
         // jonesy:allow(bounds) via ringmap::RingMap::values()
         self.messages.values().fold(0, |acc, entry| {
             if (matches!(entry.message(), PositionMessage(..)) && !show_position_updates)
                 || (matches!(entry.message(), UserMessage(..)) && !show_user_updates)
+                || (settings.mentions_only && !me.is_some_and(|me| entry.mentions(me)))
             {
                 acc
             } else if !entry.seen() {
@@ -322,19 +435,23 @@ impl Conversation {
         })
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn channel_view<'a>(
         &'a self,
         nodes: &'a HashMap<NodeId, MCNodeInfo>,
         fav_nodes: &'a HashSet<NodeId>,
+        hidden_nodes: &HashSet<NodeId>,
+        queued: &[&QueuedMessage],
         enable_position: bool,
         enable_my_info: bool,
+        enable_waypoint: bool,
         show_position_updates: bool,
         show_user_updates: bool,
     ) -> Element<'a, Message> {
         let mut channel_view_content = Column::new().padding(right(10));
 
         // jonesy:allow(assert) via ringmap::RingMap::is_empty
-        let message_area: Element<'a, Message> = if self.messages.is_empty() {
+        let message_area: Element<'a, Message> = if self.messages.is_empty() && queued.is_empty() {
             Self::empty_view()
         } else {
             let mut previous_day = u32::MIN;
@@ -354,6 +471,11 @@ impl Conversation {
                     continue;
                 }
 
+                // Hide messages from nodes being ignored, unless the user asked to see them
+                if hidden_nodes.contains(&message.from()) {
+                    continue;
+                }
+
                 let datetime_local = MCMessage::datetime_local(message.time());
 
                 // jonesy:allow(bounds) via chrono day()
@@ -366,7 +488,7 @@ impl Conversation {
                     previous_day = message_day;
                 }
 
-                channel_view_content = channel_view_content.push(message.view(
+                let message_view = message.view(
                     &self.messages,
                     nodes,
                     fav_nodes,
@@ -374,11 +496,23 @@ impl Conversation {
                     message.from() == self.my_node_num,
                     &self.emoji_picker,
                     previous_from != Some(message.from()),
-                ));
+                );
+
+                channel_view_content = if self.highlighted_message == Some(message.message_id()) {
+                    channel_view_content
+                        .push(container(message_view).style(highlight_style).padding(2))
+                } else {
+                    channel_view_content.push(message_view)
+                };
 
                 previous_from = Some(message.from());
             }
 
+            // Messages waiting to be sent once the radio is connected come after all the others
+            for queued_message in queued {
+                channel_view_content = channel_view_content.push(queued_message.view());
+            }
+
             // Wrap the list of messages in a scrollable container, with a scrollbar
             scrollable(channel_view_content)
                 .direction({
@@ -406,6 +540,12 @@ impl Conversation {
                 .on_press(DeviceViewEvent(SendSelfInfoMessage(self.conversation_id)));
         }
 
+        let mut send_waypoint_button = button(text("Send Waypoint 🚩")).style(button_chip_style);
+        if enable_waypoint {
+            send_waypoint_button = send_waypoint_button
+                .on_press(DeviceViewEvent(ComposeWaypoint(self.conversation_id)));
+        }
+
         // a button to allow easy sharing of this app
         let share_meshchat_button =
             button(row([text("Share MeshChat ").into(), icons::share().into()]))
@@ -421,7 +561,11 @@ impl Conversation {
             .push(Space::new().width(6))
             .push(send_info_button)
             .push(Space::new().width(6))
-            .push(share_meshchat_button);
+            .push(send_waypoint_button)
+            .push(Space::new().width(6))
+            .push(share_meshchat_button)
+            .push(Space::new().width(Fill))
+            .push(self.export_menu());
 
         // Place the scrollable in a column, with a row of buttons at the bottom
         let mut column = Column::new()
@@ -438,6 +582,30 @@ impl Conversation {
         column.push(self.input_box()).into()
     }
 
+    /// A menu to export the conversation to a file in one of the [ExportFormat]s
+    fn export_menu<'a>(&self) -> MenuBar<'a, Message, Theme, Renderer> {
+        let conversation_id = self.conversation_id;
+        let menu_items = ExportFormat::ALL
+            .iter()
+            .map(|format| {
+                Item::new(menu_button(
+                    format.to_string(),
+                    ExportConversation(conversation_id, *format),
+                ))
+            })
+            .collect();
+
+        let root_button = button(text("Export ▼"))
+            .style(button_chip_style)
+            .on_press(Message::None); // Needed for styling to work
+
+        // jonesy:allow(misaligned_ptr) via iced_aw menu_bar! macro (misaligned_ptr)
+        menu_bar!((root_button, { Menu::new(menu_items).spacing(3).width(100) }))
+            .close_on_background_click(true)
+            .close_on_item_click(true)
+            .style(menu_button_style)
+    }
+
     fn empty_view<'a>() -> Element<'a, Message> {
         Container::new(Column::new().push(text("No messages sent or received yet.").align_x(Center).size(20))
                            .push(text("You can use the text box at the bottom of the screen to send a text message, or the buttons to send your position or node info").align_x(Center).size(20)).align_x(Center))
@@ -624,17 +792,19 @@ impl Conversation {
 
 #[cfg(test)]
 mod test {
-    use crate::config::{Config, HistoryLength};
+    use crate::config::{Config, ConversationSettings, HistoryLength};
     use crate::conversation::ChannelViewMessage::{
         CancelPrepareReply, ClearMessage, EmojiPickerMsg, FocusMessageInput, MarkUnread,
-        MessageInput, MessageSeen, PrepareReply, SendMessage,
+        MessageInput, MessageSeen, PrepareReply, ScrollToMessage, SendMessage,
     };
     use crate::conversation::{Conversation, ConversationId};
     use crate::conversation_id::{MessageId, NodeId};
     use crate::device::Device;
-    use crate::meshchat::{MCPosition, MCUser};
-    use crate::message::MCContent::{EmojiReply, NewTextMessage};
-    use crate::message::MCMessage;
+    use crate::meshchat::{MCPosition, MCUser, MCWaypoint};
+    use crate::message::MCContent::{
+        AlertMessage, EmojiReply, NewTextMessage, TextMessageReply, Waypoint,
+    };
+    use crate::message::{DeliveryState, MCMessage};
     use crate::timestamp::TimeStamp;
     use crate::widgets::emoji_picker::PickerMessage;
     use std::collections::{HashMap, HashSet};
@@ -684,7 +854,7 @@ mod test {
             "There should be 3 messages in the list"
         );
         assert_eq!(
-            channel_view.unread_count(true, true),
+            channel_view.unread_count(true, true, &ConversationSettings::default(), None),
             3,
             "The unread count should be 3"
         );
@@ -711,7 +881,10 @@ mod test {
     #[test]
     fn test_initial_unread_count() {
         let channel_view = Conversation::new(ConversationId::Channel(0.into()), NodeId::from(0u64));
-        assert_eq!(channel_view.unread_count(true, true), 0);
+        assert_eq!(
+            channel_view.unread_count(true, true, &ConversationSettings::default(), None),
+            0
+        );
     }
 
     #[test]
@@ -725,7 +898,10 @@ mod test {
             TimeStamp::now(),
         );
         let _ = channel_view.new_message(message.clone(), &HistoryLength::All);
-        assert_eq!(channel_view.unread_count(true, true), 1);
+        assert_eq!(
+            channel_view.unread_count(true, true, &ConversationSettings::default(), None),
+            1
+        );
     }
 
     #[test]
@@ -930,7 +1106,7 @@ mod test {
                 .acked()
         );
 
-        channel_view.ack(MessageId::from(42));
+        assert!(channel_view.set_delivery(MessageId::from(42), DeliveryState::Acked));
         assert!(
             channel_view
                 .messages
@@ -945,7 +1121,7 @@ mod test {
         let mut channel_view =
             Conversation::new(ConversationId::Channel(0.into()), NodeId::from(0u64));
         // Should not panic
-        channel_view.ack(MessageId::from(999));
+        assert!(!channel_view.set_delivery(MessageId::from(999), DeliveryState::Acked));
     }
 
     #[test]
@@ -968,7 +1144,10 @@ mod test {
                 .expect("entry 42 should exist")
                 .seen()
         );
-        assert_eq!(channel_view.unread_count(true, true), 1);
+        assert_eq!(
+            channel_view.unread_count(true, true, &ConversationSettings::default(), None),
+            1
+        );
 
         let _ = channel_view.update(MessageSeen(MessageId::from(42), timestamp));
         assert!(
@@ -978,7 +1157,10 @@ mod test {
                 .expect("entry 42 should exist after marking seen")
                 .seen()
         );
-        assert_eq!(channel_view.unread_count(true, true), 0);
+        assert_eq!(
+            channel_view.unread_count(true, true, &ConversationSettings::default(), None),
+            0
+        );
     }
 
     #[test]
@@ -1003,7 +1185,10 @@ mod test {
                 .expect("entry 42 should exist")
                 .seen()
         );
-        assert_eq!(channel_view.unread_count(true, true), 0);
+        assert_eq!(
+            channel_view.unread_count(true, true, &ConversationSettings::default(), None),
+            0
+        );
 
         // Mark as unread
         let _ = channel_view.update(MarkUnread(MessageId::from(42)));
@@ -1014,7 +1199,10 @@ mod test {
                 .expect("entry 42 should exist after mark unread")
                 .seen()
         );
-        assert_eq!(channel_view.unread_count(true, true), 1);
+        assert_eq!(
+            channel_view.unread_count(true, true, &ConversationSettings::default(), None),
+            1
+        );
 
         // The message is in manually_unread, so MessageSeen should NOT re-mark it
         assert!(channel_view.manually_unread.contains(&MessageId::from(42)));
@@ -1029,7 +1217,7 @@ mod test {
             "manually unread message should not be marked seen by sensor"
         );
         assert_eq!(
-            channel_view.unread_count(true, true),
+            channel_view.unread_count(true, true, &ConversationSettings::default(), None),
             1,
             "unread count should remain 1 after guarded MessageSeen"
         );
@@ -1277,10 +1465,16 @@ mod test {
         let _ = channel_view.new_message(pos_msg, &HistoryLength::All);
 
         // With position updates shown, should be 2 unread
-        assert_eq!(channel_view.unread_count(true, true), 2);
+        assert_eq!(
+            channel_view.unread_count(true, true, &ConversationSettings::default(), None),
+            2
+        );
 
         // With position updates hidden, should be 1 unread
-        assert_eq!(channel_view.unread_count(false, true), 1);
+        assert_eq!(
+            channel_view.unread_count(false, true, &ConversationSettings::default(), None),
+            1
+        );
     }
 
     #[test]
@@ -1318,10 +1512,16 @@ mod test {
         let _ = channel_view.new_message(user_msg, &HistoryLength::All);
 
         // With user updates shown, should be 2 unread
-        assert_eq!(channel_view.unread_count(true, true), 2);
+        assert_eq!(
+            channel_view.unread_count(true, true, &ConversationSettings::default(), None),
+            2
+        );
 
         // With user updates hidden, should be 1 unread
-        assert_eq!(channel_view.unread_count(true, false), 1);
+        assert_eq!(
+            channel_view.unread_count(true, false, &ConversationSettings::default(), None),
+            1
+        );
     }
 
     #[test]
@@ -1684,4 +1884,218 @@ mod test {
         );
         // Should render the channel view with Send Position and Send Info buttons
     }
+
+    #[test]
+    fn test_restore_history_merges_in_order() {
+        let mut conversation =
+            Conversation::new(ConversationId::Channel(0.into()), NodeId::from(999u64));
+        let live = MCMessage::new(
+            MessageId::from(2u64),
+            NodeId::from(1u64),
+            NewTextMessage("live".into()),
+            TimeStamp::from(2000u64),
+        );
+        let _ = conversation.new_message(live, &HistoryLength::All);
+
+        let saved = vec![
+            MCMessage::new(
+                MessageId::from(1u64),
+                NodeId::from(1u64),
+                NewTextMessage("older".into()),
+                TimeStamp::from(1000u64),
+            ),
+            MCMessage::new(
+                MessageId::from(2u64),
+                NodeId::from(1u64),
+                NewTextMessage("saved copy".into()),
+                TimeStamp::from(2000u64),
+            ),
+        ];
+        conversation.restore_history(saved, &HistoryLength::All);
+
+        let history = conversation.history();
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].message_id(), MessageId::from(1u64));
+        assert_eq!(history[1].message().to_string(), "live");
+    }
+
+    #[test]
+    fn test_restore_history_trims() {
+        let mut conversation =
+            Conversation::new(ConversationId::Channel(0.into()), NodeId::from(999u64));
+        let saved = (0u64..5)
+            .map(|i| {
+                MCMessage::new(
+                    MessageId::from(i),
+                    NodeId::from(1u64),
+                    NewTextMessage(format!("{i}")),
+                    TimeStamp::from(i),
+                )
+            })
+            .collect();
+        conversation.restore_history(saved, &HistoryLength::NumberOfMessages(3));
+        assert_eq!(conversation.history().len(), 3);
+    }
+
+    fn search_test_conversation() -> Conversation {
+        let mut conversation =
+            Conversation::new(ConversationId::Channel(0.into()), NodeId::from(999u64));
+        let messages = vec![
+            MCMessage::new(
+                MessageId::from(1u64),
+                NodeId::from(1u64),
+                NewTextMessage("Meet at the Bridge".to_string()),
+                TimeStamp::from(1u64),
+            ),
+            MCMessage::new(
+                MessageId::from(2u64),
+                NodeId::from(2u64),
+                TextMessageReply(MessageId::from(1u64), "which bridge?".to_string()),
+                TimeStamp::from(2u64),
+            ),
+            MCMessage::new(
+                MessageId::from(3u64),
+                NodeId::from(3u64),
+                AlertMessage("Bridge closed".to_string()),
+                TimeStamp::from(3u64),
+            ),
+            MCMessage::new(
+                MessageId::from(4u64),
+                NodeId::from(1u64),
+                NewTextMessage("See you there".to_string()),
+                TimeStamp::from(4u64),
+            ),
+        ];
+        conversation.restore_history(messages, &HistoryLength::All);
+        conversation
+    }
+
+    #[test]
+    fn test_search_text_reply_and_alert() {
+        let conversation = search_test_conversation();
+        let found: Vec<MessageId> = conversation
+            .search("bridge")
+            .iter()
+            .map(|message| message.message_id())
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                MessageId::from(1u64),
+                MessageId::from(2u64),
+                MessageId::from(3u64)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_search_ignores_case() {
+        let conversation = search_test_conversation();
+        assert_eq!(conversation.search("SEE YOU").len(), 1);
+    }
+
+    #[test]
+    fn test_search_no_match() {
+        let conversation = search_test_conversation();
+        assert!(conversation.search("river").is_empty());
+    }
+
+    #[test]
+    fn test_waypoints() {
+        let mut conversation = search_test_conversation();
+        assert_eq!(conversation.waypoints().count(), 0);
+
+        let waypoint = MCWaypoint {
+            id: 7,
+            name: "Camp".into(),
+            ..Default::default()
+        };
+        let _ = conversation.new_message(
+            MCMessage::new(
+                MessageId::from(5u64),
+                NodeId::from(2u64),
+                Waypoint(waypoint.clone()),
+                TimeStamp::from(5u64),
+            ),
+            &HistoryLength::All,
+        );
+        let waypoints: Vec<(TimeStamp, &MCWaypoint)> = conversation.waypoints().collect();
+        assert_eq!(waypoints, vec![(TimeStamp::from(5u64), &waypoint)]);
+    }
+
+    #[test]
+    fn test_scroll_to_message_highlights() {
+        let mut conversation = search_test_conversation();
+        let _ = conversation.update(ScrollToMessage(MessageId::from(3u64)));
+        assert_eq!(
+            conversation.highlighted_message,
+            Some(MessageId::from(3u64))
+        );
+    }
+
+    #[test]
+    fn test_scroll_to_unknown_message() {
+        let mut conversation = search_test_conversation();
+        let _ = conversation.update(ScrollToMessage(MessageId::from(42u64)));
+        assert!(conversation.highlighted_message.is_none());
+    }
+
+    #[test]
+    fn test_cancel_interactive_clears_highlight() {
+        let mut conversation = search_test_conversation();
+        let _ = conversation.update(ScrollToMessage(MessageId::from(1u64)));
+        conversation.cancel_interactive();
+        assert!(conversation.highlighted_message.is_none());
+    }
+
+    #[test]
+    fn test_muted_unread_count() {
+        let mut channel_view =
+            Conversation::new(ConversationId::Channel(0.into()), NodeId::from(0u64));
+        let message = MCMessage::new(
+            MessageId::from(1),
+            NodeId::from(1u64),
+            NewTextMessage("Hello".to_string()),
+            TimeStamp::now(),
+        );
+        let _ = channel_view.new_message(message, &HistoryLength::All);
+        let muted = ConversationSettings {
+            muted: true,
+            ..Default::default()
+        };
+        assert_eq!(channel_view.unread_count(true, true, &muted, None), 0);
+    }
+
+    #[test]
+    fn test_mentions_only_unread_count() {
+        let mut channel_view =
+            Conversation::new(ConversationId::Channel(0.into()), NodeId::from(0u64));
+        for (id, text) in [(1, "Hello everyone"), (2, "Is Base Camp there?")] {
+            let message = MCMessage::new(
+                MessageId::from(id),
+                NodeId::from(1u64),
+                NewTextMessage(text.to_string()),
+                TimeStamp::now(),
+            );
+            let _ = channel_view.new_message(message, &HistoryLength::All);
+        }
+        let me = MCUser {
+            long_name: "Base Camp".into(),
+            short_name: "BC".into(),
+            ..Default::default()
+        };
+        let mentions_only = ConversationSettings {
+            mentions_only: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            channel_view.unread_count(true, true, &mentions_only, Some(&me)),
+            1
+        );
+        // Without knowing my user, no message can mention me
+        assert_eq!(
+            channel_view.unread_count(true, true, &mentions_only, None),
+            0
+        );
+    }
 }
@@ -93,7 +93,7 @@ impl Display for NodeId {
     }
 }
 
-#[derive(Debug, Default, Hash, PartialEq, Eq, Clone, Copy)]
+#[derive(Debug, Default, Serialize, Deserialize, Hash, PartialEq, Eq, Clone, Copy)]
 pub struct MessageId(u64);
 
 impl From<u64> for MessageId {
@@ -1,56 +1,93 @@
-use crate::config::{Config, HistoryLength};
+use crate::channel_sharing::ChannelSharing;
+use crate::config::{Config, ConversationSettings, HistoryLength, NodeSort};
 use crate::conversation::{ChannelViewMessage, Conversation, MESSAGE_INPUT_ID};
 use crate::device::ConnectionState::{Connected, Connecting, Disconnected, Disconnecting};
 use crate::device::DeviceCommand::{
-    Connect, Disconnect, SendEmojiReply, SendPosition, SendSelfInfo, SendText,
+    Connect, Disconnect, SendEmojiReply, SendText, SendTraceroute, SendWaypoint, SetChannels,
+    SetNodeIgnored,
 };
 use crate::device::DeviceEvent::{
     ChannelName, ConnectedEvent, ConnectingEvent, ConnectionError, DisconnectedEvent,
-    DisconnectingEvent, MyPosition, MyUserInfo, NotReady, Ready, SendError,
+    DisconnectingEvent, MessageResent, MyPosition, MyUserInfo, NotReady, Ready, SendError,
 };
 use crate::device::DeviceMessage::{
-    AliasInput, ChannelMsg, ClearFilter, ConnectRequest, DisconnectRequest, ForwardMessage,
-    SearchInput, SendEmojiReplyMessage, SendPositionMessage, SendSelfInfoMessage, SendTextMessage,
-    ShowChannel, StartEditingAlias, StartForwardingMessage, StopForwardingMessage,
-    SubscriptionMessage,
+    AckTimeout, AliasInput, CancelReconnect, ChannelMsg, ChannelUrlInput, ClearFilter,
+    CloseChannelSharing, CloseTraceroute, CloseWaypoints, ComposeWaypoint, ConnectRequest,
+    DisconnectRequest, ForwardMessage, HistoryLoaded, IgnoreNode, KnownNodesLoaded, LoadMapTiles,
+    MapTilesLoaded, OutboxLoaded, QueuedMessageFailed, ReconnectAttempt, ResendMessage,
+    SaveKnownNodes, SaveTracks, SearchInput, SendEmojiReplyMessage, SendPositionMessage,
+    SendSelfInfoMessage, SendTextMessage, SendWaypointMessage, ShowChannel, ShowChannelSharing,
+    ShowMessage, ShowWaypoints, StartEditingAlias, StartForwardingMessage, StopForwardingMessage,
+    SubscriptionMessage, ToggleMap, ToggleMessageSearch, ToggleShowHidden, TraceRoute,
+    TracerouteFailed, TracerouteTimeout, TracksLoaded, TrustNodeKey, UnignoreNode, WaypointMsg,
+    WriteChannels,
 };
-use crate::message::{MCContent, MCMessage};
-use crate::{MeshChat, Message, icons};
+use crate::export::{ExportEntry, ExportFormat, export_conversation, export_file};
+use crate::geo::{bearing_deg, compass_point, distance_m, format_distance};
+use crate::history::{load_history, save_conversation};
+use crate::known_nodes::{
+    KeyCheck, KnownNode, KnownNodes, NodeStatus, heard_ago, load_known_nodes, node_status,
+    save_known_nodes,
+};
+use crate::map::{MapNode, NodeMap, TileId, TileSource, Tiles, WorldPoint};
+use crate::message::{DeliveryState, LinkQuality, MCContent, MCMessage, menu_button};
+use crate::outbox::{Outbox, Outgoing, QueuedMessage, load_outbox, save_outbox};
+use crate::telemetry::{MCTelemetry, TelemetryHistory};
+use crate::traceroute::{Route, Traceroute};
+use crate::waypoint::{WaypointEditor, WaypointMessage, new_waypoint_id, waypoints_view};
+use crate::{Message, icons};
 
 use crate::Message::{
-    AddNodeAlias, AppError, DeviceViewEvent, Navigation, OpenSettingsDialog, OpenUrl,
-    RemoveNodeAlias, ShowLocation, ShowUserInfo, ToggleNodeFavourite,
+    AddNodeAlias, AppError, ConversationSettingsChanged, DeviceViewEvent, ExportPositions,
+    ExportTrack, Navigation, NodeSortSelected, OpenSettingsDialog, RemoveNodeAlias, ShowLocation,
+    ShowUserInfo, ToggleNodeFavourite,
 };
 use crate::conversation_id::ConversationId::Node;
 use crate::conversation_id::{ChannelIndex, ConversationId, MessageId, NodeId};
 use crate::device::DeviceEvent::{
-    DeviceBatteryLevel, MCMessageReceived, MessageACK, MyNodeNum, NewChannel, NewNode, NewNodeInfo,
-    NewNodePosition, RadioNotification,
+    AwaitingAck, DeviceBatteryLevel, MCMessageReceived, MessageACK, MessageFailed, MyNodeNum,
+    NeighbourInfo, NewChannel, NewChannelSettings, NewNode, NewNodeInfo, NewNodePosition,
+    NodeTelemetry, RadioNotification, TracerouteResponse,
 };
 use crate::device_list::{DeviceList, RadioType};
 use crate::meshchat::View::DeviceListView;
-use crate::meshchat::{MCChannel, MCNodeInfo, MCPosition, MCUser, View};
-use crate::message::MCContent::{PositionMessage, UserMessage};
+use crate::meshchat::{
+    MCChannel, MCChannelSet, MCChannelSettings, MCNeighbour, MCNodeInfo, MCPosition, MCUser,
+    MCWaypoint, View,
+};
+use crate::message::MCContent::{NewTextMessage, PositionMessage, TextMessageReply, UserMessage};
 use crate::styles::{
-    DAY_SEPARATOR_STYLE, battery_style, button_chip_style, channel_row_style, count_style,
-    fav_button_style, scrollbar_style, text_input_button_style, text_input_container_style,
-    text_input_style, tooltip_style,
+    COLOR_GRAY_50, COLOR_GREEN, COLOR_RED, COLOR_YELLOW, DAY_SEPARATOR_STYLE, TIME_TEXT_COLOR,
+    TIME_TEXT_SIZE, battery_style, button_chip_style, channel_row_style, count_style,
+    emoji_tab_style, fav_button_style, menu_button_style, scrollbar_style, text_input_button_style,
+    text_input_container_style, text_input_style, tooltip_style,
 };
 use crate::timestamp::TimeStamp;
+use crate::tracks::{
+    Track, TrackFormat, Tracks, load_tracks, render_positions, render_track, save_track,
+};
 use crate::widgets::battery::{Battery, BatteryState};
 #[cfg(feature = "bluetooth")]
 use btleplug::api::BDAddr;
+use iced::widget::image::Handle;
 use iced::widget::scrollable::Scrollbar;
 use iced::widget::{
-    Button, Column, Container, Id, Row, Space, button, container, operation, scrollable, text,
-    text_input, tooltip,
+    Button, Column, Container, Id, Row, Space, button, container, operation, pick_list, scrollable,
+    text, text_input, tooltip,
 };
-use iced::{Bottom, Center, Element, Fill, Padding, Task};
+use iced::{Bottom, Center, Element, Fill, Padding, Renderer, Task, Theme};
+use iced_aw::menu::{Item, Menu};
+use iced_aw::{MenuBar, menu_bar};
 #[cfg(feature = "meshcore")]
 use meshcore_rs::MeshCoreEvent;
 #[cfg(feature = "meshtastic")]
 use meshtastic::protobufs::FromRadio;
-use std::collections::{HashMap, HashSet};
+#[cfg(feature = "serial")]
+use meshtastic::utils::DEFAULT_SERIAL_BAUD;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 use tokio::sync::mpsc::Sender;
 
 #[derive(Clone, PartialEq, Debug)]
@@ -68,7 +105,8 @@ impl Default for ConnectionState {
 }
 
 /// How a device is reached. BLE keeps its existing fields; TCP records the resolved
-/// host/port and any human-readable name from mDNS.
+/// host/port and any human-readable name from mDNS; Serial records the port path and baud rate;
+/// Sim names a simulated radio; Replay is the path of a capture file being replayed.
 #[derive(Clone, PartialEq, Eq, Hash, Debug)]
 pub enum DeviceIdentifier {
     #[cfg(feature = "bluetooth")]
@@ -82,6 +120,18 @@ pub enum DeviceIdentifier {
         host: String,
         port: u16,
     },
+    #[cfg(feature = "serial")]
+    Serial {
+        path: String,
+        baud: u32,
+    },
+    #[cfg(feature = "sim")]
+    Sim {
+        name: String,
+    },
+    Replay {
+        path: String,
+    },
 }
 
 impl Default for DeviceIdentifier {
@@ -101,6 +151,13 @@ impl Default for DeviceIdentifier {
                 port: 0,
             }
         }
+        #[cfg(all(feature = "serial", not(any(feature = "bluetooth", feature = "tcp"))))]
+        {
+            DeviceIdentifier::Serial {
+                path: String::new(),
+                baud: DEFAULT_SERIAL_BAUD,
+            }
+        }
     }
 }
 
@@ -115,6 +172,14 @@ impl DeviceIdentifier {
             DeviceIdentifier::Tcp { name, host, port } => {
                 name.clone().unwrap_or_else(|| format!("{host}:{port}"))
             }
+            #[cfg(feature = "serial")]
+            DeviceIdentifier::Serial { path, .. } => path.clone(),
+            #[cfg(feature = "sim")]
+            DeviceIdentifier::Sim { name } => name.clone(),
+            DeviceIdentifier::Replay { path } => Path::new(path)
+                .file_name()
+                .map(|file_name| file_name.to_string_lossy().to_string())
+                .unwrap_or_else(|| path.clone()),
         }
     }
 
@@ -124,15 +189,60 @@ impl DeviceIdentifier {
             DeviceIdentifier::Ble { mac, .. } => mac.map(|mac| mac.to_string()),
             #[cfg(feature = "tcp")]
             DeviceIdentifier::Tcp { .. } => None,
+            #[cfg(feature = "serial")]
+            DeviceIdentifier::Serial { .. } => None,
+            #[cfg(feature = "sim")]
+            DeviceIdentifier::Sim { .. } => None,
+            DeviceIdentifier::Replay { .. } => None,
         }
     }
 }
 
 #[cfg(feature = "tcp")]
-const TCP_SCHEME: &str = "tcp://";
+pub const TCP_SCHEME: &str = "tcp://";
+
+#[cfg(feature = "serial")]
+const SERIAL_SCHEME: &str = "serial://";
+#[cfg(feature = "serial")]
+const SERIAL_BAUD_QUERY: &str = "?baud=";
+
+#[cfg(feature = "sim")]
+const SIM_SCHEME: &str = "sim://";
+
+const REPLAY_SCHEME: &str = "replay://";
 
 impl From<&str> for DeviceIdentifier {
     fn from(value: &str) -> Self {
+        if let Some(path) = value.strip_prefix(REPLAY_SCHEME) {
+            return DeviceIdentifier::Replay {
+                path: path.to_string(),
+            };
+        }
+        #[cfg(feature = "sim")]
+        {
+            if let Some(name) = value.strip_prefix(SIM_SCHEME) {
+                return DeviceIdentifier::Sim {
+                    name: name.to_string(),
+                };
+            }
+        }
+        #[cfg(feature = "serial")]
+        {
+            if let Some(rest) = value.strip_prefix(SERIAL_SCHEME) {
+                let (path, baud) = match rest.rsplit_once(SERIAL_BAUD_QUERY) {
+                    Some((path, baud_str)) => {
+                        (path, baud_str.parse::<u32>().unwrap_or(DEFAULT_SERIAL_BAUD))
+                    }
+                    None => (rest, DEFAULT_SERIAL_BAUD),
+                };
+                if !path.is_empty() && baud > 0 {
+                    return DeviceIdentifier::Serial {
+                        path: path.to_string(),
+                        baud,
+                    };
+                }
+            }
+        }
         #[cfg(feature = "tcp")]
         {
             if let Some(rest) = value.strip_prefix(TCP_SCHEME) {
@@ -175,6 +285,13 @@ impl From<&str> for DeviceIdentifier {
                 port: 0,
             }
         }
+        #[cfg(all(feature = "serial", not(any(feature = "bluetooth", feature = "tcp"))))]
+        {
+            DeviceIdentifier::Serial {
+                path: value.to_string(),
+                baud: DEFAULT_SERIAL_BAUD,
+            }
+        }
     }
 }
 
@@ -215,6 +332,13 @@ impl From<&DeviceIdentifier> for String {
                     None => endpoint,
                 }
             }
+            #[cfg(feature = "serial")]
+            DeviceIdentifier::Serial { path, baud } => {
+                format!("{SERIAL_SCHEME}{path}{SERIAL_BAUD_QUERY}{baud}")
+            }
+            #[cfg(feature = "sim")]
+            DeviceIdentifier::Sim { name } => format!("{SIM_SCHEME}{name}"),
+            DeviceIdentifier::Replay { path } => format!("{REPLAY_SCHEME}{path}"),
         }
     }
 }
@@ -237,32 +361,69 @@ pub enum DeviceEvent {
     MyUserInfo(MCUser),
     MyPosition(MCPosition),
     NewChannel(MCChannel),
+    /// The settings of the channel with the index, needed to share it
+    NewChannelSettings(i32, MCChannelSettings),
     NewNode(MCNodeInfo),
     RadioNotification(String, TimeStamp), // Message, TimeStamp
-    /// ChannelId - channel sent to, MessageId, NodeId - sending node, The Message itself, Timestamp
-    MCMessageReceived(ConversationId, MessageId, NodeId, MCContent, TimeStamp),
+    /// ChannelId - channel sent to, MessageId, NodeId - sending node, The Message itself, Timestamp,
+    /// and how well it was received, if the radio reported it
+    MCMessageReceived(
+        ConversationId,
+        MessageId,
+        NodeId,
+        MCContent,
+        TimeStamp,
+        Option<LinkQuality>,
+    ),
     /// ChannelId, MessageId
     MessageACK(ConversationId, MessageId),
+    /// A message sent to a conversation is waiting for an acknowledgement, for up to the Duration
+    AwaitingAck(ConversationId, MessageId, Duration),
+    /// The radio reported a message sent could not be delivered, for the reason given
+    MessageFailed(ConversationId, MessageId, String),
+    /// The id of the message sent by a [DeviceCommand::ResendText]
+    MessageResent(MessageId),
     NewNodeInfo(ConversationId, MessageId, NodeId, MCUser, TimeStamp), // conversation_id, id, from, MCUser, TimeStamp
     NewNodePosition(ConversationId, MessageId, NodeId, MCPosition, TimeStamp), // conversation_id, id, from, MCPosition, TimeStamp
     DeviceBatteryLevel(Option<u8>),
     ChannelName(i32, String), // channel number, name
+    /// The route traced to a node, in response to a [DeviceCommand::SendTraceroute]
+    TracerouteResponse(Route),
+    /// The nodes a node hears directly, as reported by it
+    NeighbourInfo(NodeId, Vec<MCNeighbour>),
+    /// Telemetry reported by a node, and when it was received
+    NodeTelemetry(NodeId, MCTelemetry, TimeStamp),
 }
 
 /// Messages sent from the GUI to the subscription
 pub enum DeviceCommand {
-    Connect(DeviceIdentifier, RadioType),
+    /// Connect to a device, and capture all the traffic from it to a file if the bool is true
+    Connect(DeviceIdentifier, RadioType, bool),
     Disconnect,
     SendText(String, ConversationId, Option<MessageId>), // Optional reply to message id
+    /// Send a text message again automatically after it failed, as [DeviceCommand::SendText]
+    ResendText(String, ConversationId, Option<MessageId>),
     SendEmojiReply(String, ConversationId, MessageId),
     SendPosition(ConversationId, MCPosition),
     SendSelfInfo(ConversationId, MCUser),
+    SendTraceroute(NodeId),
+    SendWaypoint(ConversationId, MCWaypoint),
+    /// Join the channels in a channel set, replacing all the channels on the radio, or adding
+    /// them to the slots not in the list of those in use
+    SetChannels(MCChannelSet, Vec<i32>),
+    /// Add a node to the radio's list of ignored nodes if true, or remove it from it if false
+    SetNodeIgnored(NodeId, bool),
+    /// Ask a node for the nodes it hears directly, for radios where they are not broadcast
+    RequestNeighbours(NodeId),
     #[cfg(feature = "meshtastic")]
     MeshTasticRadioPacket(Box<FromRadio>), // Sent from the radio to the subscription, not GUI
     #[cfg(feature = "meshcore")]
     MeshCoreRadioPacket(Box<MeshCoreEvent>), // Sent from the radio to the subscription, not GUI
     #[cfg(feature = "meshcore")]
     BatteryTick, // Periodic timer to request battery level
+    #[cfg(feature = "meshcore")]
+    AckTick, // Periodic timer to expire messages that were not acknowledged in time
+    ConnectionLost, // Sent from the radio to the subscription when the link drops, not GUI
 }
 
 #[derive(Debug, Clone)]
@@ -284,6 +445,142 @@ pub enum DeviceMessage {
     ForwardMessage(ConversationId),
     StopForwardingMessage,
     ClearFilter,
+    /// The saved history of conversations loaded from disk for a device, when connecting to it
+    HistoryLoaded(DeviceIdentifier, HashMap<ConversationId, Vec<MCMessage>>),
+    /// Toggle between searching channel and node names, and searching the text of all messages
+    ToggleMessageSearch,
+    /// Open a conversation and scroll to a message in it, such as one found by searching
+    ShowMessage(ConversationId, MessageId),
+    /// The nodes met previously on a device, loaded from disk when connecting to it
+    KnownNodesLoaded(DeviceIdentifier, HashMap<NodeId, KnownNode>),
+    /// Time to save the known nodes, if they changed since they were last saved
+    SaveKnownNodes,
+    /// The messages saved as queued for a device, loaded from disk when connecting to it
+    OutboxLoaded(DeviceIdentifier, Vec<QueuedMessage>),
+    /// A message queued for a device could not be sent to it, with the error, so it is queued again
+    QueuedMessageFailed(DeviceIdentifier, QueuedMessage, String),
+    /// The tracks of nodes previously saved for a device, loaded from disk when connecting to it
+    TracksLoaded(DeviceIdentifier, HashMap<NodeId, Track>),
+    /// Time to save the tracks that changed since they were last saved
+    SaveTracks,
+    /// Time for the numbered attempt to reconnect to a radio whose link dropped
+    ReconnectAttempt(u32),
+    /// Stop trying to reconnect to a radio whose link dropped
+    CancelReconnect,
+    /// The time to wait for an acknowledgement of a message sent has passed
+    AckTimeout(ConversationId, MessageId),
+    /// Send a message that failed again, with the same text and reply target
+    ResendMessage(ConversationId, MessageId),
+    /// Trace the route to a node, and show it in a dialog
+    TraceRoute(NodeId),
+    /// The time to wait for the route traced to a node has passed
+    TracerouteTimeout(NodeId),
+    /// The traceroute to a node could not be sent, for the reason given
+    TracerouteFailed(NodeId, String),
+    /// Close the dialog showing a traceroute
+    CloseTraceroute,
+    /// Open a dialog to compose a waypoint to send to a conversation
+    ComposeWaypoint(ConversationId),
+    /// A change to the waypoint being composed
+    WaypointMsg(WaypointMessage),
+    /// Send the waypoint composed, and close the dialog
+    SendWaypointMessage,
+    /// Show a dialog with the waypoints shared on the device that have not expired
+    ShowWaypoints,
+    /// Close the dialog composing a waypoint or showing the waypoints
+    CloseWaypoints,
+    /// Open a dialog to share the channels on the radio, or join the channels in a URL
+    ShowChannelSharing,
+    /// The URL of channels to join, as entered by the user
+    ChannelUrlInput(String),
+    /// Replace the channels on the radio with the channels in the URL entered
+    WriteChannels,
+    /// Close the dialog sharing channels
+    CloseChannelSharing,
+    /// Trust the changed public key of a node, pinning it in place of the one first seen
+    TrustNodeKey(NodeId),
+    /// Ignore a node, hiding it and its messages, and have the radio ignore it too
+    IgnoreNode(NodeId),
+    /// Stop ignoring a node
+    UnignoreNode(NodeId),
+    /// Show or hide the nodes being ignored, and their messages
+    ToggleShowHidden,
+    /// Switch between the map of nodes and the list of them
+    ToggleMap,
+    /// Read the map tiles about to be drawn, in the background
+    LoadMapTiles(Vec<TileId>),
+    /// The images of map tiles read from the tiles at the path
+    MapTilesLoaded(PathBuf, Vec<(TileId, Option<Handle>)>),
+}
+
+/// How many times to try to reconnect to a radio whose link dropped, before giving up
+const RECONNECT_ATTEMPTS: u32 = 8;
+/// How long to wait before the first attempt to reconnect, doubled for each attempt after it
+const RECONNECT_DELAY: Duration = Duration::from_secs(2);
+/// The longest time to wait between attempts to reconnect
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(60);
+/// How long to wait after the known nodes or tracks change before saving them, so that changes
+/// made as packets are heard are saved together
+const SAVE_DELAY: Duration = Duration::from_secs(30);
+/// How long to wait for the route traced to a node before showing there was no response
+const TRACEROUTE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// What a node is sorted by in the list of nodes, before its name. The flags are set when the
+/// value is not known, to list those nodes last
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+enum NodeSortKey {
+    Name,
+    /// Most recently heard first
+    LastHeard(Reverse<Option<TimeStamp>>),
+    /// Nearest first, in millimetres
+    Distance(bool, Option<u64>),
+    /// Fewest hops first
+    HopsAway(bool, Option<u32>),
+}
+
+/// Return how long to wait before the numbered `attempt` to reconnect, backing off exponentially
+fn reconnect_delay(attempt: u32) -> Duration {
+    let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+    RECONNECT_DELAY
+        .saturating_mul(factor)
+        .min(MAX_RECONNECT_DELAY)
+}
+
+/// The state of the attempts to reconnect to a radio whose link dropped unexpectedly
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reconnect {
+    device: DeviceIdentifier,
+    radio_type: RadioType,
+    /// The conversation that was open when the link dropped, to go back to once reconnected
+    conversation_id: Option<ConversationId>,
+    attempt: u32,
+}
+
+impl Reconnect {
+    /// The device being reconnected to
+    pub fn device(&self) -> &DeviceIdentifier {
+        &self.device
+    }
+
+    /// Show the progress of the reconnect to the device called `name`, with a button to cancel it
+    pub fn view<'a>(&self, name: String) -> Element<'a, Message> {
+        Row::new()
+            .spacing(4)
+            .align_y(Center)
+            .push(
+                button(text(format!(
+                    "Reconnecting to {name} (attempt {} of {RECONNECT_ATTEMPTS})",
+                    self.attempt
+                )))
+                .style(button_chip_style),
+            )
+            .push(
+                button("Cancel")
+                    .on_press(DeviceViewEvent(CancelReconnect))
+                    .style(button_chip_style),
+            )
+            .into()
+    }
 }
 
 // jonesy:allow(panic) derived Default traces into std HashMap/Option internals
@@ -294,6 +591,8 @@ pub struct Device {
     meshtastic_sender: Option<Sender<DeviceCommand>>,
     #[cfg(feature = "meshcore")]
     meshcore_sender: Option<Sender<DeviceCommand>>,
+    #[cfg(feature = "sim")]
+    sim_sender: Option<Sender<DeviceCommand>>,
     my_node_id: Option<NodeId>,
     my_position: Option<MCPosition>,
     my_user: Option<MCUser>,
@@ -311,6 +610,66 @@ pub struct Device {
     history_length: HistoryLength,
     show_position_updates: bool,
     show_user_updates: bool,
+    /// Saved history for conversations the radio has not told us about yet
+    saved_history: HashMap<ConversationId, Vec<MCMessage>>,
+    /// The saved history has been loaded, so saving conversations won't overwrite it
+    history_loaded: bool,
+    /// Conversations that changed before the saved history was loaded, to save once it is
+    unsaved_conversations: HashSet<ConversationId>,
+    /// Search the text of messages in all conversations, instead of channel and node names
+    search_messages: bool,
+    /// All the nodes met on the connected device, including ones the radio no longer reports
+    known_nodes: KnownNodes,
+    /// The nodes saved before have been loaded, so keys can be pinned and the nodes saved
+    known_nodes_loaded: bool,
+    /// The known nodes have changed since they were last saved
+    known_nodes_unsaved: bool,
+    /// A save of the known nodes is waiting for [SAVE_DELAY] to pass
+    known_nodes_save_scheduled: bool,
+    /// The tracks saved before have been loaded, so saving tracks won't overwrite them
+    tracks_loaded: bool,
+    /// A save of the tracks that changed is waiting for [SAVE_DELAY] to pass
+    tracks_save_scheduled: bool,
+    /// Capture all the traffic from radios connected to, so it can be replayed later
+    capture_traffic: bool,
+    /// Reconnect to the radio if the link to it drops
+    auto_reconnect: bool,
+    /// The user asked to disconnect, so the disconnect that follows is expected
+    disconnect_requested: bool,
+    /// Attempts underway to reconnect to a radio whose link dropped
+    reconnect: Option<Reconnect>,
+    /// Messages sent while the radio was not connected, to send once it is
+    outbox: Outbox,
+    /// Send messages that are not acknowledged again, once
+    auto_resend: bool,
+    /// The messages that were sent again automatically, so they are not sent again if they fail
+    auto_resent: HashSet<MessageId>,
+    /// The traceroute being shown in a dialog, if any
+    traceroute: Option<Traceroute>,
+    /// The nodes each node hears directly, as last reported by it
+    neighbours: HashMap<NodeId, Vec<MCNeighbour>>,
+    /// The recent telemetry reported by each node
+    telemetry: HashMap<NodeId, TelemetryHistory>,
+    /// The waypoint being composed in a dialog, if any
+    waypoint_editor: Option<WaypointEditor>,
+    /// Show a dialog with the waypoints shared on the device
+    showing_waypoints: bool,
+    /// The settings of each channel on the radio, by index, needed to share them
+    channel_settings: BTreeMap<i32, MCChannelSettings>,
+    /// The dialog sharing and joining channels, if open
+    channel_sharing: Option<ChannelSharing>,
+    /// The nodes being ignored, kept out of the list of nodes
+    ignored_nodes: HashMap<NodeId, MCNodeInfo>,
+    /// Show the nodes being ignored, and their messages in channel conversations
+    show_hidden: bool,
+    /// Show the map of nodes in place of the list of them
+    showing_map: bool,
+    /// A position to show in the middle of the map, until the map is closed
+    map_focus: Option<WorldPoint>,
+    /// The base map tiles the map is drawn on, if the user chose some
+    map_tiles: Option<Tiles>,
+    /// The positions each node has reported over time
+    tracks: Tracks,
 }
 
 // jonesy:allow(unknown) async state machine artifact
@@ -322,6 +681,11 @@ impl Device {
         &self.connection_state
     }
 
+    /// Get the attempts underway to reconnect to a radio whose link dropped, if any
+    pub fn reconnecting(&self) -> Option<&Reconnect> {
+        self.reconnect.as_ref()
+    }
+
     /// Cancel or Exit any interactive modes underway
     pub fn cancel_interactive(&mut self) {
         self.stop_editing_alias();
@@ -348,19 +712,181 @@ impl Device {
         self.show_user_updates = show_user_updates;
     }
 
+    /// Set whether the traffic from radios connected to from now on is captured to a file
+    pub fn set_capture_traffic(&mut self, capture_traffic: bool) {
+        self.capture_traffic = capture_traffic;
+    }
+
+    /// Set whether messages that are not acknowledged are sent again automatically, once
+    pub fn set_auto_resend(&mut self, auto_resend: bool) {
+        self.auto_resend = auto_resend;
+    }
+
+    /// Set whether to reconnect to the radio when the link to it drops unexpectedly
+    pub fn set_auto_reconnect(&mut self, auto_reconnect: bool) {
+        self.auto_reconnect = auto_reconnect;
+    }
+
     /// Return a true value to show we can show the device view, false for main to decide
     pub fn update(&mut self, device_view_message: DeviceMessage) -> Task<Message> {
         match device_view_message {
             ConnectRequest(ble_device, radio_type, conversation_id) => {
-                return self.device_send(
-                    Connect(ble_device, radio_type),
+                return self.connect(
+                    ble_device,
+                    radio_type,
                     Navigation(View::DeviceView(conversation_id)),
                 );
             }
             DisconnectRequest(exit) => {
                 self.exit_pending = exit;
+                self.disconnect_requested = true;
+                if self.reconnect.is_some() && matches!(self.connection_state, Disconnected(_, _)) {
+                    // There is no radio to disconnect from while waiting to reconnect to it
+                    return self.stop_reconnect();
+                }
+                self.reconnect = None;
                 return self.device_send(Disconnect, Navigation(DeviceListView));
             }
+            ReconnectAttempt(attempt) => {
+                // Ignore a timer left over from an earlier reconnect, or if connected some other way
+                if let Some(reconnect) = &self.reconnect
+                    && reconnect.attempt == attempt
+                    && matches!(self.connection_state, Disconnected(_, _))
+                {
+                    let device = reconnect.device.clone();
+                    let radio_type = reconnect.radio_type;
+                    return self.connect(device, radio_type, Message::None);
+                }
+            }
+            // An attempt already underway is left to finish, as it can't be interrupted
+            CancelReconnect => {
+                if matches!(self.connection_state, Disconnected(_, _)) {
+                    return self.stop_reconnect();
+                }
+                self.reconnect = None;
+            }
+            AckTimeout(conversation_id, message_id) => {
+                if self.delivery(&conversation_id, &message_id) == Some(DeliveryState::Sending) {
+                    return self.message_failed(conversation_id, message_id);
+                }
+            }
+            ResendMessage(conversation_id, message_id) => {
+                return self.resend(conversation_id, message_id, false);
+            }
+            TraceRoute(node_id) => {
+                self.traceroute = Some(Traceroute::new(node_id));
+                let send_task =
+                    self.device_send_or(SendTraceroute(node_id), Message::None, move |error| {
+                        DeviceViewEvent(TracerouteFailed(node_id, error))
+                    });
+                let timeout_task = Task::perform(
+                    async { tokio::time::sleep(TRACEROUTE_TIMEOUT).await },
+                    move |_| DeviceViewEvent(TracerouteTimeout(node_id)),
+                );
+                // jonesy:allow(overflow) via iced_runtime::task::Task::batch
+                return Task::batch([send_task, timeout_task]);
+            }
+            TracerouteTimeout(node_id) => {
+                if let Some(traceroute) = self.traceroute.as_mut() {
+                    traceroute.fail(node_id, "No response".into());
+                }
+            }
+            TracerouteFailed(node_id, error) => {
+                if let Some(traceroute) = self.traceroute.as_mut() {
+                    traceroute.fail(
+                        node_id,
+                        format!("The traceroute could not be sent: {error}"),
+                    );
+                }
+            }
+            CloseTraceroute => self.traceroute = None,
+            ComposeWaypoint(conversation_id) => {
+                self.waypoint_editor = Some(WaypointEditor::new(
+                    conversation_id,
+                    self.my_position.as_ref(),
+                ))
+            }
+            WaypointMsg(waypoint_message) => {
+                if let Some(editor) = self.waypoint_editor.as_mut() {
+                    editor.update(waypoint_message);
+                }
+            }
+            SendWaypointMessage => {
+                if let Some(editor) = &self.waypoint_editor
+                    && let Ok(waypoint) = editor.waypoint(new_waypoint_id(), TimeStamp::now())
+                {
+                    let conversation_id = editor.conversation_id();
+                    self.waypoint_editor = None;
+                    return self
+                        .device_send(SendWaypoint(conversation_id, waypoint), Message::None);
+                }
+            }
+            ShowWaypoints => self.showing_waypoints = true,
+            CloseWaypoints => {
+                self.waypoint_editor = None;
+                self.showing_waypoints = false;
+            }
+            ShowChannelSharing => self.channel_sharing = Some(ChannelSharing::default()),
+            ChannelUrlInput(url) => {
+                if let Some(channel_sharing) = self.channel_sharing.as_mut() {
+                    channel_sharing.set_url(url);
+                }
+            }
+            WriteChannels => {
+                let in_use: Vec<i32> = self.channel_settings.keys().copied().collect();
+                if let Some(Some(Ok(channel_set))) = self
+                    .channel_sharing
+                    .as_ref()
+                    .map(|channel_sharing| channel_sharing.channels(in_use.len()))
+                {
+                    self.channel_sharing = None;
+                    return self.device_send(
+                        SetChannels(channel_set, in_use),
+                        Message::AppNotification(
+                            "Channels".into(),
+                            "The channels are being written to the radio, which will restart to \
+                            use them"
+                                .into(),
+                            TimeStamp::now(),
+                        ),
+                    );
+                }
+            }
+            CloseChannelSharing => self.channel_sharing = None,
+            TrustNodeKey(node_id) => {
+                if self.known_nodes.trust_key(node_id) {
+                    return self.save_known_nodes();
+                }
+            }
+            IgnoreNode(node_id) => {
+                if let Some(mut node_info) = self.nodes.remove(&node_id) {
+                    node_info.is_ignored = true;
+                    self.ignored_nodes.insert(node_id, node_info);
+                    return self.set_node_ignored(node_id, true);
+                }
+            }
+            UnignoreNode(node_id) => {
+                if let Some(mut node_info) = self.ignored_nodes.remove(&node_id) {
+                    node_info.is_ignored = false;
+                    self.add_node(node_info);
+                    return self.set_node_ignored(node_id, false);
+                }
+            }
+            ToggleShowHidden => self.show_hidden = !self.show_hidden,
+            ToggleMap => {
+                self.showing_map = !self.showing_map;
+                self.map_focus = None;
+            }
+            LoadMapTiles(tiles) => {
+                if let Some(map_tiles) = &self.map_tiles {
+                    return map_tiles.load(tiles);
+                }
+            }
+            MapTilesLoaded(path, images) => {
+                if let Some(map_tiles) = &mut self.map_tiles {
+                    map_tiles.loaded(&path, images);
+                }
+            }
             ForwardMessage(conversation_id) => {
                 if let Some(entry) = self.forwarding_message.take() {
                     let message_text = format!(
@@ -382,23 +908,18 @@ impl Device {
                 );
             }
             SendTextMessage(message, conversation_id, reply_to_id) => {
-                return self.device_send(
-                    SendText(message, conversation_id, reply_to_id),
-                    Message::None,
-                );
+                return self.send_or_queue(conversation_id, Outgoing::Text(message, reply_to_id));
             }
             SendPositionMessage(conversation_id) => {
                 if let Some(position) = &self.my_position {
-                    return self.device_send(
-                        SendPosition(conversation_id, position.clone()),
-                        Message::None,
-                    );
+                    let outgoing = Outgoing::Position(position.clone());
+                    return self.send_or_queue(conversation_id, outgoing);
                 }
             }
             SendSelfInfoMessage(conversation_id) => {
                 if let Some(user) = &self.my_user {
-                    return self
-                        .device_send(SendSelfInfo(conversation_id, user.clone()), Message::None);
+                    let outgoing = Outgoing::SelfInfo(user.clone());
+                    return self.send_or_queue(conversation_id, outgoing);
                 }
             }
             ShowChannel(conversation_id) => {
@@ -410,7 +931,14 @@ impl Device {
             // jonesy:allow(overflow) via iced_runtime::task::Task::chain
             ChannelMsg(conversation_id, msg) => {
                 if let Some(channel_view) = self.conversations.get_mut(&conversation_id) {
-                    return channel_view.update(msg);
+                    let all = ConversationSettings::default();
+                    let unread_before = channel_view.unread_count(true, true, &all, None);
+                    let task = channel_view.update(msg);
+                    // Only save when the seen state of messages has changed
+                    if channel_view.unread_count(true, true, &all, None) != unread_before {
+                        return task.chain(self.save_conversation(&conversation_id));
+                    }
+                    return task;
                 } else {
                     eprintln!("Error: No channel for ChannelMsg");
                 }
@@ -423,123 +951,823 @@ impl Device {
             }
             StopForwardingMessage => self.forwarding_message = None,
             ClearFilter => self.filter.clear(),
+            HistoryLoaded(device, history) => {
+                // Another device has been connected to since the load was started
+                if !self.connected_to(&device) {
+                    return Task::none();
+                }
+                self.restore_history(history);
+                return self.save_unsaved_conversations();
+            }
+            ToggleMessageSearch => self.search_messages = !self.search_messages,
+            KnownNodesLoaded(device, known_nodes) => {
+                // Another device has been connected to since the load was started
+                if !self.connected_to(&device) {
+                    return Task::none();
+                }
+                self.known_nodes.merge(known_nodes);
+                self.known_nodes_loaded = true;
+                self.add_known_nodes();
+                // Keys reported before the saved nodes were loaded are checked against the keys
+                // pinned on earlier connections
+                let node_ids: Vec<NodeId> = self
+                    .known_nodes
+                    .values()
+                    .map(|known| known.node_info.node_id)
+                    .collect();
+                let key_warnings: Vec<Task<Message>> = node_ids
+                    .into_iter()
+                    .map(|node_id| self.check_node_key(node_id).1)
+                    .collect();
+                return self.save_known_nodes().chain(Task::batch(key_warnings));
+            }
+            SaveKnownNodes => {
+                self.known_nodes_save_scheduled = false;
+                if let Connected(device, _) = &self.connection_state {
+                    let device = device.clone();
+                    return self.flush_known_nodes(&device);
+                }
+            }
+            TracksLoaded(device, tracks) => {
+                // Another device has been connected to since the load was started
+                if !self.connected_to(&device) {
+                    return Task::none();
+                }
+                self.tracks.merge(tracks);
+                self.tracks_loaded = true;
+                return self.save_tracks();
+            }
+            SaveTracks => {
+                self.tracks_save_scheduled = false;
+                if let Connected(device, _) = &self.connection_state {
+                    let device = device.clone();
+                    return self.flush_tracks(&device);
+                }
+            }
+            OutboxLoaded(device, queued) => {
+                self.outbox.restore(&device, queued);
+                if self.connected_to(&device) {
+                    return self.flush_outbox(&device);
+                }
+            }
+            QueuedMessageFailed(device, queued, error) => {
+                self.outbox.requeue(&device, queued);
+                return Task::batch([
+                    save_outbox(&device, &self.outbox),
+                    Task::done(AppError(
+                        "Connection Error".to_string(),
+                        error,
+                        TimeStamp::now(),
+                    )),
+                ]);
+            }
+            ShowMessage(conversation_id, message_id) => {
+                // Defer the scroll until the conversation is being shown and its scrollable exists
+                // jonesy:allow(overflow) via iced_runtime::task::Task::chain
+                return self
+                    .channel_change(Some(conversation_id))
+                    .chain(Task::perform(empty(), move |_| {
+                        DeviceViewEvent(ChannelMsg(
+                            conversation_id,
+                            ChannelViewMessage::ScrollToMessage(message_id),
+                        ))
+                    }));
+            }
         }
 
         Task::none()
     }
 
-    /// Send a SubscriberMessage to the device_subscription, if successful, then send `success_message`
-    /// and report any errors
-    fn device_send(&mut self, command: DeviceCommand, success_message: Message) -> Task<Message> {
-        // Either we are connected and know the radio type of we are disconnected and being asked
-        // to connect to a specific type of radio
-        let radio_type = if let Connected(_, radio_type) = self.connection_state {
-            radio_type
-        } else if let Connect(_, radio_type) = &command {
-            *radio_type
-        } else {
-            return Task::perform(empty(), |_| DeviceViewEvent(SubscriptionMessage(NotReady)));
-        };
-
-        let subscription_sender: Option<Sender<DeviceCommand>> = match radio_type {
-            #[cfg(feature = "meshtastic")]
-            RadioType::Meshtastic => self.meshtastic_sender.clone(),
-            #[cfg(feature = "meshcore")]
-            RadioType::MeshCore => self.meshcore_sender.clone(),
-        };
+    /// Ask the subscription for `radio_type` to connect to `ble_device`, if successful, then send
+    /// `success_message`
+    fn connect(
+        &mut self,
+        ble_device: DeviceIdentifier,
+        radio_type: RadioType,
+        success_message: Message,
+    ) -> Task<Message> {
+        // There is nothing new to capture when replaying an earlier capture
+        let capture =
+            self.capture_traffic && !matches!(ble_device, DeviceIdentifier::Replay { .. });
+        self.device_send(Connect(ble_device, radio_type, capture), success_message)
+    }
 
-        if let Some(sender) = subscription_sender {
-            let future = async move { sender.send(command).await };
-            Task::perform(future, |result| match result {
-                Ok(()) => success_message,
-                Err(e) => AppError(
-                    "Connection Error".to_string(),
-                    format!("{:?}", e),
-                    TimeStamp::now(),
-                ),
-            })
-        } else {
-            Task::perform(empty(), |_| DeviceViewEvent(SubscriptionMessage(NotReady)))
+    /// The device connected to, or being connected to, disconnected from or reconnected to
+    pub fn device(&self) -> Option<&DeviceIdentifier> {
+        match &self.connection_state {
+            Connected(device, _) | Connecting(device) | Disconnecting(device) => Some(device),
+            Disconnected(device, _) => device.as_ref(),
         }
     }
 
-    /// Save the new channel (which could be None)
-    fn channel_change(&mut self, conversation_id: Option<ConversationId>) -> Task<Message> {
-        if self.viewing_conversation != conversation_id {
-            self.viewing_conversation = conversation_id;
-
-            if let Connected(ble_device, radio_type) = &self.connection_state {
-                let device = ble_device.clone();
-                let radio = *radio_type;
+    /// Return true if `device` is the device connected to
+    fn connected_to(&self, device: &DeviceIdentifier) -> bool {
+        matches!(&self.connection_state, Connected(connected, _) if connected == device)
+    }
+
+    /// Forget everything about the radio that was connected, once it is not being reconnected to
+    fn clear_device_state(&mut self) {
+        self.conversations.clear();
+        self.nodes.clear();
+        self.channels.clear();
+        self.saved_history.clear();
+        self.history_loaded = false;
+        self.unsaved_conversations.clear();
+        self.known_nodes.clear();
+        self.known_nodes_loaded = false;
+        self.known_nodes_unsaved = false;
+        self.known_nodes_save_scheduled = false;
+        self.tracks.clear();
+        self.tracks_loaded = false;
+        self.tracks_save_scheduled = false;
+        self.my_node_id = None;
+        self.viewing_conversation = None;
+        self.traceroute = None;
+        self.neighbours.clear();
+        self.telemetry.clear();
+        self.waypoint_editor = None;
+        self.showing_waypoints = false;
+        self.channel_settings.clear();
+        self.channel_sharing = None;
+        self.ignored_nodes.clear();
+        self.auto_resent.clear();
+    }
+
+    /// Stop trying to reconnect to a radio whose link dropped, forget it and go back to the list
+    /// of devices
+    fn stop_reconnect(&mut self) -> Task<Message> {
+        self.reconnect = None;
+        self.clear_device_state();
+        Task::perform(empty(), |_| Navigation(DeviceListView))
+    }
+
+    /// Start trying to reconnect to a radio whose link dropped, going back to `conversation_id`
+    /// once reconnected
+    fn start_reconnect(
+        &mut self,
+        device: DeviceIdentifier,
+        radio_type: RadioType,
+        conversation_id: Option<ConversationId>,
+    ) -> Task<Message> {
+        self.reconnect = Some(Reconnect {
+            device,
+            radio_type,
+            conversation_id,
+            attempt: 1,
+        });
+        Self::schedule_reconnect(1)
+    }
 
-                let input_to_focus = match self.viewing_conversation {
-                    None => CHANNEL_SEARCH_ID,
-                    Some(_channel) => MESSAGE_INPUT_ID,
-                };
+    /// Wait, backing off for each attempt, then make the numbered `attempt` to reconnect
+    fn schedule_reconnect(attempt: u32) -> Task<Message> {
+        let delay = reconnect_delay(attempt);
+        Task::perform(async move { tokio::time::sleep(delay).await }, move |_| {
+            DeviceViewEvent(ReconnectAttempt(attempt))
+        })
+    }
 
-                // Focus on the correct input text and then save the config change
+    /// An attempt to reconnect failed, so schedule the next one, or give up after the last one
+    /// and report why the last one failed
+    fn retry_reconnect(&mut self, detail: String) -> Task<Message> {
+        match self.reconnect.as_mut() {
+            Some(reconnect) if reconnect.attempt < RECONNECT_ATTEMPTS => {
+                reconnect.attempt += 1;
+                Self::schedule_reconnect(reconnect.attempt)
+            }
+            Some(reconnect) => {
+                let summary = format!("Could not reconnect to {}", reconnect.device.name());
                 // jonesy:allow(overflow) via iced_runtime::task::Task::chain
-                return operation::focus(input_to_focus).chain(Task::perform(empty(), move |_| {
-                    Message::DeviceAndChannelConfigChange(Some((device, radio)), conversation_id)
-                }));
+                self.stop_reconnect()
+                    .chain(Task::perform(empty(), move |_| {
+                        AppError(summary.clone(), detail.clone(), TimeStamp::now())
+                    }))
             }
+            None => Task::none(),
         }
-        Task::none()
     }
 
-    /// Called when the user selects to alias a node name
-    fn start_editing_alias(&mut self, node_id: NodeId) {
-        self.editing_alias = Some(node_id);
-        self.alias = String::new();
+    /// Find the conversation the message with `message_id` is in. The radio does not always
+    /// know which conversation an acknowledgement is for, so look in all of them if need be.
+    fn find_message(
+        &self,
+        conversation_id: &ConversationId,
+        message_id: &MessageId,
+    ) -> Option<ConversationId> {
+        if self
+            .conversations
+            .get(conversation_id)
+            .is_some_and(|conversation| conversation.message(message_id).is_some())
+        {
+            return Some(*conversation_id);
+        }
+        self.conversations
+            .iter()
+            .find(|(_, conversation)| conversation.message(message_id).is_some())
+            .map(|(conversation_id, _)| *conversation_id)
     }
 
-    /// Called from above when we have finished editing the alias
-    pub fn stop_editing_alias(&mut self) {
-        self.editing_alias = None;
-        self.alias = String::new();
+    /// Return how far a message has got in being delivered, if it can be found
+    fn delivery(
+        &self,
+        conversation_id: &ConversationId,
+        message_id: &MessageId,
+    ) -> Option<DeliveryState> {
+        let conversation_id = self.find_message(conversation_id, message_id)?;
+        self.conversations
+            .get(&conversation_id)
+            .and_then(|conversation| conversation.message(message_id))
+            .map(MCMessage::delivery)
     }
 
-    /// Process a new message arrival on this device
-    pub fn new_message(
+    /// Set how far a message has got in being delivered, returning the conversation it is in
+    fn set_delivery(
         &mut self,
-        conversation_id: &ConversationId,
-        new_message: MCMessage,
+        conversation_id: ConversationId,
+        message_id: MessageId,
+        delivery: DeliveryState,
+    ) -> Option<ConversationId> {
+        let conversation_id = self.find_message(&conversation_id, &message_id)?;
+        self.conversations
+            .get_mut(&conversation_id)
+            .map(|conversation| conversation.set_delivery(message_id, delivery))
+            .map(|_| conversation_id)
+    }
+
+    /// Mark a message as failed, and send it again if set to do so and it hasn't been already
+    fn message_failed(
+        &mut self,
+        conversation_id: ConversationId,
+        message_id: MessageId,
     ) -> Task<Message> {
-        if let Some(conversation) = self.conversations.get_mut(conversation_id) {
-            conversation.new_message(new_message, &self.history_length)
+        let Some(conversation_id) =
+            self.set_delivery(conversation_id, message_id, DeliveryState::Failed)
+        else {
+            return Task::none();
+        };
+
+        let is_text = self
+            .conversations
+            .get(&conversation_id)
+            .and_then(|conversation| conversation.message(&message_id))
+            .and_then(|message| message.message().text())
+            .is_some();
+
+        let resent_before = self.auto_resent.remove(&message_id);
+        if self.auto_resend && !resent_before && is_text {
+            return self.resend(conversation_id, message_id, true);
+        }
+
+        self.save_conversation(&conversation_id)
+    }
+
+    /// Send a message again with the same text and reply target, replacing the original.
+    /// If sent again `automatically`, the radio reports the new id so it is not sent a third time
+    fn resend(
+        &mut self,
+        conversation_id: ConversationId,
+        message_id: MessageId,
+        automatically: bool,
+    ) -> Task<Message> {
+        let Some(conversation) = self.conversations.get_mut(&conversation_id) else {
+            return Task::none();
+        };
+        let (text, reply_to_id) = match conversation.message(&message_id).map(MCMessage::message) {
+            Some(NewTextMessage(text)) => (text.clone(), None),
+            Some(TextMessageReply(reply_to_id, text)) => (text.clone(), Some(*reply_to_id)),
+            _ => return Task::none(),
+        };
+        let outgoing = if automatically {
+            Outgoing::Resend(text, reply_to_id)
         } else {
-            eprintln!(
-                "No channel for MCMessage: conversation_id = {:?}",
-                conversation_id
-            );
-            Task::none()
+            Outgoing::Text(text, reply_to_id)
+        };
+        conversation.remove_message(&message_id);
+
+        let save_task = self.save_conversation(&conversation_id);
+        // jonesy:allow(overflow) via iced_runtime::task::Task::chain
+        save_task.chain(self.send_or_queue(conversation_id, outgoing))
+    }
+
+    /// Send `outgoing` to `conversation_id` if the radio is connected, otherwise queue it in the
+    /// [Outbox] to be sent once the radio last used is connected again
+    fn send_or_queue(
+        &mut self,
+        conversation_id: ConversationId,
+        outgoing: Outgoing,
+    ) -> Task<Message> {
+        if let Connected(_, radio_type) = self.connection_state
+            && self.subscription_sender(radio_type).is_some()
+        {
+            let command = QueuedMessage::new(conversation_id, outgoing).command();
+            return self.device_send(command, Message::None);
+        }
+
+        let device = match &self.connection_state {
+            Disconnected(Some(device), _)
+            | Connecting(device)
+            | Connected(device, _)
+            | Disconnecting(device) => device.clone(),
+            Disconnected(None, _) => {
+                return Task::perform(empty(), |_| DeviceViewEvent(SubscriptionMessage(NotReady)));
+            }
+        };
+        self.outbox.queue(&device, conversation_id, outgoing);
+        save_outbox(&device, &self.outbox)
+    }
+
+    /// Send the messages queued in the [Outbox] for `device`, now that it is connected, in the
+    /// order they were queued. They stay queued until the radio can be sent to, and any that
+    /// can't be sent to it are queued again.
+    fn flush_outbox(&mut self, device: &DeviceIdentifier) -> Task<Message> {
+        let Connected(connected, radio_type) = &self.connection_state else {
+            return Task::none();
+        };
+        if connected != device || self.subscription_sender(*radio_type).is_none() {
+            return Task::none();
+        }
+
+        let queued = self.outbox.take(device);
+        if queued.is_empty() {
+            return Task::none();
         }
+
+        let mut flush = save_outbox(device, &self.outbox);
+        for queued in queued {
+            let device = device.clone();
+            let command = queued.clone().command();
+            // jonesy:allow(overflow) via iced_runtime::task::Task::chain
+            flush = flush.chain(self.device_send_or(command, Message::None, move |error| {
+                DeviceViewEvent(QueuedMessageFailed(device, queued, error))
+            }));
+        }
+        flush
     }
 
-    /// Process an event sent by the subscription connected to the radio
-    fn process_device_event(&mut self, subscription_event: DeviceEvent) -> Task<Message> {
-        match subscription_event {
-            ConnectingEvent(mac_address) => {
-                self.connection_state = Connecting(mac_address);
-                Task::none()
+    /// Get the sender to the subscription for `radio_type`, if it is ready
+    fn subscription_sender(&self, radio_type: RadioType) -> Option<Sender<DeviceCommand>> {
+        match radio_type {
+            #[cfg(feature = "meshtastic")]
+            RadioType::Meshtastic => self.meshtastic_sender.clone(),
+            #[cfg(feature = "meshcore")]
+            RadioType::MeshCore => self.meshcore_sender.clone(),
+            #[cfg(feature = "sim")]
+            RadioType::Sim => self.sim_sender.clone(),
+        }
+    }
+
+    /// Get the messages waiting to be sent once the radio is connected
+    pub fn outbox(&self) -> &Outbox {
+        &self.outbox
+    }
+
+    /// A dialog showing the traceroute underway, if there is one
+    pub fn traceroute_view<'a>(&self, config: &Config) -> Option<Element<'a, Message>> {
+        let traceroute = self.traceroute.as_ref()?;
+        Some(traceroute.view(|node_id| {
+            if Some(node_id) == self.my_node_id {
+                return "Me".into();
             }
-            ConnectedEvent(ble_device, radio_type) => {
-                self.connection_state = Connected(ble_device.clone(), radio_type);
-                match self.viewing_conversation {
-                    None => {
-                        let conversation_id = self.viewing_conversation;
-                        let device = ble_device.clone();
-                        Task::perform(empty(), move |_| {
-                            Message::DeviceAndChannelConfigChange(
-                                Some((device, radio_type)),
-                                conversation_id,
-                            )
-                        })
-                    }
-                    Some(conversation_id) => Task::perform(empty(), move |_| {
-                        DeviceViewEvent(ShowChannel(Some(conversation_id)))
-                    }),
-                }
+            self.aliased_long_name(config, node_id)
+                .map(str::to_string)
+                .unwrap_or(format!("!{:08x}", u64::from(node_id)))
+        }))
+    }
+
+    /// The latest version of each waypoint shared on the device that has not expired at `now`,
+    /// in order of their names
+    fn active_waypoints(&self, now: TimeStamp) -> Vec<&MCWaypoint> {
+        let mut latest: HashMap<u32, (TimeStamp, &MCWaypoint)> = HashMap::new();
+        for (time, waypoint) in self
+            .conversations
+            .values()
+            .flat_map(Conversation::waypoints)
+        {
+            if latest
+                .get(&waypoint.id)
+                .is_none_or(|(latest_time, _)| *latest_time <= time)
+            {
+                latest.insert(waypoint.id, (time, waypoint));
+            }
+        }
+
+        let mut waypoints: Vec<&MCWaypoint> = latest
+            .into_values()
+            .map(|(_, waypoint)| waypoint)
+            .filter(|waypoint| !waypoint.is_expired(now))
+            .collect();
+        waypoints.sort_by(|a, b| a.name.cmp(&b.name));
+        waypoints
+    }
+
+    /// A dialog composing a waypoint, or listing the waypoints shared, if either is open
+    pub fn waypoints_view<'a>(&self, config: &Config) -> Option<Element<'a, Message>> {
+        if let Some(editor) = &self.waypoint_editor {
+            let destination = self.conversation_name(config, editor.conversation_id());
+            return Some(editor.view(destination));
+        }
+        self.showing_waypoints
+            .then(|| waypoints_view(&self.active_waypoints(TimeStamp::now())))
+    }
+
+    /// A dialog sharing the channels on the radio, and joining others, if it is open
+    pub fn channel_sharing_view<'a>(&self) -> Option<Element<'a, Message>> {
+        self.channel_sharing.as_ref().map(|channel_sharing| {
+            let current: Vec<MCChannelSettings> = self.channel_settings.values().cloned().collect();
+            channel_sharing.view(&current)
+        })
+    }
+
+    /// Find the node with `user`, as the same user info is reported by the node
+    fn user_node_id(&self, user: &MCUser) -> Option<NodeId> {
+        self.nodes
+            .values()
+            .find(|node| {
+                node.user
+                    .as_ref()
+                    .is_some_and(|node_user| node_user.id == user.id)
+            })
+            .map(|node| node.node_id)
+    }
+
+    /// The nodes the node with `user` hears directly, as last reported by it
+    fn neighbours(&self, user: &MCUser) -> &[MCNeighbour] {
+        self.user_node_id(user)
+            .and_then(|node_id| self.neighbours.get(&node_id))
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    /// Ask the node with `user` for its neighbours, if the radio does not get them broadcast
+    pub fn request_neighbours(&mut self, user: &MCUser) -> Task<Message> {
+        match (&self.connection_state, self.user_node_id(user)) {
+            #[cfg(feature = "meshcore")]
+            (Connected(_, RadioType::MeshCore), Some(node_id))
+                if Some(node_id) != self.my_node_id =>
+            {
+                self.device_send(DeviceCommand::RequestNeighbours(node_id), Message::None)
+            }
+            _ => Task::none(),
+        }
+    }
+
+    /// A list of the nodes the node with `user` hears directly, for the node's details
+    pub fn neighbours_view<'a>(&self, user: &MCUser, config: &Config) -> Element<'a, Message> {
+        let neighbours = self.neighbours(user);
+        let mut column = Column::new()
+            .spacing(2)
+            .push(text(format!("Neighbours: {}", neighbours.len())));
+
+        let now = TimeStamp::now();
+        for neighbour in neighbours {
+            let name = self
+                .aliased_long_name(config, neighbour.node_id)
+                .map(str::to_string)
+                .unwrap_or(neighbour.node_id.to_string());
+            let snr = neighbour
+                .snr
+                .map(|snr| format!("{snr:.2} dB"))
+                .unwrap_or_default();
+            column = column.push(
+                Row::new()
+                    .spacing(8)
+                    .push(text(format!("  {name}")).width(Fill))
+                    .push(text(snr).color(TIME_TEXT_COLOR))
+                    .push(
+                        text(heard_ago(now, neighbour.last_heard))
+                            .size(TIME_TEXT_SIZE)
+                            .color(TIME_TEXT_COLOR),
+                    ),
+            );
+        }
+        column.into()
+    }
+
+    /// The latest telemetry reported by the node with `user`, and its history, if it reported any
+    pub fn telemetry_view<'a>(&self, user: &MCUser) -> Element<'a, Message> {
+        self.user_node_id(user)
+            .and_then(|node_id| self.telemetry.get(&node_id))
+            .map(TelemetryHistory::view)
+            .unwrap_or(Column::new().into())
+    }
+
+    /// Return true if the connected radio can trace the route to a node
+    fn can_traceroute(&self) -> bool {
+        match self.connection_state {
+            #[cfg(feature = "meshtastic")]
+            Connected(_, RadioType::Meshtastic) => true,
+            #[cfg(feature = "sim")]
+            Connected(_, RadioType::Sim) => true,
+            _ => false,
+        }
+    }
+
+    /// Return true if the connected radio keeps a list of nodes to ignore
+    fn can_ignore_nodes(&self) -> bool {
+        match self.connection_state {
+            #[cfg(feature = "meshtastic")]
+            Connected(_, RadioType::Meshtastic) => true,
+            #[cfg(feature = "sim")]
+            Connected(_, RadioType::Sim) => true,
+            _ => false,
+        }
+    }
+
+    /// Return true if the connected radio's channels can be shared and replaced using URLs
+    fn can_share_channels(&self) -> bool {
+        match self.connection_state {
+            #[cfg(feature = "meshtastic")]
+            Connected(_, RadioType::Meshtastic) => true,
+            _ => false,
+        }
+    }
+
+    /// Return true if the connected radio can send waypoints
+    pub fn can_send_waypoints(&self) -> bool {
+        match self.connection_state {
+            #[cfg(feature = "meshtastic")]
+            Connected(_, RadioType::Meshtastic) => true,
+            #[cfg(feature = "sim")]
+            Connected(_, RadioType::Sim) => true,
+            _ => false,
+        }
+    }
+
+    /// Send a SubscriberMessage to the device_subscription, if successful, then send `success_message`
+    /// and report any errors
+    fn device_send(&mut self, command: DeviceCommand, success_message: Message) -> Task<Message> {
+        self.device_send_or(command, success_message, |error| {
+            AppError("Connection Error".to_string(), error, TimeStamp::now())
+        })
+    }
+
+    /// Send a command to the radio as [Device::device_send] does, returning the message made by
+    /// `failure`, from the error, if it could not be sent
+    fn device_send_or(
+        &mut self,
+        command: DeviceCommand,
+        success_message: Message,
+        failure: impl FnOnce(String) -> Message + Send + 'static,
+    ) -> Task<Message> {
+        // Either we are connected and know the radio type of we are disconnected and being asked
+        // to connect to a specific type of radio
+        let radio_type = if let Connected(_, radio_type) = self.connection_state {
+            radio_type
+        } else if let Connect(_, radio_type, _) = &command {
+            *radio_type
+        } else {
+            return Task::perform(empty(), |_| DeviceViewEvent(SubscriptionMessage(NotReady)));
+        };
+
+        if let Some(sender) = self.subscription_sender(radio_type) {
+            let future = async move { sender.send(command).await };
+            Task::perform(future, |result| match result {
+                Ok(()) => success_message,
+                Err(e) => failure(format!("{:?}", e)),
+            })
+        } else {
+            Task::perform(empty(), |_| DeviceViewEvent(SubscriptionMessage(NotReady)))
+        }
+    }
+
+    /// Save the new channel (which could be None)
+    fn channel_change(&mut self, conversation_id: Option<ConversationId>) -> Task<Message> {
+        if self.viewing_conversation != conversation_id {
+            // Don't leave a message highlighted in the conversation we are leaving
+            if let Some(previous) = &self.viewing_conversation
+                && let Some(conversation) = self.conversations.get_mut(previous)
+            {
+                conversation.cancel_interactive();
+            }
+            self.viewing_conversation = conversation_id;
+
+            if let Connected(ble_device, radio_type) = &self.connection_state {
+                let device = ble_device.clone();
+                let radio = *radio_type;
+
+                let input_to_focus = match self.viewing_conversation {
+                    None => CHANNEL_SEARCH_ID,
+                    Some(_channel) => MESSAGE_INPUT_ID,
+                };
+
+                // Focus on the correct input text and then save the config change
+                // jonesy:allow(overflow) via iced_runtime::task::Task::chain
+                return operation::focus(input_to_focus).chain(Task::perform(empty(), move |_| {
+                    Message::DeviceAndChannelConfigChange(Some((device, radio)), conversation_id)
+                }));
+            }
+        }
+        Task::none()
+    }
+
+    /// Called when the user selects to alias a node name
+    fn start_editing_alias(&mut self, node_id: NodeId) {
+        self.editing_alias = Some(node_id);
+        self.alias = String::new();
+    }
+
+    /// Called from above when we have finished editing the alias
+    pub fn stop_editing_alias(&mut self) {
+        self.editing_alias = None;
+        self.alias = String::new();
+    }
+
+    /// Restore the saved history into the conversations that exist, and keep the rest until
+    /// the radio tells us about the channel or node they belong to
+    fn restore_history(&mut self, history: HashMap<ConversationId, Vec<MCMessage>>) {
+        for (conversation_id, messages) in history {
+            if let Some(conversation) = self.conversations.get_mut(&conversation_id) {
+                conversation.restore_history(messages, &self.history_length);
+            } else {
+                self.saved_history.insert(conversation_id, messages);
+            }
+        }
+    }
+
+    /// Create a [Conversation] if it doesn't already exist, restoring any saved history for it
+    fn add_conversation(&mut self, conversation_id: ConversationId, my_node_num: NodeId) {
+        if !self.conversations.contains_key(&conversation_id) {
+            let mut conversation = Conversation::new(conversation_id, my_node_num);
+            if let Some(messages) = self.saved_history.remove(&conversation_id) {
+                conversation.restore_history(messages, &self.history_length);
+            }
+            self.conversations.insert(conversation_id, conversation);
+        }
+    }
+
+    /// Save the messages of a conversation to disk, in the history for the connected device.
+    /// Until the saved history has been loaded, just note that it needs saving.
+    fn save_conversation(&mut self, conversation_id: &ConversationId) -> Task<Message> {
+        if !self.history_loaded {
+            self.unsaved_conversations.insert(*conversation_id);
+            return Task::none();
+        }
+
+        if let Connected(device, _) = &self.connection_state
+            && let Some(conversation) = self.conversations.get(conversation_id)
+        {
+            save_conversation(device, *conversation_id, conversation.history())
+        } else {
+            Task::none()
+        }
+    }
+
+    /// The saved history has been loaded and merged, so save the conversations that changed
+    /// before it was
+    fn save_unsaved_conversations(&mut self) -> Task<Message> {
+        self.history_loaded = true;
+        let unsaved: Vec<ConversationId> = self.unsaved_conversations.drain().collect();
+        Task::batch(
+            unsaved
+                .iter()
+                .map(|conversation_id| self.save_conversation(conversation_id))
+                .collect::<Vec<_>>(),
+        )
+    }
+
+    /// Export all the messages of a conversation to a file, in the requested [ExportFormat]
+    pub fn export_conversation(
+        &self,
+        conversation_id: ConversationId,
+        format: ExportFormat,
+        config: &Config,
+    ) -> Task<Message> {
+        if let Some(conversation) = self.conversations.get(&conversation_id) {
+            let entries: Vec<ExportEntry> = conversation
+                .history()
+                .iter()
+                .map(|message| ExportEntry::new(message, &self.nodes, &config.aliases))
+                .collect();
+            export_conversation(
+                &self.conversation_name(config, conversation_id),
+                &entries,
+                format,
+            )
+        } else {
+            Task::none()
+        }
+    }
+
+    /// Return the name of a channel, or the (aliased) name of a node, for a [ConversationId]
+    fn conversation_name(&self, config: &Config, conversation_id: ConversationId) -> String {
+        match conversation_id {
+            ConversationId::Channel(channel_index) => self
+                .channels
+                .get(usize::from(channel_index))
+                .map(|channel| channel.name.clone())
+                .unwrap_or_else(|| format!("Channel {}", usize::from(channel_index))),
+            Node(node_id) => self
+                .aliased_long_name(config, node_id)
+                .map(str::to_string)
+                .unwrap_or_else(|| node_id.to_string()),
+        }
+    }
+
+    /// A task to notify the user of a message that mentions them, which is only shown if the
+    /// conversation's settings in the config are to notify of mentions only and it is not muted
+    fn mention_notification(
+        &self,
+        conversation_id: &ConversationId,
+        message: &MCMessage,
+    ) -> Task<Message> {
+        if Some(message.from()) != self.my_node_id
+            && !self.ignored_nodes.contains_key(&message.from())
+            && let Some(me) = &self.my_user
+            && message.mentions(me)
+            && let Some(device) = self.device()
+        {
+            let device = device.clone();
+            let conversation_id = *conversation_id;
+            let summary = format!("{} mentioned you", short_name(&self.nodes, message.from()));
+            let detail = message.message().to_string();
+            let timestamp = message.time();
+            Task::perform(empty(), move |_| {
+                Message::MentionNotification(device, conversation_id, summary, detail, timestamp)
+            })
+        } else {
+            Task::none()
+        }
+    }
+
+    /// Process a new message arrival on this device
+    pub fn new_message(
+        &mut self,
+        conversation_id: &ConversationId,
+        new_message: MCMessage,
+    ) -> Task<Message> {
+        let mention_task = self.mention_notification(conversation_id, &new_message);
+        if let Some(conversation) = self.conversations.get_mut(conversation_id) {
+            // Messages from ignored nodes are hidden, so shouldn't count as unread
+            let mut new_message = new_message;
+            if self.ignored_nodes.contains_key(&new_message.from()) {
+                new_message.mark_seen();
+            }
+            conversation
+                .new_message(new_message, &self.history_length)
+                .chain(self.save_conversation(conversation_id))
+                .chain(mention_task)
+        } else {
+            eprintln!(
+                "No channel for MCMessage: conversation_id = {:?}",
+                conversation_id
+            );
+            Task::none()
+        }
+    }
+
+    /// Process an event sent by the subscription connected to the radio
+    fn process_device_event(&mut self, subscription_event: DeviceEvent) -> Task<Message> {
+        match subscription_event {
+            ConnectingEvent(mac_address) => {
+                self.connection_state = Connecting(mac_address);
+                Task::none()
+            }
+            ConnectedEvent(ble_device, radio_type) => {
+                self.connection_state = Connected(ble_device.clone(), radio_type);
+                self.disconnect_requested = false;
+                self.history_loaded = false;
+                self.unsaved_conversations.clear();
+                self.known_nodes_loaded = false;
+                self.tracks_loaded = false;
+                let history_task = load_history(&ble_device);
+                let known_nodes_task = load_known_nodes(&ble_device);
+                let tracks_task = load_tracks(&ble_device);
+                let task = if let Some(reconnect) = self.reconnect.take() {
+                    // Go back to the conversation that was open when the link dropped
+                    let conversation_id = reconnect.conversation_id;
+                    Task::perform(empty(), move |_| {
+                        Navigation(View::DeviceView(conversation_id))
+                    })
+                } else {
+                    match self.viewing_conversation {
+                        None => {
+                            let conversation_id = self.viewing_conversation;
+                            let device = ble_device.clone();
+                            Task::perform(empty(), move |_| {
+                                Message::DeviceAndChannelConfigChange(
+                                    Some((device, radio_type)),
+                                    conversation_id,
+                                )
+                            })
+                        }
+                        Some(conversation_id) => Task::perform(empty(), move |_| {
+                            DeviceViewEvent(ShowChannel(Some(conversation_id)))
+                        }),
+                    }
+                };
+                // Messages queued since the app started include any saved before, otherwise send
+                // the ones saved when the app was last used
+                let outbox_task = if self.outbox.has_device(&ble_device) {
+                    self.flush_outbox(&ble_device)
+                } else {
+                    load_outbox(&ble_device)
+                };
+                Task::batch([
+                    task,
+                    history_task,
+                    known_nodes_task,
+                    tracks_task,
+                    outbox_task,
+                ])
             }
             DisconnectingEvent(mac_address) => {
                 self.connection_state = Disconnecting(mac_address);
@@ -549,13 +1777,39 @@ impl Device {
                 if self.exit_pending {
                     std::process::exit(0);
                 }
+                // Save the changes to the known nodes and tracks not saved yet, before they may be
+                // forgotten
+                let flush_task = Task::batch([self.flush_known_nodes(&id), self.flush_tracks(&id)]);
+                // The link to a connected radio dropped without the user asking to disconnect
+                let dropped = match &self.connection_state {
+                    Connected(device, radio_type)
+                        if self.auto_reconnect && !self.disconnect_requested =>
+                    {
+                        Some((device.clone(), *radio_type))
+                    }
+                    _ => None,
+                };
+                self.disconnect_requested = false;
                 self.connection_state = Disconnected(Some(id), None);
-                self.conversations.clear();
-                self.nodes.clear();
-                self.channels.clear();
-                self.my_node_id = None;
-                self.viewing_conversation = None;
-                Task::perform(empty(), |_| Navigation(DeviceListView))
+                // The dialogs that need the radio can't be used until it is reconnected
+                self.traceroute = None;
+                self.channel_sharing = None;
+                if let Some((device, radio_type)) = dropped {
+                    // Stay in the conversation, so messages can still be written, and queued to be
+                    // sent once reconnected
+                    let conversation_id = self.viewing_conversation;
+                    return flush_task.chain(self.start_reconnect(
+                        device,
+                        radio_type,
+                        conversation_id,
+                    ));
+                }
+                if self.reconnect.is_some() {
+                    // Stay where we are while the reconnect is underway
+                    return flush_task;
+                }
+                self.clear_device_state();
+                flush_task.chain(Task::perform(empty(), |_| Navigation(DeviceListView)))
             }
             #[allow(unused_variables)]
             Ready(sender, radio_type) => {
@@ -564,11 +1818,17 @@ impl Device {
                     RadioType::Meshtastic => self.meshtastic_sender = Some(sender),
                     #[cfg(feature = "meshcore")]
                     RadioType::MeshCore => self.meshcore_sender = Some(sender),
+                    #[cfg(feature = "sim")]
+                    RadioType::Sim => self.sim_sender = Some(sender),
                 }
                 Task::none()
             }
             ConnectionError(id, summary, detail) => {
                 self.connection_state = Disconnected(Some(id), Some(summary.clone()));
+                // Errors from the attempts to reconnect are only reported if they all fail
+                if self.reconnect.is_some() {
+                    return self.retry_reconnect(detail);
+                }
                 Task::perform(empty(), |_| Navigation(DeviceListView))
                     // jonesy:allow(overflow) via iced_runtime::task::Task::chain
                     .chain(Task::perform(empty(), move |_| {
@@ -593,6 +1853,8 @@ impl Device {
             }
             MyNodeNum(my_node_num) => {
                 self.my_node_id = Some(my_node_num);
+                // Nodes can only be added once we know our own node number
+                self.add_known_nodes();
                 Task::none()
             }
             MyUserInfo(my_user_info) => {
@@ -607,16 +1869,29 @@ impl Device {
                 self.add_channel(channel);
                 Task::none()
             }
-            NewNode(node_info) => {
-                self.add_node(node_info);
+            NewChannelSettings(index, settings) => {
+                self.channel_settings.insert(index, settings);
                 Task::none()
             }
+            NewNode(mut node_info) => {
+                let node_id = node_info.node_id;
+                let new_node = self.known_nodes.seen(&mut node_info, TimeStamp::now());
+                self.add_node(node_info);
+                let (key_stored, key_warning) = self.check_node_key(node_id);
+                if new_node || key_stored {
+                    self.save_known_nodes().chain(key_warning)
+                } else {
+                    key_warning
+                }
+            }
             RadioNotification(message, timestamp) => Task::perform(empty(), move |_| {
                 Message::AppNotification("Radio Notification".to_string(), message, timestamp)
             }),
-            MCMessageReceived(conversation_id, id, from, mc_content, timestamp) => {
-                let new_message = MCMessage::new(id, from, mc_content, timestamp);
+            MCMessageReceived(conversation_id, id, from, mc_content, timestamp, link_quality) => {
+                let mut new_message = MCMessage::new(id, from, mc_content, timestamp);
+                new_message.set_link_quality(link_quality);
                 self.new_message(&conversation_id, new_message)
+                    .chain(self.node_heard(from))
             }
             DeviceBatteryLevel(level) => {
                 self.battery_level = level;
@@ -624,42 +1899,94 @@ impl Device {
             }
             NewNodeInfo(conversation_id, id, from, mc_user, timestamp) => {
                 self.update_node_user(from, &mc_user);
+                self.known_nodes.update_user(from, &mc_user);
+                // Hearing from a known node saves the known nodes, including any key pinned
+                let (_, key_warning) = self.check_node_key(from);
+                let heard_task = self.node_heard(from).chain(key_warning);
 
                 if self.show_user_updates {
-                    if let Some(channel_view) = &mut self.conversations.get_mut(&conversation_id) {
+                    if self.conversations.contains_key(&conversation_id) {
                         let new_message = MCMessage::new(id, from, UserMessage(mc_user), timestamp);
-                        return channel_view.new_message(new_message, &self.history_length);
+                        return self
+                            .new_message(&conversation_id, new_message)
+                            .chain(heard_task);
                     } else {
                         eprintln!("NewNodeInfo: Node '{}' unknown", mc_user.long_name);
                     }
                 }
-                Task::none()
+                heard_task
             }
             NewNodePosition(conversation_id, id, from, position, timestamp) => {
                 self.update_node_position(from, &position);
+                self.known_nodes.update_position(from, &position);
+                let heard_task = self
+                    .node_heard(from)
+                    .chain(self.add_to_track(from, timestamp, &position));
                 if self.show_position_updates {
-                    if let Some(channel_view) = &mut self.conversations.get_mut(&conversation_id) {
+                    if self.conversations.contains_key(&conversation_id) {
                         let new_message =
                             MCMessage::new(id, from, PositionMessage(position), timestamp);
-                        return channel_view.new_message(new_message, &self.history_length);
+                        return self
+                            .new_message(&conversation_id, new_message)
+                            .chain(heard_task);
                     } else {
                         eprintln!("No channel for: {}", conversation_id);
                     }
                 }
-                Task::none()
+                heard_task
             }
             ChannelName(channel_number, name) => {
                 self.set_channel_name(channel_number, name);
                 Task::none()
             }
             MessageACK(conversation_id, message_id) => {
-                if let Some(channel_view) = &mut self.conversations.get_mut(&conversation_id) {
-                    channel_view.ack(message_id)
-                } else {
-                    eprintln!("No channel for MessageACK");
+                match self.set_delivery(conversation_id, message_id, DeliveryState::Acked) {
+                    Some(conversation_id) => {
+                        // A message that was sent again automatically has got through
+                        self.auto_resent.remove(&message_id);
+                        self.save_conversation(&conversation_id)
+                    }
+                    None => {
+                        eprintln!("No channel for MessageACK");
+                        Task::none()
+                    }
+                }
+            }
+            AwaitingAck(conversation_id, message_id, timeout) => {
+                self.set_delivery(conversation_id, message_id, DeliveryState::Sending);
+                Task::perform(
+                    async move { tokio::time::sleep(timeout).await },
+                    move |_| DeviceViewEvent(AckTimeout(conversation_id, message_id)),
+                )
+            }
+            MessageFailed(conversation_id, message_id, reason) => {
+                eprintln!("Message {message_id} failed: {reason}");
+                match self.delivery(&conversation_id, &message_id) {
+                    Some(DeliveryState::Acked | DeliveryState::Failed) | None => Task::none(),
+                    Some(_) => self.message_failed(conversation_id, message_id),
+                }
+            }
+            MessageResent(message_id) => {
+                self.auto_resent.insert(message_id);
+                Task::none()
+            }
+            TracerouteResponse(route) => {
+                if let Some(traceroute) = self.traceroute.as_mut() {
+                    traceroute.set_route(route);
                 }
                 Task::none()
             }
+            NeighbourInfo(node_id, neighbours) => {
+                self.neighbours.insert(node_id, neighbours);
+                Task::none()
+            }
+            NodeTelemetry(node_id, telemetry, timestamp) => {
+                self.telemetry
+                    .entry(node_id)
+                    .or_default()
+                    .add(timestamp, telemetry);
+                Task::none()
+            }
         }
     }
 
@@ -676,11 +2003,13 @@ impl Device {
         .into()
     }
 
-    ///  Add a new node to the list if it has the User info we want and is not marked to be ignored
+    /// Add a new node to the list, or to the ignored nodes if it is marked to be ignored
     fn add_node(&mut self, node_info: MCNodeInfo) {
-        if !node_info.is_ignored
-            && let Some(my_node_num) = self.my_node_id
-        {
+        if node_info.is_ignored {
+            self.nodes.remove(&node_info.node_id);
+            self.ignored_nodes.insert(node_info.node_id, node_info);
+        } else if let Some(my_node_num) = self.my_node_id {
+            self.ignored_nodes.remove(&node_info.node_id);
             if node_info.node_id == my_node_num {
                 self.my_position = node_info.position.clone();
                 self.my_user = node_info.user.clone();
@@ -688,61 +2017,278 @@ impl Device {
 
             let conversation_id = Node(node_info.node_id);
             self.nodes.insert(node_info.node_id, node_info);
-            self.conversations.insert(
-                conversation_id,
-                Conversation::new(conversation_id, my_node_num),
-            );
+            self.add_conversation(conversation_id, my_node_num);
         }
     }
 
-    /// Set my user info to be the [MCUser] passed in
-    fn set_my_user(&mut self, user: MCUser) {
-        self.my_user = Some(user);
-    }
-
-    /// Set my position to be the [MCPosition] passed in
-    fn set_my_position(&mut self, position: MCPosition) {
-        self.my_position = Some(position);
-    }
-
-    /// Add a channel from the radio to the list if it is not disabled and has some Settings
-    pub fn add_channel(&mut self, channel: MCChannel) {
-        {
-            if let Some(my_node_num) = self.my_node_id {
-                let last_index = self.channels.len();
-                self.channels.push(channel);
-                let conversation_id = ConversationId::Channel(ChannelIndex::from(last_index));
-                self.conversations.insert(
-                    conversation_id,
-                    Conversation::new(conversation_id, my_node_num),
-                );
-            }
+    /// Add the nodes met before on this device that the radio has not (yet) reported
+    fn add_known_nodes(&mut self) {
+        let missing: Vec<MCNodeInfo> = self
+            .known_nodes
+            .values()
+            .filter(|known| {
+                !self.nodes.contains_key(&known.node_info.node_id)
+                    && !self.ignored_nodes.contains_key(&known.node_info.node_id)
+            })
+            .map(|known| known.node_info.clone())
+            .collect();
+        for node_info in missing {
+            self.add_node(node_info);
         }
     }
 
-    /// set the name of an existing channel if it can be found using its number
-    pub fn set_channel_name(&mut self, channel_number: i32, name: String) {
-        if let Some(channel) = self.channels.get_mut(channel_number as usize) {
-            channel.name = name;
+    /// Record that we have heard from a node, and save the known nodes if it is one we know
+    fn node_heard(&mut self, node_id: NodeId) -> Task<Message> {
+        if self.known_nodes.heard(node_id, TimeStamp::now()) {
+            self.save_known_nodes()
+        } else {
+            Task::none()
         }
     }
 
-    /// If the Node is known already, then update its Position with a PositionApp update
-    /// it has sent
-    /// NOTE: This position maybe more recent, but it could have less accuracy, as position
-    /// accuracy is set per channel. Consider deciding whether to update or not if it has lower
-    /// accuracy depending on the age of the previous position?
-    fn update_node_position(&mut self, from: NodeId, position: &MCPosition) {
-        if let Some(node) = self.nodes.get_mut(&from) {
-            node.position = Some(position.clone());
+    /// Check the public key reported by a node against the one pinned for it. Return true if a
+    /// key was pinned or found to have changed, so the known nodes need saving, and a task that
+    /// warns the user if the key has changed. Keys are only checked once the keys pinned before
+    /// have been loaded.
+    fn check_node_key(&mut self, node_id: NodeId) -> (bool, Task<Message>) {
+        if !self.known_nodes_loaded {
+            return (false, Task::none());
+        }
 
-            if Some(from) == self.my_node_id {
-                self.my_position = Some(position.clone());
+        match self.known_nodes.check_key(node_id) {
+            KeyCheck::Unchanged => (false, Task::none()),
+            KeyCheck::Pinned => (true, Task::none()),
+            KeyCheck::Changed => {
+                let name = long_name(&self.nodes, node_id).to_string();
+                (
+                    true,
+                    Task::perform(empty(), move |_| {
+                        AppError(
+                            format!("The public key of '{name}' has changed"),
+                            "It may have been reset, or another node may be pretending to be it. \
+                            Check with its owner before trusting the new key."
+                                .to_string(),
+                            TimeStamp::now(),
+                        )
+                    }),
+                )
             }
         }
     }
 
-    /// If the Node is known already, then update its User with a NodeInfoApp User update
+    /// Return true if direct messages to a node are encrypted with its public key, as it is
+    /// known, rather than with the key of the primary channel
+    pub fn dm_encrypted(&self, node_id: NodeId) -> bool {
+        self.nodes
+            .get(&node_id)
+            .and_then(|node| node.user.as_ref())
+            .is_some_and(|user| !user.public_key.is_empty())
+    }
+
+    /// Remember whether a node is ignored, and have the radio ignore it, or stop ignoring it
+    fn set_node_ignored(&mut self, node_id: NodeId, ignored: bool) -> Task<Message> {
+        self.known_nodes.set_ignored(node_id, ignored);
+        self.save_known_nodes()
+            .chain(self.device_send(SetNodeIgnored(node_id, ignored), Message::None))
+    }
+
+    /// The nodes whose messages are hidden from channel conversations, none if showing them
+    pub fn hidden_nodes(&self) -> HashSet<NodeId> {
+        if self.show_hidden {
+            HashSet::new()
+        } else {
+            self.ignored_nodes.keys().copied().collect()
+        }
+    }
+
+    /// Add a position of a node to its track, and save the track if it changed
+    fn add_to_track(
+        &mut self,
+        node_id: NodeId,
+        timestamp: TimeStamp,
+        position: &MCPosition,
+    ) -> Task<Message> {
+        if self.tracks.add(node_id, timestamp, position) {
+            self.save_tracks()
+        } else {
+            Task::none()
+        }
+    }
+
+    /// Schedule the tracks that changed to be saved to disk, once the ones saved before have been
+    /// loaded and merged, so they are not overwritten. Positions are received all the time, so
+    /// changes are saved together after [SAVE_DELAY].
+    fn save_tracks(&mut self) -> Task<Message> {
+        if !self.tracks_loaded
+            || self.tracks_save_scheduled
+            || !matches!(self.connection_state, Connected(..))
+        {
+            return Task::none();
+        }
+
+        self.tracks_save_scheduled = true;
+        Task::perform(async { tokio::time::sleep(SAVE_DELAY).await }, |_| {
+            DeviceViewEvent(SaveTracks)
+        })
+    }
+
+    /// Save the tracks of nodes on `device` that changed since they were last saved, now
+    fn flush_tracks(&mut self, device: &DeviceIdentifier) -> Task<Message> {
+        if !self.tracks_loaded {
+            return Task::none();
+        }
+
+        Task::batch(
+            self.tracks
+                .take_unsaved()
+                .into_iter()
+                .map(|(node_id, track)| save_track(device, node_id, track))
+                .collect::<Vec<_>>(),
+        )
+    }
+
+    /// Export the track of a node to a file, in a format mapping tools can read
+    pub fn export_track(
+        &self,
+        node_id: NodeId,
+        format: TrackFormat,
+        config: &Config,
+    ) -> Task<Message> {
+        if let Some(track) = self.tracks.get(&node_id) {
+            let name = self.conversation_name(config, Node(node_id));
+            export_file(
+                "Track",
+                &name,
+                (format.to_string(), format.extension()),
+                render_track(&name, track, format),
+            )
+        } else {
+            Task::none()
+        }
+    }
+
+    /// Export the last position of every node with a track to a file, in a format mapping tools
+    /// can read
+    pub fn export_positions(&self, format: TrackFormat, config: &Config) -> Task<Message> {
+        let mut positions: Vec<(String, _)> = self
+            .tracks
+            .iter()
+            .filter_map(|(node_id, track)| {
+                track
+                    .last()
+                    .map(|point| (self.conversation_name(config, Node(*node_id)), point))
+            })
+            .collect();
+        positions.sort_by(|(a, _), (b, _)| a.cmp(b));
+        export_file(
+            "Positions",
+            "positions",
+            (format.to_string(), format.extension()),
+            render_positions(&positions, format),
+        )
+    }
+
+    /// How many positions of the node with `user` are in its track, and buttons to export it
+    pub fn track_view<'a>(&self, user: &MCUser) -> Element<'a, Message> {
+        let Some((node_id, track)) = self
+            .user_node_id(user)
+            .and_then(|node_id| self.tracks.get(&node_id).map(|track| (node_id, track)))
+        else {
+            return Column::new().into();
+        };
+
+        TrackFormat::ALL
+            .into_iter()
+            .fold(
+                Row::new()
+                    .spacing(8)
+                    .align_y(Center)
+                    .push(text(format!("Track: {} positions", track.len())).width(Fill)),
+                |row, format| {
+                    row.push(
+                        button(text(format!("Export {format}")))
+                            .style(button_chip_style)
+                            .on_press(ExportTrack(node_id, format)),
+                    )
+                },
+            )
+            .into()
+    }
+
+    /// Note that the nodes met on the connected device have changed, and schedule them to be
+    /// saved to disk once the ones saved before have been loaded, so they are not overwritten.
+    /// Nodes are heard from with every packet, so changes are saved together after
+    /// [SAVE_DELAY].
+    fn save_known_nodes(&mut self) -> Task<Message> {
+        self.known_nodes_unsaved = true;
+        if !self.known_nodes_loaded
+            || self.known_nodes_save_scheduled
+            || !matches!(self.connection_state, Connected(..))
+        {
+            return Task::none();
+        }
+
+        self.known_nodes_save_scheduled = true;
+        Task::perform(async { tokio::time::sleep(SAVE_DELAY).await }, |_| {
+            DeviceViewEvent(SaveKnownNodes)
+        })
+    }
+
+    /// Save the nodes met on `device` to disk now, if they changed since they were last saved
+    fn flush_known_nodes(&mut self, device: &DeviceIdentifier) -> Task<Message> {
+        if self.known_nodes_loaded && self.known_nodes_unsaved {
+            self.known_nodes_unsaved = false;
+            save_known_nodes(device, &self.known_nodes)
+        } else {
+            Task::none()
+        }
+    }
+
+    /// Set my user info to be the [MCUser] passed in
+    fn set_my_user(&mut self, user: MCUser) {
+        self.my_user = Some(user);
+    }
+
+    /// Set my position to be the [MCPosition] passed in
+    fn set_my_position(&mut self, position: MCPosition) {
+        self.my_position = Some(position);
+    }
+
+    /// Add a channel from the radio to the list if it is not disabled and has some Settings
+    pub fn add_channel(&mut self, channel: MCChannel) {
+        {
+            if let Some(my_node_num) = self.my_node_id {
+                let last_index = self.channels.len();
+                self.channels.push(channel);
+                let conversation_id = ConversationId::Channel(ChannelIndex::from(last_index));
+                self.add_conversation(conversation_id, my_node_num);
+            }
+        }
+    }
+
+    /// set the name of an existing channel if it can be found using its number
+    pub fn set_channel_name(&mut self, channel_number: i32, name: String) {
+        if let Some(channel) = self.channels.get_mut(channel_number as usize) {
+            channel.name = name;
+        }
+    }
+
+    /// If the Node is known already, then update its Position with a PositionApp update
+    /// it has sent
+    /// NOTE: This position maybe more recent, but it could have less accuracy, as position
+    /// accuracy is set per channel. Consider deciding whether to update or not if it has lower
+    /// accuracy depending on the age of the previous position?
+    fn update_node_position(&mut self, from: NodeId, position: &MCPosition) {
+        if let Some(node) = self.nodes.get_mut(&from) {
+            node.position = Some(position.clone());
+
+            if Some(from) == self.my_node_id {
+                self.my_position = Some(position.clone());
+            }
+        }
+    }
+
+    /// If the Node is known already, then update its User with a NodeInfoApp User update
     fn update_node_user(&mut self, from: NodeId, user: &MCUser) {
         if let Some(node) = self.nodes.get_mut(&from) {
             node.user = Some(user.clone());
@@ -763,11 +2309,18 @@ impl Device {
                 .on_press(Navigation(DeviceListView)),
         );
 
-        header = match state {
-            Disconnected(_, _) => header
+        header = match (state, &self.reconnect) {
+            (Disconnected(_, _) | Connecting(_), Some(reconnect)) => {
+                let name =
+                    device_list_view.device_name_or_alias(&reconnect.device().name(), config);
+                header
+                    .push(Space::new().width(Fill))
+                    .push(reconnect.view(name))
+            }
+            (Disconnected(_, _), None) => header
                 .push(Space::new().width(Fill))
                 .push(button("Disconnected").style(button_chip_style)),
-            Connecting(ble_device) => {
+            (Connecting(ble_device), None) => {
                 let name_button = button(text(format!(
                     "Connecting to {}",
                     device_list_view.device_name_or_alias(&ble_device.name(), config)
@@ -775,7 +2328,7 @@ impl Device {
                 .style(button_chip_style);
                 header.push(Space::new().width(Fill)).push(name_button)
             }
-            Connected(device, _) => {
+            (Connected(device, _), _) => {
                 let name_row = Row::new()
                     .push(text(format!(
                         "📱 {}",
@@ -783,6 +2336,7 @@ impl Device {
                     )))
                     .push(Space::new().width(4))
                     .push(Self::unread_counter(self.unread_count(
+                        config,
                         self.show_position_updates,
                         self.show_user_updates,
                     )));
@@ -797,7 +2351,7 @@ impl Device {
                     .push(Space::new().width(4))
                     .push(self.battery_level())
             }
-            Disconnecting(device) => header
+            (Disconnecting(device), _) => header
                 .push(
                     button(text(format!(
                         "📱 {}",
@@ -809,7 +2363,10 @@ impl Device {
                 .push(iced::widget::button("Disconnecting").style(button_chip_style)),
         };
 
-        // possibly add a node/channel name button next
+        // possibly add a node/channel name button next, and how it notifies of new messages
+        if let Some(conversation_id) = self.viewing_conversation {
+            header = header.push(self.notification_settings(config, conversation_id));
+        }
         match &self.viewing_conversation {
             Some(ConversationId::Channel(channel_index)) => {
                 let index: usize = (*channel_index).into();
@@ -822,6 +2379,7 @@ impl Device {
                 if let Some(node_name) = self.aliased_long_name(config, *node_id) {
                     header = header.push(button(text(node_name)).style(button_chip_style))
                 }
+                header = header.push(self.encryption_status(*node_id));
             }
             None => {}
         }
@@ -838,6 +2396,102 @@ impl Device {
         header.push(Self::settings_button()).into()
     }
 
+    /// Buttons to change how a conversation counts unread messages and notifies of new ones
+    fn notification_settings<'a>(
+        &self,
+        config: &Config,
+        conversation_id: ConversationId,
+    ) -> Element<'a, Message> {
+        let Some(device) = self.device() else {
+            return Row::new().into();
+        };
+        let settings = config.conversation_settings(device, &conversation_id);
+        let setting_button = |label: &'static str, selected: bool, explanation, changed| {
+            tooltip(
+                button(text(label).size(14))
+                    .style(move |theme, status| emoji_tab_style(theme, status, selected))
+                    .on_press(ConversationSettingsChanged(
+                        device.clone(),
+                        conversation_id,
+                        changed,
+                    )),
+                text(explanation),
+                tooltip::Position::Bottom,
+            )
+            .style(tooltip_style)
+        };
+
+        Row::new()
+            .align_y(Center)
+            .spacing(2)
+            .push(setting_button(
+                "🔇",
+                settings.muted,
+                "Mute, don't count unread messages or notify of them",
+                ConversationSettings {
+                    muted: !settings.muted,
+                    ..settings
+                },
+            ))
+            .push(setting_button(
+                "#",
+                settings.count_unread,
+                "Count unread messages toward the device's unread messages",
+                ConversationSettings {
+                    count_unread: !settings.count_unread,
+                    ..settings
+                },
+            ))
+            .push(setting_button(
+                "@",
+                settings.mentions_only,
+                "Only count and notify of messages that mention me",
+                ConversationSettings {
+                    mentions_only: !settings.mentions_only,
+                    ..settings
+                },
+            ))
+            .into()
+    }
+
+    /// Show how direct messages to a node are encrypted, and a warning if its key has changed
+    fn encryption_status(&self, node_id: NodeId) -> Element<'static, Message> {
+        let (icon, explanation) = if self.dm_encrypted(node_id) {
+            (
+                "🔒",
+                "Encrypted with the node's public key, only it can read them",
+            )
+        } else {
+            (
+                "🔓",
+                "The node's public key is not known, so messages are encrypted with the primary \
+                channel's key, and anyone with that key can read them",
+            )
+        };
+        let mut status = Row::new().align_y(Center).spacing(4).push(
+            tooltip(text(icon), text(explanation), tooltip::Position::Bottom).style(tooltip_style),
+        );
+
+        if self.known_nodes.key_changed(node_id) {
+            status = status.push(
+                tooltip(
+                    button(text("⚠ Public key changed").color(COLOR_RED))
+                        .style(button_chip_style)
+                        .on_press(DeviceViewEvent(TrustNodeKey(node_id))),
+                    text(
+                        "The node's public key is not the one first seen for it. It may have \
+                        been reset, or another node may be pretending to be it. Check with its \
+                        owner, then click to trust the new key",
+                    ),
+                    tooltip::Position::Bottom,
+                )
+                .style(tooltip_style),
+            );
+        }
+
+        status.into()
+    }
+
     /// Return an element that displays the battery level of the connected device
     fn battery_level(&self) -> Element<'_, Message> {
         let (battery_state, tooltip_text) = match self.battery_level {
@@ -858,12 +2512,58 @@ impl Device {
         .into()
     }
 
-    /// Count all the unread messages available to this device across channels and nodes
-    pub fn unread_count(&self, show_position_updates: bool, show_user_updates: bool) -> usize {
-        self.conversations.values().fold(0, |acc, channel| {
-            // jonesy:allow(bounds) via Conversation::unread_count -> RingMap
-            acc.saturating_add(channel.unread_count(show_position_updates, show_user_updates))
-        })
+    /// Count all the unread messages available to this device across channels and nodes,
+    /// leaving out the conversations set not to count toward them
+    pub fn unread_count(
+        &self,
+        config: &Config,
+        show_position_updates: bool,
+        show_user_updates: bool,
+    ) -> usize {
+        self.conversations
+            .iter()
+            .fold(0, |acc, (conversation_id, channel)| {
+                let settings = self.conversation_settings(config, conversation_id);
+                if settings.count_unread {
+                    // jonesy:allow(bounds) via Conversation::unread_count -> RingMap
+                    acc.saturating_add(channel.unread_count(
+                        show_position_updates,
+                        show_user_updates,
+                        &settings,
+                        self.my_user.as_ref(),
+                    ))
+                } else {
+                    acc
+                }
+            })
+    }
+
+    /// Count the unread messages in a conversation, according to its settings
+    fn conversation_unread_count(
+        &self,
+        config: &Config,
+        conversation_id: &ConversationId,
+        conversation: &Conversation,
+    ) -> usize {
+        // jonesy:allow(bounds) via Conversation::unread_count -> RingMap
+        conversation.unread_count(
+            self.show_position_updates,
+            self.show_user_updates,
+            &self.conversation_settings(config, conversation_id),
+            self.my_user.as_ref(),
+        )
+    }
+
+    /// The settings in the config of a conversation on this device, the default settings if they
+    /// haven't been changed
+    fn conversation_settings(
+        &self,
+        config: &Config,
+        conversation_id: &ConversationId,
+    ) -> ConversationSettings {
+        self.device()
+            .map(|device| config.conversation_settings(device, conversation_id))
+            .unwrap_or_default()
     }
 
     /// Create the Element that shows the channels, nodes, etc.
@@ -886,12 +2586,25 @@ impl Device {
         let select =
             |channel_number: ConversationId| DeviceViewEvent(ShowChannel(Some(channel_number)));
 
-        // If not viewing a channel/user, show the list of channels and users
-        let channel_and_node_scroll = self.conversation_list(config, true, select);
+        // If not viewing a channel/user, show the list of channels and users, or the messages
+        // found if searching all messages
+        let channel_and_node_scroll = if self.search_messages && !self.filter.is_empty() {
+            self.message_search_results(config)
+        } else if self.showing_map {
+            NodeMap::new(
+                self.map_nodes(config),
+                self.map_tiles.as_ref(),
+                self.map_focus,
+                select,
+            )
+            .view()
+        } else {
+            self.conversation_list(config, true, select)
+        };
 
         // Add a search box at the top, outside the scrollable area
         Column::new()
-            .push(self.search_box())
+            .push(self.search_box(config.node_sort))
             .push(channel_and_node_scroll)
             .push(self.button_row())
             .into()
@@ -906,7 +2619,7 @@ impl Device {
             row = row.push(
                 button(text("Show Position 📌"))
                     .style(button_chip_style)
-                    .on_press(OpenUrl(MeshChat::location_url(position))),
+                    .on_press(ShowLocation(position.clone())),
             );
         }
 
@@ -918,9 +2631,69 @@ impl Device {
             );
         }
 
+        let map_label = if self.showing_map {
+            "List ☰"
+        } else {
+            "Map 🗺"
+        };
+        row = row.push(
+            button(text(map_label))
+                .style(button_chip_style)
+                .on_press(DeviceViewEvent(ToggleMap)),
+        );
+
+        if self.tracks.iter().next().is_some() {
+            row = row.push(Self::export_positions_menu());
+        }
+
+        row = row.push(
+            button(text("Waypoints 🚩"))
+                .style(button_chip_style)
+                .on_press(DeviceViewEvent(ShowWaypoints)),
+        );
+
+        if self.can_share_channels() {
+            row = row.push(
+                button(text("Channels 🔗"))
+                    .style(button_chip_style)
+                    .on_press(DeviceViewEvent(ShowChannelSharing)),
+            );
+        }
+
+        if !self.ignored_nodes.is_empty() {
+            let label = if self.show_hidden {
+                "Hide Ignored 🙈"
+            } else {
+                "Show Hidden 👁"
+            };
+            row = row.push(
+                button(text(label))
+                    .style(button_chip_style)
+                    .on_press(DeviceViewEvent(ToggleShowHidden)),
+            );
+        }
+
         row.into()
     }
 
+    /// A menu to export the last positions of all nodes to a file in one of the [TrackFormat]s
+    fn export_positions_menu<'a>() -> MenuBar<'a, Message, Theme, Renderer> {
+        let menu_items = TrackFormat::ALL
+            .iter()
+            .map(|format| Item::new(menu_button(format.to_string(), ExportPositions(*format))))
+            .collect();
+
+        let root_button = button(text("Export Positions ▼"))
+            .style(button_chip_style)
+            .on_press(Message::None); // Needed for styling to work
+
+        // jonesy:allow(misaligned_ptr) via iced_aw menu_bar! macro (misaligned_ptr)
+        menu_bar!((root_button, { Menu::new(menu_items).spacing(3).width(100) }))
+            .close_on_background_click(true)
+            .close_on_item_click(true)
+            .style(menu_button_style)
+    }
+
     /// Create a list of channels and nodes in this device with a button to select one of them
     pub fn conversation_list<'a>(
         &'a self,
@@ -929,14 +2702,22 @@ impl Device {
         select: fn(ConversationId) -> Message,
     ) -> Element<'a, Message> {
         // jonesy:allow(overflow) via channel_list -> iterator next()
-        let mut conversation_list = self.channel_list(select);
+        let mut conversation_list = self.channel_list(config, select);
 
-        // Add the favourite nodes to the list if there are any
-        conversation_list = self.favourite_nodes(conversation_list, config, add_buttons, select);
+        // Add the favourite nodes to the list if there are any, when they are listed first
+        if config.node_sort == NodeSort::FavouritesFirst {
+            conversation_list =
+                self.favourite_nodes(conversation_list, config, add_buttons, select);
+        }
 
         // Add the list of non-favourite nodes
         conversation_list = self.nodes_list(conversation_list, config, add_buttons, select);
 
+        // Add the nodes being ignored, if the user wants to see them
+        if self.show_hidden {
+            conversation_list = self.ignored_nodes_list(conversation_list, config);
+        }
+
         // Wrap the whole thing in a scrollable area
         scrollable(conversation_list)
             .direction({
@@ -949,8 +2730,116 @@ impl Device {
             .into()
     }
 
+    /// Return the messages in all conversations that contain the text of the search filter,
+    /// grouped by conversation, with channels first and then nodes in order of their name
+    fn search_results<'a>(
+        &'a self,
+        config: &Config,
+    ) -> Vec<(ConversationId, String, Vec<&'a MCMessage>)> {
+        let mut results: Vec<(ConversationId, String, Vec<&'a MCMessage>)> = self
+            .conversations
+            .iter()
+            .map(|(conversation_id, conversation)| {
+                (
+                    *conversation_id,
+                    self.conversation_name(config, *conversation_id),
+                    conversation.search(&self.filter),
+                )
+            })
+            .filter(|(_, _, messages)| !messages.is_empty())
+            .collect();
+
+        results.sort_by(|(id_a, name_a, _), (id_b, name_b, _)| match (id_a, id_b) {
+            (ConversationId::Channel(a), ConversationId::Channel(b)) => {
+                usize::from(*a).cmp(&usize::from(*b))
+            }
+            (ConversationId::Channel(_), Node(_)) => std::cmp::Ordering::Less,
+            (Node(_), ConversationId::Channel(_)) => std::cmp::Ordering::Greater,
+            (Node(_), Node(_)) => name_a.cmp(name_b),
+        });
+
+        results
+    }
+
+    /// Create a list of the messages found when searching all messages, grouped by conversation,
+    /// with a button to open the conversation at that message
+    fn message_search_results<'a>(&'a self, config: &'a Config) -> Element<'a, Message> {
+        let mut results_list = Column::new();
+        let results = self.search_results(config);
+
+        if results.is_empty() {
+            results_list = results_list.push(
+                container(text("No messages found"))
+                    .padding(12)
+                    .width(Fill)
+                    .align_x(Center),
+            );
+        }
+
+        for (conversation_id, name, messages) in results {
+            results_list =
+                results_list.push(self.section_header(format!("{name} ({})", messages.len())));
+            for message in messages {
+                results_list =
+                    results_list.push(self.search_result_row(config, conversation_id, message));
+            }
+        }
+
+        scrollable(results_list)
+            .direction({
+                let scrollbar = Scrollbar::new().width(10);
+                scrollable::Direction::Vertical(scrollbar)
+            })
+            .style(scrollbar_style)
+            .width(Fill)
+            .height(Fill)
+            .into()
+    }
+
+    /// A row for one message found by searching, showing when it was sent, who by and its text
+    fn search_result_row<'a>(
+        &'a self,
+        config: &'a Config,
+        conversation_id: ConversationId,
+        message: &MCMessage,
+    ) -> Element<'a, Message> {
+        let time = MCMessage::datetime_local(message.time())
+            .format("%Y-%m-%d %H:%M")
+            .to_string();
+        let from = self
+            .aliased_long_name(config, message.from())
+            .map(str::to_string)
+            .unwrap_or_else(|| message.from().to_string());
+        let message_text = message.message().text().unwrap_or_default();
+
+        let result_row = Row::new()
+            .push(text(time).size(12))
+            .push(Space::new().width(8))
+            .push(text(from).size(12))
+            .push(Space::new().width(8))
+            .push(text(message_text.to_string()))
+            .align_y(Center);
+
+        Row::new()
+            .push(
+                button(result_row)
+                    .on_press(DeviceViewEvent(ShowMessage(
+                        conversation_id,
+                        message.message_id(),
+                    )))
+                    .width(Fill)
+                    .style(channel_row_style),
+            )
+            .push(Space::new().width(10))
+            .into()
+    }
+
     /// Create a column with a set of rows, one for each channel
-    fn channel_list(&self, select: fn(ConversationId) -> Message) -> Column<'_, Message> {
+    fn channel_list(
+        &self,
+        config: &Config,
+        select: fn(ConversationId) -> Message,
+    ) -> Column<'_, Message> {
         let mut channels_list = Column::new();
 
         let mut filtered_channels: Vec<(usize, String)> = vec![];
@@ -973,8 +2862,8 @@ impl Device {
                 if let Some(channel_view) = self.conversations.get(&conversation_id) {
                     let channel_row = Self::channel_row(
                         channel_name,
-                        channel_view
-                            .unread_count(self.show_position_updates, self.show_user_updates), // jonesy:allow(bounds) via Conversation::unread_count -> RingMap
+                        self.conversation_unread_count(config, &conversation_id, channel_view),
+                        self.conversation_settings(config, &conversation_id).muted,
                         conversation_id,
                         select,
                     );
@@ -1009,6 +2898,7 @@ impl Device {
 
         // filter out my own node if the node number is known yet
         fav_nodes.retain(|fav_node_id| Some(*fav_node_id) != self.my_node_id);
+        self.sort_nodes(&mut fav_nodes, config);
 
         // If there are favourite nodes, show the header and list them
         if !fav_nodes.is_empty() {
@@ -1017,17 +2907,14 @@ impl Device {
             for fav_node_id in fav_nodes {
                 let conversation_id = Node(fav_node_id);
                 if let Some(channel_view) = self.conversations.get(&conversation_id) {
-                    channels_list = channels_list.push(
-                        self.node_row(
-                            channel_view
-                                .unread_count(self.show_position_updates, self.show_user_updates), // jonesy:allow(bounds) via Conversation::unread_count -> RingMap
-                            fav_node_id,
-                            true, // Favourite
-                            config,
-                            add_buttons,
-                            select,
-                        ),
-                    );
+                    channels_list = channels_list.push(self.node_row(
+                        self.conversation_unread_count(config, &conversation_id, channel_view),
+                        fav_node_id,
+                        true, // Favourite
+                        config,
+                        add_buttons,
+                        select,
+                    ));
                 }
             }
         }
@@ -1035,8 +2922,8 @@ impl Device {
         channels_list
     }
 
-    /// Create the list of nodes that are not already in the favourite nodes list
-    /// If there is a filter, only show nodes that contain the filter in their name
+    /// Create the list of nodes that are not already in the favourite nodes list, in the order
+    /// chosen. If there is a filter, only show nodes that contain the filter in their name
     fn nodes_list<'a>(
         &'a self,
         mut channels_list: Column<'a, Message>,
@@ -1044,41 +2931,42 @@ impl Device {
         add_buttons: bool,
         select: fn(ConversationId) -> Message,
     ) -> Column<'a, Message> {
-        // Initial list of nodes that are NOT already in the list of favourite nodes and does
-        // not include my own node (if the node number is known)
-        let other_nodes_list = self
+        let favourites_first = config.node_sort == NodeSort::FavouritesFirst;
+        // Initial list of nodes that are NOT already in the list of favourite nodes (when
+        // those are listed first) and does not include my own node (if the node number is known)
+        let mut other_nodes_list = self
             .nodes
             .keys()
+            .copied()
             .filter(|node_id| {
-                !config.fav_nodes.contains(node_id) && Some(**node_id) != self.my_node_id
+                !(favourites_first && config.fav_nodes.contains(node_id))
+                    && Some(*node_id) != self.my_node_id
             })
             .filter(|node_id| {
-                if let Some(node_name) = self.aliased_long_name(config, **node_id) {
+                if let Some(node_name) = self.aliased_long_name(config, *node_id) {
                     node_name.contains(&self.filter)
                 } else {
                     false
                 }
             })
             .collect::<Vec<_>>();
+        self.sort_nodes(&mut other_nodes_list, config);
 
         if !other_nodes_list.is_empty() {
             channels_list = channels_list
                 .push(self.section_header(format!("Nodes ({})", other_nodes_list.len())));
 
             for node_id in other_nodes_list {
-                let conversation_id = Node(*node_id);
+                let conversation_id = Node(node_id);
                 if let Some(channel_view) = self.conversations.get(&conversation_id) {
-                    channels_list = channels_list.push(
-                        self.node_row(
-                            channel_view
-                                .unread_count(self.show_position_updates, self.show_user_updates), // jonesy:allow(bounds) via Conversation::unread_count -> RingMap
-                            *node_id,
-                            false, // Not a Favourite
-                            config,
-                            add_buttons,
-                            select,
-                        ),
-                    );
+                    channels_list = channels_list.push(self.node_row(
+                        self.conversation_unread_count(config, &conversation_id, channel_view),
+                        node_id,
+                        config.fav_nodes.contains(&node_id),
+                        config,
+                        add_buttons,
+                        select,
+                    ));
                 }
             }
         }
@@ -1086,22 +2974,261 @@ impl Device {
         channels_list
     }
 
-    /// Add a section header between areas of the list
-    fn section_header(&self, title: String) -> Element<'_, Message> {
-        Column::new()
-            .push(
-                Container::new(text(title).size(16))
-                    .align_x(Center)
-                    .padding(Padding::from([6, 12]))
-                    .style(|_| DAY_SEPARATOR_STYLE),
-            )
-            .width(Fill)
-            .align_x(Center)
-            .into()
-    }
+    /// Create the list of nodes being ignored, each with a button to stop ignoring it
+    fn ignored_nodes_list<'a>(
+        &'a self,
+        mut channels_list: Column<'a, Message>,
+        config: &'a Config,
+    ) -> Column<'a, Message> {
+        let mut ignored: Vec<(NodeId, &str)> = self
+            .ignored_nodes
+            .values()
+            .map(|node_info| {
+                let name = config
+                    .aliases
+                    .get(&node_info.node_id)
+                    .map(String::as_str)
+                    .or(node_info.user.as_ref().map(|user| user.long_name.as_str()))
+                    .unwrap_or("Unknown");
+                (node_info.node_id, name)
+            })
+            .filter(|(_, name)| name.contains(&self.filter))
+            .collect();
+        ignored.sort_by_key(|(_, name)| *name);
 
-    /// Return the long name for the node with id node_id - prefixed with a node/device emoji -
-    /// if the node is known and has a name
+        if !ignored.is_empty() {
+            channels_list = channels_list
+                .push(self.section_header(format!("Hidden Nodes ({})", ignored.len())));
+            for (node_id, name) in ignored {
+                channels_list = channels_list.push(
+                    Row::new()
+                        .align_y(Center)
+                        .padding([0, 10])
+                        .push(text(format!("📱  {name}")).color(TIME_TEXT_COLOR))
+                        .push(Space::new().width(Fill))
+                        .push(
+                            tooltip(
+                                button(text("👁"))
+                                    .on_press(DeviceViewEvent(UnignoreNode(node_id)))
+                                    .style(fav_button_style),
+                                "Stop ignoring this node",
+                                tooltip::Position::Left,
+                            )
+                            .gap(6)
+                            .style(tooltip_style),
+                        ),
+                );
+            }
+        }
+
+        channels_list
+    }
+
+    /// Sort nodes in the order chosen in the config. Nodes without the value sorted by go last,
+    /// and nodes with the same value are sorted by name
+    fn sort_nodes(&self, node_ids: &mut [NodeId], config: &Config) {
+        // What each node is sorted by is worked out once, not again for every comparison
+        node_ids.sort_by_cached_key(|node_id| {
+            let first = match config.node_sort {
+                NodeSort::FavouritesFirst | NodeSort::Name => NodeSortKey::Name,
+                NodeSort::LastHeard => NodeSortKey::LastHeard(Reverse(
+                    self.known_nodes.get(node_id).map(|known| known.last_heard),
+                )),
+                NodeSort::Distance => {
+                    let metres = self
+                        .distance_and_bearing(*node_id)
+                        .map(|(metres, _)| metres);
+                    NodeSortKey::Distance(
+                        metres.is_none(),
+                        metres.map(|metres| (metres * 1000.0) as u64),
+                    )
+                }
+                NodeSort::HopsAway => {
+                    let hops = self.nodes.get(node_id).and_then(|node| node.hops_away);
+                    NodeSortKey::HopsAway(hops.is_none(), hops)
+                }
+            };
+            let name = self
+                .aliased_long_name(config, *node_id)
+                .unwrap_or_default()
+                .to_lowercase();
+            (first, name)
+        });
+    }
+
+    /// The distance in metres, and the bearing in degrees, from my position to a node, if both
+    /// positions are known
+    fn distance_and_bearing(&self, node_id: NodeId) -> Option<(f64, f64)> {
+        let mine = self.my_position.as_ref()?;
+        let theirs = self.nodes.get(&node_id)?.position.as_ref()?;
+        Some((distance_m(mine, theirs), bearing_deg(mine, theirs)))
+    }
+
+    /// How far away a node is, and in which direction, for its row in the node list
+    fn distance(&self, node_id: NodeId, config: &Config) -> Element<'static, Message> {
+        if let Some((metres, bearing)) = self.distance_and_bearing(node_id) {
+            tooltip(
+                text(format!(
+                    "{} {}",
+                    format_distance(metres, config.units),
+                    compass_point(bearing)
+                ))
+                .size(TIME_TEXT_SIZE)
+                .color(TIME_TEXT_COLOR),
+                text(format!("Bearing {bearing:.0}° from my position")),
+                tooltip::Position::Left,
+            )
+            .style(tooltip_style)
+            .into()
+        } else {
+            Space::new().width(0).into()
+        }
+    }
+
+    /// The distance and bearing from my position to the node with `user`, for the node's details
+    pub fn distance_view<'a>(&self, user: &MCUser, config: &Config) -> Element<'a, Message> {
+        match self
+            .user_node_id(user)
+            .and_then(|node_id| self.distance_and_bearing(node_id))
+        {
+            Some((metres, bearing)) => text(format!(
+                "Distance: {}, Bearing: {bearing:.0}° {}",
+                format_distance(metres, config.units),
+                compass_point(bearing)
+            ))
+            .into(),
+            None => Column::new().into(),
+        }
+    }
+
+    /// A dot showing if a node is online, stale or offline, going by when it was last heard
+    fn status_indicator(&self, node_id: NodeId, config: &Config) -> Element<'static, Message> {
+        let Some(known) = self.known_nodes.get(&node_id) else {
+            return Space::new().width(0).into();
+        };
+
+        let (color, explanation) = match node_status(
+            TimeStamp::now(),
+            known.last_heard,
+            config.online_within.duration(),
+            config.offline_after.duration(),
+        ) {
+            NodeStatus::Online => (
+                COLOR_GREEN,
+                format!("Online, heard within {}", config.online_within),
+            ),
+            NodeStatus::Stale => (
+                COLOR_YELLOW,
+                format!("Not heard within {}", config.online_within),
+            ),
+            NodeStatus::Offline => (
+                COLOR_GRAY_50,
+                format!("Offline, not heard for {}", config.offline_after),
+            ),
+        };
+        tooltip(
+            text("●").color(color),
+            text(explanation),
+            tooltip::Position::Right,
+        )
+        .style(tooltip_style)
+        .into()
+    }
+
+    /// The nodes with known positions, and my own position, to show on the map. If there is a
+    /// filter, only nodes that contain the filter in their name are shown
+    fn map_nodes(&self, config: &Config) -> Vec<MapNode> {
+        let mut map_nodes: Vec<MapNode> = self
+            .nodes
+            .values()
+            .filter(|node| Some(node.node_id) != self.my_node_id)
+            .filter_map(|node| {
+                let position = node.position.as_ref()?;
+                let name = self.aliased_long_name(config, node.node_id)?;
+                if !name.contains(&self.filter) {
+                    return None;
+                }
+                // Show the name of the node's user as well as any alias the user gave it
+                let label = match (config.aliases.get(&node.node_id), &node.user) {
+                    (Some(alias), Some(user)) => format!("{alias} ({})", user.long_name),
+                    _ => name.to_string(),
+                };
+                Some(MapNode {
+                    node_id: Some(node.node_id),
+                    label,
+                    position: position.into(),
+                    mine: false,
+                })
+            })
+            .collect();
+
+        if let Some(position) = &self.my_position {
+            let label = self
+                .my_user
+                .as_ref()
+                .map_or("My position".to_string(), |user| user.long_name.clone());
+            map_nodes.push(MapNode {
+                node_id: self.my_node_id,
+                label,
+                position: position.into(),
+                mine: true,
+            });
+        }
+        map_nodes
+    }
+
+    /// Show `position` in the middle of the map of nodes, leaving any conversation or dialog
+    /// open so the map can be seen
+    pub fn show_location(&mut self, position: &MCPosition) -> Task<Message> {
+        self.showing_waypoints = false;
+        self.showing_map = true;
+        self.map_focus = Some(WorldPoint::from(position));
+        self.channel_change(None)
+    }
+
+    /// The MBTiles file, or directory of tiles, the map is drawn on, if any
+    pub fn map_tiles_path(&self) -> Option<&Path> {
+        self.map_tiles.as_ref().map(Tiles::path)
+    }
+
+    /// Draw the map on the MBTiles file, or directory of tiles, at `path`, or on a plain grid
+    /// if there is none. If the tiles could not be opened, the map is drawn as it was
+    pub fn set_map_tiles(&mut self, path: Option<&Path>) -> Task<Message> {
+        match path.map(|path| (path, TileSource::open(path))) {
+            Some((path, Ok(source))) => {
+                self.map_tiles = Some(Tiles::new(path.to_path_buf(), source));
+                Task::none()
+            }
+            Some((_, Err(detail))) => Task::perform(empty(), move |_| {
+                AppError(
+                    "Error opening map tiles".to_string(),
+                    detail.clone(),
+                    TimeStamp::now(),
+                )
+            }),
+            None => {
+                self.map_tiles = None;
+                Task::none()
+            }
+        }
+    }
+
+    /// Add a section header between areas of the list
+    fn section_header(&self, title: String) -> Element<'_, Message> {
+        Column::new()
+            .push(
+                Container::new(text(title).size(16))
+                    .align_x(Center)
+                    .padding(Padding::from([6, 12]))
+                    .style(|_| DAY_SEPARATOR_STYLE),
+            )
+            .width(Fill)
+            .align_x(Center)
+            .into()
+    }
+
+    /// Return the long name for the node with id node_id - prefixed with a node/device emoji -
+    /// if the node is known and has a name
     fn aliased_long_name<'a>(&'a self, config: &'a Config, node_id: NodeId) -> Option<&'a str> {
         // Avoid returning an (aliased) name until we have the node info
         let node = self.nodes.get(&node_id)?;
@@ -1129,18 +3256,35 @@ impl Device {
         }
     }
 
+    /// An icon showing a conversation is muted, or nothing if it is not
+    fn mute_icon(muted: bool) -> Element<'static, Message> {
+        if muted {
+            tooltip(
+                text("🔇"),
+                text("Muted, unread messages are not counted"),
+                tooltip::Position::Right,
+            )
+            .style(tooltip_style)
+            .into()
+        } else {
+            Space::new().width(0).into()
+        }
+    }
+
     /// Create a Button that represents either a Channel or a Node
     /// DeviceViewEvent(ShowChannel(Some(conversation_id)))
     /// DeviceViewEvent(ShowChannel(Some(conversation_id)))
     fn channel_row(
         name: String,
         unread_count: usize,
+        muted: bool,
         conversation_id: ConversationId,
         select: fn(ConversationId) -> Message,
     ) -> Element<'static, Message> {
         let name_row = Row::new()
             .push(text(name))
             .push(Space::new().width(4))
+            .push(Self::mute_icon(muted))
             .push(Self::unread_counter(unread_count));
 
         Row::new()
@@ -1192,10 +3336,18 @@ impl Device {
         };
 
         let name_row = Row::new()
-            .push("📱  ")
+            .push(self.status_indicator(node_id, config))
+            .push(" 📱  ")
             .push(name_element)
             .push(Space::new().width(4))
+            .push(Self::mute_icon(
+                self.conversation_settings(config, &Node(node_id)).muted,
+            ))
             .push(Self::unread_counter(num_messages))
+            .push(Space::new().width(Fill))
+            .push(self.distance(node_id, config))
+            .push(Space::new().width(8))
+            .push(self.last_heard(node_id))
             .align_y(Center);
 
         let mut node_row = Row::new().align_y(Bottom);
@@ -1219,6 +3371,26 @@ impl Device {
         node_row.into()
     }
 
+    /// An element showing how long ago a node was last heard, with when it was first seen
+    fn last_heard(&self, node_id: NodeId) -> Element<'static, Message> {
+        if let Some(known) = self.known_nodes.get(&node_id) {
+            let first_seen = MCMessage::datetime_local(known.first_seen)
+                .format("%Y-%m-%d %H:%M")
+                .to_string();
+            tooltip(
+                text(heard_ago(TimeStamp::now(), known.last_heard))
+                    .size(TIME_TEXT_SIZE)
+                    .color(TIME_TEXT_COLOR),
+                text(format!("First seen: {first_seen}")),
+                tooltip::Position::Left,
+            )
+            .style(tooltip_style)
+            .into()
+        } else {
+            Space::new().width(0).into()
+        }
+    }
+
     fn add_buttons<'a>(
         &self,
         mut node_row: Row<'a, Message>,
@@ -1276,7 +3448,7 @@ impl Device {
                         .style(fav_button_style)
                         .on_press(ShowLocation(position.clone()))
                         .width(36),
-                    "Show node position in maps",
+                    "Show node position on the map",
                     tooltip::Position::Left,
                 )
                 .gap(6)
@@ -1286,6 +3458,42 @@ impl Device {
             node_row.push(Space::new().width(36))
         };
 
+        // Add a button to trace the route to the node, if the radio can
+        node_row = if self.can_traceroute() {
+            node_row.push(
+                tooltip(
+                    button(text("🔀"))
+                        .style(fav_button_style)
+                        .on_press(DeviceViewEvent(TraceRoute(node_id)))
+                        .width(36),
+                    "Trace the route to this node",
+                    tooltip::Position::Left,
+                )
+                .gap(6)
+                .style(tooltip_style),
+            )
+        } else {
+            node_row
+        };
+
+        // Add a button to ignore the node, if the radio keeps a list of nodes to ignore
+        node_row = if self.can_ignore_nodes() {
+            node_row.push(
+                tooltip(
+                    button(text("🙈"))
+                        .style(fav_button_style)
+                        .on_press(DeviceViewEvent(IgnoreNode(node_id)))
+                        .width(36),
+                    "Ignore this node, hiding it and its messages",
+                    tooltip::Position::Left,
+                )
+                .gap(6)
+                .style(tooltip_style),
+            )
+        } else {
+            node_row
+        };
+
         // Add a button to toggle the favourite status of the node
         let (tooltip_text, icon) = if favourite {
             ("Unfavourite this node", icons::star())
@@ -1308,13 +3516,29 @@ impl Device {
             .push(Space::new().width(10))
     }
 
-    fn search_box(&self) -> Element<'static, Message> {
+    fn search_box(&self, node_sort: NodeSort) -> Element<'static, Message> {
+        let placeholder = if self.search_messages {
+            "Search all messages"
+        } else {
+            "Search for Channel or Node"
+        };
+
+        let selected = self.search_messages;
+        let search_messages_button = tooltip(
+            button(text("💬").size(14))
+                .style(move |theme, status| emoji_tab_style(theme, status, selected))
+                .on_press(DeviceViewEvent(ToggleMessageSearch)),
+            text("Search the text of all messages"),
+            tooltip::Position::Bottom,
+        )
+        .style(tooltip_style);
+
         container(
             container(
                 Row::new()
                     .push(Space::new().width(9.0))
                     .push(
-                        text_input("Search for Channel or Node", &self.filter)
+                        text_input(placeholder, &self.filter)
                             .style(text_input_style)
                             .padding([4, 4])
                             .id(CHANNEL_SEARCH_ID)
@@ -1323,6 +3547,19 @@ impl Device {
                     .push(Space::new().width(4.0))
                     .push(text_input_clear_button(!self.filter.is_empty()))
                     .push(Space::new().width(4.0))
+                    .push(search_messages_button)
+                    .push(Space::new().width(4.0))
+                    .push(
+                        tooltip(
+                            pick_list(NodeSort::ALL, Some(node_sort), NodeSortSelected)
+                                .text_size(14)
+                                .padding([2, 6]),
+                            text("The order nodes are listed in"),
+                            tooltip::Position::Bottom,
+                        )
+                        .style(tooltip_style),
+                    )
+                    .push(Space::new().width(4.0))
                     .align_y(Center),
             )
             .style(text_input_container_style),
@@ -1372,10 +3609,12 @@ pub fn long_name(nodes: &HashMap<NodeId, MCNodeInfo>, from: NodeId) -> &str {
 mod tests {
     use super::*;
     use crate::Message::Navigation;
+    use crate::config::Units;
     use crate::device::ConnectionState::{Connected, Connecting, Disconnected, Disconnecting};
-    use crate::device::DeviceCommand::Disconnect;
+    use crate::device::DeviceCommand::{Disconnect, ResendText};
     use crate::device::DeviceMessage::{ClearFilter, SearchInput};
     use crate::meshchat;
+    use crate::telemetry::MCDeviceMetrics;
 
     fn test_position(lat: f64, lon: f64) -> MCPosition {
         MCPosition {
@@ -1451,6 +3690,7 @@ mod tests {
                 }),
                 position: None,
                 is_ignored: false,
+                hops_away: None,
             },
         );
         assert_eq!(short_name(&nodes, NodeId::from(12345u64)), "TEST");
@@ -1495,6 +3735,7 @@ mod tests {
                 }),
                 position: None,
                 is_ignored: false,
+                hops_away: None,
             },
         );
         assert_eq!(
@@ -1507,7 +3748,7 @@ mod tests {
     fn test_device_view_default() {
         let device_view = Device::default();
         assert_eq!(device_view.connection_state(), &Disconnected(None, None));
-        assert_eq!(device_view.unread_count(true, true), 0);
+        assert_eq!(device_view.unread_count(&Config::default(), true, true), 0);
         assert!(device_view.conversations.is_empty());
     }
 
@@ -1649,7 +3890,7 @@ mod tests {
     #[test]
     fn test_unread_count_empty() {
         let device_view = Device::default();
-        assert_eq!(device_view.unread_count(true, true), 0);
+        assert_eq!(device_view.unread_count(&Config::default(), true, true), 0);
     }
 
     // Tests for process_subscription_event
@@ -1761,6 +4002,7 @@ mod tests {
             }),
             position: None,
             is_ignored: false,
+            hops_away: None,
             // jonesy:allow(unknown) via metal::device::DeviceRef::new_library_with_source
         };
 
@@ -1787,6 +4029,7 @@ mod tests {
             user: None,
             position: None,
             is_ignored: true, // Should be ignored
+            hops_away: None,
         };
 
         let _ = device_view.update(SubscriptionMessage(NewNode(node_info)));
@@ -1816,6 +4059,7 @@ mod tests {
             user: Some(user.clone()),
             position: Some(position.clone()),
             is_ignored: false,
+            hops_away: None,
         };
 
         let _ = device_view.update(SubscriptionMessage(NewNode(node_info)));
@@ -1849,6 +4093,7 @@ mod tests {
             }),
             position: None,
             is_ignored: false,
+            hops_away: None,
         };
         let _ = device_view.update(SubscriptionMessage(NewNode(node_info)));
 
@@ -1962,6 +4207,7 @@ mod tests {
             }),
             position: None,
             is_ignored: false,
+            hops_away: None,
         };
         let _ = device_view.update(SubscriptionMessage(NewNode(node_info)));
 
@@ -1991,6 +4237,7 @@ mod tests {
             }),
             position: None,
             is_ignored: false,
+            hops_away: None,
         };
         let _ = device_view.update(SubscriptionMessage(NewNode(node_info)));
 
@@ -2279,6 +4526,7 @@ mod tests {
             NodeId::from(100u64),
             MCContent::NewTextMessage("test".into()),
             TimeStamp::from(1234567890u64),
+            None,
         )));
     }
 
@@ -2299,6 +4547,7 @@ mod tests {
             }),
             position: None,
             is_ignored: false,
+            hops_away: None,
         };
         let _ = device_view.update(SubscriptionMessage(NewNode(node_info)));
 
@@ -2415,6 +4664,186 @@ mod tests {
         // Should not panic, no user info available
     }
 
+    #[test]
+    fn test_reconnect_delay_backs_off() {
+        assert_eq!(reconnect_delay(1), RECONNECT_DELAY);
+        assert_eq!(reconnect_delay(2), RECONNECT_DELAY * 2);
+        assert_eq!(reconnect_delay(3), RECONNECT_DELAY * 4);
+        assert_eq!(reconnect_delay(RECONNECT_ATTEMPTS), MAX_RECONNECT_DELAY);
+        assert_eq!(reconnect_delay(u32::MAX), MAX_RECONNECT_DELAY);
+    }
+
+    #[cfg(feature = "meshtastic")]
+    fn dropped_device() -> Device {
+        let mut device = Device::default();
+        device.set_auto_reconnect(true);
+        device.connection_state = Connected("device1".into(), RadioType::Meshtastic);
+        device.viewing_conversation = Some(ConversationId::Channel(ChannelIndex::from(1u8)));
+        let _ = device.update(SubscriptionMessage(DisconnectedEvent("device1".into())));
+        device
+    }
+
+    #[cfg(feature = "meshtastic")]
+    #[test]
+    fn test_link_drop_starts_reconnect() {
+        let device = dropped_device();
+        assert_eq!(
+            device.reconnecting(),
+            Some(&Reconnect {
+                device: "device1".into(),
+                radio_type: RadioType::Meshtastic,
+                conversation_id: Some(ConversationId::Channel(ChannelIndex::from(1u8))),
+                attempt: 1,
+            })
+        );
+        assert_eq!(
+            device.connection_state,
+            Disconnected(Some("device1".into()), None)
+        );
+        // Stay in the conversation while reconnecting
+        assert_eq!(
+            device.viewing_conversation,
+            Some(ConversationId::Channel(ChannelIndex::from(1u8)))
+        );
+    }
+
+    #[cfg(feature = "meshtastic")]
+    #[test]
+    fn test_send_while_reconnecting_queues() {
+        let mut device = Device::default();
+        device.set_auto_reconnect(true);
+        device.my_node_id = Some(NodeId::from(999u64));
+        device.add_channel(MCChannel {
+            index: 0,
+            name: "Test".to_string(),
+        });
+        let channel = ConversationId::Channel(0.into());
+        device.connection_state = Connected("device1".into(), RadioType::Meshtastic);
+        device.viewing_conversation = Some(channel);
+        let _ = device.update(SubscriptionMessage(DisconnectedEvent("device1".into())));
+
+        assert!(device.conversations.contains_key(&channel));
+        let _ = device.update(DeviceMessage::SendTextMessage(
+            "Hello".to_string(),
+            channel,
+            None,
+        ));
+        assert_eq!(
+            device
+                .outbox()
+                .conversation(&"device1".into(), channel)
+                .count(),
+            1
+        );
+        let config = Config::default();
+        let _element = device.view(&config);
+    }
+
+    #[cfg(feature = "meshtastic")]
+    #[test]
+    fn test_stopping_reconnect_clears_device_state() {
+        for stop in [CancelReconnect, DisconnectRequest(false)] {
+            let mut device = dropped_device();
+            let _ = device.update(stop);
+            assert!(device.reconnecting().is_none());
+            assert!(device.viewing_conversation.is_none());
+        }
+    }
+
+    #[cfg(feature = "meshtastic")]
+    #[test]
+    fn test_no_reconnect_when_disabled() {
+        let mut device = Device::default();
+        device.connection_state = Connected("device1".into(), RadioType::Meshtastic);
+        let _ = device.update(SubscriptionMessage(DisconnectedEvent("device1".into())));
+        assert!(device.reconnecting().is_none());
+    }
+
+    #[cfg(feature = "meshtastic")]
+    #[test]
+    fn test_no_reconnect_after_disconnect_request() {
+        let mut device = Device::default();
+        device.set_auto_reconnect(true);
+        device.connection_state = Connected("device1".into(), RadioType::Meshtastic);
+        let _ = device.update(DisconnectRequest(false));
+        let _ = device.update(SubscriptionMessage(DisconnectedEvent("device1".into())));
+        assert!(device.reconnecting().is_none());
+        assert!(!device.disconnect_requested);
+    }
+
+    #[cfg(feature = "meshtastic")]
+    #[test]
+    fn test_reconnect_failure_retries_then_gives_up() {
+        let mut device = dropped_device();
+        for attempt in 1..RECONNECT_ATTEMPTS {
+            assert_eq!(device.reconnecting().map(|r| r.attempt), Some(attempt));
+            let _ = device.update(SubscriptionMessage(ConnectionError(
+                "device1".into(),
+                "Failed to connect".into(),
+                "Not found".into(),
+            )));
+        }
+        assert_eq!(
+            device.reconnecting().map(|r| r.attempt),
+            Some(RECONNECT_ATTEMPTS)
+        );
+        let _ = device.update(SubscriptionMessage(ConnectionError(
+            "device1".into(),
+            "Failed to connect".into(),
+            "Not found".into(),
+        )));
+        assert!(device.reconnecting().is_none());
+        assert!(device.viewing_conversation.is_none());
+    }
+
+    #[cfg(feature = "meshtastic")]
+    #[test]
+    fn test_reconnected_ends_reconnect() {
+        let mut device = dropped_device();
+        let _ = device.update(SubscriptionMessage(ConnectedEvent(
+            "device1".into(),
+            RadioType::Meshtastic,
+        )));
+        assert!(device.reconnecting().is_none());
+        assert_eq!(
+            device.connection_state,
+            Connected("device1".into(), RadioType::Meshtastic)
+        );
+    }
+
+    #[cfg(feature = "meshtastic")]
+    #[test]
+    fn test_cancel_reconnect() {
+        let mut device = dropped_device();
+        let _ = device.update(CancelReconnect);
+        assert!(device.reconnecting().is_none());
+        // A timer for an attempt that was cancelled does nothing
+        let _ = device.update(ReconnectAttempt(1));
+        assert_eq!(
+            device.connection_state,
+            Disconnected(Some("device1".into()), None)
+        );
+    }
+
+    #[cfg(feature = "meshtastic")]
+    #[test]
+    fn test_disconnect_request_cancels_reconnect() {
+        let mut device = dropped_device();
+        let _ = device.update(DisconnectRequest(false));
+        assert!(device.reconnecting().is_none());
+    }
+
+    #[cfg(feature = "meshtastic")]
+    #[test]
+    fn test_header_while_reconnecting() {
+        let device = dropped_device();
+        let config = Config::default();
+        let device_list = DeviceList::default();
+        let _element = device.header(&config, device.connection_state(), &device_list);
+        let _element =
+            device_list.header(&config, device.connection_state(), device.reconnecting());
+    }
+
     #[test]
     fn test_disconnect_request_with_exit() {
         let mut device_view = Device::default();
@@ -2563,6 +4992,7 @@ mod tests {
                 user: None,
                 position: None,
                 is_ignored: false,
+                hops_away: None,
             },
         );
         assert_eq!(
@@ -2689,6 +5119,7 @@ mod tests {
             }),
             position: None,
             is_ignored: false,
+            hops_away: None,
         })));
 
         let config = Config::default();
@@ -2754,6 +5185,7 @@ mod tests {
             }),
             position: None,
             is_ignored: false,
+            hops_away: None,
         })));
 
         let mut config = Config::default();
@@ -2780,6 +5212,7 @@ mod tests {
             }),
             position: None,
             is_ignored: false,
+            hops_away: None,
         })));
 
         let mut config = Config::default();
@@ -2807,6 +5240,7 @@ mod tests {
             }),
             position: None,
             is_ignored: false,
+            hops_away: None,
         })));
 
         // Start editing alias
@@ -2834,6 +5268,7 @@ mod tests {
             }),
             position: Some(test_position(37.7749, -122.4194)),
             is_ignored: false,
+            hops_away: None,
         })));
 
         let config = Config::default();
@@ -2907,6 +5342,7 @@ mod tests {
             }),
             position: None,
             is_ignored: false,
+            hops_away: None,
         })));
 
         device_view.viewing_conversation = Some(Node(NodeId::from(12345u64)));
@@ -2977,6 +5413,7 @@ mod tests {
         let _element = Device::channel_row(
             "Test Channel".into(),
             0,
+            false,
             ConversationId::Channel(0.into()),
             select,
         );
@@ -2984,20 +5421,29 @@ mod tests {
         let _element = Device::channel_row(
             "Channel with unread".into(),
             5,
+            false,
             ConversationId::Channel(1.into()),
             select,
         );
+
+        let _element = Device::channel_row(
+            "Muted channel".into(),
+            0,
+            true,
+            ConversationId::Channel(2.into()),
+            select,
+        );
     }
 
     #[test]
     fn test_search_box() {
         let device_view = Device::default();
-        let _element = device_view.search_box();
+        let _element = device_view.search_box(NodeSort::FavouritesFirst);
 
         // With filter text
         let mut device_view_with_filter = Device::default();
         let _ = device_view_with_filter.update(SearchInput("test filter".into()));
-        let _element = device_view_with_filter.search_box();
+        let _element = device_view_with_filter.search_box(NodeSort::Name);
     }
 
     #[test]
@@ -3021,6 +5467,7 @@ mod tests {
             }),
             position: None,
             is_ignored: false,
+            hops_away: None,
         })));
 
         let config = Config::default();
@@ -3147,28 +5594,121 @@ mod tests {
         );
     }
 
-    #[cfg(all(feature = "bluetooth", feature = "tcp"))]
+    #[cfg(feature = "sim")]
     #[test]
-    fn test_ble_name_not_misidentified_as_tcp() {
-        let id = DeviceIdentifier::from("MyDevice");
-        assert!(matches!(id, DeviceIdentifier::Ble { .. }));
+    fn test_sim_identifier_roundtrip() {
+        let id = DeviceIdentifier::from("sim://Simulated Radio");
+        assert!(matches!(id, DeviceIdentifier::Sim { ref name } if name == "Simulated Radio"));
+        assert_eq!(id.name(), "Simulated Radio");
+        assert_eq!(String::from(&id), "sim://Simulated Radio");
     }
 
     #[test]
-    fn test_set_my_user() {
-        let mut device = Device::default();
-        assert!(device.my_user.is_none());
-        let user = MCUser {
-            id: "!test".to_string(),
-            long_name: "Test".to_string(),
-            short_name: "T".to_string(),
-            hw_model_str: "TBEAM".to_string(),
-            hw_model: 0,
-            is_licensed: false,
-            role_str: "CLIENT".to_string(),
-            role: 0,
-            public_key: vec![],
-            is_unmessagable: false,
+    fn test_replay_identifier_roundtrip() {
+        let id = DeviceIdentifier::from("replay:///tmp/captures/radio_20260101.jsonl");
+        assert!(
+            matches!(id, DeviceIdentifier::Replay { ref path } if path == "/tmp/captures/radio_20260101.jsonl")
+        );
+        assert_eq!(id.name(), "radio_20260101.jsonl");
+        assert_eq!(
+            String::from(&id),
+            "replay:///tmp/captures/radio_20260101.jsonl"
+        );
+    }
+
+    #[test]
+    fn test_set_capture_traffic() {
+        let mut device_view = Device::default();
+        assert!(!device_view.capture_traffic);
+        device_view.set_capture_traffic(true);
+        assert!(device_view.capture_traffic);
+    }
+
+    #[cfg(feature = "sim")]
+    #[test]
+    fn test_sim_subscription_ready() {
+        let mut device_view = Device::default();
+        let (sender, _receiver) = tokio::sync::mpsc::channel(10);
+        let _ = device_view.update(SubscriptionMessage(Ready(sender, RadioType::Sim)));
+        assert!(device_view.sim_sender.is_some());
+    }
+
+    #[cfg(feature = "serial")]
+    #[test]
+    fn test_serial_identifier_parse() {
+        let id = DeviceIdentifier::from("serial:///dev/ttyUSB0?baud=9600");
+        assert!(
+            matches!(id, DeviceIdentifier::Serial { ref path, baud } if path == "/dev/ttyUSB0" && baud == 9600)
+        );
+    }
+
+    #[cfg(feature = "serial")]
+    #[test]
+    fn test_serial_identifier_default_baud() {
+        let id = DeviceIdentifier::from("serial://COM3");
+        assert!(
+            matches!(id, DeviceIdentifier::Serial { ref path, baud } if path == "COM3" && baud == DEFAULT_SERIAL_BAUD)
+        );
+    }
+
+    #[cfg(feature = "serial")]
+    #[test]
+    fn test_serial_identifier_roundtrip() {
+        let original = "serial:///dev/cu.usbmodem1101?baud=115200";
+        let id = DeviceIdentifier::from(original);
+        let serialized: String = (&id).into();
+        assert_eq!(serialized, original);
+    }
+
+    #[cfg(feature = "serial")]
+    #[test]
+    fn test_serial_name_is_path() {
+        let id = DeviceIdentifier::Serial {
+            path: "/dev/ttyACM0".to_string(),
+            baud: 115_200,
+        };
+        assert_eq!(id.name(), "/dev/ttyACM0");
+    }
+
+    #[cfg(all(feature = "serial", feature = "bluetooth"))]
+    #[test]
+    fn test_serial_mac_returns_none() {
+        let id = DeviceIdentifier::Serial {
+            path: "/dev/ttyACM0".to_string(),
+            baud: 115_200,
+        };
+        assert!(id.mac().is_none());
+    }
+
+    #[cfg(all(feature = "serial", feature = "bluetooth"))]
+    #[test]
+    fn test_serial_rejects_empty_path() {
+        let id = DeviceIdentifier::from("serial://?baud=115200");
+        assert!(matches!(id, DeviceIdentifier::Ble { .. }));
+    }
+
+    #[cfg(all(feature = "bluetooth", feature = "tcp"))]
+    #[test]
+    fn test_ble_name_not_misidentified_as_tcp() {
+        let id = DeviceIdentifier::from("MyDevice");
+        assert!(matches!(id, DeviceIdentifier::Ble { .. }));
+    }
+
+    #[test]
+    fn test_set_my_user() {
+        let mut device = Device::default();
+        assert!(device.my_user.is_none());
+        let user = MCUser {
+            id: "!test".to_string(),
+            long_name: "Test".to_string(),
+            short_name: "T".to_string(),
+            hw_model_str: "TBEAM".to_string(),
+            hw_model: 0,
+            is_licensed: false,
+            role_str: "CLIENT".to_string(),
+            role: 0,
+            public_key: vec![],
+            is_unmessagable: false,
         };
         device.set_my_user(user.clone());
         assert!(device.my_user.is_some());
@@ -3260,6 +5800,171 @@ mod tests {
         // Should not panic when not connected
     }
 
+    #[test]
+    fn test_send_text_message_no_device_not_queued() {
+        let mut device = Device::default();
+        let _ = device.update(DeviceMessage::SendTextMessage(
+            "Hello".to_string(),
+            ConversationId::Channel(0.into()),
+            None,
+        ));
+        assert!(device.outbox().is_empty());
+    }
+
+    #[test]
+    fn test_send_while_disconnected_queues() {
+        let mut device = Device::default();
+        device.connection_state = Disconnected(Some("device1".into()), None);
+        device.my_user = Some(MCUser::default());
+        let conversation_id = ConversationId::Channel(0.into());
+        let _ = device.update(DeviceMessage::SendTextMessage(
+            "Hello".to_string(),
+            conversation_id,
+            None,
+        ));
+        let _ = device.update(DeviceMessage::SendSelfInfoMessage(conversation_id));
+        assert_eq!(device.outbox().len(), 2);
+        assert_eq!(
+            device
+                .outbox()
+                .conversation(&"device1".into(), conversation_id)
+                .count(),
+            2
+        );
+    }
+
+    #[cfg(feature = "meshtastic")]
+    #[test]
+    fn test_send_while_connected_without_subscription_queues() {
+        let mut device = Device::default();
+        device.connection_state = Connected("device1".into(), RadioType::Meshtastic);
+        let _ = device.update(DeviceMessage::SendTextMessage(
+            "Hello".to_string(),
+            ConversationId::Channel(0.into()),
+            None,
+        ));
+        assert_eq!(device.outbox().len(), 1);
+    }
+
+    #[cfg(feature = "meshtastic")]
+    #[test]
+    fn test_send_while_connected_not_queued() {
+        let mut device = Device::default();
+        let (sender, _receiver) = tokio::sync::mpsc::channel::<DeviceCommand>(10);
+        device.meshtastic_sender = Some(sender);
+        device.connection_state = Connected("device1".into(), RadioType::Meshtastic);
+        let _ = device.update(DeviceMessage::SendTextMessage(
+            "Hello".to_string(),
+            ConversationId::Channel(0.into()),
+            None,
+        ));
+        assert!(device.outbox().is_empty());
+    }
+
+    #[cfg(feature = "meshtastic")]
+    #[test]
+    fn test_connected_flushes_outbox() {
+        let mut device = Device::default();
+        device.connection_state = Disconnected(Some("device1".into()), None);
+        let _ = device.update(DeviceMessage::SendTextMessage(
+            "Hello".to_string(),
+            ConversationId::Channel(0.into()),
+            None,
+        ));
+        assert_eq!(device.outbox().len(), 1);
+
+        let (sender, _receiver) = tokio::sync::mpsc::channel::<DeviceCommand>(10);
+        device.meshtastic_sender = Some(sender);
+        let _ = device.update(SubscriptionMessage(ConnectedEvent(
+            "device1".into(),
+            RadioType::Meshtastic,
+        )));
+        assert!(device.outbox().is_empty());
+    }
+
+    #[cfg(feature = "meshtastic")]
+    #[test]
+    fn test_connected_without_subscription_keeps_outbox() {
+        let mut device = Device::default();
+        device.connection_state = Disconnected(Some("device1".into()), None);
+        let _ = device.update(DeviceMessage::SendTextMessage(
+            "Hello".to_string(),
+            ConversationId::Channel(0.into()),
+            None,
+        ));
+
+        let _ = device.update(SubscriptionMessage(ConnectedEvent(
+            "device1".into(),
+            RadioType::Meshtastic,
+        )));
+        assert_eq!(device.outbox().len(), 1);
+    }
+
+    #[test]
+    fn test_queued_message_failed_requeued() {
+        let mut device = Device::default();
+        let conversation_id = ConversationId::Channel(0.into());
+        let queued = QueuedMessage::new(conversation_id, Outgoing::Text("Hello".into(), None));
+        let task = device.update(QueuedMessageFailed(
+            "device1".into(),
+            queued,
+            "channel closed".into(),
+        ));
+        assert!(task.units() > 0);
+        assert_eq!(
+            device
+                .outbox()
+                .conversation(&"device1".into(), conversation_id)
+                .count(),
+            1
+        );
+    }
+
+    #[cfg(feature = "meshtastic")]
+    #[test]
+    fn test_connected_loads_saved_outbox() {
+        let mut device = Device::default();
+        let (sender, _receiver) = tokio::sync::mpsc::channel::<DeviceCommand>(10);
+        device.meshtastic_sender = Some(sender);
+        let task = device.update(SubscriptionMessage(ConnectedEvent(
+            "device1".into(),
+            RadioType::Meshtastic,
+        )));
+        assert!(task.units() > 0);
+
+        let saved = vec![QueuedMessage::new(
+            ConversationId::Channel(0.into()),
+            Outgoing::Text("Saved".into(), None),
+        )];
+        let task = device.update(OutboxLoaded("device1".into(), saved));
+        assert!(task.units() > 0);
+        assert!(device.outbox().is_empty());
+    }
+
+    #[test]
+    fn test_outbox_loaded_for_other_device_kept() {
+        let mut device = Device::default();
+        let saved = vec![QueuedMessage::new(
+            ConversationId::Channel(0.into()),
+            Outgoing::Text("Saved".into(), None),
+        )];
+        let _ = device.update(OutboxLoaded("device1".into(), saved));
+        assert_eq!(device.outbox().len(), 1);
+    }
+
+    #[test]
+    fn test_outbox_survives_disconnect() {
+        let mut device = Device::default();
+        device.connection_state = Connecting("device1".into());
+        let _ = device.update(DeviceMessage::SendTextMessage(
+            "Hello".to_string(),
+            ConversationId::Channel(0.into()),
+            None,
+        ));
+        let _ = device.update(SubscriptionMessage(DisconnectedEvent("device1".into())));
+        assert_eq!(device.outbox().len(), 1);
+    }
+
     #[test]
     fn test_send_emoji_reply_not_connected() {
         let mut device = Device::default();
@@ -3349,4 +6054,1869 @@ mod tests {
         let _ = device.update(ShowChannel(None));
         assert!(device.viewing_conversation.is_none());
     }
+
+    fn history_message(id: u64, time: u64) -> MCMessage {
+        MCMessage::new(
+            MessageId::from(id),
+            NodeId::from(100u64),
+            MCContent::NewTextMessage(format!("message {id}")),
+            TimeStamp::from(time),
+        )
+    }
+
+    #[test]
+    fn test_history_loaded_restores_existing_conversation() {
+        let mut device = Device::default();
+        device.connection_state = Connected("device1".into(), RadioType::Meshtastic);
+        device.my_node_id = Some(NodeId::from(999u64));
+        device.add_channel(MCChannel {
+            index: 0,
+            name: "Test".to_string(),
+        });
+
+        let channel = ConversationId::Channel(0.into());
+        let history = HashMap::from([(
+            channel,
+            vec![history_message(1, 10), history_message(2, 20)],
+        )]);
+        let _ = device.update(HistoryLoaded("device1".into(), history));
+
+        let conversation = device.conversations.get(&channel).expect("No conversation");
+        assert_eq!(conversation.history().len(), 2);
+        assert!(device.saved_history.is_empty());
+    }
+
+    #[test]
+    fn test_conversation_not_saved_until_history_loaded() {
+        let mut device = Device::default();
+        device.my_node_id = Some(NodeId::from(999u64));
+        device.connection_state = Connected("device1".into(), RadioType::Meshtastic);
+        device.add_channel(MCChannel {
+            index: 0,
+            name: "Test".to_string(),
+        });
+        let channel = ConversationId::Channel(0.into());
+
+        assert_eq!(device.save_conversation(&channel).units(), 0);
+        assert!(device.unsaved_conversations.contains(&channel));
+
+        let history = HashMap::from([(channel, vec![history_message(1, 10)])]);
+        let task = device.update(HistoryLoaded("device1".into(), history));
+        assert!(task.units() > 0);
+        assert!(device.unsaved_conversations.is_empty());
+        assert!(device.history_loaded);
+        assert!(device.save_conversation(&channel).units() > 0);
+    }
+
+    #[test]
+    fn test_history_loaded_for_other_device_ignored() {
+        let mut device = Device::default();
+        device.connection_state = Connected("device2".into(), RadioType::Meshtastic);
+        let channel = ConversationId::Channel(0.into());
+        let history = HashMap::from([(channel, vec![history_message(1, 10)])]);
+        let task = device.update(HistoryLoaded("device1".into(), history));
+        assert_eq!(task.units(), 0);
+        assert!(device.saved_history.is_empty());
+        assert!(!device.history_loaded);
+    }
+
+    #[test]
+    fn test_history_loaded_reset_on_disconnect() {
+        let mut device = Device::default();
+        device.connection_state = Connected("device1".into(), RadioType::Meshtastic);
+        let _ = device.update(HistoryLoaded("device1".into(), HashMap::new()));
+        assert!(device.history_loaded);
+        let _ = device.update(SubscriptionMessage(DisconnectedEvent("device1".into())));
+        assert!(!device.history_loaded);
+    }
+
+    #[test]
+    fn test_history_loaded_before_channel_known() {
+        let mut device = Device::default();
+        device.connection_state = Connected("device1".into(), RadioType::Meshtastic);
+        device.my_node_id = Some(NodeId::from(999u64));
+
+        let channel = ConversationId::Channel(0.into());
+        let history = HashMap::from([(channel, vec![history_message(1, 10)])]);
+        let _ = device.update(HistoryLoaded("device1".into(), history));
+        assert!(device.saved_history.contains_key(&channel));
+
+        device.add_channel(MCChannel {
+            index: 0,
+            name: "Test".to_string(),
+        });
+
+        let conversation = device.conversations.get(&channel).expect("No conversation");
+        assert_eq!(conversation.history().len(), 1);
+        assert!(device.saved_history.is_empty());
+    }
+
+    #[test]
+    fn test_history_loaded_before_node_known() {
+        let mut device = Device::default();
+        device.connection_state = Connected("device1".into(), RadioType::Meshtastic);
+        device.my_node_id = Some(NodeId::from(999u64));
+
+        let node = ConversationId::Node(NodeId::from(100u64));
+        let history = HashMap::from([(node, vec![history_message(1, 10)])]);
+        let _ = device.update(HistoryLoaded("device1".into(), history));
+
+        let _ = device.update(SubscriptionMessage(NewNode(MCNodeInfo {
+            node_id: NodeId::from(100u64),
+            user: None,
+            position: None,
+            is_ignored: false,
+            hops_away: None,
+        })));
+
+        let conversation = device.conversations.get(&node).expect("No conversation");
+        assert_eq!(conversation.history().len(), 1);
+    }
+
+    #[test]
+    fn test_history_loaded_respects_history_length() {
+        let mut device = Device::default();
+        device.connection_state = Connected("device1".into(), RadioType::Meshtastic);
+        device.my_node_id = Some(NodeId::from(999u64));
+        device.set_history_length(HistoryLength::NumberOfMessages(1));
+        device.add_channel(MCChannel {
+            index: 0,
+            name: "Test".to_string(),
+        });
+
+        let channel = ConversationId::Channel(0.into());
+        let history = HashMap::from([(
+            channel,
+            vec![history_message(1, 10), history_message(2, 20)],
+        )]);
+        let _ = device.update(HistoryLoaded("device1".into(), history));
+
+        let conversation = device.conversations.get(&channel).expect("No conversation");
+        let messages = conversation.history();
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].message_id(), MessageId::from(2u64));
+    }
+
+    #[test]
+    fn test_node_readded_keeps_conversation() {
+        let mut device = Device::default();
+        device.my_node_id = Some(NodeId::from(999u64));
+        let node_info = MCNodeInfo {
+            node_id: NodeId::from(100u64),
+            user: None,
+            position: None,
+            is_ignored: false,
+            hops_away: None,
+        };
+        let node = ConversationId::Node(NodeId::from(100u64));
+
+        let _ = device.update(SubscriptionMessage(NewNode(node_info.clone())));
+        let _ = device.new_message(&node, history_message(1, 10));
+        let _ = device.update(SubscriptionMessage(NewNode(node_info)));
+
+        let conversation = device.conversations.get(&node).expect("No conversation");
+        assert_eq!(conversation.history().len(), 1);
+    }
+
+    #[test]
+    fn test_disconnected_clears_saved_history() {
+        let mut device = Device::default();
+        device.connection_state = Connected("device1".into(), RadioType::Meshtastic);
+        let history = HashMap::from([(
+            ConversationId::Channel(0.into()),
+            vec![history_message(1, 10)],
+        )]);
+        let _ = device.update(HistoryLoaded("device1".into(), history));
+        assert!(!device.saved_history.is_empty());
+
+        let _ = device.update(SubscriptionMessage(DisconnectedEvent("device1".into())));
+        assert!(device.saved_history.is_empty());
+    }
+
+    #[test]
+    fn test_toggle_message_search() {
+        let mut device = Device::default();
+        assert!(!device.search_messages);
+        let _ = device.update(ToggleMessageSearch);
+        assert!(device.search_messages);
+        let _ = device.update(ToggleMessageSearch);
+        assert!(!device.search_messages);
+    }
+
+    #[test]
+    fn test_search_results_grouped_by_conversation() {
+        let mut device = Device::default();
+        device.connection_state = Connected("device1".into(), RadioType::Meshtastic);
+        device.my_node_id = Some(NodeId::from(999u64));
+        device.add_channel(MCChannel {
+            index: 0,
+            name: "Primary".to_string(),
+        });
+        device.add_channel(MCChannel {
+            index: 1,
+            name: "Other".to_string(),
+        });
+        let _ = device.update(SubscriptionMessage(NewNode(MCNodeInfo {
+            node_id: NodeId::from(100u64),
+            user: None,
+            position: None,
+            is_ignored: false,
+            hops_away: None,
+        })));
+
+        let channel0 = ConversationId::Channel(0.into());
+        let channel1 = ConversationId::Channel(1.into());
+        let node = ConversationId::Node(NodeId::from(100u64));
+        let history = HashMap::from([
+            (node, vec![history_message(1, 10), history_message(11, 11)]),
+            (channel1, vec![history_message(2, 20)]),
+            (
+                channel0,
+                vec![history_message(3, 30), history_message(4, 40)],
+            ),
+        ]);
+        let _ = device.update(HistoryLoaded("device1".into(), history));
+
+        let _ = device.update(SearchInput("message 1".into()));
+        let config = Config::default();
+        let results = device.search_results(&config);
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].0, node);
+        assert_eq!(results[0].2.len(), 2);
+
+        let _ = device.update(SearchInput("MESSAGE".into()));
+        let results = device.search_results(&config);
+        let order: Vec<ConversationId> = results.iter().map(|(id, _, _)| *id).collect();
+        assert_eq!(order, vec![channel0, channel1, node]);
+        assert_eq!(results[0].1, "Primary");
+    }
+
+    #[test]
+    fn test_show_message_opens_conversation() {
+        let mut device = Device::default();
+        device.my_node_id = Some(NodeId::from(999u64));
+        device.add_channel(MCChannel {
+            index: 0,
+            name: "Primary".to_string(),
+        });
+        let channel = ConversationId::Channel(0.into());
+        let _ = device.update(ShowMessage(channel, MessageId::from(1u64)));
+        assert_eq!(device.viewing_conversation, Some(channel));
+    }
+
+    fn known_node(id: u64, long_name: &str, first_seen: u64, last_heard: u64) -> KnownNode {
+        KnownNode {
+            node_info: MCNodeInfo {
+                node_id: NodeId::from(id),
+                user: Some(MCUser {
+                    long_name: long_name.into(),
+                    ..Default::default()
+                }),
+                position: None,
+                is_ignored: false,
+                hops_away: None,
+            },
+            first_seen: TimeStamp::from(first_seen),
+            last_heard: TimeStamp::from(last_heard),
+            pinned_key: None,
+            changed_key: None,
+        }
+    }
+
+    #[test]
+    fn test_known_nodes_loaded_adds_nodes() {
+        let mut device = Device::default();
+        device.connection_state = Connected("device1".into(), RadioType::Meshtastic);
+        device.my_node_id = Some(NodeId::from(999u64));
+        let known = HashMap::from([(NodeId::from(100u64), known_node(100, "Old Friend", 1, 2))]);
+        let _ = device.update(KnownNodesLoaded("device1".into(), known));
+
+        assert!(device.nodes.contains_key(&NodeId::from(100u64)));
+        assert!(
+            device
+                .conversations
+                .contains_key(&ConversationId::Node(NodeId::from(100u64)))
+        );
+    }
+
+    #[test]
+    fn test_known_nodes_loaded_for_other_device_ignored() {
+        let mut device = Device::default();
+        device.connection_state = Connected("device2".into(), RadioType::Meshtastic);
+        device.my_node_id = Some(NodeId::from(999u64));
+        let known = HashMap::from([(NodeId::from(100u64), known_node(100, "Old Friend", 1, 2))]);
+        let task = device.update(KnownNodesLoaded("device1".into(), known));
+        assert_eq!(task.units(), 0);
+        assert!(!device.known_nodes_loaded);
+        assert!(device.known_nodes.get(&NodeId::from(100u64)).is_none());
+    }
+
+    #[test]
+    fn test_known_nodes_saves_batched() {
+        let mut device = Device::default();
+        device.connection_state = Connected("device1".into(), RadioType::Meshtastic);
+        let known = HashMap::from([(NodeId::from(100u64), known_node(100, "Old Friend", 1, 2))]);
+        let _ = device.update(KnownNodesLoaded("device1".into(), known));
+        assert!(device.known_nodes_save_scheduled);
+
+        // Hearing from the node while a save is waiting doesn't start another
+        assert_eq!(device.node_heard(NodeId::from(100u64)).units(), 0);
+        assert!(device.known_nodes_unsaved);
+
+        assert_eq!(device.update(SaveKnownNodes).units(), 1);
+        assert!(!device.known_nodes_unsaved);
+        assert!(!device.known_nodes_save_scheduled);
+        assert_eq!(device.update(SaveKnownNodes).units(), 0);
+
+        assert_eq!(device.node_heard(NodeId::from(100u64)).units(), 1);
+        assert!(device.known_nodes_save_scheduled);
+    }
+
+    #[test]
+    fn test_known_nodes_saved_on_disconnect() {
+        let mut device = Device::default();
+        device.connection_state = Connected("device1".into(), RadioType::Meshtastic);
+        let known = HashMap::from([(NodeId::from(100u64), known_node(100, "Old Friend", 1, 2))]);
+        let _ = device.update(KnownNodesLoaded("device1".into(), known));
+        assert!(device.known_nodes_unsaved);
+
+        // The save, and going back to the device list
+        let task = device.update(SubscriptionMessage(DisconnectedEvent("device1".into())));
+        assert_eq!(task.units(), 2);
+        assert!(!device.known_nodes_unsaved);
+    }
+
+    #[test]
+    fn test_known_nodes_loaded_before_my_node_num() {
+        let mut device = Device::default();
+        device.connection_state = Connected("device1".into(), RadioType::Meshtastic);
+        let known = HashMap::from([(NodeId::from(100u64), known_node(100, "Old Friend", 1, 2))]);
+        let _ = device.update(KnownNodesLoaded("device1".into(), known));
+        assert!(device.nodes.is_empty());
+
+        let _ = device.update(SubscriptionMessage(MyNodeNum(NodeId::from(999u64))));
+        assert!(device.nodes.contains_key(&NodeId::from(100u64)));
+    }
+
+    #[test]
+    fn test_new_node_merged_with_known_node() {
+        let mut device = Device::default();
+        device.connection_state = Connected("device1".into(), RadioType::Meshtastic);
+        device.my_node_id = Some(NodeId::from(999u64));
+        let known = HashMap::from([(NodeId::from(100u64), known_node(100, "Old Friend", 1, 2))]);
+        let _ = device.update(KnownNodesLoaded("device1".into(), known));
+
+        // The radio reports the node without a user, so keep the one we know
+        let _ = device.update(SubscriptionMessage(NewNode(MCNodeInfo {
+            node_id: NodeId::from(100u64),
+            user: None,
+            position: None,
+            is_ignored: false,
+            hops_away: None,
+        })));
+        let node = device
+            .nodes
+            .get(&NodeId::from(100u64))
+            .expect("Node missing");
+        assert_eq!(
+            node.user.as_ref().map(|user| user.long_name.as_str()),
+            Some("Old Friend")
+        );
+        let known = device
+            .known_nodes
+            .get(&NodeId::from(100u64))
+            .expect("Known node missing");
+        assert_eq!(known.first_seen, TimeStamp::from(1u64));
+    }
+
+    #[test]
+    fn test_new_node_is_known() {
+        let mut device = Device::default();
+        device.my_node_id = Some(NodeId::from(999u64));
+        let _ = device.update(SubscriptionMessage(NewNode(MCNodeInfo {
+            node_id: NodeId::from(100u64),
+            user: None,
+            position: None,
+            is_ignored: false,
+            hops_away: None,
+        })));
+        assert!(device.known_nodes.get(&NodeId::from(100u64)).is_some());
+    }
+
+    #[test]
+    fn test_message_received_updates_last_heard() {
+        let mut device = Device::default();
+        device.connection_state = Connected("device1".into(), RadioType::Meshtastic);
+        device.my_node_id = Some(NodeId::from(999u64));
+        let known = HashMap::from([(NodeId::from(100u64), known_node(100, "Old Friend", 1, 2))]);
+        let _ = device.update(KnownNodesLoaded("device1".into(), known));
+
+        let _ = device.update(SubscriptionMessage(MCMessageReceived(
+            ConversationId::Node(NodeId::from(100u64)),
+            MessageId::from(1u64),
+            NodeId::from(100u64),
+            MCContent::NewTextMessage("Hello".into()),
+            TimeStamp::now(),
+            None,
+        )));
+
+        let known = device
+            .known_nodes
+            .get(&NodeId::from(100u64))
+            .expect("Known node missing");
+        assert!(known.last_heard > TimeStamp::from(2u64));
+        assert_eq!(known.first_seen, TimeStamp::from(1u64));
+    }
+
+    #[test]
+    fn test_node_position_stored_in_known_nodes() {
+        let mut device = Device::default();
+        device.connection_state = Connected("device1".into(), RadioType::Meshtastic);
+        device.my_node_id = Some(NodeId::from(999u64));
+        let known = HashMap::from([(NodeId::from(100u64), known_node(100, "Old Friend", 1, 2))]);
+        let _ = device.update(KnownNodesLoaded("device1".into(), known));
+
+        let _ = device.update(SubscriptionMessage(NewNodePosition(
+            ConversationId::Node(NodeId::from(100u64)),
+            MessageId::from(1u64),
+            NodeId::from(100u64),
+            test_position(1.0, 2.0),
+            TimeStamp::now(),
+        )));
+
+        let known = device
+            .known_nodes
+            .get(&NodeId::from(100u64))
+            .expect("Known node missing");
+        assert!(known.node_info.position.is_some());
+        assert!(known.last_heard > TimeStamp::from(2u64));
+    }
+
+    #[test]
+    fn test_disconnected_clears_known_nodes() {
+        let mut device = Device::default();
+        device.connection_state = Connected("device1".into(), RadioType::Meshtastic);
+        let known = HashMap::from([(NodeId::from(100u64), known_node(100, "Old Friend", 1, 2))]);
+        let _ = device.update(KnownNodesLoaded("device1".into(), known));
+        let _ = device.update(SubscriptionMessage(DisconnectedEvent("device1".into())));
+        assert!(device.known_nodes.get(&NodeId::from(100u64)).is_none());
+    }
+
+    /// A device with a channel holding a message I sent, with id 42
+    fn device_with_sent_message() -> (Device, ConversationId, MessageId) {
+        let mut device = Device::default();
+        device.my_node_id = Some(NodeId::from(999u64));
+        let _ = device.update(SubscriptionMessage(NewChannel(MCChannel {
+            index: 0,
+            name: "TestChannel".into(),
+        })));
+        let conversation_id = ConversationId::Channel(0.into());
+        let message_id = MessageId::from(42u64);
+        let _ = device.update(SubscriptionMessage(MCMessageReceived(
+            conversation_id,
+            message_id,
+            NodeId::from(999u64),
+            MCContent::NewTextMessage("Hello".into()),
+            TimeStamp::now(),
+            None,
+        )));
+        (device, conversation_id, message_id)
+    }
+
+    #[test]
+    fn test_awaiting_ack_is_sending() {
+        let (mut device, conversation_id, message_id) = device_with_sent_message();
+        assert_eq!(
+            device.delivery(&conversation_id, &message_id),
+            Some(DeliveryState::Sent)
+        );
+        let _ = device.update(SubscriptionMessage(AwaitingAck(
+            conversation_id,
+            message_id,
+            Duration::from_secs(1),
+        )));
+        assert_eq!(
+            device.delivery(&conversation_id, &message_id),
+            Some(DeliveryState::Sending)
+        );
+    }
+
+    #[test]
+    fn test_ack_timeout_fails_message() {
+        let (mut device, conversation_id, message_id) = device_with_sent_message();
+        let _ = device.update(SubscriptionMessage(AwaitingAck(
+            conversation_id,
+            message_id,
+            Duration::from_secs(1),
+        )));
+        let _ = device.update(AckTimeout(conversation_id, message_id));
+        assert_eq!(
+            device.delivery(&conversation_id, &message_id),
+            Some(DeliveryState::Failed)
+        );
+    }
+
+    #[test]
+    fn test_ack_timeout_after_ack_ignored() {
+        let (mut device, conversation_id, message_id) = device_with_sent_message();
+        let _ = device.update(SubscriptionMessage(AwaitingAck(
+            conversation_id,
+            message_id,
+            Duration::from_secs(1),
+        )));
+        let _ = device.update(SubscriptionMessage(MessageACK(conversation_id, message_id)));
+        let _ = device.update(AckTimeout(conversation_id, message_id));
+        assert_eq!(
+            device.delivery(&conversation_id, &message_id),
+            Some(DeliveryState::Acked)
+        );
+    }
+
+    #[test]
+    fn test_ack_found_in_other_conversation() {
+        let (mut device, conversation_id, message_id) = device_with_sent_message();
+        let _ = device.update(SubscriptionMessage(MessageACK(
+            ConversationId::Node(NodeId::from(100u64)),
+            message_id,
+        )));
+        assert_eq!(
+            device.delivery(&conversation_id, &message_id),
+            Some(DeliveryState::Acked)
+        );
+    }
+
+    #[test]
+    fn test_message_failed() {
+        let (mut device, conversation_id, message_id) = device_with_sent_message();
+        let _ = device.update(SubscriptionMessage(MessageFailed(
+            conversation_id,
+            message_id,
+            "no route".into(),
+        )));
+        assert_eq!(
+            device.delivery(&conversation_id, &message_id),
+            Some(DeliveryState::Failed)
+        );
+    }
+
+    #[test]
+    fn test_message_failed_after_ack_ignored() {
+        let (mut device, conversation_id, message_id) = device_with_sent_message();
+        let _ = device.update(SubscriptionMessage(MessageACK(conversation_id, message_id)));
+        let _ = device.update(SubscriptionMessage(MessageFailed(
+            conversation_id,
+            message_id,
+            "no route".into(),
+        )));
+        assert_eq!(
+            device.delivery(&conversation_id, &message_id),
+            Some(DeliveryState::Acked)
+        );
+    }
+
+    #[test]
+    fn test_resend_replaces_message() {
+        let (mut device, conversation_id, message_id) = device_with_sent_message();
+        device.connection_state = Disconnected(Some("device1".into()), None);
+        let _ = device.update(SubscriptionMessage(MessageFailed(
+            conversation_id,
+            message_id,
+            "no route".into(),
+        )));
+        let _ = device.update(ResendMessage(conversation_id, message_id));
+        assert_eq!(device.delivery(&conversation_id, &message_id), None);
+        let queued: Vec<DeviceCommand> = device
+            .outbox
+            .take(&"device1".into())
+            .into_iter()
+            .map(QueuedMessage::command)
+            .collect();
+        assert!(matches!(
+            queued.as_slice(),
+            [SendText(text, id, None)] if text == "Hello" && *id == conversation_id
+        ));
+    }
+
+    #[test]
+    fn test_auto_resend_only_once() {
+        let (mut device, conversation_id, message_id) = device_with_sent_message();
+        device.connection_state = Disconnected(Some("device1".into()), None);
+        device.set_auto_resend(true);
+        let _ = device.update(SubscriptionMessage(MessageFailed(
+            conversation_id,
+            message_id,
+            "no route".into(),
+        )));
+        assert_eq!(device.delivery(&conversation_id, &message_id), None);
+        let queued: Vec<DeviceCommand> = device
+            .outbox
+            .take(&"device1".into())
+            .into_iter()
+            .map(QueuedMessage::command)
+            .collect();
+        assert!(matches!(
+            queued.as_slice(),
+            [ResendText(text, id, None)] if text == "Hello" && *id == conversation_id
+        ));
+
+        // A message with the same text typed by hand comes back from the radio first
+        let typed_id = MessageId::from(44u64);
+        let _ = device.update(SubscriptionMessage(MCMessageReceived(
+            conversation_id,
+            typed_id,
+            NodeId::from(999u64),
+            MCContent::NewTextMessage("Hello".into()),
+            TimeStamp::now(),
+            None,
+        )));
+
+        // Then the resent message, with the id the radio reports it was resent with
+        let resent_id = MessageId::from(43u64);
+        let _ = device.update(SubscriptionMessage(MCMessageReceived(
+            conversation_id,
+            resent_id,
+            NodeId::from(999u64),
+            MCContent::NewTextMessage("Hello".into()),
+            TimeStamp::now(),
+            None,
+        )));
+        let _ = device.update(SubscriptionMessage(MessageResent(resent_id)));
+
+        // The resent message is not sent again when it fails too
+        let _ = device.update(SubscriptionMessage(MessageFailed(
+            conversation_id,
+            resent_id,
+            "no route".into(),
+        )));
+        assert_eq!(
+            device.delivery(&conversation_id, &resent_id),
+            Some(DeliveryState::Failed)
+        );
+        assert!(device.outbox().is_empty());
+
+        // The one typed by hand is still sent again when it fails
+        let _ = device.update(SubscriptionMessage(MessageFailed(
+            conversation_id,
+            typed_id,
+            "no route".into(),
+        )));
+        assert_eq!(device.delivery(&conversation_id, &typed_id), None);
+        assert_eq!(device.outbox().len(), 1);
+    }
+
+    #[test]
+    fn test_traceroute_dialog() {
+        let mut device = Device::default();
+        let node_id = NodeId::from(42u64);
+        assert!(device.traceroute_view(&Config::default()).is_none());
+
+        let _ = device.update(TraceRoute(node_id));
+        assert!(device.traceroute_view(&Config::default()).is_some());
+
+        let _ = device.update(CloseTraceroute);
+        assert!(device.traceroute_view(&Config::default()).is_none());
+    }
+
+    #[test]
+    fn test_traceroute_response_shown() {
+        let mut device = Device::default();
+        let node_id = NodeId::from(42u64);
+        let _ = device.update(TraceRoute(node_id));
+        let route = Route {
+            destination: node_id,
+            towards: vec![],
+            back: vec![],
+        };
+        let _ = device.update(SubscriptionMessage(TracerouteResponse(route.clone())));
+        assert_eq!(
+            device.traceroute.as_ref().and_then(Traceroute::route),
+            Some(&route)
+        );
+    }
+
+    #[test]
+    fn test_traceroute_timeout_shows_no_response() {
+        let mut device = Device::default();
+        let node_id = NodeId::from(42u64);
+        assert!(device.update(TraceRoute(node_id)).units() > 1);
+        let _ = device.update(TracerouteTimeout(NodeId::from(43u64)));
+        assert!(
+            device
+                .traceroute
+                .as_ref()
+                .is_some_and(|t| t.failure().is_none())
+        );
+        let _ = device.update(TracerouteTimeout(node_id));
+        assert_eq!(
+            device.traceroute.as_ref().and_then(Traceroute::failure),
+            Some("No response")
+        );
+    }
+
+    #[test]
+    fn test_traceroute_send_failure_shown() {
+        let mut device = Device::default();
+        let node_id = NodeId::from(42u64);
+        let _ = device.update(TraceRoute(node_id));
+        let _ = device.update(TracerouteFailed(node_id, "Channel closed".into()));
+        assert!(
+            device
+                .traceroute
+                .as_ref()
+                .and_then(Traceroute::failure)
+                .is_some_and(|failure| failure.contains("Channel closed"))
+        );
+    }
+
+    #[test]
+    fn test_traceroute_response_without_dialog_ignored() {
+        let mut device = Device::default();
+        let _ = device.update(SubscriptionMessage(TracerouteResponse(Route {
+            destination: NodeId::from(42u64),
+            towards: vec![],
+            back: vec![],
+        })));
+        assert!(device.traceroute.is_none());
+    }
+
+    #[test]
+    fn test_traceroute_closed_on_disconnect() {
+        let mut device = Device::default();
+        let _ = device.update(TraceRoute(NodeId::from(42u64)));
+        let _ = device.update(SubscriptionMessage(DisconnectedEvent("device1".into())));
+        assert!(device.traceroute.is_none());
+    }
+
+    #[cfg(feature = "meshtastic")]
+    #[test]
+    fn test_can_traceroute_meshtastic() {
+        let mut device = Device::default();
+        assert!(!device.can_traceroute());
+        device.connection_state = Connected("device1".into(), RadioType::Meshtastic);
+        assert!(device.can_traceroute());
+    }
+
+    #[cfg(feature = "meshcore")]
+    #[test]
+    fn test_cannot_traceroute_meshcore() {
+        let mut device = Device::default();
+        device.connection_state = Connected("device1".into(), RadioType::MeshCore);
+        assert!(!device.can_traceroute());
+    }
+
+    fn neighbour(node_id: u64) -> MCNeighbour {
+        MCNeighbour {
+            node_id: NodeId::from(node_id),
+            snr: Some(4.0),
+            last_heard: TimeStamp::now(),
+        }
+    }
+
+    fn device_with_node(node_id: u64, user_id: &str) -> (Device, MCUser) {
+        let mut device = Device::default();
+        device.my_node_id = Some(NodeId::from(999u64));
+        let user = MCUser {
+            id: user_id.into(),
+            long_name: "Neighbourly".into(),
+            ..Default::default()
+        };
+        let _ = device.update(SubscriptionMessage(NewNode(MCNodeInfo {
+            node_id: NodeId::from(node_id),
+            user: Some(user.clone()),
+            position: None,
+            is_ignored: false,
+            hops_away: None,
+        })));
+        (device, user)
+    }
+
+    #[test]
+    fn test_neighbour_info_kept_per_node() {
+        let (mut device, user) = device_with_node(100, "!00000064");
+        assert!(device.neighbours(&user).is_empty());
+
+        let _ = device.update(SubscriptionMessage(NeighbourInfo(
+            NodeId::from(100u64),
+            vec![neighbour(200), neighbour(300)],
+        )));
+        assert_eq!(device.neighbours(&user).len(), 2);
+
+        // A new report replaces the last one
+        let _ = device.update(SubscriptionMessage(NeighbourInfo(
+            NodeId::from(100u64),
+            vec![neighbour(200)],
+        )));
+        assert_eq!(device.neighbours(&user), &[neighbour(200)][..]);
+        let _element = device.neighbours_view(&user, &Config::default());
+    }
+
+    #[test]
+    fn test_neighbours_cleared_on_disconnect() {
+        let (mut device, user) = device_with_node(100, "!00000064");
+        let _ = device.update(SubscriptionMessage(NeighbourInfo(
+            NodeId::from(100u64),
+            vec![neighbour(200)],
+        )));
+        let _ = device.update(SubscriptionMessage(DisconnectedEvent("device1".into())));
+        assert!(device.neighbours(&user).is_empty());
+    }
+
+    #[test]
+    fn test_unknown_user_has_no_neighbours() {
+        let device = Device::default();
+        assert!(device.neighbours(&MCUser::default()).is_empty());
+    }
+
+    #[cfg(feature = "meshtastic")]
+    #[test]
+    fn test_meshtastic_neighbours_not_requested() {
+        let (mut device, user) = device_with_node(100, "!00000064");
+        device.connection_state = Connected("device1".into(), RadioType::Meshtastic);
+        assert_eq!(device.request_neighbours(&user).units(), 0);
+    }
+
+    fn battery(level: u32) -> MCTelemetry {
+        MCTelemetry::Device(MCDeviceMetrics {
+            battery_level: Some(level),
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_telemetry_kept_per_node() {
+        let (mut device, user) = device_with_node(100, "!00000064");
+        let _ = device.update(SubscriptionMessage(NodeTelemetry(
+            NodeId::from(100u64),
+            battery(90),
+            TimeStamp::from(1000u64),
+        )));
+        let _ = device.update(SubscriptionMessage(NodeTelemetry(
+            NodeId::from(100u64),
+            battery(80),
+            TimeStamp::from(2000u64),
+        )));
+        let _ = device.update(SubscriptionMessage(NodeTelemetry(
+            NodeId::from(200u64),
+            battery(10),
+            TimeStamp::from(2000u64),
+        )));
+
+        let history = device
+            .telemetry
+            .get(&NodeId::from(100u64))
+            .expect("Telemetry for node 100 expected");
+        assert_eq!(history.len(), 2);
+        assert_eq!(
+            history
+                .latest_device()
+                .and_then(|metrics| metrics.battery_level),
+            Some(80)
+        );
+        let _element = device.telemetry_view(&user);
+        let _empty = device.telemetry_view(&MCUser::default());
+    }
+
+    #[test]
+    fn test_telemetry_cleared_on_disconnect() {
+        let (mut device, _user) = device_with_node(100, "!00000064");
+        let _ = device.update(SubscriptionMessage(NodeTelemetry(
+            NodeId::from(100u64),
+            battery(90),
+            TimeStamp::now(),
+        )));
+        let _ = device.update(SubscriptionMessage(DisconnectedEvent("device1".into())));
+        assert!(device.telemetry.is_empty());
+    }
+
+    fn waypoint(id: u32, name: &str, expire: Option<TimeStamp>) -> MCWaypoint {
+        MCWaypoint {
+            id,
+            name: name.into(),
+            expire,
+            ..Default::default()
+        }
+    }
+
+    fn device_with_waypoints(waypoints: Vec<(MCWaypoint, u64)>) -> Device {
+        let mut device = Device::default();
+        device.my_node_id = Some(NodeId::from(999u64));
+        let _ = device.update(SubscriptionMessage(NewChannel(MCChannel {
+            index: 0,
+            name: "TestChannel".into(),
+        })));
+        for (message_id, (waypoint, time)) in (1u64..).zip(waypoints) {
+            let _ = device.update(SubscriptionMessage(MCMessageReceived(
+                ConversationId::Channel(0.into()),
+                MessageId::from(message_id),
+                NodeId::from(100u64),
+                MCContent::Waypoint(waypoint),
+                TimeStamp::from(time),
+                None,
+            )));
+        }
+        device
+    }
+
+    #[test]
+    fn test_active_waypoints_latest_version() {
+        let device = device_with_waypoints(vec![
+            (waypoint(1, "Old name", None), 1000),
+            (waypoint(2, "Another", None), 1500),
+            (waypoint(1, "New name", None), 2000),
+        ]);
+        let names: Vec<&str> = device
+            .active_waypoints(TimeStamp::from(3000u64))
+            .iter()
+            .map(|waypoint| waypoint.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["Another", "New name"]);
+    }
+
+    #[test]
+    fn test_expired_waypoints_not_active() {
+        let device = device_with_waypoints(vec![
+            (waypoint(1, "Expired", Some(TimeStamp::from(2000u64))), 1000),
+            (waypoint(2, "Current", Some(TimeStamp::from(5000u64))), 1000),
+        ]);
+        let active = device.active_waypoints(TimeStamp::from(3000u64));
+        assert_eq!(
+            active,
+            vec![&waypoint(2, "Current", Some(TimeStamp::from(5000u64)))]
+        );
+    }
+
+    #[test]
+    fn test_waypoints_dialog() {
+        let mut device = device_with_waypoints(vec![(waypoint(1, "Camp", None), 1000)]);
+        assert!(device.waypoints_view(&Config::default()).is_none());
+
+        let _ = device.update(ShowWaypoints);
+        assert!(device.waypoints_view(&Config::default()).is_some());
+
+        let _ = device.update(CloseWaypoints);
+        assert!(device.waypoints_view(&Config::default()).is_none());
+    }
+
+    #[test]
+    fn test_compose_waypoint() {
+        let mut device = Device::default();
+        device.my_position = Some(MCPosition {
+            latitude: 50.5,
+            longitude: -1.25,
+            ..Default::default()
+        });
+        let conversation_id = ConversationId::Channel(0.into());
+        let _ = device.update(ComposeWaypoint(conversation_id));
+        assert!(device.waypoints_view(&Config::default()).is_some());
+
+        // Not sent until it has a name
+        let _ = device.update(SendWaypointMessage);
+        assert!(device.waypoint_editor.is_some());
+
+        let _ = device.update(WaypointMsg(WaypointMessage::NameInput("Camp".into())));
+        let _ = device.update(SendWaypointMessage);
+        assert!(device.waypoint_editor.is_none());
+    }
+
+    #[test]
+    fn test_waypoints_dialog_closed_on_disconnect() {
+        let mut device = Device::default();
+        let _ = device.update(ComposeWaypoint(ConversationId::Channel(0.into())));
+        let _ = device.update(SubscriptionMessage(DisconnectedEvent("device1".into())));
+        assert!(device.waypoints_view(&Config::default()).is_none());
+    }
+
+    #[cfg(feature = "meshtastic")]
+    #[test]
+    fn test_can_send_waypoints_meshtastic() {
+        let mut device = Device::default();
+        assert!(!device.can_send_waypoints());
+        device.connection_state = Connected("device1".into(), RadioType::Meshtastic);
+        assert!(device.can_send_waypoints());
+    }
+
+    #[cfg(feature = "meshcore")]
+    #[test]
+    fn test_cannot_send_waypoints_meshcore() {
+        let mut device = Device::default();
+        device.connection_state = Connected("device1".into(), RadioType::MeshCore);
+        assert!(!device.can_send_waypoints());
+    }
+
+    fn channel_settings(name: &str) -> MCChannelSettings {
+        MCChannelSettings {
+            name: name.into(),
+            psk: vec![1],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_channel_settings_kept_in_index_order() {
+        let mut device = Device::default();
+        let _ = device.update(SubscriptionMessage(NewChannelSettings(
+            1,
+            channel_settings("Second"),
+        )));
+        let _ = device.update(SubscriptionMessage(NewChannelSettings(
+            0,
+            channel_settings(""),
+        )));
+        let names: Vec<&str> = device
+            .channel_settings
+            .values()
+            .map(|settings| settings.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["", "Second"]);
+    }
+
+    #[test]
+    fn test_channel_sharing_dialog() {
+        let mut device = Device::default();
+        assert!(device.channel_sharing_view().is_none());
+
+        let _ = device.update(ShowChannelSharing);
+        assert!(device.channel_sharing_view().is_some());
+
+        let _ = device.update(CloseChannelSharing);
+        assert!(device.channel_sharing_view().is_none());
+    }
+
+    #[test]
+    fn test_write_channels_needs_valid_url() {
+        let mut device = Device::default();
+        let _ = device.update(ShowChannelSharing);
+        let _ = device.update(ChannelUrlInput("not a channel url".into()));
+        let _ = device.update(WriteChannels);
+        assert!(device.channel_sharing.is_some());
+    }
+
+    #[cfg(feature = "meshtastic")]
+    #[test]
+    fn test_write_channels_closes_dialog() {
+        let mut device = Device::default();
+        let _ = device.update(ShowChannelSharing);
+        let _ = device.update(ChannelUrlInput(
+            "https://meshtastic.org/e/#CgMSAQESCDgBQANIAVAe".into(),
+        ));
+        let _ = device.update(WriteChannels);
+        assert!(device.channel_sharing.is_none());
+    }
+
+    #[test]
+    fn test_channel_sharing_cleared_on_disconnect() {
+        let mut device = Device::default();
+        let _ = device.update(SubscriptionMessage(NewChannelSettings(
+            0,
+            channel_settings(""),
+        )));
+        let _ = device.update(ShowChannelSharing);
+        let _ = device.update(SubscriptionMessage(DisconnectedEvent("device1".into())));
+        assert!(device.channel_settings.is_empty());
+        assert!(device.channel_sharing_view().is_none());
+    }
+
+    #[cfg(feature = "meshtastic")]
+    #[test]
+    fn test_can_share_channels_meshtastic() {
+        let mut device = Device::default();
+        assert!(!device.can_share_channels());
+        device.connection_state = Connected("device1".into(), RadioType::Meshtastic);
+        assert!(device.can_share_channels());
+    }
+
+    #[cfg(feature = "meshcore")]
+    #[test]
+    fn test_cannot_share_channels_meshcore() {
+        let mut device = Device::default();
+        device.connection_state = Connected("device1".into(), RadioType::MeshCore);
+        assert!(!device.can_share_channels());
+    }
+
+    #[test]
+    fn test_received_message_keeps_link_quality() {
+        let mut device = Device::default();
+        device.my_node_id = Some(NodeId::from(999u64));
+        let _ = device.update(SubscriptionMessage(NewChannel(MCChannel {
+            index: 0,
+            name: "TestChannel".into(),
+        })));
+        let link_quality = LinkQuality {
+            snr: Some(2.5),
+            rssi: Some(-100),
+            hops: Some(1),
+        };
+        let _ = device.update(SubscriptionMessage(MCMessageReceived(
+            ConversationId::Channel(0.into()),
+            MessageId::from(1u64),
+            NodeId::from(100u64),
+            NewTextMessage("Hello".into()),
+            TimeStamp::now(),
+            Some(link_quality),
+        )));
+        let message = device
+            .conversations
+            .get(&ConversationId::Channel(0.into()))
+            .and_then(|conversation| conversation.message(&MessageId::from(1u64)))
+            .expect("Message not received");
+        assert_eq!(message.link_quality(), Some(&link_quality));
+    }
+
+    fn keyed_node(node_id: u64, public_key: Vec<u8>) -> MCNodeInfo {
+        MCNodeInfo {
+            node_id: NodeId::from(node_id),
+            user: Some(MCUser {
+                long_name: "Keyed".into(),
+                public_key,
+                ..Default::default()
+            }),
+            position: None,
+            is_ignored: false,
+            hops_away: None,
+        }
+    }
+
+    #[test]
+    fn test_dm_encrypted_when_key_known() {
+        let mut device = Device::default();
+        device.my_node_id = Some(NodeId::from(999u64));
+        let _ = device.update(SubscriptionMessage(NewNode(keyed_node(100, vec![1; 32]))));
+        let _ = device.update(SubscriptionMessage(NewNode(keyed_node(200, vec![]))));
+        assert!(device.dm_encrypted(NodeId::from(100u64)));
+        assert!(!device.dm_encrypted(NodeId::from(200u64)));
+        assert!(!device.dm_encrypted(NodeId::from(300u64)));
+    }
+
+    #[test]
+    fn test_changed_key_in_node_info_warns() {
+        let mut device = Device::default();
+        device.connection_state = Connected("device1".into(), RadioType::Meshtastic);
+        device.my_node_id = Some(NodeId::from(999u64));
+        let _ = device.update(KnownNodesLoaded("device1".into(), HashMap::new()));
+        let node_id = NodeId::from(100u64);
+        let _ = device.update(SubscriptionMessage(NewNode(keyed_node(100, vec![1; 32]))));
+        assert!(!device.known_nodes.key_changed(node_id));
+
+        let user = keyed_node(100, vec![2; 32]).user.expect("No user");
+        let _ = device.update(SubscriptionMessage(NewNodeInfo(
+            ConversationId::Channel(0.into()),
+            MessageId::from(1u64),
+            node_id,
+            user,
+            TimeStamp::now(),
+        )));
+        assert!(device.known_nodes.key_changed(node_id));
+
+        let _ = device.update(TrustNodeKey(node_id));
+        assert!(!device.known_nodes.key_changed(node_id));
+    }
+
+    #[test]
+    fn test_header_shows_encryption_status() {
+        let mut device = Device::default();
+        device.connection_state = Connected("device1".into(), RadioType::Meshtastic);
+        device.my_node_id = Some(NodeId::from(999u64));
+        let _ = device.update(KnownNodesLoaded("device1".into(), HashMap::new()));
+        let _ = device.update(SubscriptionMessage(NewNode(keyed_node(100, vec![1; 32]))));
+        let _ = device.update(SubscriptionMessage(NewNode(keyed_node(100, vec![2; 32]))));
+        assert!(device.known_nodes.key_changed(NodeId::from(100u64)));
+
+        device.viewing_conversation = Some(ConversationId::Node(NodeId::from(100u64)));
+        let config = Config::default();
+        let device_list = DeviceList::default();
+        let _element = device.header(&config, device.connection_state(), &device_list);
+    }
+
+    #[test]
+    fn test_key_not_pinned_before_known_nodes_loaded() {
+        let mut device = Device::default();
+        device.my_node_id = Some(NodeId::from(999u64));
+        device.connection_state = Connected("device1".into(), RadioType::Meshtastic);
+        let task = device.update(SubscriptionMessage(NewNode(keyed_node(100, vec![2; 32]))));
+        assert_eq!(task.units(), 0);
+        assert!(
+            device
+                .known_nodes
+                .get(&NodeId::from(100u64))
+                .is_some_and(|known| known.pinned_key.is_none())
+        );
+    }
+
+    #[test]
+    fn test_changed_key_found_when_known_nodes_loaded_warns() {
+        let mut device = Device::default();
+        device.connection_state = Connected("device1".into(), RadioType::Meshtastic);
+        device.my_node_id = Some(NodeId::from(999u64));
+        let node_id = NodeId::from(100u64);
+        let _ = device.update(SubscriptionMessage(NewNode(keyed_node(100, vec![2; 32]))));
+
+        let mut pinned = known_node(100, "Keyed", 1, 2);
+        pinned.pinned_key = Some(vec![1; 32]);
+        let task = device.update(KnownNodesLoaded(
+            "device1".into(),
+            HashMap::from([(node_id, pinned)]),
+        ));
+
+        assert!(device.known_nodes.key_changed(node_id));
+        // The warning, and waiting to save the known nodes
+        assert_eq!(task.units(), 2);
+        assert_eq!(
+            device
+                .known_nodes
+                .get(&node_id)
+                .and_then(|known| known.pinned_key.clone()),
+            Some(vec![1; 32])
+        );
+    }
+
+    fn device_with_channel_and_node() -> Device {
+        let mut device = Device::default();
+        device.my_node_id = Some(NodeId::from(999u64));
+        let _ = device.update(SubscriptionMessage(NewChannel(MCChannel {
+            index: 0,
+            name: "TestChannel".into(),
+        })));
+        let _ = device.update(SubscriptionMessage(NewNode(keyed_node(100, vec![]))));
+        device
+    }
+
+    #[test]
+    fn test_ignore_node() {
+        let mut device = device_with_channel_and_node();
+        let node_id = NodeId::from(100u64);
+        let _ = device.update(IgnoreNode(node_id));
+        assert!(!device.nodes.contains_key(&node_id));
+        assert!(
+            device
+                .ignored_nodes
+                .get(&node_id)
+                .is_some_and(|node_info| node_info.is_ignored)
+        );
+        assert!(
+            device
+                .known_nodes
+                .get(&node_id)
+                .is_some_and(|known| known.node_info.is_ignored)
+        );
+        assert!(device.hidden_nodes().contains(&node_id));
+    }
+
+    #[test]
+    fn test_unignore_node() {
+        let mut device = device_with_channel_and_node();
+        let node_id = NodeId::from(100u64);
+        let _ = device.update(IgnoreNode(node_id));
+        let _ = device.update(UnignoreNode(node_id));
+        assert!(device.ignored_nodes.is_empty());
+        assert!(
+            device
+                .nodes
+                .get(&node_id)
+                .is_some_and(|node_info| !node_info.is_ignored)
+        );
+        assert!(
+            device
+                .known_nodes
+                .get(&node_id)
+                .is_some_and(|known| !known.node_info.is_ignored)
+        );
+    }
+
+    #[test]
+    fn test_ignore_unknown_node_does_nothing() {
+        let mut device = device_with_channel_and_node();
+        let _ = device.update(IgnoreNode(NodeId::from(200u64)));
+        let _ = device.update(UnignoreNode(NodeId::from(300u64)));
+        assert!(device.ignored_nodes.is_empty());
+        assert_eq!(device.nodes.len(), 1);
+    }
+
+    #[test]
+    fn test_node_ignored_by_radio_then_not() {
+        let mut device = device_with_channel_and_node();
+        let mut node_info = keyed_node(100, vec![]);
+        node_info.is_ignored = true;
+        let _ = device.update(SubscriptionMessage(NewNode(node_info.clone())));
+        assert!(!device.nodes.contains_key(&NodeId::from(100u64)));
+        assert!(device.ignored_nodes.contains_key(&NodeId::from(100u64)));
+
+        node_info.is_ignored = false;
+        let _ = device.update(SubscriptionMessage(NewNode(node_info)));
+        assert!(device.nodes.contains_key(&NodeId::from(100u64)));
+        assert!(device.ignored_nodes.is_empty());
+    }
+
+    #[test]
+    fn test_messages_from_ignored_node_not_unread() {
+        let mut device = device_with_channel_and_node();
+        let _ = device.update(IgnoreNode(NodeId::from(100u64)));
+        for (id, from) in [(1u64, 100u64), (2, 200)] {
+            let _ = device.update(SubscriptionMessage(MCMessageReceived(
+                ConversationId::Channel(0.into()),
+                MessageId::from(id),
+                NodeId::from(from),
+                NewTextMessage("Hello".into()),
+                TimeStamp::now(),
+                None,
+            )));
+        }
+        assert_eq!(device.unread_count(&Config::default(), true, true), 1);
+    }
+
+    #[test]
+    fn test_toggle_show_hidden() {
+        let mut device = device_with_channel_and_node();
+        let node_id = NodeId::from(100u64);
+        let _ = device.update(IgnoreNode(node_id));
+        let _ = device.update(ToggleShowHidden);
+        assert!(device.show_hidden);
+        assert!(device.hidden_nodes().is_empty());
+        let _ = device.update(ToggleShowHidden);
+        assert!(device.hidden_nodes().contains(&node_id));
+    }
+
+    #[test]
+    fn test_ignored_nodes_cleared_on_disconnect() {
+        let mut device = device_with_channel_and_node();
+        let _ = device.update(IgnoreNode(NodeId::from(100u64)));
+        let _ = device.update(SubscriptionMessage(DisconnectedEvent("device1".into())));
+        assert!(device.ignored_nodes.is_empty());
+    }
+
+    #[test]
+    fn test_view_with_ignored_nodes() {
+        let mut device = device_with_channel_and_node();
+        let _ = device.update(SubscriptionMessage(MCMessageReceived(
+            ConversationId::Channel(0.into()),
+            MessageId::from(1u64),
+            NodeId::from(100u64),
+            NewTextMessage("Hello".into()),
+            TimeStamp::now(),
+            None,
+        )));
+        let _ = device.update(IgnoreNode(NodeId::from(100u64)));
+        let config = Config::default();
+        let _ = device.view(&config);
+        let _ = device.update(ToggleShowHidden);
+        let _ = device.view(&config);
+        device.viewing_conversation = Some(ConversationId::Channel(0.into()));
+        let _ = device.view(&config);
+    }
+
+    #[cfg(feature = "meshtastic")]
+    #[test]
+    fn test_can_ignore_nodes_meshtastic() {
+        let mut device = Device::default();
+        assert!(!device.can_ignore_nodes());
+        device.connection_state = Connected("device1".into(), RadioType::Meshtastic);
+        assert!(device.can_ignore_nodes());
+    }
+
+    #[cfg(feature = "meshcore")]
+    #[test]
+    fn test_cannot_ignore_nodes_meshcore() {
+        let mut device = Device::default();
+        device.connection_state = Connected("device1".into(), RadioType::MeshCore);
+        assert!(!device.can_ignore_nodes());
+    }
+
+    fn channel_message(id: u64, from: u64, text: &str) -> DeviceEvent {
+        MCMessageReceived(
+            ConversationId::Channel(0.into()),
+            MessageId::from(id),
+            NodeId::from(from),
+            NewTextMessage(text.into()),
+            TimeStamp::now(),
+            None,
+        )
+    }
+
+    fn channel_settings_set(config: &mut Config, settings: ConversationSettings) {
+        config.set_conversation_settings(
+            &"device1".into(),
+            ConversationId::Channel(0.into()),
+            settings,
+        );
+    }
+
+    #[test]
+    fn test_muted_conversation_unread_not_counted() {
+        let mut device = device_with_channel_and_node();
+        device.connection_state = Connected("device1".into(), RadioType::Meshtastic);
+        let mut config = Config::default();
+        let _ = device.update(SubscriptionMessage(channel_message(1, 100, "Hello")));
+        assert_eq!(device.unread_count(&config, true, true), 1);
+        channel_settings_set(
+            &mut config,
+            ConversationSettings {
+                muted: true,
+                ..Default::default()
+            },
+        );
+        assert_eq!(device.unread_count(&config, true, true), 0);
+
+        // The settings of the same conversation on another device don't apply
+        device.connection_state = Connected("device2".into(), RadioType::Meshtastic);
+        assert_eq!(device.unread_count(&config, true, true), 1);
+    }
+
+    #[test]
+    fn test_conversation_not_counted_toward_device() {
+        let mut device = device_with_channel_and_node();
+        device.connection_state = Connected("device1".into(), RadioType::Meshtastic);
+        let mut config = Config::default();
+        let _ = device.update(SubscriptionMessage(channel_message(1, 100, "Hello")));
+        channel_settings_set(
+            &mut config,
+            ConversationSettings {
+                count_unread: false,
+                ..Default::default()
+            },
+        );
+        assert_eq!(device.unread_count(&config, true, true), 0);
+        let conversation_id = ConversationId::Channel(0.into());
+        let conversation = device
+            .conversations
+            .get(&conversation_id)
+            .expect("No conversation");
+        assert_eq!(
+            device.conversation_unread_count(&config, &conversation_id, conversation),
+            1
+        );
+    }
+
+    #[test]
+    fn test_mention_notification() {
+        let mut device = device_with_channel_and_node();
+        device.connection_state = Connected("device1".into(), RadioType::Meshtastic);
+        device.my_user = Some(MCUser {
+            long_name: "Base Camp".into(),
+            short_name: "BC".into(),
+            ..Default::default()
+        });
+        let conversation_id = ConversationId::Channel(0.into());
+        let mention = MCMessage::new(
+            MessageId::from(1u64),
+            NodeId::from(100u64),
+            NewTextMessage("Are you there @BC?".into()),
+            TimeStamp::now(),
+        );
+        let other = MCMessage::new(
+            MessageId::from(2u64),
+            NodeId::from(100u64),
+            NewTextMessage("Hello all".into()),
+            TimeStamp::now(),
+        );
+        let mine = MCMessage::new(
+            MessageId::from(3u64),
+            NodeId::from(999u64),
+            NewTextMessage("Are you there @BC?".into()),
+            TimeStamp::now(),
+        );
+
+        // Whether to notify depends on the conversation's settings in the config, checked when
+        // the notification arrives
+        assert_eq!(
+            device
+                .mention_notification(&conversation_id, &mention)
+                .units(),
+            1
+        );
+        assert_eq!(
+            device
+                .mention_notification(&conversation_id, &other)
+                .units(),
+            0
+        );
+        assert_eq!(
+            device.mention_notification(&conversation_id, &mine).units(),
+            0
+        );
+    }
+
+    #[test]
+    fn test_header_notification_settings() {
+        let mut device = device_with_channel_and_node();
+        device.connection_state = Connected("device1".into(), RadioType::Meshtastic);
+        device.viewing_conversation = Some(ConversationId::Channel(0.into()));
+        let mut config = Config::default();
+        channel_settings_set(
+            &mut config,
+            ConversationSettings {
+                muted: true,
+                ..Default::default()
+            },
+        );
+        let device_list = DeviceList::default();
+        let _element = device.header(&config, device.connection_state(), &device_list);
+    }
+
+    fn placed_node(node_id: u64, long_name: &str, latitude: f64, hops_away: u32) -> MCNodeInfo {
+        MCNodeInfo {
+            node_id: NodeId::from(node_id),
+            user: Some(MCUser {
+                id: format!("!{node_id:08x}"),
+                long_name: long_name.into(),
+                ..Default::default()
+            }),
+            position: Some(MCPosition {
+                latitude,
+                longitude: 0.0,
+                ..Default::default()
+            }),
+            is_ignored: false,
+            hops_away: Some(hops_away),
+        }
+    }
+
+    fn device_with_placed_nodes() -> Device {
+        let mut device = Device::default();
+        device.my_node_id = Some(NodeId::from(999u64));
+        device.my_position = Some(MCPosition::default());
+        // Heard later than when they were added, which was now
+        for (node_id, name, latitude, hops, heard_before_end) in [
+            (100u64, "charlie", 1.0, 0u32, 1000u64),
+            (200, "Alpha", 3.0, 2, 3000),
+            (300, "bravo", 2.0, 1, 2000),
+        ] {
+            let node_info = placed_node(node_id, name, latitude, hops);
+            let _ = device.update(SubscriptionMessage(NewNode(node_info)));
+            device.known_nodes.heard(
+                NodeId::from(node_id),
+                TimeStamp::from(u64::MAX - heard_before_end),
+            );
+        }
+        device
+    }
+
+    fn sorted(device: &Device, config: &Config) -> Vec<u64> {
+        let mut node_ids: Vec<NodeId> = device.nodes.keys().copied().collect();
+        device.sort_nodes(&mut node_ids, config);
+        node_ids.into_iter().map(u64::from).collect()
+    }
+
+    #[test]
+    fn test_sort_nodes() {
+        let device = device_with_placed_nodes();
+        let mut config = Config::default();
+        for (node_sort, expected) in [
+            (NodeSort::FavouritesFirst, [200, 300, 100]),
+            (NodeSort::Name, [200, 300, 100]),
+            (NodeSort::LastHeard, [100, 300, 200]),
+            (NodeSort::Distance, [100, 300, 200]),
+            (NodeSort::HopsAway, [100, 300, 200]),
+        ] {
+            config.node_sort = node_sort;
+            assert_eq!(sorted(&device, &config), expected, "{node_sort}");
+        }
+    }
+
+    #[test]
+    fn test_sort_nodes_unknown_values_last() {
+        let mut device = device_with_placed_nodes();
+        let _ = device.update(SubscriptionMessage(NewNode(MCNodeInfo {
+            position: None,
+            hops_away: None,
+            ..placed_node(50, "aaron", 0.0, 0)
+        })));
+        let mut config = Config {
+            node_sort: NodeSort::Distance,
+            ..Default::default()
+        };
+        assert_eq!(sorted(&device, &config), [100, 300, 200, 50]);
+        config.node_sort = NodeSort::HopsAway;
+        assert_eq!(sorted(&device, &config), [100, 300, 200, 50]);
+        config.node_sort = NodeSort::Name;
+        assert_eq!(sorted(&device, &config), [50, 200, 300, 100]);
+    }
+
+    #[test]
+    fn test_distance_needs_both_positions() {
+        let mut device = device_with_placed_nodes();
+        let (metres, bearing) = device
+            .distance_and_bearing(NodeId::from(100u64))
+            .expect("Distance and bearing");
+        assert!((metres - 111_195.0).abs() < 100.0, "{metres}");
+        assert!(bearing.abs() < 1e-9, "{bearing}");
+        assert!(device.distance_and_bearing(NodeId::from(400u64)).is_none());
+        device.my_position = None;
+        assert!(device.distance_and_bearing(NodeId::from(100u64)).is_none());
+    }
+
+    #[test]
+    fn test_view_sorted_with_favourites() {
+        let device = device_with_placed_nodes();
+        let mut config = Config::default();
+        config.fav_nodes.insert(NodeId::from(300u64));
+        for node_sort in NodeSort::ALL {
+            config.node_sort = node_sort;
+            let _ = device.view(&config);
+            let _ = device.status_indicator(NodeId::from(100u64), &config);
+            let _ = device.status_indicator(NodeId::from(400u64), &config);
+        }
+    }
+
+    #[test]
+    fn test_distance_views() {
+        let device = device_with_placed_nodes();
+        let user = device
+            .nodes
+            .get(&NodeId::from(300u64))
+            .and_then(|node| node.user.clone())
+            .expect("User");
+        for units in Units::ALL {
+            let config = Config {
+                units,
+                ..Default::default()
+            };
+            let _ = device.distance(NodeId::from(300u64), &config);
+            let _ = device.distance(NodeId::from(400u64), &config);
+            let _ = device.distance_view(&user, &config);
+            let _ = device.distance_view(&MCUser::default(), &config);
+        }
+    }
+
+    #[test]
+    fn test_map_nodes() {
+        let mut device = device_with_placed_nodes();
+        let mut config = Config::default();
+        config
+            .aliases
+            .insert(NodeId::from(200u64), "Base".to_string());
+
+        let map_nodes = device.map_nodes(&config);
+        assert_eq!(map_nodes.len(), 4);
+        assert!(map_nodes.iter().any(|node| node.label == "Base (Alpha)"));
+        assert!(
+            map_nodes
+                .iter()
+                .any(|node| node.mine && node.node_id == Some(NodeId::from(999u64)))
+        );
+
+        device.filter = "brav".to_string();
+        let labels: Vec<String> = device
+            .map_nodes(&config)
+            .into_iter()
+            .filter(|node| !node.mine)
+            .map(|node| node.label)
+            .collect();
+        assert_eq!(labels, ["bravo"]);
+
+        device.my_position = None;
+        assert!(device.map_nodes(&config).iter().all(|node| !node.mine));
+    }
+
+    #[test]
+    fn test_toggle_map_view() {
+        let mut device = device_with_placed_nodes();
+        let config = Config::default();
+        let _ = device.update(ToggleMap);
+        assert!(device.showing_map);
+        let _ = device.view(&config);
+        let _ = device.update(ToggleMap);
+        assert!(!device.showing_map);
+    }
+
+    #[test]
+    fn test_set_map_tiles() {
+        let mut device = Device::default();
+        let tempdir = tempfile::Builder::new()
+            .prefix("meshchat")
+            .tempdir()
+            .expect("Could not create a temp dir");
+        let task = device.set_map_tiles(Some(tempdir.path()));
+        assert!(device.map_tiles.is_some());
+        assert_eq!(task.units(), 0);
+
+        // Tiles that can't be opened leave the map drawn on the tiles it was
+        let task = device.set_map_tiles(Some(&tempdir.path().join("missing.mbtiles")));
+        assert_eq!(device.map_tiles_path(), Some(tempdir.path()));
+        assert!(task.units() > 0);
+
+        let _ = device.set_map_tiles(Some(tempdir.path()));
+        let _ = device.set_map_tiles(None);
+        assert!(device.map_tiles.is_none());
+    }
+
+    #[test]
+    fn test_show_location_on_map() {
+        let mut device = device_with_channel_and_node();
+        device.connection_state = Connected("device1".into(), RadioType::Meshtastic);
+        device.viewing_conversation = Some(ConversationId::Channel(0.into()));
+        device.showing_waypoints = true;
+        let position = MCPosition {
+            latitude: 45.5,
+            longitude: -73.5,
+            ..Default::default()
+        };
+        let _ = device.show_location(&position);
+        assert!(device.viewing_conversation.is_none());
+        assert!(!device.showing_waypoints);
+        assert!(device.showing_map);
+        assert_eq!(device.map_focus, Some(WorldPoint::from(&position)));
+        let _ = device.view(&Config::default());
+
+        // Closing the map forgets the position
+        let _ = device.update(ToggleMap);
+        let _ = device.update(ToggleMap);
+        assert!(device.map_focus.is_none());
+    }
+
+    #[test]
+    fn test_map_tiles_loaded_in_background() {
+        let mut device = Device::default();
+        let tile = TileId {
+            zoom: 1,
+            x: 0,
+            y: 0,
+        };
+        // Nothing to read tiles from
+        assert_eq!(device.update(LoadMapTiles(vec![tile])).units(), 0);
+
+        let tempdir = tempfile::Builder::new()
+            .prefix("meshchat")
+            .tempdir()
+            .expect("Could not create a temp dir");
+        let _ = device.set_map_tiles(Some(tempdir.path()));
+        assert!(device.update(LoadMapTiles(vec![tile])).units() > 0);
+        let _ = device.update(MapTilesLoaded(
+            tempdir.path().to_path_buf(),
+            vec![(tile, Some(Handle::from_bytes(vec![1, 2, 3])))],
+        ));
+        let _ = device.update(ToggleMap);
+        let _ = device.view(&Config::default());
+    }
+
+    fn position_of(device: &mut Device, node_id: u64, latitude: f64, timestamp: u64) {
+        let _ = device.update(SubscriptionMessage(NewNodePosition(
+            ConversationId::Node(NodeId::from(node_id)),
+            MessageId::from(timestamp as u32),
+            NodeId::from(node_id),
+            test_position(latitude, 0.0),
+            TimeStamp::from(timestamp),
+        )));
+    }
+
+    #[test]
+    fn test_new_node_position_added_to_track() {
+        let (mut device, user) = device_with_node(100, "!00000064");
+        position_of(&mut device, 100, 2.0, 2000);
+        position_of(&mut device, 100, 1.0, 1000);
+        position_of(&mut device, 100, 1.0, 1000);
+
+        let track = device
+            .tracks
+            .get(&NodeId::from(100u64))
+            .expect("Track for node 100 expected");
+        let latitudes: Vec<f64> = track
+            .points()
+            .map(|point| point.position.latitude)
+            .collect();
+        assert_eq!(latitudes, vec![1.0, 2.0]);
+        // The node's position is the last one received, not the last in time
+        assert_eq!(
+            device
+                .nodes
+                .get(&NodeId::from(100u64))
+                .and_then(|node| node.position.as_ref())
+                .map(|position| position.latitude),
+            Some(1.0)
+        );
+        let _element = device.track_view(&user);
+        let _empty = device.track_view(&MCUser::default());
+    }
+
+    #[test]
+    fn test_tracks_loaded_merged() {
+        let mut device = Device::default();
+        device.connection_state = Connected("device1".into(), RadioType::Meshtastic);
+        position_of(&mut device, 100, 2.0, 2000);
+        let mut loaded = Track::default();
+        loaded.add(TimeStamp::from(1000u64), &test_position(1.0, 0.0));
+        let _ = device.update(TracksLoaded(
+            "device1".into(),
+            HashMap::from([(NodeId::from(100u64), loaded)]),
+        ));
+
+        assert_eq!(
+            device.tracks.get(&NodeId::from(100u64)).map(Track::len),
+            Some(2)
+        );
+    }
+
+    #[test]
+    fn test_track_not_saved_until_tracks_loaded() {
+        let mut device = Device::default();
+        device.connection_state = Connected("device1".into(), RadioType::Meshtastic);
+        let task = device.add_to_track(
+            NodeId::from(100u64),
+            TimeStamp::from(1000u64),
+            &test_position(1.0, 0.0),
+        );
+        assert_eq!(task.units(), 0);
+
+        let task = device.update(TracksLoaded("device1".into(), HashMap::new()));
+        assert!(device.tracks_loaded);
+        assert!(device.tracks_save_scheduled);
+        assert_eq!(task.units(), 1);
+
+        // Positions received while a save is waiting are saved with it
+        let task = device.add_to_track(
+            NodeId::from(100u64),
+            TimeStamp::from(2000u64),
+            &test_position(2.0, 0.0),
+        );
+        assert_eq!(task.units(), 0);
+
+        assert_eq!(device.update(SaveTracks).units(), 1);
+        assert!(!device.tracks_save_scheduled);
+        assert_eq!(device.update(SaveTracks).units(), 0);
+    }
+
+    #[test]
+    fn test_tracks_loaded_for_other_device_ignored() {
+        let mut device = Device::default();
+        device.connection_state = Connected("device2".into(), RadioType::Meshtastic);
+        let mut loaded = Track::default();
+        loaded.add(TimeStamp::from(1000u64), &test_position(1.0, 0.0));
+        let task = device.update(TracksLoaded(
+            "device1".into(),
+            HashMap::from([(NodeId::from(100u64), loaded)]),
+        ));
+        assert_eq!(task.units(), 0);
+        assert!(!device.tracks_loaded);
+        assert!(device.tracks.get(&NodeId::from(100u64)).is_none());
+    }
+
+    #[test]
+    fn test_tracks_cleared_on_disconnect() {
+        let mut device = Device::default();
+        position_of(&mut device, 100, 1.0, 1000);
+        let _ = device.update(SubscriptionMessage(DisconnectedEvent("device1".into())));
+        assert!(device.tracks.get(&NodeId::from(100u64)).is_none());
+    }
+
+    #[test]
+    fn test_export_track() {
+        let mut device = Device::default();
+        let config = Config::default();
+        assert_eq!(
+            device
+                .export_track(NodeId::from(100u64), TrackFormat::Gpx, &config)
+                .units(),
+            0
+        );
+        position_of(&mut device, 100, 1.0, 1000);
+        assert_eq!(
+            device
+                .export_track(NodeId::from(100u64), TrackFormat::Kml, &config)
+                .units(),
+            1
+        );
+        assert_eq!(
+            device.export_positions(TrackFormat::Gpx, &config).units(),
+            1
+        );
+        let _row = device.button_row();
+    }
 }
@@ -1,25 +1,38 @@
 use crate::Message;
+#[cfg(feature = "tcp")]
+use crate::Message::AddManualDevice;
 use crate::Message::{
     AddDeviceAlias, DeviceListViewEvent, DeviceViewEvent, Navigation, RemoveDeviceAlias,
+    RemoveManualDevice, ReplayCapture,
 };
 use crate::config::Config;
 use crate::device::ConnectionState::{Connected, Connecting, Disconnected, Disconnecting};
 use crate::device::DeviceMessage::{ConnectRequest, DisconnectRequest};
-use crate::device::{ConnectionState, Device, DeviceIdentifier};
+#[cfg(feature = "tcp")]
+use crate::device::TCP_SCHEME;
+use crate::device::{ConnectionState, Device, DeviceIdentifier, Reconnect};
 use crate::device_list::DeviceListEvent::{
-    AliasInput, CriticalError, Error, MeshRadioFound, MeshRadioLost, Scanning, StartEditingAlias,
+    AliasInput, CriticalError, Error, MeshRadioFound, MeshRadioLost, Scanning, SelectRadioType,
+    StartEditingAlias,
+};
+#[cfg(feature = "tcp")]
+use crate::device_list::DeviceListEvent::{
+    NewDeviceHostInput, NewDevicePortInput, NewDeviceRadioType,
 };
 use crate::meshchat::View;
 use crate::styles::{button_chip_style, menu_button_style, text_input_style, tooltip_style};
 use crate::timestamp::TimeStamp;
 use crate::widgets::easing;
 use crate::widgets::linear::Linear;
+#[cfg(all(feature = "meshtastic", feature = "meshcore", feature = "tcp"))]
+use iced::widget::pick_list;
 use iced::widget::scrollable::Scrollbar;
 use iced::widget::{
     Column, Container, Id, Row, Space, button, container, image, operation, scrollable, text,
     text_input, tooltip,
 };
 use iced::{Center, Element, Fill, Renderer, Task, Theme, alignment};
+use iced_aw::menu::Item;
 use iced_aw::{Menu, MenuBar, menu_bar, menu_items};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -37,13 +50,41 @@ static MESHCORE_ICON: LazyLock<image::Handle> = LazyLock::new(|| {
     image::Handle::from_bytes(include_bytes!("../assets/images/meshcore.png").to_vec())
 });
 
-/// The type of radio firmware detected
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+/// The type of radio firmware detected. The default is assumed for endpoints, such as TCP ones,
+/// that don't identify the firmware behind them
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub enum RadioType {
     #[cfg(feature = "meshtastic")]
+    #[default]
     Meshtastic,
     #[cfg(feature = "meshcore")]
+    #[cfg_attr(not(feature = "meshtastic"), default)]
     MeshCore,
+    #[cfg(feature = "sim")]
+    Sim,
+}
+
+impl RadioType {
+    /// The types of real radio firmware, that the user can choose between for an endpoint
+    pub const ALL: &'static [RadioType] = &[
+        #[cfg(feature = "meshtastic")]
+        RadioType::Meshtastic,
+        #[cfg(feature = "meshcore")]
+        RadioType::MeshCore,
+    ];
+}
+
+impl std::fmt::Display for RadioType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            #[cfg(feature = "meshtastic")]
+            RadioType::Meshtastic => write!(f, "Meshtastic"),
+            #[cfg(feature = "meshcore")]
+            RadioType::MeshCore => write!(f, "MeshCore"),
+            #[cfg(feature = "sim")]
+            RadioType::Sim => write!(f, "Simulated"),
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -55,6 +96,13 @@ pub enum DeviceListEvent {
     StartEditingAlias(String),
     AliasInput(String), // From text_input
     Scanning(bool),
+    SelectRadioType(DeviceIdentifier, RadioType),
+    #[cfg(feature = "tcp")]
+    NewDeviceHostInput(String), // From text_input
+    #[cfg(feature = "tcp")]
+    NewDevicePortInput(String), // From text_input
+    #[cfg(feature = "tcp")]
+    NewDeviceRadioType(RadioType),
 }
 
 /// Information about a discovered device
@@ -66,10 +114,16 @@ pub struct DeviceInfo {
 
 #[derive(Default)]
 pub struct DeviceList {
-    devices: HashMap<DeviceIdentifier, DeviceInfo>, // BLE address -> DeviceInfo
+    pub(crate) devices: HashMap<DeviceIdentifier, DeviceInfo>, // BLE address -> DeviceInfo
     alias: String,
     editing_alias: Option<String>,
     scanning: bool,
+    #[cfg(feature = "tcp")]
+    new_device_host: String,
+    #[cfg(feature = "tcp")]
+    new_device_port: String,
+    #[cfg(feature = "tcp")]
+    new_device_radio_type: RadioType,
 }
 
 // jonesy:allow(unknown) async state machine artifact
@@ -81,14 +135,13 @@ impl DeviceList {
     pub fn update(&mut self, device_list_event: DeviceListEvent) -> Task<Message> {
         match device_list_event {
             MeshRadioFound(device, radio_type) => {
-                if let std::collections::hash_map::Entry::Vacant(vacant_entry) =
-                    self.devices.entry(device.clone())
-                {
-                    vacant_entry.insert(DeviceInfo {
+                self.devices
+                    .entry(device.clone())
+                    .and_modify(|device_info| device_info.radio_type = radio_type)
+                    .or_insert_with(|| DeviceInfo {
                         original_name: String::from(device),
                         radio_type,
                     });
-                }
             }
             MeshRadioLost(device) => {
                 let _ = self.devices.remove(&device);
@@ -114,6 +167,17 @@ impl DeviceList {
             StartEditingAlias(device) => return self.start_editing_alias(device),
             AliasInput(alias) => self.alias = alias,
             Scanning(scanning) => self.scanning = scanning,
+            SelectRadioType(device, radio_type) => {
+                if let Some(device_info) = self.devices.get_mut(&device) {
+                    device_info.radio_type = radio_type;
+                }
+            }
+            #[cfg(feature = "tcp")]
+            NewDeviceHostInput(host) => self.new_device_host = host,
+            #[cfg(feature = "tcp")]
+            NewDevicePortInput(port) => self.new_device_port = port,
+            #[cfg(feature = "tcp")]
+            NewDeviceRadioType(radio_type) => self.new_device_radio_type = radio_type,
         };
 
         Task::none()
@@ -132,6 +196,40 @@ impl DeviceList {
         self.alias = String::new();
     }
 
+    /// Return the [DeviceIdentifier] of the device entered in the "add device" form, if the host
+    /// and port entered make a valid TCP endpoint
+    #[cfg(feature = "tcp")]
+    fn new_device(&self) -> Option<DeviceIdentifier> {
+        let host = self.new_device_host.trim();
+        let port = self.new_device_port.trim();
+        let endpoint = if host.contains(':') {
+            format!("{TCP_SCHEME}[{host}]:{port}")
+        } else {
+            format!("{TCP_SCHEME}{host}:{port}")
+        };
+
+        match DeviceIdentifier::from(endpoint.as_str()) {
+            // Reject hosts that only parse by losing some of what the user typed, e.g. a '#'
+            DeviceIdentifier::Tcp {
+                host: parsed_host,
+                port,
+                name: None,
+            } if !host.is_empty() && parsed_host == host => Some(DeviceIdentifier::Tcp {
+                host: parsed_host,
+                port,
+                name: None,
+            }),
+            _ => None,
+        }
+    }
+
+    /// Called from above when a device has been added from the "add device" form
+    #[cfg(feature = "tcp")]
+    pub fn clear_new_device(&mut self) {
+        self.new_device_host = String::new();
+        self.new_device_port = String::new();
+    }
+
     /// Return the device name or any alias to it that might exist in the config
     pub fn device_name_or_alias<'a>(&'a self, ble_device: &'a str, config: &'a Config) -> String {
         if let Some(alias) = config.device_aliases.get(ble_device) {
@@ -146,14 +244,21 @@ impl DeviceList {
         &'a self,
         config: &'a Config,
         connection_state: &'a ConnectionState,
+        reconnect: Option<&Reconnect>,
     ) -> Element<'a, Message> {
         let mut header_row = Row::new()
             .padding(4)
             .align_y(Center)
             .push(button("Devices").style(button_chip_style));
 
-        header_row = match connection_state {
-            Disconnected(_, _) => {
+        header_row = match (connection_state, reconnect) {
+            (Disconnected(_, _) | Connecting(_), Some(reconnect)) => {
+                let name = self.device_name_or_alias(&reconnect.device().name(), config);
+                header_row
+                    .push(Space::new().width(Fill))
+                    .push(reconnect.view(name))
+            }
+            (Disconnected(_, _), None) => {
                 let state_message = if self.scanning {
                     "Scanning"
                 } else {
@@ -161,9 +266,19 @@ impl DeviceList {
                 };
                 header_row
                     .push(Space::new().width(Fill))
+                    .push(
+                        tooltip(
+                            button("Replay capture")
+                                .on_press(ReplayCapture)
+                                .style(button_chip_style),
+                            text("Replay a capture of the traffic from a radio"),
+                            tooltip::Position::Bottom,
+                        )
+                        .style(tooltip_style),
+                    )
                     .push(iced::widget::button(state_message).style(button_chip_style))
             }
-            Connecting(device) => {
+            (Connecting(device), None) => {
                 let name_button = iced::widget::button(text(format!(
                     "Connecting to {}",
                     self.device_name_or_alias(&device.name(), config)
@@ -171,7 +286,7 @@ impl DeviceList {
                 .style(button_chip_style);
                 header_row.push(Space::new().width(Fill)).push(name_button)
             }
-            Connected(device, _) => header_row
+            (Connected(device, _), _) => header_row
                 .push(
                     button(text(format!(
                         "📱 {}",
@@ -181,7 +296,7 @@ impl DeviceList {
                     .on_press(Navigation(View::DeviceView(None))),
                 )
                 .push(Space::new().width(Fill)),
-            Disconnecting(device) => header_row
+            (Disconnecting(device), _) => header_row
                 .push(
                     button(text(format!(
                         "📱 {}",
@@ -205,7 +320,10 @@ impl DeviceList {
         header_row = header_row.push(Device::settings_button());
 
         // If busy or connecting or disconnecting, add a busy bar to the header
-        if self.scanning || matches!(connection_state, Connecting(_) | Disconnecting(_)) {
+        if self.scanning
+            || reconnect.is_some()
+            || matches!(connection_state, Connecting(_) | Disconnecting(_))
+        {
             Column::new()
                 .push(header_row)
                 .push(Space::new().width(Fill))
@@ -226,10 +344,28 @@ impl DeviceList {
         config: &'a Config,
         connection_state: &'a ConnectionState,
     ) -> Element<'a, Message> {
-        if self.devices.is_empty() {
-            return self.empty_view();
-        }
+        let devices_view = if self.devices.is_empty() {
+            self.empty_view()
+        } else {
+            self.devices_view(config, connection_state)
+        };
 
+        #[cfg(feature = "tcp")]
+        return Column::new()
+            .push(devices_view)
+            .push(self.add_device_form())
+            .into();
+
+        #[cfg(not(feature = "tcp"))]
+        devices_view
+    }
+
+    /// A list of the devices found, with the controls to alias and connect to them
+    fn devices_view<'a>(
+        &'a self,
+        config: &'a Config,
+        connection_state: &'a ConnectionState,
+    ) -> Element<'a, Message> {
         let mut main_col = Column::new();
 
         for (device_identifier, device_info) in &self.devices {
@@ -237,13 +373,14 @@ impl DeviceList {
             let mut device_row = Row::new().align_y(Center).padding(2);
 
             // Add firmware icon based on the radio type (using static handles)
-            let icon_handle = match device_info.radio_type {
+            let icon: Element<'a, Message> = match device_info.radio_type {
                 #[cfg(feature = "meshtastic")]
-                RadioType::Meshtastic => MESHTASTIC_ICON.clone(),
+                RadioType::Meshtastic => image(MESHTASTIC_ICON.clone()).width(24).height(24).into(),
                 #[cfg(feature = "meshcore")]
-                RadioType::MeshCore => MESHCORE_ICON.clone(),
+                RadioType::MeshCore => image(MESHCORE_ICON.clone()).width(24).height(24).into(),
+                #[cfg(feature = "sim")]
+                RadioType::Sim => text("🧪").size(18).width(24).center().into(),
             };
-            let icon = image(icon_handle).width(24).height(24);
             device_row = device_row.push(icon);
             device_row = device_row.push(Space::new().width(8));
 
@@ -254,6 +391,19 @@ impl DeviceList {
                 DeviceIdentifier::Tcp { host, port, .. } => {
                     ("TCP", format!("Reachable over TCP at {host}:{port}"))
                 }
+                #[cfg(feature = "serial")]
+                DeviceIdentifier::Serial { path, baud } => (
+                    "USB",
+                    format!("Connected via serial port {path} at {baud} baud"),
+                ),
+                #[cfg(feature = "sim")]
+                DeviceIdentifier::Sim { .. } => (
+                    "SIM",
+                    "A simulated radio, for demos and development".to_string(),
+                ),
+                DeviceIdentifier::Replay { path } => {
+                    ("REPLAY", format!("A replay of the capture file {path}"))
+                }
             };
             device_row = device_row.push(
                 tooltip(
@@ -265,6 +415,25 @@ impl DeviceList {
             );
             device_row = device_row.push(Space::new().width(8));
 
+            // A TCP endpoint doesn't tell us what firmware is behind it, so let the user pick
+            #[cfg(all(feature = "tcp", feature = "meshtastic", feature = "meshcore"))]
+            if matches!(device_identifier, DeviceIdentifier::Tcp { .. }) {
+                device_row = device_row.push(
+                    pick_list(
+                        RadioType::ALL,
+                        Some(device_info.radio_type),
+                        move |radio_type| {
+                            DeviceListViewEvent(SelectRadioType(
+                                device_identifier.clone(),
+                                radio_type,
+                            ))
+                        },
+                    )
+                    .text_size(12),
+                );
+                device_row = device_row.push(Space::new().width(8));
+            }
+
             let name_element: Element<'a, Message> =
                 if let Some(alias) = config.device_aliases.get(ble_device) {
                     tooltip(
@@ -300,6 +469,7 @@ impl DeviceList {
                 config
                     .device_aliases
                     .contains_key(&device_info.original_name),
+                config.manual_devices.contains_key(ble_device),
             ));
 
             device_row = device_row.push(Space::new().width(6));
@@ -355,11 +525,69 @@ impl DeviceList {
             .into()
     }
 
-    fn menu_bar<'a>(ble_device: &str, alias_exists: bool) -> MenuBar<'a, Message, Theme, Renderer> {
+    /// A form to add a device that can't be discovered, by its host and port
+    #[cfg(feature = "tcp")]
+    fn add_device_form(&self) -> Element<'_, Message> {
+        let add_device = self
+            .new_device()
+            .map(|device| AddManualDevice(device, self.new_device_radio_type));
+
+        let mut form_row = Row::new()
+            .padding(4)
+            .spacing(4)
+            .align_y(Center)
+            .push(
+                text_input("Host", &self.new_device_host)
+                    .width(250)
+                    .on_input(|s| DeviceListViewEvent(NewDeviceHostInput(s)))
+                    .on_submit_maybe(add_device.clone())
+                    .style(text_input_style),
+            )
+            .push(text(":"))
+            .push(
+                text_input("Port", &self.new_device_port)
+                    .width(70)
+                    .on_input(|s| DeviceListViewEvent(NewDevicePortInput(s)))
+                    .on_submit_maybe(add_device.clone())
+                    .style(text_input_style),
+            );
+
+        #[cfg(all(feature = "meshtastic", feature = "meshcore"))]
+        {
+            form_row = form_row.push(
+                pick_list(
+                    RadioType::ALL,
+                    Some(self.new_device_radio_type),
+                    |radio_type| DeviceListViewEvent(NewDeviceRadioType(radio_type)),
+                )
+                .text_size(12),
+            );
+        }
+
+        form_row = form_row.push(
+            button("Add device")
+                .on_press_maybe(add_device)
+                .style(button_chip_style),
+        );
+
+        tooltip(
+            form_row,
+            text("Add a radio that is reachable over TCP but can't be discovered"),
+            tooltip::Position::Top,
+        )
+        .style(tooltip_style)
+        .into()
+    }
+
+    fn menu_bar<'a>(
+        ble_device: &str,
+        alias_exists: bool,
+        manually_added: bool,
+    ) -> MenuBar<'a, Message, Theme, Renderer> {
         let menu_tpl_1 = |items| Menu::new(items).spacing(3);
 
         // jonesy:allow(misaligned_ptr) via iced_aw menu_items!/menu_bar! macros (misaligned_ptr)
-        let menu_items = if alias_exists {
+        let mut menu_items = if alias_exists {
             menu_items!(
                 (menu_button(
                     "Unalias this device".into(),
@@ -375,6 +603,13 @@ impl DeviceList {
             )
         };
 
+        if manually_added {
+            menu_items.push(Item::new(menu_button(
+                "Remove this device".into(),
+                RemoveManualDevice(ble_device.to_string()),
+            )));
+        }
+
         // Create the menu bar with the root button and list of options
         menu_bar!((menu_root_button("▼"), {
             menu_tpl_1(menu_items).width(180)
@@ -1100,7 +1335,7 @@ mod tests {
         let view = DeviceList::default();
         let config = Config::default();
         let connection_state = Disconnected(None, None);
-        let _element = view.header(&config, &connection_state);
+        let _element = view.header(&config, &connection_state, None);
         // Should not panic
     }
 
@@ -1109,7 +1344,7 @@ mod tests {
         let view = DeviceList::default();
         let config = Config::default();
         let connection_state = Connecting("device1".into());
-        let _element = view.header(&config, &connection_state);
+        let _element = view.header(&config, &connection_state, None);
         // Should not panic
     }
 
@@ -1119,7 +1354,7 @@ mod tests {
         let view = DeviceList::default();
         let config = Config::default();
         let connection_state = Connected("device1".into(), RadioType::Meshtastic);
-        let _element = view.header(&config, &connection_state);
+        let _element = view.header(&config, &connection_state, None);
         // Should not panic
     }
 
@@ -1128,7 +1363,7 @@ mod tests {
         let view = DeviceList::default();
         let config = Config::default();
         let connection_state = Disconnecting("device1".into());
-        let _element = view.header(&config, &connection_state);
+        let _element = view.header(&config, &connection_state, None);
         // Should not panic
     }
 
@@ -1142,7 +1377,7 @@ mod tests {
             .insert("device1".into(), "My Radio".into());
 
         let connection_state = Connected("device1".into(), RadioType::Meshtastic);
-        let _element = view.header(&config, &connection_state);
+        let _element = view.header(&config, &connection_state, None);
         // Should not panic and use alias in display
     }
 
@@ -1168,7 +1403,7 @@ mod tests {
         let config = Config::default();
         let connection_state =
             Disconnected(Some("device1".into()), Some("Connection failed".into()));
-        let _element = view.header(&config, &connection_state);
+        let _element = view.header(&config, &connection_state, None);
         // Should not panic
     }
 
@@ -1308,14 +1543,20 @@ mod tests {
     #[test]
     fn test_menu_bar_with_alias_exists() {
         // When alias exists, the menu should show "Unalias this device"
-        let _menu = DeviceList::menu_bar("device1", true);
+        let _menu = DeviceList::menu_bar("device1", true, false);
         // Exercises alias_exists == true branch
     }
 
+    #[test]
+    fn test_menu_bar_manually_added() {
+        // When added by hand, the menu should also show "Remove this device"
+        let _menu = DeviceList::menu_bar("tcp://192.168.1.10:4403", false, true);
+    }
+
     #[test]
     fn test_menu_bar_without_alias() {
         // When no alias, the menu should show "Alias this device"
-        let _menu = DeviceList::menu_bar("device1", false);
+        let _menu = DeviceList::menu_bar("device1", false, false);
         // Exercises alias_exists == false branch
     }
 
@@ -1427,4 +1668,173 @@ mod tests {
         let _element = view.view(&config, &connection_state);
         // Should show "No compatible Meshtastic radios found." message
     }
+
+    #[cfg(all(feature = "tcp", feature = "meshtastic", feature = "meshcore"))]
+    #[test]
+    fn test_select_radio_type_for_tcp_device() {
+        let mut view = DeviceList::default();
+        let device = DeviceIdentifier::from("tcp://192.168.1.10:4403#Radio");
+        let _ = view.update(MeshRadioFound(device.clone(), RadioType::Meshtastic));
+
+        let _ = view.update(SelectRadioType(device.clone(), RadioType::MeshCore));
+        assert_eq!(
+            view.devices
+                .get(&device)
+                .expect("Device should exist")
+                .radio_type,
+            RadioType::MeshCore
+        );
+
+        // Adding the endpoint again, with another radio type, updates it
+        let _ = view.update(MeshRadioFound(device.clone(), RadioType::Meshtastic));
+        assert_eq!(
+            view.devices
+                .get(&device)
+                .expect("Device should exist")
+                .radio_type,
+            RadioType::Meshtastic
+        );
+
+        let config = Config::default();
+        let connection_state = Disconnected(None, None);
+        let _element = view.view(&config, &connection_state);
+    }
+
+    #[cfg(feature = "meshcore")]
+    #[test]
+    fn test_select_radio_type_unknown_device() {
+        let mut view = DeviceList::default();
+        let _ = view.update(SelectRadioType("device1".into(), RadioType::MeshCore));
+        assert!(view.devices.is_empty());
+    }
+
+    #[cfg(feature = "sim")]
+    #[test]
+    fn test_sim_radio_found_and_viewed() {
+        let mut view = DeviceList::default();
+        let _ = view.update(MeshRadioFound(
+            DeviceIdentifier::from("sim://Simulated Radio"),
+            RadioType::Sim,
+        ));
+        assert_eq!(RadioType::Sim.to_string(), "Simulated");
+        // The simulated radio type cannot be chosen for a real endpoint
+        assert!(!RadioType::ALL.contains(&RadioType::Sim));
+
+        let config = Config::default();
+        let connection_state = Disconnected(None, None);
+        let _element = view.view(&config, &connection_state);
+    }
+
+    #[test]
+    fn test_radio_type_default() {
+        #[cfg(feature = "meshtastic")]
+        assert_eq!(RadioType::default(), RadioType::Meshtastic);
+        #[cfg(not(feature = "meshtastic"))]
+        assert_eq!(RadioType::default(), RadioType::MeshCore);
+    }
+
+    #[test]
+    fn test_radio_type_all_and_display() {
+        #[cfg(feature = "meshtastic")]
+        {
+            assert!(RadioType::ALL.contains(&RadioType::Meshtastic));
+            assert_eq!(RadioType::Meshtastic.to_string(), "Meshtastic");
+        }
+        #[cfg(feature = "meshcore")]
+        {
+            assert!(RadioType::ALL.contains(&RadioType::MeshCore));
+            assert_eq!(RadioType::MeshCore.to_string(), "MeshCore");
+        }
+    }
+
+    #[cfg(feature = "tcp")]
+    fn new_device_form(host: &str, port: &str) -> DeviceList {
+        let mut view = DeviceList::default();
+        let _ = view.update(NewDeviceHostInput(host.to_string()));
+        let _ = view.update(NewDevicePortInput(port.to_string()));
+        view
+    }
+
+    #[cfg(feature = "tcp")]
+    #[test]
+    fn test_new_device_valid() {
+        let view = new_device_form(" 192.168.1.10 ", "4403");
+        assert_eq!(
+            view.new_device(),
+            Some(DeviceIdentifier::Tcp {
+                name: None,
+                host: "192.168.1.10".to_string(),
+                port: 4403,
+            })
+        );
+    }
+
+    #[cfg(feature = "tcp")]
+    #[test]
+    fn test_new_device_hostname_and_ipv6() {
+        let view = new_device_form("meshtastic.local", "4403");
+        assert!(matches!(
+            view.new_device(),
+            Some(DeviceIdentifier::Tcp { host, .. }) if host == "meshtastic.local"
+        ));
+
+        let view = new_device_form("fe80::1", "5000");
+        assert!(matches!(
+            view.new_device(),
+            Some(DeviceIdentifier::Tcp { host, port: 5000, .. }) if host == "fe80::1"
+        ));
+    }
+
+    #[cfg(feature = "tcp")]
+    #[test]
+    fn test_new_device_invalid() {
+        assert!(new_device_form("", "4403").new_device().is_none());
+        assert!(new_device_form("192.168.1.10", "").new_device().is_none());
+        assert!(new_device_form("192.168.1.10", "0").new_device().is_none());
+        assert!(
+            new_device_form("192.168.1.10", "70000")
+                .new_device()
+                .is_none()
+        );
+        assert!(
+            new_device_form("192.168.1.10", "port")
+                .new_device()
+                .is_none()
+        );
+        assert!(new_device_form("radio#1", "4403").new_device().is_none());
+    }
+
+    #[cfg(feature = "tcp")]
+    #[test]
+    fn test_clear_new_device() {
+        let mut view = new_device_form("192.168.1.10", "4403");
+        view.clear_new_device();
+        assert!(view.new_device_host.is_empty());
+        assert!(view.new_device_port.is_empty());
+        assert!(view.new_device().is_none());
+    }
+
+    #[cfg(all(feature = "tcp", feature = "meshcore"))]
+    #[test]
+    fn test_new_device_radio_type() {
+        let mut view = DeviceList::default();
+        assert_eq!(view.new_device_radio_type, RadioType::default());
+        let _ = view.update(NewDeviceRadioType(RadioType::MeshCore));
+        assert_eq!(view.new_device_radio_type, RadioType::MeshCore);
+    }
+
+    #[cfg(feature = "tcp")]
+    #[test]
+    fn test_view_with_manual_device_and_form() {
+        let mut view = new_device_form("192.168.1.10", "4403");
+        let device = view.new_device().expect("Device should be valid");
+        let _ = view.update(MeshRadioFound(device.clone(), RadioType::default()));
+
+        let mut config = Config::default();
+        config
+            .manual_devices
+            .insert(String::from(&device), RadioType::default());
+        let connection_state = Disconnected(None, None);
+        let _element = view.view(&config, &connection_state);
+    }
 }
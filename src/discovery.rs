@@ -1,44 +1,88 @@
-#[cfg(any(feature = "bluetooth", feature = "tcp"))]
+#[cfg(any(
+    feature = "bluetooth",
+    all(feature = "tcp", feature = "meshtastic"),
+    feature = "serial"
+))]
 use crate::device::DeviceIdentifier;
-#[cfg(any(feature = "bluetooth", feature = "tcp"))]
+#[cfg(any(
+    feature = "bluetooth",
+    all(feature = "tcp", feature = "meshtastic"),
+    feature = "serial"
+))]
 use crate::device_list::DeviceListEvent;
-#[cfg(any(feature = "bluetooth", feature = "tcp"))]
+#[cfg(any(
+    feature = "bluetooth",
+    all(feature = "tcp", feature = "meshtastic"),
+    feature = "serial"
+))]
 use crate::device_list::DeviceListEvent::Error;
 #[cfg(feature = "bluetooth")]
 use crate::device_list::DeviceListEvent::{CriticalError, Scanning};
-#[cfg(any(feature = "bluetooth", feature = "tcp"))]
+#[cfg(any(
+    feature = "bluetooth",
+    all(feature = "tcp", feature = "meshtastic"),
+    feature = "serial"
+))]
 use crate::device_list::DeviceListEvent::{MeshRadioFound, MeshRadioLost};
-#[cfg(any(feature = "bluetooth", feature = "tcp"))]
+#[cfg(any(
+    feature = "bluetooth",
+    all(feature = "tcp", feature = "meshtastic"),
+    feature = "serial"
+))]
 use crate::device_list::RadioType;
 #[cfg(feature = "meshcore")]
 use crate::meshc::MESHCORE_SERVICE_UUID;
 #[cfg(all(feature = "meshtastic", feature = "bluetooth"))]
 use crate::mesht::MESHTASTIC_SERVICE_UUID;
+#[cfg(feature = "sim")]
+use crate::sim::SIM_RADIO_NAME;
 #[cfg(feature = "bluetooth")]
 use btleplug::api::{Central, Manager as _, Peripheral, ScanFilter};
 #[cfg(feature = "bluetooth")]
 use btleplug::platform::{Adapter, Manager};
-#[cfg(any(feature = "bluetooth", feature = "tcp"))]
+#[cfg(any(
+    feature = "bluetooth",
+    all(feature = "tcp", feature = "meshtastic"),
+    feature = "serial"
+))]
 use futures::SinkExt;
-#[cfg(any(feature = "bluetooth", feature = "tcp"))]
+#[cfg(any(
+    feature = "bluetooth",
+    all(feature = "tcp", feature = "meshtastic"),
+    feature = "serial"
+))]
 use futures_channel::mpsc::Sender;
-#[cfg(any(feature = "bluetooth", feature = "tcp"))]
+#[cfg(any(
+    feature = "bluetooth",
+    all(feature = "tcp", feature = "meshtastic"),
+    feature = "serial"
+))]
 use iced::futures::Stream;
-#[cfg(any(feature = "bluetooth", feature = "tcp"))]
+#[cfg(any(
+    feature = "bluetooth",
+    all(feature = "tcp", feature = "meshtastic"),
+    feature = "serial"
+))]
 use iced::stream;
-#[cfg(feature = "tcp")]
+#[cfg(all(feature = "tcp", feature = "meshtastic"))]
 use mdns_sd::ScopedIp;
-#[cfg(feature = "tcp")]
+#[cfg(all(feature = "tcp", feature = "meshtastic"))]
 use mdns_sd::{ServiceDaemon, ServiceEvent};
-#[cfg(any(feature = "bluetooth", feature = "tcp"))]
+#[cfg(feature = "serial")]
+use meshtastic::utils::DEFAULT_SERIAL_BAUD;
+#[cfg(any(feature = "bluetooth", all(feature = "tcp", feature = "meshtastic")))]
 use std::collections::HashMap;
-#[cfg(feature = "bluetooth")]
+#[cfg(feature = "serial")]
+use std::collections::HashSet;
+#[cfg(any(feature = "bluetooth", feature = "serial"))]
 use std::time::Duration;
+#[cfg(feature = "serial")]
+use tokio_serial::{SerialPortInfo, SerialPortType, available_ports};
 #[cfg(feature = "bluetooth")]
 use uuid::Uuid;
 
 /// mDNS service type advertised by Meshtastic devices for their TCP API (port 4403 by default).
-#[cfg(feature = "tcp")]
+#[cfg(all(feature = "tcp", feature = "meshtastic"))]
 const MESHTASTIC_MDNS_SERVICE: &str = "_meshtastic._tcp.local.";
 
 /// A stream of [DeviceListEvent] announcing the discovery or loss of devices via BLE
@@ -238,10 +282,10 @@ fn process_device_changes(
 /// A stream of [DeviceListEvent] announcing the discovery or loss of Meshtastic devices reachable
 /// over TCP on the local network. Devices advertise themselves via mDNS-SD as
 /// `_meshtastic._tcp.local.`.
-#[cfg(feature = "tcp")]
+#[cfg(all(feature = "tcp", feature = "meshtastic"))]
 const MDNS_MAX_RETRIES: u32 = 5;
 
-#[cfg(feature = "tcp")]
+#[cfg(all(feature = "tcp", feature = "meshtastic"))]
 pub fn mdns_discovery() -> impl Stream<Item = DeviceListEvent> {
     stream::channel(
         100,
@@ -274,7 +318,7 @@ pub fn mdns_discovery() -> impl Stream<Item = DeviceListEvent> {
     )
 }
 
-#[cfg(feature = "tcp")]
+#[cfg(all(feature = "tcp", feature = "meshtastic"))]
 async fn mdns_browse(gui_sender: &mut Sender<DeviceListEvent>) -> Result<(), String> {
     let daemon = ServiceDaemon::new().map_err(|e| format!("mDNS daemon could not start: {e}"))?;
     let receiver = daemon
@@ -332,6 +376,113 @@ async fn mdns_browse(gui_sender: &mut Sender<DeviceListEvent>) -> Result<(), Str
     Err("mDNS browse session ended unexpectedly".to_string())
 }
 
+/// A stream of [DeviceListEvent] announcing the simulated radio, which is always there to be found
+#[cfg(feature = "sim")]
+pub fn sim_discovery() -> impl Stream<Item = DeviceListEvent> {
+    stream::channel(
+        100,
+        move |mut gui_sender: Sender<DeviceListEvent>| async move {
+            let sim_device = DeviceIdentifier::Sim {
+                name: SIM_RADIO_NAME.to_string(),
+            };
+            gui_sender
+                .send(MeshRadioFound(sim_device, RadioType::Sim))
+                .await
+                .unwrap_or_else(|e| eprintln!("Discovery could not send MeshRadioFound: {e}"));
+        },
+    )
+}
+
+/// How often to check for radios being plugged into or unplugged from USB serial ports
+#[cfg(feature = "serial")]
+const SERIAL_SCAN_INTERVAL: Duration = Duration::from_secs(2);
+
+/// A stream of [DeviceListEvent] announcing Meshtastic radios being plugged into, or unplugged
+/// from, USB serial ports
+#[cfg(feature = "serial")]
+pub fn serial_discovery() -> impl Stream<Item = DeviceListEvent> {
+    stream::channel(
+        100,
+        move |mut gui_sender: Sender<DeviceListEvent>| async move {
+            let mut tracked_ports: HashSet<String> = HashSet::new();
+
+            loop {
+                match available_ports() {
+                    Ok(ports) => {
+                        let (found, lost) = process_serial_port_changes(
+                            &usb_serial_ports(&ports),
+                            &mut tracked_ports,
+                        );
+
+                        for path in lost {
+                            gui_sender
+                                .send(MeshRadioLost(serial_identifier(path)))
+                                .await
+                                .unwrap_or_else(|e| {
+                                    eprintln!("Discovery could not send MeshRadioLost: {e}")
+                                });
+                        }
+
+                        for path in found {
+                            gui_sender
+                                .send(MeshRadioFound(
+                                    serial_identifier(path),
+                                    RadioType::Meshtastic,
+                                ))
+                                .await
+                                .unwrap_or_else(|e| {
+                                    eprintln!("Discovery could not send MeshRadioFound: {e}")
+                                });
+                        }
+                    }
+                    Err(e) => {
+                        gui_sender
+                            .send(Error(format!("Could not list serial ports: {e}")))
+                            .await
+                            .unwrap_or_else(|e| eprintln!("Discovery gui send error: {e}"));
+                    }
+                }
+                tokio::time::sleep(SERIAL_SCAN_INTERVAL).await;
+            }
+        },
+    )
+}
+
+/// The [DeviceIdentifier] of a radio on the serial port at `path`, at the default baud rate
+#[cfg(feature = "serial")]
+fn serial_identifier(path: String) -> DeviceIdentifier {
+    DeviceIdentifier::Serial {
+        path,
+        baud: DEFAULT_SERIAL_BAUD,
+    }
+}
+
+/// Return the paths of the serial ports that are USB devices, which is how radios are connected.
+/// On macOS each device appears as both a "/dev/tty." and a "/dev/cu." port, and only the
+/// "cu" (call-up) one should be used.
+#[cfg(feature = "serial")]
+fn usb_serial_ports(ports: &[SerialPortInfo]) -> HashSet<String> {
+    ports
+        .iter()
+        .filter(|port| matches!(port.port_type, SerialPortType::UsbPort(_)))
+        .filter(|port| !port.port_name.starts_with("/dev/tty."))
+        .map(|port| port.port_name.clone())
+        .collect()
+}
+
+/// Compare the serial ports present now with those being tracked, returning the ones that
+/// have been found and those that have been lost, and update the tracked ports.
+#[cfg(feature = "serial")]
+fn process_serial_port_changes(
+    current_ports: &HashSet<String>,
+    tracked_ports: &mut HashSet<String>,
+) -> (Vec<String>, Vec<String>) {
+    let lost: Vec<String> = tracked_ports.difference(current_ports).cloned().collect();
+    let found: Vec<String> = current_ports.difference(tracked_ports).cloned().collect();
+    tracked_ports.clone_from(current_ports);
+    (found, lost)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1323,4 +1474,91 @@ mod tests {
             assert_eq!(tracked.len(), 100);
         }
     } // mod ble_tests
+
+    #[cfg(feature = "serial")]
+    mod serial_tests {
+        use super::*;
+        use tokio_serial::UsbPortInfo;
+
+        fn port(name: &str, port_type: SerialPortType) -> SerialPortInfo {
+            SerialPortInfo {
+                port_name: name.to_string(),
+                port_type,
+            }
+        }
+
+        fn usb() -> SerialPortType {
+            SerialPortType::UsbPort(UsbPortInfo {
+                vid: 0x239a,
+                pid: 0x8029,
+                serial_number: None,
+                manufacturer: None,
+                product: None,
+            })
+        }
+
+        fn ports(names: &[&str]) -> HashSet<String> {
+            names.iter().map(|name| name.to_string()).collect()
+        }
+
+        #[test]
+        fn test_only_usb_ports() {
+            let found = usb_serial_ports(&[
+                port("/dev/ttyUSB0", usb()),
+                port("/dev/ttyS0", SerialPortType::Unknown),
+                port("/dev/rfcomm0", SerialPortType::BluetoothPort),
+            ]);
+            assert_eq!(found, ports(&["/dev/ttyUSB0"]));
+        }
+
+        #[test]
+        fn test_macos_call_up_ports_only() {
+            let found = usb_serial_ports(&[
+                port("/dev/tty.usbmodem1101", usb()),
+                port("/dev/cu.usbmodem1101", usb()),
+            ]);
+            assert_eq!(found, ports(&["/dev/cu.usbmodem1101"]));
+        }
+
+        #[test]
+        fn test_serial_port_found() {
+            let mut tracked = HashSet::new();
+            let (found, lost) =
+                process_serial_port_changes(&ports(&["/dev/ttyUSB0"]), &mut tracked);
+            assert_eq!(found, vec!["/dev/ttyUSB0".to_string()]);
+            assert!(lost.is_empty());
+            assert_eq!(tracked, ports(&["/dev/ttyUSB0"]));
+        }
+
+        #[test]
+        fn test_serial_port_unchanged() {
+            let mut tracked = ports(&["/dev/ttyUSB0"]);
+            let (found, lost) =
+                process_serial_port_changes(&ports(&["/dev/ttyUSB0"]), &mut tracked);
+            assert!(found.is_empty());
+            assert!(lost.is_empty());
+        }
+
+        #[test]
+        fn test_serial_port_lost() {
+            let mut tracked = ports(&["/dev/ttyUSB0", "/dev/ttyACM0"]);
+            let (found, lost) =
+                process_serial_port_changes(&ports(&["/dev/ttyACM0"]), &mut tracked);
+            assert!(found.is_empty());
+            assert_eq!(lost, vec!["/dev/ttyUSB0".to_string()]);
+            assert_eq!(tracked, ports(&["/dev/ttyACM0"]));
+        }
+
+        #[test]
+        fn test_serial_identifier_default_baud() {
+            let identifier = serial_identifier("/dev/ttyUSB0".to_string());
+            assert_eq!(
+                identifier,
+                DeviceIdentifier::Serial {
+                    path: "/dev/ttyUSB0".to_string(),
+                    baud: DEFAULT_SERIAL_BAUD,
+                }
+            );
+        }
+    } // mod serial_tests
 }
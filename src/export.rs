@@ -0,0 +1,485 @@
+use crate::Message;
+use crate::conversation_id::{MessageId, NodeId};
+use crate::meshchat::MCNodeInfo;
+use crate::message::MCContent::{
+    AlertMessage, EmojiReply, NewTextMessage, PositionMessage, TextMessageReply, UserMessage,
+    Waypoint,
+};
+use crate::message::MCMessage;
+use crate::timestamp::TimeStamp;
+use iced::Task;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fmt::Formatter;
+use std::io;
+use std::path::PathBuf;
+
+/// The file formats a conversation can be exported to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Json,
+    Csv,
+    Text,
+}
+
+impl ExportFormat {
+    pub const ALL: [ExportFormat; 3] = [ExportFormat::Json, ExportFormat::Csv, ExportFormat::Text];
+
+    /// The file extension used for files of this format
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Json => "json",
+            ExportFormat::Csv => "csv",
+            ExportFormat::Text => "txt",
+        }
+    }
+}
+
+impl fmt::Display for ExportFormat {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ExportFormat::Json => f.write_str("JSON"),
+            ExportFormat::Csv => f.write_str("CSV"),
+            ExportFormat::Text => f.write_str("Text"),
+        }
+    }
+}
+
+/// A message of a conversation, with everything resolved that is needed to read it outside the app
+#[derive(Debug, Serialize, PartialEq)]
+pub struct ExportEntry {
+    pub message_id: MessageId,
+    pub from: NodeId,
+    pub from_name: String,
+    pub timestamp: TimeStamp,
+    pub time: String,
+    pub kind: &'static str,
+    pub text: String,
+    pub reply_to: Option<MessageId>,
+    pub delivery: &'static str,
+    pub emoji_reactions: BTreeMap<String, Vec<NodeId>>,
+}
+
+impl ExportEntry {
+    /// Create an [ExportEntry] from an [MCMessage], resolving the sender's name using any alias
+    /// for it first, and then the long name of the node, if known
+    pub fn new(
+        message: &MCMessage,
+        nodes: &HashMap<NodeId, MCNodeInfo>,
+        aliases: &HashMap<NodeId, String>,
+    ) -> Self {
+        let from = message.from();
+        let from_name = aliases
+            .get(&from)
+            .cloned()
+            .or_else(|| {
+                nodes
+                    .get(&from)
+                    .and_then(|node| node.user.as_ref())
+                    .map(|user| user.long_name.clone())
+            })
+            .unwrap_or_else(|| from.to_string());
+
+        let (text, reply_to) = match message.message() {
+            AlertMessage(text) | NewTextMessage(text) => (text.clone(), None),
+            TextMessageReply(reply_to, text) | EmojiReply(reply_to, text) => {
+                (text.clone(), Some(*reply_to))
+            }
+            PositionMessage(position) => (position.to_string(), None),
+            UserMessage(user) => (user.to_string(), None),
+            Waypoint(waypoint) => (waypoint.to_string(), None),
+        };
+
+        ExportEntry {
+            message_id: message.message_id(),
+            from,
+            from_name,
+            timestamp: message.time(),
+            time: MCMessage::datetime_local(message.time())
+                .format("%Y-%m-%d %H:%M:%S")
+                .to_string(),
+            kind: message.message().kind(),
+            text,
+            reply_to,
+            delivery: message.delivery().name(),
+            emoji_reactions: message
+                .emojis()
+                .iter()
+                .map(|(emoji, sources)| (emoji.clone(), sources.clone()))
+                .collect(),
+        }
+    }
+
+    /// Format the emoji reactions as "emoji node node; emoji node" for the CSV and Text formats
+    fn reactions(&self) -> String {
+        self.emoji_reactions
+            .iter()
+            .map(|(emoji, sources)| {
+                let sources: Vec<String> = sources.iter().map(NodeId::to_string).collect();
+                format!("{emoji} {}", sources.join(" "))
+            })
+            .collect::<Vec<String>>()
+            .join("; ")
+    }
+}
+
+/// Render the `entries` of a conversation called `title` in the requested [ExportFormat]
+pub fn render(title: &str, entries: &[ExportEntry], format: ExportFormat) -> io::Result<String> {
+    match format {
+        ExportFormat::Json => serde_json::to_string_pretty(entries).map_err(io::Error::other),
+        ExportFormat::Csv => Ok(to_csv(entries)),
+        ExportFormat::Text => Ok(to_text(title, entries)),
+    }
+}
+
+/// Quote a CSV field if it contains a separator, quote or line break
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+fn to_csv(entries: &[ExportEntry]) -> String {
+    let mut csv = String::from(
+        "message_id,from,from_name,timestamp,time,kind,text,reply_to,delivery,emoji_reactions\n",
+    );
+    for entry in entries {
+        let fields = [
+            entry.message_id.to_string(),
+            entry.from.to_string(),
+            entry.from_name.clone(),
+            u128::from(entry.timestamp).to_string(),
+            entry.time.clone(),
+            entry.kind.to_string(),
+            entry.text.clone(),
+            entry.reply_to.map(|id| id.to_string()).unwrap_or_default(),
+            entry.delivery.to_string(),
+            entry.reactions(),
+        ];
+        let line: Vec<String> = fields.iter().map(|field| csv_field(field)).collect();
+        csv.push_str(&line.join(","));
+        csv.push('\n');
+    }
+    csv
+}
+
+fn to_text(title: &str, entries: &[ExportEntry]) -> String {
+    let mut transcript = format!("MeshChat conversation: {title}\n\n");
+    for entry in entries {
+        transcript.push_str(&format!(
+            "[{}] {} ({}) {}: {}",
+            entry.time, entry.from_name, entry.from, entry.kind, entry.text
+        ));
+        if let Some(reply_to) = entry.reply_to {
+            transcript.push_str(&format!(" (in reply to {reply_to})"));
+        }
+        match entry.delivery {
+            "acked" => transcript.push_str(" ✓"),
+            "sending" | "failed" => transcript.push_str(&format!(" ({})", entry.delivery)),
+            _ => {}
+        }
+        transcript.push('\n');
+        if !entry.emoji_reactions.is_empty() {
+            transcript.push_str(&format!("    reactions: {}\n", entry.reactions()));
+        }
+    }
+    transcript
+}
+
+/// Ask the user where to save the export of `what`, and write it there. Returns the path written
+/// to, or None if the user cancelled the dialog
+async fn save(
+    what: &'static str,
+    file_name: String,
+    (file_type, extension): (String, &'static str),
+    contents: String,
+) -> io::Result<Option<PathBuf>> {
+    let file_handle = rfd::AsyncFileDialog::new()
+        .set_title(format!("Export {what}"))
+        .set_file_name(file_name)
+        .add_filter(file_type, &[extension])
+        .save_file()
+        .await;
+
+    match file_handle {
+        Some(file_handle) => {
+            let path = file_handle.path().to_path_buf();
+            tokio::fs::write(&path, contents).await?;
+            Ok(Some(path))
+        }
+        None => Ok(None),
+    }
+}
+
+/// Use `export_conversation` to export the `entries` of a conversation called `title` to a file
+/// the user chooses, in the requested [ExportFormat]
+pub fn export_conversation(
+    title: &str,
+    entries: &[ExportEntry],
+    format: ExportFormat,
+) -> Task<Message> {
+    match render(title, entries, format) {
+        Ok(contents) => export_file(
+            "Conversation",
+            title,
+            (format.to_string(), format.extension()),
+            contents,
+        ),
+        Err(e) => Task::perform(async {}, move |_| {
+            Message::AppError(
+                "Error exporting conversation".to_string(),
+                e.to_string(),
+                TimeStamp::now(),
+            )
+        }),
+    }
+}
+
+/// Use `export_file` to save the `contents` of an export of `what`, called `title`, to a file of
+/// `file_type` the user chooses, and tell them where it was saved
+pub fn export_file(
+    what: &'static str,
+    title: &str,
+    (file_type, extension): (String, &'static str),
+    contents: String,
+) -> Task<Message> {
+    let file_name = format!("meshchat-{}.{extension}", file_stem(title));
+    Task::perform(
+        save(what, file_name, (file_type, extension), contents),
+        move |result| match result {
+            Ok(Some(path)) => Message::AppNotification(
+                format!("{what} exported"),
+                path.to_string_lossy().to_string(),
+                TimeStamp::now(),
+            ),
+            Ok(None) => Message::None,
+            Err(e) => Message::AppError(
+                format!("Error exporting {}", what.to_lowercase()),
+                e.to_string(),
+                TimeStamp::now(), // jonesy:allow(expect)
+            ),
+        },
+    )
+}
+
+/// Turn the conversation title into something that can be used in a file name
+fn file_stem(title: &str) -> String {
+    title
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '-' })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::meshchat::{MCPosition, MCUser, MCWaypoint};
+    use crate::message::DeliveryState;
+
+    fn node(node_id: u64, long_name: &str) -> (NodeId, MCNodeInfo) {
+        (
+            NodeId::from(node_id),
+            MCNodeInfo {
+                node_id: NodeId::from(node_id),
+                user: Some(MCUser {
+                    long_name: long_name.to_string(),
+                    ..Default::default()
+                }),
+                position: None,
+                is_ignored: false,
+                hops_away: None,
+            },
+        )
+    }
+
+    fn text_message(id: u64, from: u64, text: &str) -> MCMessage {
+        MCMessage::new(
+            MessageId::from(id),
+            NodeId::from(from),
+            NewTextMessage(text.to_string()),
+            TimeStamp::from(1_700_000_000_000u64),
+        )
+    }
+
+    #[test]
+    fn format_extensions() {
+        assert_eq!(ExportFormat::Json.extension(), "json");
+        assert_eq!(ExportFormat::Csv.extension(), "csv");
+        assert_eq!(ExportFormat::Text.extension(), "txt");
+    }
+
+    #[test]
+    fn entry_uses_long_name() {
+        let nodes = HashMap::from([node(1, "Alice")]);
+        let entry = ExportEntry::new(&text_message(10, 1, "hi"), &nodes, &HashMap::new());
+        assert_eq!(entry.from_name, "Alice");
+        assert_eq!(entry.kind, "text");
+        assert_eq!(entry.text, "hi");
+        assert!(entry.reply_to.is_none());
+        assert_eq!(entry.delivery, "sent");
+    }
+
+    #[test]
+    fn entry_prefers_alias() {
+        let nodes = HashMap::from([node(1, "Alice")]);
+        let aliases = HashMap::from([(NodeId::from(1u64), "Ally".to_string())]);
+        let entry = ExportEntry::new(&text_message(10, 1, "hi"), &nodes, &aliases);
+        assert_eq!(entry.from_name, "Ally");
+    }
+
+    #[test]
+    fn entry_unknown_node_uses_id() {
+        let entry = ExportEntry::new(
+            &text_message(10, 42, "hi"),
+            &HashMap::new(),
+            &HashMap::new(),
+        );
+        assert_eq!(entry.from_name, "42");
+    }
+
+    #[test]
+    fn entry_reply_target() {
+        let message = MCMessage::new(
+            MessageId::from(11u64),
+            NodeId::from(1u64),
+            TextMessageReply(MessageId::from(10u64), "yes".to_string()),
+            TimeStamp::from(0u64),
+        );
+        let entry = ExportEntry::new(&message, &HashMap::new(), &HashMap::new());
+        assert_eq!(entry.kind, "reply");
+        assert_eq!(entry.reply_to, Some(MessageId::from(10u64)));
+    }
+
+    #[test]
+    fn entry_ack_and_reactions() {
+        let mut message = text_message(10, 1, "hi");
+        message.ack();
+        message.add_emoji("👍".to_string(), NodeId::from(2u64));
+        message.add_emoji("👍".to_string(), NodeId::from(3u64));
+        let entry = ExportEntry::new(&message, &HashMap::new(), &HashMap::new());
+        assert_eq!(entry.delivery, "acked");
+        assert_eq!(
+            entry.emoji_reactions.get("👍"),
+            Some(&vec![NodeId::from(2u64), NodeId::from(3u64)])
+        );
+        assert_eq!(entry.reactions(), "👍 2 3");
+    }
+
+    #[test]
+    fn entry_position_kind() {
+        let message = MCMessage::new(
+            MessageId::from(1u64),
+            NodeId::from(1u64),
+            PositionMessage(MCPosition::default()),
+            TimeStamp::from(0u64),
+        );
+        let entry = ExportEntry::new(&message, &HashMap::new(), &HashMap::new());
+        assert_eq!(entry.kind, "position");
+    }
+
+    #[test]
+    fn entry_waypoint_kind() {
+        let message = MCMessage::new(
+            MessageId::from(1u64),
+            NodeId::from(1u64),
+            Waypoint(MCWaypoint {
+                name: "Camp".into(),
+                ..Default::default()
+            }),
+            TimeStamp::from(0u64),
+        );
+        let entry = ExportEntry::new(&message, &HashMap::new(), &HashMap::new());
+        assert_eq!(entry.kind, "waypoint");
+        assert_eq!(entry.text, "🚩 Camp");
+    }
+
+    #[test]
+    fn csv_quotes_fields() {
+        assert_eq!(csv_field("plain"), "plain");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_field("two\nlines"), "\"two\nlines\"");
+    }
+
+    #[test]
+    fn csv_has_header_and_rows() {
+        let nodes = HashMap::from([node(1, "Alice")]);
+        let entries = vec![ExportEntry::new(
+            &text_message(10, 1, "hello, world"),
+            &nodes,
+            &HashMap::new(),
+        )];
+        let csv = render("Test", &entries, ExportFormat::Csv).expect("Could not render CSV");
+        let mut lines = csv.lines();
+        assert!(
+            lines
+                .next()
+                .expect("No header")
+                .starts_with("message_id,from,from_name")
+        );
+        let row = lines.next().expect("No row");
+        assert!(row.starts_with("10,1,Alice,1700000000000,"));
+        assert!(row.contains(",text,\"hello, world\",,sent,"));
+        assert!(lines.next().is_none());
+    }
+
+    #[test]
+    fn json_is_array_of_entries() {
+        let entries = vec![
+            ExportEntry::new(
+                &text_message(10, 1, "one"),
+                &HashMap::new(),
+                &HashMap::new(),
+            ),
+            ExportEntry::new(
+                &text_message(11, 1, "two"),
+                &HashMap::new(),
+                &HashMap::new(),
+            ),
+        ];
+        let json = render("Test", &entries, ExportFormat::Json).expect("Could not render JSON");
+        let value: serde_json::Value = serde_json::from_str(&json).expect("Invalid JSON");
+        let array = value.as_array().expect("Not an array");
+        assert_eq!(array.len(), 2);
+        assert_eq!(array[0]["text"], "one");
+        assert_eq!(array[1]["message_id"], 11);
+        assert_eq!(array[1]["kind"], "text");
+    }
+
+    #[test]
+    fn text_transcript() {
+        let nodes = HashMap::from([node(1, "Alice")]);
+        let mut message = text_message(10, 1, "hello");
+        message.ack();
+        let entries = vec![ExportEntry::new(&message, &nodes, &HashMap::new())];
+        let transcript =
+            render("LongFast", &entries, ExportFormat::Text).expect("Could not render text");
+        assert!(transcript.starts_with("MeshChat conversation: LongFast\n"));
+        assert!(transcript.contains("Alice (1) text: hello ✓"));
+    }
+
+    #[test]
+    fn failed_message_exported_as_failed() {
+        let mut message = text_message(10, 1, "hello");
+        message.set_delivery(DeliveryState::Failed);
+        let entries = vec![ExportEntry::new(&message, &HashMap::new(), &HashMap::new())];
+        assert_eq!(entries[0].delivery, "failed");
+
+        let csv = render("Test", &entries, ExportFormat::Csv).expect("Could not render CSV");
+        assert!(csv.contains(",text,hello,,failed,"));
+        let json = render("Test", &entries, ExportFormat::Json).expect("Could not render JSON");
+        let value: serde_json::Value = serde_json::from_str(&json).expect("Invalid JSON");
+        assert_eq!(value[0]["delivery"], "failed");
+        let transcript =
+            render("Test", &entries, ExportFormat::Text).expect("Could not render text");
+        assert!(transcript.contains("1 (1) text: hello (failed)"));
+    }
+
+    #[test]
+    fn file_stem_replaces_unsafe_chars() {
+        assert_eq!(file_stem("My Channel/1"), "My-Channel-1");
+    }
+}
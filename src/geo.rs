@@ -0,0 +1,122 @@
+use crate::config::Units;
+use crate::meshchat::MCPosition;
+
+/// The mean radius of the Earth in metres
+const EARTH_RADIUS_M: f64 = 6_371_000.0;
+/// Metres in a mile, and in a foot
+const METRES_PER_MILE: f64 = 1_609.344;
+const METRES_PER_FOOT: f64 = 0.3048;
+/// The points of the compass, clockwise from North, each 45° apart
+const COMPASS_POINTS: [&str; 8] = ["N", "NE", "E", "SE", "S", "SW", "W", "NW"];
+
+/// The distance in metres between two positions, along the surface of the Earth
+pub fn distance_m(from: &MCPosition, to: &MCPosition) -> f64 {
+    let (lat1, lat2) = (from.latitude.to_radians(), to.latitude.to_radians());
+    let delta_lat = lat2 - lat1;
+    let delta_long = (to.longitude - from.longitude).to_radians();
+
+    let a = (delta_lat / 2.0).sin().powi(2)
+        + lat1.cos() * lat2.cos() * (delta_long / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS_M * a.sqrt().min(1.0).asin()
+}
+
+/// The initial bearing in degrees, clockwise from North in 0..360, to set off from one position
+/// along the great circle to another
+pub fn bearing_deg(from: &MCPosition, to: &MCPosition) -> f64 {
+    let (lat1, lat2) = (from.latitude.to_radians(), to.latitude.to_radians());
+    let delta_long = (to.longitude - from.longitude).to_radians();
+
+    let y = delta_long.sin() * lat2.cos();
+    let x = lat1.cos() * lat2.sin() - lat1.sin() * lat2.cos() * delta_long.cos();
+    y.atan2(x).to_degrees().rem_euclid(360.0)
+}
+
+/// The nearest point of the compass to a bearing in degrees
+pub fn compass_point(bearing: f64) -> &'static str {
+    let index = (bearing.rem_euclid(360.0) / 45.0).round() as usize % COMPASS_POINTS.len();
+    COMPASS_POINTS[index]
+}
+
+/// A distance in metres, in the `units` chosen, with the small unit when it's close
+pub fn format_distance(metres: f64, units: Units) -> String {
+    match units {
+        Units::Metric if metres < 1_000.0 => format!("{metres:.0} m"),
+        Units::Metric => format!("{:.1} km", metres / 1_000.0),
+        Units::Imperial if metres < 0.1 * METRES_PER_MILE => {
+            format!("{:.0} ft", metres / METRES_PER_FOOT)
+        }
+        Units::Imperial => format!("{:.1} mi", metres / METRES_PER_MILE),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn position(latitude: f64, longitude: f64) -> MCPosition {
+        MCPosition {
+            latitude,
+            longitude,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_same_position() {
+        let here = position(46.5191, 6.6335);
+        assert_eq!(distance_m(&here, &here), 0.0);
+    }
+
+    #[test]
+    fn test_london_to_paris() {
+        let london = position(51.5074, -0.1278);
+        let paris = position(48.8566, 2.3522);
+        let distance = distance_m(&london, &paris);
+        assert!((distance - 343_500.0).abs() < 1_000.0, "{distance}");
+        assert_eq!(distance, distance_m(&paris, &london));
+    }
+
+    #[test]
+    fn test_across_the_antimeridian() {
+        let west = position(0.0, 179.5);
+        let east = position(0.0, -179.5);
+        let distance = distance_m(&west, &east);
+        assert!((distance - 111_195.0).abs() < 100.0, "{distance}");
+    }
+
+    #[test]
+    fn test_bearing() {
+        let here = position(0.0, 0.0);
+        assert_eq!(bearing_deg(&here, &position(1.0, 0.0)), 0.0);
+        assert!((bearing_deg(&here, &position(0.0, 1.0)) - 90.0).abs() < 1e-9);
+        assert!((bearing_deg(&here, &position(-1.0, 0.0)) - 180.0).abs() < 1e-9);
+        assert!((bearing_deg(&here, &position(0.0, -1.0)) - 270.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_bearing_london_to_paris() {
+        let london = position(51.5074, -0.1278);
+        let paris = position(48.8566, 2.3522);
+        let bearing = bearing_deg(&london, &paris);
+        assert!((bearing - 148.1).abs() < 0.5, "{bearing}");
+        assert_eq!(compass_point(bearing), "SE");
+    }
+
+    #[test]
+    fn test_compass_point() {
+        assert_eq!(compass_point(0.0), "N");
+        assert_eq!(compass_point(22.4), "N");
+        assert_eq!(compass_point(22.6), "NE");
+        assert_eq!(compass_point(270.0), "W");
+        assert_eq!(compass_point(350.0), "N");
+        assert_eq!(compass_point(-90.0), "W");
+    }
+
+    #[test]
+    fn test_format_distance() {
+        assert_eq!(format_distance(850.4, Units::Metric), "850 m");
+        assert_eq!(format_distance(12_345.0, Units::Metric), "12.3 km");
+        assert_eq!(format_distance(100.0, Units::Imperial), "328 ft");
+        assert_eq!(format_distance(16_093.44, Units::Imperial), "10.0 mi");
+    }
+}
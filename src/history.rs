@@ -0,0 +1,333 @@
+use crate::Message;
+use crate::conversation_id::ConversationId;
+use crate::device::DeviceIdentifier;
+use crate::device::DeviceMessage::HistoryLoaded;
+use crate::message::MCMessage;
+use crate::store::{read_json_dir, report_bad_files, write_json};
+use crate::timestamp::TimeStamp;
+use directories::ProjectDirs;
+use iced::Task;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io;
+use std::path::PathBuf;
+
+const HISTORY_DIR: &str = "history";
+const HISTORY_EXTENSION: &str = "json";
+
+/// The on-disk format of the messages of one [ConversationId] for a device
+#[derive(Debug, Serialize, Deserialize)]
+struct ConversationHistory {
+    conversation_id: ConversationId,
+    messages: Vec<MCMessage>,
+}
+
+/// Return the directory the history of conversations on `device` are stored in, if there is one
+pub fn history_dir(device: &DeviceIdentifier) -> Option<PathBuf> {
+    ProjectDirs::from("net", "Mackenzie Serres", "meshchat").map(|proj_dirs| {
+        proj_dirs
+            .data_dir()
+            .join(HISTORY_DIR)
+            .join(device_dir_name(device))
+    })
+}
+
+/// Turn a [DeviceIdentifier] into a name that is safe to use as a directory name on all platforms
+pub fn device_dir_name(device: &DeviceIdentifier) -> String {
+    String::from(device)
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect()
+}
+
+/// The name of the file the history of a [ConversationId] is stored in
+fn conversation_file_name(conversation_id: &ConversationId) -> String {
+    match conversation_id {
+        ConversationId::Channel(channel_index) => {
+            format!("channel_{}.{HISTORY_EXTENSION}", u8::from(*channel_index))
+        }
+        ConversationId::Node(node_id) => format!("node_{node_id}.{HISTORY_EXTENSION}"),
+    }
+}
+
+/// The saved conversations, and the files that could not be read
+type LoadedHistory = (
+    HashMap<ConversationId, Vec<MCMessage>>,
+    Vec<(PathBuf, String)>,
+);
+
+// Private methods for async reading and writing of history files
+async fn load(history_dir: PathBuf) -> io::Result<LoadedHistory> {
+    let contents = read_json_dir::<ConversationHistory>(&history_dir, HISTORY_EXTENSION).await?;
+    let history = contents
+        .values
+        .into_iter()
+        .map(|conversation| (conversation.conversation_id, conversation.messages))
+        .collect();
+    Ok((history, contents.bad_files))
+}
+
+async fn save(history_dir: PathBuf, conversation: ConversationHistory) -> io::Result<()> {
+    let history_path = history_dir.join(conversation_file_name(&conversation.conversation_id));
+    write_json(&history_path, &conversation).await
+}
+
+/// Use `load_history` to load all the conversations previously saved for `device` from disk.
+/// A conversation file that can't be read is skipped and reported, and the rest are loaded.
+/// If the history can't be loaded at all the error is reported, and it is loaded as empty so
+/// conversations are still saved.
+pub fn load_history(device: &DeviceIdentifier) -> Task<Message> {
+    if let Some(history_dir) = history_dir(device) {
+        let device = device.clone();
+        Task::future(load(history_dir.clone())).then(move |result| match result {
+            Ok((history, bad_files)) => report_bad_files(
+                Message::DeviceViewEvent(HistoryLoaded(device.clone(), history)),
+                "message history",
+                bad_files,
+            ),
+            Err(e) => Task::batch([
+                Task::done(Message::AppError(
+                    format!(
+                        "Error loading message history: '{}'",
+                        history_dir.to_string_lossy()
+                    ),
+                    e.to_string(),
+                    TimeStamp::now(), // jonesy:allow(expect)
+                )),
+                Task::done(Message::DeviceViewEvent(HistoryLoaded(
+                    device.clone(),
+                    HashMap::new(),
+                ))),
+            ]),
+        })
+    } else {
+        Task::none()
+    }
+}
+
+/// Use `save_conversation` to save the messages of a conversation on `device` to disk
+pub fn save_conversation(
+    device: &DeviceIdentifier,
+    conversation_id: ConversationId,
+    messages: Vec<MCMessage>,
+) -> Task<Message> {
+    if let Some(history_dir) = history_dir(device) {
+        let conversation = ConversationHistory {
+            conversation_id,
+            messages,
+        };
+        Task::perform(save(history_dir.clone(), conversation), {
+            move |result| match result {
+                Ok(_) => Message::None,
+                Err(e) => Message::AppError(
+                    format!(
+                        "Error saving message history: '{}'",
+                        history_dir.to_string_lossy()
+                    ),
+                    e.to_string(),
+                    TimeStamp::now(), // jonesy:allow(expect)
+                ),
+            }
+        })
+    } else {
+        Task::none()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::conversation_id::{ChannelIndex, MessageId, NodeId};
+    use crate::message::MCContent;
+    use tokio::fs::File;
+    use tokio::io::AsyncWriteExt;
+
+    fn test_messages() -> Vec<MCMessage> {
+        vec![
+            MCMessage::new(
+                MessageId::from(1u64),
+                NodeId::from(100u64),
+                MCContent::NewTextMessage("Hello".to_string()),
+                TimeStamp::from(1000u64),
+            ),
+            MCMessage::new(
+                MessageId::from(2u64),
+                NodeId::from(200u64),
+                MCContent::TextMessageReply(MessageId::from(1u64), "Hi".to_string()),
+                TimeStamp::from(2000u64),
+            ),
+        ]
+    }
+
+    #[test]
+    fn channel_file_name() {
+        let conversation_id = ConversationId::Channel(ChannelIndex::from(3u8));
+        assert_eq!(conversation_file_name(&conversation_id), "channel_3.json");
+    }
+
+    #[test]
+    fn node_file_name() {
+        let conversation_id = ConversationId::Node(NodeId::from(12345u64));
+        assert_eq!(conversation_file_name(&conversation_id), "node_12345.json");
+    }
+
+    #[cfg(feature = "tcp")]
+    #[test]
+    fn device_dir_name_is_safe() {
+        let device = DeviceIdentifier::from("tcp://192.168.1.10:4403#My Radio");
+        let dir_name = device_dir_name(&device);
+        assert!(
+            dir_name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_')
+        );
+    }
+
+    #[test]
+    fn different_devices_different_dirs() {
+        let device1 = DeviceIdentifier::from("AA:BB:CC:DD:EE:FF");
+        let device2 = DeviceIdentifier::from("11:22:33:44:55:66");
+        assert_ne!(device_dir_name(&device1), device_dir_name(&device2));
+    }
+
+    #[tokio::test]
+    async fn load_missing_dir_is_empty() {
+        let tempdir = tempfile::Builder::new()
+            .prefix("meshchat")
+            .tempdir()
+            .expect("Could not create a temp dir");
+        let (history, bad_files) = load(tempdir.path().join("missing"))
+            .await
+            .expect("Could not load history");
+        assert!(history.is_empty());
+        assert!(bad_files.is_empty());
+    }
+
+    #[tokio::test]
+    async fn save_and_load_roundtrip() {
+        let tempdir = tempfile::Builder::new()
+            .prefix("meshchat")
+            .tempdir()
+            .expect("Could not create a temp dir");
+        let history_dir = tempdir.path().join("device");
+        let channel = ConversationId::Channel(ChannelIndex::from(0u8));
+        let node = ConversationId::Node(NodeId::from(100u64));
+
+        save(
+            history_dir.clone(),
+            ConversationHistory {
+                conversation_id: channel,
+                messages: test_messages(),
+            },
+        )
+        .await
+        .expect("Could not save channel history");
+        save(
+            history_dir.clone(),
+            ConversationHistory {
+                conversation_id: node,
+                messages: vec![],
+            },
+        )
+        .await
+        .expect("Could not save node history");
+
+        let (history, _) = load(history_dir).await.expect("Could not load history");
+        assert_eq!(history.len(), 2);
+        let messages = history.get(&channel).expect("Channel history missing");
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0].message_id(), MessageId::from(1u64));
+        assert_eq!(messages[0].from(), NodeId::from(100u64));
+        assert_eq!(messages[1].time(), TimeStamp::from(2000u64));
+        assert!(matches!(
+            messages[1].message(),
+            MCContent::TextMessageReply(_, text) if text == "Hi"
+        ));
+        assert!(history.get(&node).expect("Node history missing").is_empty());
+    }
+
+    #[tokio::test]
+    async fn save_overwrites_previous() {
+        let tempdir = tempfile::Builder::new()
+            .prefix("meshchat")
+            .tempdir()
+            .expect("Could not create a temp dir");
+        let history_dir = tempdir.path().to_path_buf();
+        let channel = ConversationId::Channel(ChannelIndex::from(1u8));
+
+        save(
+            history_dir.clone(),
+            ConversationHistory {
+                conversation_id: channel,
+                messages: test_messages(),
+            },
+        )
+        .await
+        .expect("Could not save history");
+        save(
+            history_dir.clone(),
+            ConversationHistory {
+                conversation_id: channel,
+                messages: test_messages().into_iter().take(1).collect(),
+            },
+        )
+        .await
+        .expect("Could not save history");
+
+        let (history, _) = load(history_dir).await.expect("Could not load history");
+        assert_eq!(history.get(&channel).map(Vec::len), Some(1));
+    }
+
+    #[tokio::test]
+    async fn load_ignores_other_files() {
+        let tempdir = tempfile::Builder::new()
+            .prefix("meshchat")
+            .tempdir()
+            .expect("Could not create a temp dir");
+        let mut other = File::create(tempdir.path().join("notes.txt"))
+            .await
+            .expect("Could not create file");
+        other
+            .write_all(b"not history")
+            .await
+            .expect("Could not write file");
+
+        let (history, bad_files) = load(tempdir.path().to_path_buf())
+            .await
+            .expect("Could not load history");
+        assert!(history.is_empty());
+        assert!(bad_files.is_empty());
+    }
+
+    #[tokio::test]
+    async fn load_skips_corrupt_file() {
+        let tempdir = tempfile::Builder::new()
+            .prefix("meshchat")
+            .tempdir()
+            .expect("Could not create a temp dir");
+        let channel = ConversationId::Channel(ChannelIndex::from(1u8));
+        save(
+            tempdir.path().to_path_buf(),
+            ConversationHistory {
+                conversation_id: channel,
+                messages: test_messages(),
+            },
+        )
+        .await
+        .expect("Could not save history");
+        let mut corrupt = File::create(tempdir.path().join("channel_0.json"))
+            .await
+            .expect("Could not create file");
+        corrupt
+            .write_all(b"{ not json")
+            .await
+            .expect("Could not write file");
+
+        let (history, bad_files) = load(tempdir.path().to_path_buf())
+            .await
+            .expect("Could not load history");
+        assert_eq!(history.get(&channel).map(Vec::len), Some(2));
+        assert_eq!(bad_files.len(), 1);
+        assert_eq!(bad_files[0].0, tempdir.path().join("channel_0.json"));
+    }
+}
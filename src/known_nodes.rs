@@ -0,0 +1,732 @@
+use crate::Message;
+use crate::conversation_id::NodeId;
+use crate::device::DeviceIdentifier;
+use crate::device::DeviceMessage::KnownNodesLoaded;
+use crate::history::device_dir_name;
+use crate::meshchat::{MCNodeInfo, MCPosition, MCUser};
+use crate::store::{read_json, write_json};
+use crate::timestamp::TimeStamp;
+use directories::ProjectDirs;
+use iced::Task;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io;
+use std::path::PathBuf;
+use std::time::Duration;
+
+const NODES_DIR: &str = "nodes";
+const NODES_EXTENSION: &str = "json";
+
+const MINUTE_MS: u128 = 60 * 1000;
+const HOUR_MS: u128 = 60 * MINUTE_MS;
+const DAY_MS: u128 = 24 * HOUR_MS;
+
+/// A node we have met on a device, with when we first saw it and when we last heard from it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KnownNode {
+    pub node_info: MCNodeInfo,
+    pub first_seen: TimeStamp,
+    pub last_heard: TimeStamp,
+    /// The public key first seen for the node, that keys it reports later are checked against
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pinned_key: Option<Vec<u8>>,
+    /// A public key reported by the node that is not the pinned one, until it is trusted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub changed_key: Option<Vec<u8>>,
+}
+
+/// The result of checking the public key a node reported against the one pinned for it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum KeyCheck {
+    /// The key is the pinned one, was already reported as changed, or there is no key
+    Unchanged,
+    /// This is the first key seen for the node, and it is now pinned
+    Pinned,
+    /// The key is different to the one pinned, and was not reported before
+    Changed,
+}
+
+impl KnownNode {
+    /// The public key the node reported last, if any
+    fn public_key(&self) -> Option<&Vec<u8>> {
+        self.node_info
+            .user
+            .as_ref()
+            .map(|user| &user.public_key)
+            .filter(|public_key| !public_key.is_empty())
+    }
+
+    /// Check the public key the node reported last against the pinned one, pinning it if there
+    /// is none yet
+    fn check_key(&mut self) -> KeyCheck {
+        let Some(public_key) = self.public_key().cloned() else {
+            return KeyCheck::Unchanged;
+        };
+        match &self.pinned_key {
+            None => {
+                self.pinned_key = Some(public_key);
+                KeyCheck::Pinned
+            }
+            Some(pinned) if *pinned == public_key => KeyCheck::Unchanged,
+            Some(_) if self.changed_key.as_ref() == Some(&public_key) => KeyCheck::Unchanged,
+            Some(_) => {
+                self.changed_key = Some(public_key);
+                KeyCheck::Changed
+            }
+        }
+    }
+}
+
+/// All the nodes we have met on a device, kept across connections so that nodes the radio
+/// has dropped are not forgotten
+#[derive(Debug, Default)]
+pub struct KnownNodes {
+    nodes: HashMap<NodeId, KnownNode>,
+}
+
+impl KnownNodes {
+    /// Get the [KnownNode] for `node_id`, if we have met it before
+    pub fn get(&self, node_id: &NodeId) -> Option<&KnownNode> {
+        self.nodes.get(node_id)
+    }
+
+    /// Iterate over all the nodes we have met
+    pub fn values(&self) -> impl Iterator<Item = &KnownNode> {
+        self.nodes.values()
+    }
+
+    /// Forget all known nodes, such as when disconnecting from the device they were met on
+    pub fn clear(&mut self) {
+        self.nodes.clear();
+    }
+
+    /// Merge nodes loaded from disk, keeping the earliest first-seen and latest last-heard
+    /// times, and any user or position we have from this connection
+    pub fn merge(&mut self, loaded: HashMap<NodeId, KnownNode>) {
+        for (node_id, loaded_node) in loaded {
+            if let Some(known) = self.nodes.get_mut(&node_id) {
+                known.first_seen = known.first_seen.min(loaded_node.first_seen);
+                known.last_heard = known.last_heard.max(loaded_node.last_heard);
+                if known.node_info.user.is_none() {
+                    known.node_info.user = loaded_node.node_info.user;
+                }
+                if known.node_info.position.is_none() {
+                    known.node_info.position = loaded_node.node_info.position;
+                }
+                // The key pinned before takes precedence over one pinned on this connection
+                if let Some(loaded_key) = loaded_node.pinned_key {
+                    let pinned_now = known.pinned_key.replace(loaded_key);
+                    known.changed_key = loaded_node.changed_key.or(pinned_now);
+                    if known.changed_key == known.pinned_key {
+                        known.changed_key = None;
+                    }
+                }
+            } else {
+                self.nodes.insert(node_id, loaded_node);
+            }
+        }
+    }
+
+    /// A node has been reported by the radio. Add it if it is new to us, otherwise fill in
+    /// any user or position missing from `node_info` with the last ones we stored, and store
+    /// the ones it has. Return true if the node was not known before.
+    pub fn seen(&mut self, node_info: &mut MCNodeInfo, now: TimeStamp) -> bool {
+        if let Some(known) = self.nodes.get_mut(&node_info.node_id) {
+            match &node_info.user {
+                Some(user) => known.node_info.user = Some(user.clone()),
+                None => node_info.user = known.node_info.user.clone(),
+            }
+            match &node_info.position {
+                Some(position) => known.node_info.position = Some(position.clone()),
+                None => node_info.position = known.node_info.position.clone(),
+            }
+            known.node_info.is_ignored = node_info.is_ignored;
+            known.node_info.hops_away = node_info.hops_away;
+            false
+        } else {
+            self.nodes.insert(
+                node_info.node_id,
+                KnownNode {
+                    node_info: node_info.clone(),
+                    first_seen: now,
+                    last_heard: now,
+                    pinned_key: None,
+                    changed_key: None,
+                },
+            );
+            true
+        }
+    }
+
+    /// We have heard from a node. Return true if it is a node we know about
+    pub fn heard(&mut self, node_id: NodeId, now: TimeStamp) -> bool {
+        if let Some(known) = self.nodes.get_mut(&node_id) {
+            known.last_heard = known.last_heard.max(now);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Store the last [MCUser] we have heard for a node
+    pub fn update_user(&mut self, node_id: NodeId, user: &MCUser) {
+        if let Some(known) = self.nodes.get_mut(&node_id) {
+            known.node_info.user = Some(user.clone());
+        }
+    }
+
+    /// Check the public key last reported by a node against the one pinned for it
+    pub fn check_key(&mut self, node_id: NodeId) -> KeyCheck {
+        self.nodes
+            .get_mut(&node_id)
+            .map(KnownNode::check_key)
+            .unwrap_or(KeyCheck::Unchanged)
+    }
+
+    /// Return true if a node reported a public key that is not the one pinned for it
+    pub fn key_changed(&self, node_id: NodeId) -> bool {
+        self.nodes
+            .get(&node_id)
+            .is_some_and(|known| known.changed_key.is_some())
+    }
+
+    /// Trust the changed public key of a node, pinning it in place of the old one. Return true
+    /// if there was a changed key to trust.
+    pub fn trust_key(&mut self, node_id: NodeId) -> bool {
+        if let Some(known) = self.nodes.get_mut(&node_id)
+            && let Some(changed_key) = known.changed_key.take()
+        {
+            known.pinned_key = Some(changed_key);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Store whether a node is ignored
+    pub fn set_ignored(&mut self, node_id: NodeId, ignored: bool) {
+        if let Some(known) = self.nodes.get_mut(&node_id) {
+            known.node_info.is_ignored = ignored;
+        }
+    }
+
+    /// Store the last [MCPosition] we have heard for a node
+    pub fn update_position(&mut self, node_id: NodeId, position: &MCPosition) {
+        if let Some(known) = self.nodes.get_mut(&node_id) {
+            known.node_info.position = Some(position.clone());
+        }
+    }
+}
+
+/// Whether a node is online, going by how long ago it was last heard
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NodeStatus {
+    Online,
+    /// Not heard recently, but not for long enough to be offline
+    Stale,
+    Offline,
+}
+
+/// The status of a node last heard at `last_heard`: online if heard within `online_within`,
+/// offline if not heard for `offline_after`, and stale in between
+pub fn node_status(
+    now: TimeStamp,
+    last_heard: TimeStamp,
+    online_within: Duration,
+    offline_after: Duration,
+) -> NodeStatus {
+    let elapsed = u128::from(now - last_heard);
+    if elapsed < online_within.as_millis() {
+        NodeStatus::Online
+    } else if elapsed >= offline_after.as_millis() {
+        NodeStatus::Offline
+    } else {
+        NodeStatus::Stale
+    }
+}
+
+/// Return a short description of how long ago `then` was, such as "5m ago"
+pub fn heard_ago(now: TimeStamp, then: TimeStamp) -> String {
+    let elapsed = u128::from(now - then);
+    if elapsed < MINUTE_MS {
+        "just now".to_string()
+    } else if elapsed < HOUR_MS {
+        format!("{}m ago", elapsed / MINUTE_MS)
+    } else if elapsed < DAY_MS {
+        format!("{}h ago", elapsed / HOUR_MS)
+    } else {
+        format!("{}d ago", elapsed / DAY_MS)
+    }
+}
+
+/// Return the file the known nodes of `device` are stored in, if there is one
+fn nodes_file(device: &DeviceIdentifier) -> Option<PathBuf> {
+    ProjectDirs::from("net", "Mackenzie Serres", "meshchat").map(|proj_dirs| {
+        proj_dirs
+            .data_dir()
+            .join(NODES_DIR)
+            .join(format!("{}.{NODES_EXTENSION}", device_dir_name(device)))
+    })
+}
+
+// Private methods for async reading and writing of the known nodes file
+async fn load(nodes_file: PathBuf) -> io::Result<HashMap<NodeId, KnownNode>> {
+    let nodes: Vec<KnownNode> = read_json(&nodes_file).await?.unwrap_or_default();
+    Ok(nodes
+        .into_iter()
+        .map(|node| (node.node_info.node_id, node))
+        .collect())
+}
+
+async fn save(nodes_file: PathBuf, nodes: Vec<KnownNode>) -> io::Result<()> {
+    write_json(&nodes_file, &nodes).await
+}
+
+/// Use `load_known_nodes` to load all the nodes previously met on `device` from disk
+pub fn load_known_nodes(device: &DeviceIdentifier) -> Task<Message> {
+    if let Some(nodes_file) = nodes_file(device) {
+        let device = device.clone();
+        Task::future(load(nodes_file.clone())).then(move |result| match result {
+            Ok(nodes) => Task::done(Message::DeviceViewEvent(KnownNodesLoaded(
+                device.clone(),
+                nodes,
+            ))),
+            // Nodes met from now on are still remembered, and their keys pinned
+            Err(e) => Task::batch([
+                Task::done(Message::AppError(
+                    format!(
+                        "Error loading known nodes: '{}'",
+                        nodes_file.to_string_lossy()
+                    ),
+                    e.to_string(),
+                    TimeStamp::now(), // jonesy:allow(expect)
+                )),
+                Task::done(Message::DeviceViewEvent(KnownNodesLoaded(
+                    device.clone(),
+                    HashMap::new(),
+                ))),
+            ]),
+        })
+    } else {
+        Task::none()
+    }
+}
+
+/// Use `save_known_nodes` to save all the nodes met on `device` to disk
+pub fn save_known_nodes(device: &DeviceIdentifier, known_nodes: &KnownNodes) -> Task<Message> {
+    if let Some(nodes_file) = nodes_file(device) {
+        let nodes = known_nodes.values().cloned().collect();
+        Task::perform(save(nodes_file.clone(), nodes), {
+            move |result| match result {
+                Ok(_) => Message::None,
+                Err(e) => Message::AppError(
+                    format!(
+                        "Error saving known nodes: '{}'",
+                        nodes_file.to_string_lossy()
+                    ),
+                    e.to_string(),
+                    TimeStamp::now(), // jonesy:allow(expect)
+                ),
+            }
+        })
+    } else {
+        Task::none()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::fs::File;
+    use tokio::io::AsyncWriteExt;
+
+    fn test_user(long_name: &str) -> MCUser {
+        MCUser {
+            long_name: long_name.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn test_node(id: u64, user: Option<MCUser>) -> MCNodeInfo {
+        MCNodeInfo {
+            node_id: NodeId::from(id),
+            user,
+            position: None,
+            is_ignored: false,
+            hops_away: None,
+        }
+    }
+
+    #[test]
+    fn seen_new_node() {
+        let mut known_nodes = KnownNodes::default();
+        let mut node = test_node(1, None);
+        assert!(known_nodes.seen(&mut node, TimeStamp::from(100u64)));
+        let known = known_nodes
+            .get(&NodeId::from(1u64))
+            .expect("Node not known");
+        assert_eq!(known.first_seen, TimeStamp::from(100u64));
+        assert_eq!(known.last_heard, TimeStamp::from(100u64));
+    }
+
+    #[test]
+    fn seen_again_keeps_times() {
+        let mut known_nodes = KnownNodes::default();
+        let mut node = test_node(1, None);
+        known_nodes.seen(&mut node, TimeStamp::from(100u64));
+        assert!(!known_nodes.seen(&mut node, TimeStamp::from(200u64)));
+        let known = known_nodes
+            .get(&NodeId::from(1u64))
+            .expect("Node not known");
+        assert_eq!(known.first_seen, TimeStamp::from(100u64));
+        assert_eq!(known.last_heard, TimeStamp::from(100u64));
+    }
+
+    #[test]
+    fn seen_fills_in_missing_user() {
+        let mut known_nodes = KnownNodes::default();
+        known_nodes.seen(
+            &mut test_node(1, Some(test_user("Old Name"))),
+            TimeStamp::from(100u64),
+        );
+
+        let mut without_user = test_node(1, None);
+        known_nodes.seen(&mut without_user, TimeStamp::from(200u64));
+        assert_eq!(
+            without_user.user.map(|user| user.long_name),
+            Some("Old Name".to_string())
+        );
+    }
+
+    #[test]
+    fn seen_stores_new_user() {
+        let mut known_nodes = KnownNodes::default();
+        known_nodes.seen(
+            &mut test_node(1, Some(test_user("Old Name"))),
+            TimeStamp::from(100u64),
+        );
+        known_nodes.seen(
+            &mut test_node(1, Some(test_user("New Name"))),
+            TimeStamp::from(200u64),
+        );
+        let known = known_nodes
+            .get(&NodeId::from(1u64))
+            .expect("Node not known");
+        assert_eq!(
+            known
+                .node_info
+                .user
+                .as_ref()
+                .map(|user| user.long_name.as_str()),
+            Some("New Name")
+        );
+    }
+
+    #[test]
+    fn heard_updates_last_heard() {
+        let mut known_nodes = KnownNodes::default();
+        known_nodes.seen(&mut test_node(1, None), TimeStamp::from(100u64));
+        assert!(known_nodes.heard(NodeId::from(1u64), TimeStamp::from(500u64)));
+        assert!(!known_nodes.heard(NodeId::from(2u64), TimeStamp::from(500u64)));
+        let known = known_nodes
+            .get(&NodeId::from(1u64))
+            .expect("Node not known");
+        assert_eq!(known.first_seen, TimeStamp::from(100u64));
+        assert_eq!(known.last_heard, TimeStamp::from(500u64));
+    }
+
+    #[test]
+    fn set_ignored_stored() {
+        let mut known_nodes = KnownNodes::default();
+        known_nodes.seen(&mut test_node(1, None), TimeStamp::from(100u64));
+        known_nodes.set_ignored(NodeId::from(1u64), true);
+        assert!(
+            known_nodes
+                .get(&NodeId::from(1u64))
+                .is_some_and(|known| known.node_info.is_ignored)
+        );
+        known_nodes.set_ignored(NodeId::from(2u64), true);
+        assert!(known_nodes.get(&NodeId::from(2u64)).is_none());
+    }
+
+    #[test]
+    fn update_position_stored() {
+        let mut known_nodes = KnownNodes::default();
+        known_nodes.seen(&mut test_node(1, None), TimeStamp::from(100u64));
+        known_nodes.update_position(
+            NodeId::from(1u64),
+            &MCPosition {
+                latitude: 1.0,
+                longitude: 2.0,
+                ..Default::default()
+            },
+        );
+        let known = known_nodes
+            .get(&NodeId::from(1u64))
+            .expect("Node not known");
+        assert!(known.node_info.position.is_some());
+    }
+
+    #[test]
+    fn merge_keeps_earliest_and_latest() {
+        let mut known_nodes = KnownNodes::default();
+        known_nodes.seen(&mut test_node(1, None), TimeStamp::from(500u64));
+
+        let loaded = HashMap::from([
+            (
+                NodeId::from(1u64),
+                KnownNode {
+                    node_info: test_node(1, Some(test_user("Saved"))),
+                    first_seen: TimeStamp::from(100u64),
+                    last_heard: TimeStamp::from(200u64),
+                    pinned_key: None,
+                    changed_key: None,
+                },
+            ),
+            (
+                NodeId::from(2u64),
+                KnownNode {
+                    node_info: test_node(2, None),
+                    first_seen: TimeStamp::from(10u64),
+                    last_heard: TimeStamp::from(20u64),
+                    pinned_key: None,
+                    changed_key: None,
+                },
+            ),
+        ]);
+        known_nodes.merge(loaded);
+
+        let known = known_nodes
+            .get(&NodeId::from(1u64))
+            .expect("Node not known");
+        assert_eq!(known.first_seen, TimeStamp::from(100u64));
+        assert_eq!(known.last_heard, TimeStamp::from(500u64));
+        assert!(known.node_info.user.is_some());
+        assert!(known_nodes.get(&NodeId::from(2u64)).is_some());
+    }
+
+    #[test]
+    fn heard_ago_descriptions() {
+        let now = TimeStamp::from(10 * DAY_MS);
+        assert_eq!(heard_ago(now, now), "just now");
+        assert_eq!(
+            heard_ago(now, TimeStamp::from(10 * DAY_MS - 5 * MINUTE_MS)),
+            "5m ago"
+        );
+        assert_eq!(
+            heard_ago(now, TimeStamp::from(10 * DAY_MS - 3 * HOUR_MS)),
+            "3h ago"
+        );
+        assert_eq!(heard_ago(now, TimeStamp::from(DAY_MS)), "9d ago");
+    }
+
+    #[test]
+    fn node_status_from_last_heard() {
+        let hour = Duration::from_secs(60 * 60);
+        let day = 24 * hour;
+        let now = TimeStamp::from(10 * DAY_MS);
+        let ago = |ms: u128| TimeStamp::from(10 * DAY_MS - ms);
+        assert_eq!(node_status(now, ago(0), hour, day), NodeStatus::Online);
+        assert_eq!(
+            node_status(now, ago(HOUR_MS - 1), hour, day),
+            NodeStatus::Online
+        );
+        assert_eq!(node_status(now, ago(HOUR_MS), hour, day), NodeStatus::Stale);
+        assert_eq!(
+            node_status(now, ago(DAY_MS - 1), hour, day),
+            NodeStatus::Stale
+        );
+        assert_eq!(
+            node_status(now, ago(DAY_MS), hour, day),
+            NodeStatus::Offline
+        );
+        // Heard in the future, as clocks differ
+        let future = TimeStamp::from(11 * DAY_MS);
+        assert_eq!(node_status(now, future, hour, day), NodeStatus::Online);
+    }
+
+    #[test]
+    fn heard_in_future_is_just_now() {
+        assert_eq!(
+            heard_ago(TimeStamp::from(100u64), TimeStamp::from(200u64)),
+            "just now"
+        );
+    }
+
+    #[tokio::test]
+    async fn load_missing_file_is_empty() {
+        let tempdir = tempfile::Builder::new()
+            .prefix("meshchat")
+            .tempdir()
+            .expect("Could not create a temp dir");
+        let nodes = load(tempdir.path().join("missing.json"))
+            .await
+            .expect("Could not load nodes");
+        assert!(nodes.is_empty());
+    }
+
+    #[tokio::test]
+    async fn save_and_load_roundtrip() {
+        let tempdir = tempfile::Builder::new()
+            .prefix("meshchat")
+            .tempdir()
+            .expect("Could not create a temp dir");
+        let nodes_file = tempdir.path().join("nodes").join("device.json");
+        let nodes = vec![KnownNode {
+            node_info: test_node(42, Some(test_user("Saved"))),
+            first_seen: TimeStamp::from(100u64),
+            last_heard: TimeStamp::from(200u64),
+            pinned_key: None,
+            changed_key: None,
+        }];
+
+        save(nodes_file.clone(), nodes)
+            .await
+            .expect("Could not save nodes");
+        let loaded = load(nodes_file).await.expect("Could not load nodes");
+        let known = loaded.get(&NodeId::from(42u64)).expect("Node not loaded");
+        assert_eq!(known.first_seen, TimeStamp::from(100u64));
+        assert_eq!(known.last_heard, TimeStamp::from(200u64));
+        assert_eq!(
+            known
+                .node_info
+                .user
+                .as_ref()
+                .map(|user| user.long_name.as_str()),
+            Some("Saved")
+        );
+
+        // No temporary files should be left behind
+        let mut entries = tokio::fs::read_dir(tempdir.path().join("nodes"))
+            .await
+            .expect("Could not read dir");
+        let mut count = 0;
+        while entries
+            .next_entry()
+            .await
+            .expect("Could not read entry")
+            .is_some()
+        {
+            count += 1;
+        }
+        assert_eq!(count, 1);
+    }
+
+    #[tokio::test]
+    async fn load_corrupt_file_errors() {
+        let tempdir = tempfile::Builder::new()
+            .prefix("meshchat")
+            .tempdir()
+            .expect("Could not create a temp dir");
+        let nodes_file = tempdir.path().join("device.json");
+        let mut corrupt = File::create(&nodes_file)
+            .await
+            .expect("Could not create file");
+        corrupt
+            .write_all(b"[ not json")
+            .await
+            .expect("Could not write file");
+
+        assert!(load(nodes_file).await.is_err());
+    }
+
+    fn keyed_user(public_key: Vec<u8>) -> MCUser {
+        MCUser {
+            public_key,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn first_key_is_pinned() {
+        let mut known_nodes = KnownNodes::default();
+        let node_id = NodeId::from(1u64);
+        known_nodes.seen(&mut test_node(1, None), TimeStamp::from(100u64));
+        assert_eq!(known_nodes.check_key(node_id), KeyCheck::Unchanged);
+
+        known_nodes.update_user(node_id, &keyed_user(vec![1; 32]));
+        assert_eq!(known_nodes.check_key(node_id), KeyCheck::Pinned);
+        assert_eq!(known_nodes.check_key(node_id), KeyCheck::Unchanged);
+        assert!(!known_nodes.key_changed(node_id));
+    }
+
+    #[test]
+    fn changed_key_reported_once() {
+        let mut known_nodes = KnownNodes::default();
+        let node_id = NodeId::from(1u64);
+        known_nodes.seen(
+            &mut test_node(1, Some(keyed_user(vec![1; 32]))),
+            TimeStamp::from(100u64),
+        );
+        known_nodes.check_key(node_id);
+
+        known_nodes.update_user(node_id, &keyed_user(vec![2; 32]));
+        assert_eq!(known_nodes.check_key(node_id), KeyCheck::Changed);
+        assert_eq!(known_nodes.check_key(node_id), KeyCheck::Unchanged);
+        assert!(known_nodes.key_changed(node_id));
+
+        // An empty key, such as from older firmware, is not a change
+        known_nodes.update_user(node_id, &keyed_user(vec![]));
+        assert_eq!(known_nodes.check_key(node_id), KeyCheck::Unchanged);
+    }
+
+    #[test]
+    fn trust_changed_key() {
+        let mut known_nodes = KnownNodes::default();
+        let node_id = NodeId::from(1u64);
+        known_nodes.seen(
+            &mut test_node(1, Some(keyed_user(vec![1; 32]))),
+            TimeStamp::from(100u64),
+        );
+        known_nodes.check_key(node_id);
+        assert!(!known_nodes.trust_key(node_id));
+
+        known_nodes.update_user(node_id, &keyed_user(vec![2; 32]));
+        known_nodes.check_key(node_id);
+        assert!(known_nodes.trust_key(node_id));
+        assert!(!known_nodes.key_changed(node_id));
+        assert_eq!(
+            known_nodes
+                .get(&node_id)
+                .and_then(|known| known.pinned_key.clone()),
+            Some(vec![2; 32])
+        );
+        assert_eq!(known_nodes.check_key(node_id), KeyCheck::Unchanged);
+    }
+
+    #[test]
+    fn merge_keeps_key_pinned_before() {
+        let mut known_nodes = KnownNodes::default();
+        let node_id = NodeId::from(1u64);
+        // Pinned on this connection, before the saved nodes were loaded
+        known_nodes.seen(
+            &mut test_node(1, Some(keyed_user(vec![2; 32]))),
+            TimeStamp::from(500u64),
+        );
+        known_nodes.check_key(node_id);
+
+        let loaded = HashMap::from([(
+            node_id,
+            KnownNode {
+                node_info: test_node(1, None),
+                first_seen: TimeStamp::from(100u64),
+                last_heard: TimeStamp::from(200u64),
+                pinned_key: Some(vec![1; 32]),
+                changed_key: None,
+            },
+        )]);
+        known_nodes.merge(loaded);
+
+        let known = known_nodes.get(&node_id).expect("Node not known");
+        assert_eq!(known.pinned_key, Some(vec![1; 32]));
+        assert_eq!(known.changed_key, Some(vec![2; 32]));
+    }
+
+    #[test]
+    fn old_nodes_file_loads_without_keys() {
+        let json = r#"{"node_info":{"node_id":1,"user":null,"position":null,"is_ignored":false},"first_seen":100,"last_heard":200}"#;
+        let known: KnownNode = serde_json::from_str(json).expect("Could not load node");
+        assert!(known.pinned_key.is_none());
+        assert!(known.changed_key.is_none());
+    }
+}
@@ -4,21 +4,34 @@
 #[cfg(not(any(feature = "meshtastic", feature = "meshcore")))]
 compile_error!("At least one of 'meshtastic' or 'meshcore' features must be enabled");
 
-#[cfg(not(any(feature = "bluetooth", feature = "tcp")))]
-compile_error!("At least one of 'bluetooth' or 'tcp' features must be enabled");
+#[cfg(not(any(feature = "bluetooth", feature = "tcp", feature = "serial")))]
+compile_error!("At least one of 'bluetooth', 'tcp' or 'serial' features must be enabled");
 
 use crate::meshchat::MeshChat;
 use iced::window;
 
 mod meshchat;
 
+mod capture;
+mod channel_sharing;
 mod config;
 mod conversation;
 mod device;
 mod device_list;
 mod discovery;
+mod export;
+mod geo;
+mod history;
+mod known_nodes;
+mod map;
 mod message;
+mod outbox;
+mod store;
 mod styles;
+mod telemetry;
+mod traceroute;
+mod tracks;
+mod waypoint;
 mod widgets;
 
 mod conversation_id;
@@ -31,6 +44,8 @@ mod ui_test;
 mod meshc;
 #[cfg(feature = "meshtastic")]
 mod mesht;
+#[cfg(feature = "sim")]
+mod sim;
 
 #[rustfmt::skip]
 /// Icons generated as a font using iced_fontello
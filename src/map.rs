@@ -0,0 +1,1044 @@
+//! A map of the positions of nodes, drawn on base map tiles read from local files so it works
+//! without an internet connection, or on a plain grid when there are none
+
+use crate::Message;
+use crate::Message::{DeviceViewEvent, MapTilesSelected};
+use crate::conversation_id::ConversationId::Node;
+use crate::conversation_id::{ConversationId, NodeId};
+use crate::device::DeviceMessage::{LoadMapTiles, MapTilesLoaded};
+use crate::meshchat::MCPosition;
+use crate::styles::{COLOR_BLUE, COLOR_GREEN, COLOR_RED};
+use iced::widget::canvas::{Action, Canvas, Event, Frame, Geometry, Path, Program, Stroke, Text};
+use iced::widget::image::Handle;
+use iced::{
+    Color, Element, Fill, Pixels, Point, Rectangle, Renderer, Size, Task, Theme, Vector, mouse,
+    window,
+};
+use rusqlite::{Connection, OpenFlags};
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::f64::consts::PI;
+use std::path::{Path as FilePath, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// The width and height of a tile, in pixels
+const TILE_SIZE: f64 = 256.0;
+/// How far out and in the map can be zoomed, in tile zoom levels
+const MIN_ZOOM: f64 = 1.0;
+const MAX_ZOOM: f64 = 18.0;
+/// How far in the map is zoomed when there is only one node, or they are all in one place
+const CLOSE_ZOOM: f64 = 14.0;
+/// The farthest north and south Web Mercator maps go
+const MAX_LATITUDE: f64 = 85.051_128_78;
+/// How far, in pixels, the pointer can be from a node and still be over it
+const NODE_RADIUS: f32 = 6.0;
+const HOVER_DISTANCE: f32 = 10.0;
+/// How many tile images to keep, so panning back and forth doesn't read them again
+const TILE_CACHE_SIZE: usize = 512;
+/// The image types tried, in order, for the tiles in a tile directory
+const TILE_EXTENSIONS: [&str; 4] = ["png", "jpg", "jpeg", "webp"];
+
+/// A point in Web Mercator world coordinates, from (0, 0) at the top left (north west) of the
+/// world to (1, 1) at the bottom right
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WorldPoint {
+    pub x: f64,
+    pub y: f64,
+}
+
+impl From<&MCPosition> for WorldPoint {
+    fn from(position: &MCPosition) -> Self {
+        let latitude = position
+            .latitude
+            .clamp(-MAX_LATITUDE, MAX_LATITUDE)
+            .to_radians();
+        WorldPoint {
+            x: (position.longitude + 180.0) / 360.0,
+            y: (1.0 - (latitude.tan() + 1.0 / latitude.cos()).ln() / PI) / 2.0,
+        }
+    }
+}
+
+/// A tile of the map, at a zoom level, as numbered in "slippy map" tile names
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TileId {
+    pub zoom: u8,
+    pub x: u32,
+    pub y: u32,
+}
+
+/// The part of the world shown, and how far in it's zoomed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Viewport {
+    center: WorldPoint,
+    zoom: f64,
+}
+
+impl Viewport {
+    /// The size of the whole world, in pixels, at this zoom
+    fn scale(&self) -> f64 {
+        TILE_SIZE * 2f64.powf(self.zoom)
+    }
+
+    /// Where a point in the world is on a map of `size`
+    fn screen_point(&self, point: WorldPoint, size: Size) -> Point {
+        let scale = self.scale();
+        Point::new(
+            ((point.x - self.center.x) * scale + f64::from(size.width) / 2.0) as f32,
+            ((point.y - self.center.y) * scale + f64::from(size.height) / 2.0) as f32,
+        )
+    }
+
+    /// Where a point on a map of `size` is in the world
+    fn world_point(&self, point: Point, size: Size) -> WorldPoint {
+        let scale = self.scale();
+        WorldPoint {
+            x: self.center.x + (f64::from(point.x) - f64::from(size.width) / 2.0) / scale,
+            y: self.center.y + (f64::from(point.y) - f64::from(size.height) / 2.0) / scale,
+        }
+    }
+
+    /// Move the map by `delta` pixels, as when it is dragged
+    fn pan(&mut self, delta: Vector) {
+        let scale = self.scale();
+        self.center.x -= f64::from(delta.x) / scale;
+        self.center.y = (self.center.y - f64::from(delta.y) / scale).clamp(0.0, 1.0);
+    }
+
+    /// Zoom in (or out, when `levels` is negative), keeping what is at `point` where it is
+    fn zoom_about(&mut self, point: Point, levels: f64, size: Size) {
+        let before = self.world_point(point, size);
+        self.zoom = (self.zoom + levels).clamp(MIN_ZOOM, MAX_ZOOM);
+        let after = self.world_point(point, size);
+        self.center.x += before.x - after.x;
+        self.center.y += before.y - after.y;
+    }
+
+    /// The view that shows all `points` on a map of `size`
+    fn fit(points: impl Iterator<Item = WorldPoint>, size: Size) -> Viewport {
+        let (mut min, mut max) = (WorldPoint { x: 1.0, y: 1.0 }, WorldPoint { x: 0.0, y: 0.0 });
+        for point in points {
+            min = WorldPoint {
+                x: min.x.min(point.x),
+                y: min.y.min(point.y),
+            };
+            max = WorldPoint {
+                x: max.x.max(point.x),
+                y: max.y.max(point.y),
+            };
+        }
+
+        if min.x > max.x {
+            return Viewport {
+                center: WorldPoint { x: 0.5, y: 0.5 },
+                zoom: MIN_ZOOM,
+            };
+        }
+
+        let center = WorldPoint {
+            x: (min.x + max.x) / 2.0,
+            y: (min.y + max.y) / 2.0,
+        };
+        // Leave a margin of a quarter of the map around the nodes
+        let span = (max.x - min.x).max(max.y - min.y);
+        let pixels = f64::from(size.width.min(size.height)) * 0.75;
+        let zoom = if span > 0.0 && pixels > 0.0 {
+            (pixels / (span * TILE_SIZE)).log2().min(CLOSE_ZOOM)
+        } else {
+            CLOSE_ZOOM
+        };
+        Viewport {
+            center,
+            zoom: zoom.clamp(MIN_ZOOM, MAX_ZOOM),
+        }
+    }
+
+    /// The tiles that cover a map of `size`, with where each is drawn
+    fn visible_tiles(&self, size: Size) -> Vec<(TileId, Rectangle)> {
+        let level = self.zoom.floor();
+        let tiles_across = 2f64.powf(level);
+        let tile_pixels = (self.scale() / tiles_across) as f32;
+        let top_left = self.world_point(Point::ORIGIN, size);
+        let bottom_right = self.world_point(Point::new(size.width, size.height), size);
+
+        let first_x = (top_left.x * tiles_across).floor().max(0.0);
+        let last_x = (bottom_right.x * tiles_across).ceil().min(tiles_across);
+        let first_y = (top_left.y * tiles_across).floor().max(0.0);
+        let last_y = (bottom_right.y * tiles_across).ceil().min(tiles_across);
+
+        let mut tiles = vec![];
+        let mut y = first_y;
+        while y < last_y {
+            let mut x = first_x;
+            while x < last_x {
+                let position = self.screen_point(
+                    WorldPoint {
+                        x: x / tiles_across,
+                        y: y / tiles_across,
+                    },
+                    size,
+                );
+                tiles.push((
+                    TileId {
+                        zoom: level as u8,
+                        x: x as u32,
+                        y: y as u32,
+                    },
+                    Rectangle::new(position, Size::new(tile_pixels, tile_pixels)),
+                ));
+                x += 1.0;
+            }
+            y += 1.0;
+        }
+        tiles
+    }
+}
+
+/// Where the base map tiles are read from
+pub enum TileSource {
+    /// An MBTiles file, the SQLite database of tiles that map tools export
+    MBTiles(Connection),
+    /// A directory of tiles, laid out as `{zoom}/{x}/{y}.png`
+    Directory(PathBuf),
+}
+
+impl TileSource {
+    /// Open the MBTiles file, or the directory of tiles, at `path`
+    pub fn open(path: &FilePath) -> Result<Self, String> {
+        if path.is_dir() {
+            return Ok(TileSource::Directory(path.to_path_buf()));
+        }
+
+        let connection = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+            .map_err(|e| format!("'{}' could not be opened: {e}", path.display()))?;
+        connection
+            .prepare_cached(MBTILES_QUERY)
+            .map_err(|e| format!("'{}' is not an MBTiles file: {e}", path.display()))?;
+        Ok(TileSource::MBTiles(connection))
+    }
+
+    /// The image data of a tile, if there is one
+    fn tile(&self, tile: TileId) -> Option<Vec<u8>> {
+        match self {
+            TileSource::MBTiles(connection) => {
+                // MBTiles numbers rows from the south, as TMS does
+                let row = (1u32 << tile.zoom) - 1 - tile.y;
+                connection
+                    .prepare_cached(MBTILES_QUERY)
+                    .and_then(|mut statement| {
+                        statement.query_row((tile.zoom, tile.x, row), |row| row.get(0))
+                    })
+                    .ok()
+            }
+            TileSource::Directory(directory) => {
+                let column = directory
+                    .join(tile.zoom.to_string())
+                    .join(tile.x.to_string());
+                TILE_EXTENSIONS.iter().find_map(|extension| {
+                    std::fs::read(column.join(format!("{}.{extension}", tile.y))).ok()
+                })
+            }
+        }
+    }
+}
+
+/// Ask the user to choose an MBTiles file, or a `folder` of tiles, to draw the map on
+pub fn choose_tiles(folder: bool) -> Task<Message> {
+    Task::perform(
+        async move {
+            let dialog = rfd::AsyncFileDialog::new().set_title("Choose Map Tiles");
+            let file_handle = if folder {
+                dialog.pick_folder().await
+            } else {
+                dialog.add_filter("MBTiles", &["mbtiles"]).pick_file().await
+            };
+            file_handle.map(|file_handle| file_handle.path().to_path_buf())
+        },
+        |path| match path {
+            Some(path) => MapTilesSelected(Some(path)),
+            None => Message::None,
+        },
+    )
+}
+
+/// The query for one tile in an MBTiles file
+const MBTILES_QUERY: &str =
+    "SELECT tile_data FROM tiles WHERE zoom_level = ?1 AND tile_column = ?2 AND tile_row = ?3";
+
+/// The base map tiles from the file or directory at `path`, with the images of the ones read
+/// so far. Tiles are read in the background, so drawing the map never waits for the disk
+pub struct Tiles {
+    path: PathBuf,
+    source: Arc<Mutex<TileSource>>,
+    images: HashMap<TileId, Option<Handle>>,
+    /// The tiles asked for that have not been read yet, so they are only asked for once
+    loading: RefCell<HashSet<TileId>>,
+}
+
+impl Tiles {
+    pub fn new(path: PathBuf, source: TileSource) -> Self {
+        Tiles {
+            path,
+            source: Arc::new(Mutex::new(source)),
+            images: HashMap::new(),
+            loading: RefCell::new(HashSet::new()),
+        }
+    }
+
+    /// The MBTiles file, or directory of tiles, read from
+    pub fn path(&self) -> &FilePath {
+        &self.path
+    }
+
+    /// The image of a tile, if it has been read and there is one
+    fn image(&self, tile: TileId) -> Option<Handle> {
+        self.images.get(&tile).cloned().flatten()
+    }
+
+    /// The tiles of `tiles` that have not been read, or asked for, yet. They are noted as
+    /// being read, so the next call doesn't return them again
+    fn missing(&self, tiles: impl Iterator<Item = TileId>) -> Vec<TileId> {
+        let mut loading = self.loading.borrow_mut();
+        tiles
+            .filter(|tile| !self.images.contains_key(tile) && loading.insert(*tile))
+            .collect()
+    }
+
+    /// A task to read `tiles` from the source, without blocking the UI, that returns their
+    /// images in a [MapTilesLoaded] message
+    pub fn load(&self, tiles: Vec<TileId>) -> Task<Message> {
+        let path = self.path.clone();
+        let source = self.source.clone();
+        Task::perform(
+            async move {
+                tokio::task::spawn_blocking(move || {
+                    let Ok(source) = source.lock() else {
+                        return vec![];
+                    };
+                    tiles
+                        .into_iter()
+                        .map(|tile| (tile, source.tile(tile).map(Handle::from_bytes)))
+                        .collect()
+                })
+                .await
+                .unwrap_or_default()
+            },
+            move |images| DeviceViewEvent(MapTilesLoaded(path.clone(), images)),
+        )
+    }
+
+    /// Add the images of tiles read by [Tiles::load] from the source at `path`, ignoring
+    /// them if the tiles have been changed since
+    pub fn loaded(&mut self, path: &FilePath, images: Vec<(TileId, Option<Handle>)>) {
+        if path != self.path {
+            return;
+        }
+        let loading = self.loading.get_mut();
+        for (tile, image) in images {
+            if self.images.len() >= TILE_CACHE_SIZE {
+                self.images.clear();
+            }
+            loading.remove(&tile);
+            self.images.insert(tile, image);
+        }
+    }
+}
+
+/// A node shown on the map
+#[derive(Debug, Clone, PartialEq)]
+pub struct MapNode {
+    pub node_id: Option<NodeId>,
+    /// The name shown when the pointer is over the node
+    pub label: String,
+    pub position: WorldPoint,
+    /// This is my own node
+    pub mine: bool,
+}
+
+/// The state of the map between events: where it's looking, and any drag underway
+#[derive(Debug, Default)]
+pub struct MapState {
+    /// None until the user moves the map, so it fits all the nodes until then
+    viewport: Option<Viewport>,
+    /// Where the pointer was when last seen during a drag
+    dragging: Option<Point>,
+    /// The pointer moved since the button was pressed, so releasing it is not a click
+    dragged: bool,
+    /// The position the map was centred on when the user moved it, so the viewport is
+    /// forgotten when another position is to be shown
+    focus: Option<WorldPoint>,
+}
+
+/// A map of `nodes` that can be dragged and zoomed, on which clicking a node selects the
+/// conversation with it. If there is a `focus`, the map is centred on it and it is marked
+pub struct NodeMap<'a> {
+    nodes: Vec<MapNode>,
+    tiles: Option<&'a Tiles>,
+    focus: Option<WorldPoint>,
+    select: fn(ConversationId) -> Message,
+}
+
+impl<'a> NodeMap<'a> {
+    pub fn new(
+        nodes: Vec<MapNode>,
+        tiles: Option<&'a Tiles>,
+        focus: Option<WorldPoint>,
+        select: fn(ConversationId) -> Message,
+    ) -> Self {
+        NodeMap {
+            nodes,
+            tiles,
+            focus,
+            select,
+        }
+    }
+
+    pub fn view(self) -> Element<'a, Message> {
+        Canvas::new(self).width(Fill).height(Fill).into()
+    }
+
+    /// Where the map is looking: centred on the focus, or fitting all the nodes in, until the
+    /// user moves it
+    fn viewport(&self, state: &MapState, size: Size) -> Viewport {
+        match (state.viewport, self.focus) {
+            (Some(viewport), focus) if focus == state.focus => viewport,
+            (_, Some(center)) => Viewport {
+                center,
+                zoom: CLOSE_ZOOM,
+            },
+            (_, None) => Viewport::fit(self.nodes.iter().map(|node| node.position), size),
+        }
+    }
+
+    /// Keep where the user moved the map to
+    fn move_to(&self, state: &mut MapState, viewport: Viewport) {
+        state.viewport = Some(viewport);
+        state.focus = self.focus;
+    }
+
+    /// The node at `point` on the map, the closest if there are several
+    fn node_at(&self, viewport: &Viewport, size: Size, point: Point) -> Option<&MapNode> {
+        self.nodes
+            .iter()
+            .map(|node| {
+                (
+                    node,
+                    viewport.screen_point(node.position, size).distance(point),
+                )
+            })
+            .filter(|(_, distance)| *distance <= HOVER_DISTANCE)
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(node, _)| node)
+    }
+}
+
+impl Program<Message> for NodeMap<'_> {
+    type State = MapState;
+
+    fn update(
+        &self,
+        state: &mut MapState,
+        event: &Event,
+        bounds: Rectangle,
+        cursor: mouse::Cursor,
+    ) -> Option<Action<Message>> {
+        let size = bounds.size();
+        let position = cursor.position_in(bounds);
+
+        match event {
+            // Ask for the tiles about to be drawn that haven't been read yet
+            Event::Window(window::Event::RedrawRequested(_)) => {
+                let tiles = self.tiles?;
+                let visible = self.viewport(state, size).visible_tiles(size);
+                let missing = tiles.missing(visible.into_iter().map(|(tile, _)| tile));
+                (!missing.is_empty())
+                    .then(|| Action::publish(DeviceViewEvent(LoadMapTiles(missing))))
+            }
+            Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Left)) => {
+                state.dragging = Some(position?);
+                state.dragged = false;
+                Some(Action::capture())
+            }
+            Event::Mouse(mouse::Event::CursorMoved { .. }) => {
+                if let Some(from) = state.dragging
+                    && let Some(to) = position
+                {
+                    let mut viewport = self.viewport(state, size);
+                    viewport.pan(to - from);
+                    self.move_to(state, viewport);
+                    state.dragging = Some(to);
+                    state.dragged = true;
+                }
+                // Redraw to show the name of any node the pointer is now over
+                Some(Action::request_redraw())
+            }
+            Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Left)) => {
+                let clicked = state.dragging.take().is_some() && !state.dragged;
+                match (clicked, position) {
+                    (true, Some(position)) => self
+                        .node_at(&self.viewport(state, size), size, position)
+                        .filter(|node| !node.mine)
+                        .and_then(|node| node.node_id)
+                        .map(|node_id| Action::publish((self.select)(Node(node_id))).and_capture()),
+                    _ => None,
+                }
+            }
+            Event::Mouse(mouse::Event::WheelScrolled { delta }) => {
+                let levels = match delta {
+                    mouse::ScrollDelta::Lines { y, .. } => f64::from(*y) / 2.0,
+                    mouse::ScrollDelta::Pixels { y, .. } => f64::from(*y) / 200.0,
+                };
+                let mut viewport = self.viewport(state, size);
+                viewport.zoom_about(position?, levels, size);
+                self.move_to(state, viewport);
+                Some(Action::request_redraw().and_capture())
+            }
+            _ => None,
+        }
+    }
+
+    fn draw(
+        &self,
+        state: &MapState,
+        renderer: &Renderer,
+        theme: &Theme,
+        bounds: Rectangle,
+        cursor: mouse::Cursor,
+    ) -> Vec<Geometry> {
+        let size = bounds.size();
+        let viewport = self.viewport(state, size);
+        let palette = theme.extended_palette();
+        let mut frame = Frame::new(renderer, size);
+
+        frame.fill_rectangle(Point::ORIGIN, size, palette.background.base.color);
+        let grid = Stroke::default()
+            .with_color(palette.background.strong.color)
+            .with_width(1.0);
+        for (tile, area) in viewport.visible_tiles(size) {
+            match self.tiles.and_then(|tiles| tiles.image(tile)) {
+                Some(image) => frame.draw_image(area, &image),
+                None => frame.stroke_rectangle(area.position(), area.size(), grid),
+            }
+        }
+
+        let hovered = cursor
+            .position_in(bounds)
+            .and_then(|point| self.node_at(&viewport, size, point));
+        for node in &self.nodes {
+            let center = viewport.screen_point(node.position, size);
+            let color = if node.mine { COLOR_GREEN } else { COLOR_BLUE };
+            frame.fill(&Path::circle(center, NODE_RADIUS), color);
+            frame.stroke(
+                &Path::circle(center, NODE_RADIUS),
+                Stroke::default().with_color(Color::WHITE).with_width(1.5),
+            );
+        }
+
+        if let Some(focus) = self.focus {
+            frame.stroke(
+                &Path::circle(viewport.screen_point(focus, size), NODE_RADIUS + 4.0),
+                Stroke::default().with_color(COLOR_RED).with_width(2.0),
+            );
+        }
+
+        if let Some(node) = hovered {
+            let center = viewport.screen_point(node.position, size);
+            frame.fill_text(Text {
+                content: node.label.clone(),
+                position: center + Vector::new(NODE_RADIUS + 4.0, -NODE_RADIUS - 14.0),
+                color: palette.background.base.text,
+                size: Pixels(14.0),
+                ..Default::default()
+            });
+        }
+
+        if self.tiles.is_none() {
+            frame.fill_text(Text {
+                content: "No map tiles, choose an MBTiles file or tile folder in Settings".into(),
+                position: Point::new(8.0, size.height - 22.0),
+                color: palette.background.strong.text,
+                size: Pixels(12.0),
+                ..Default::default()
+            });
+        }
+
+        vec![frame.into_geometry()]
+    }
+
+    fn mouse_interaction(
+        &self,
+        state: &MapState,
+        bounds: Rectangle,
+        cursor: mouse::Cursor,
+    ) -> mouse::Interaction {
+        let Some(point) = cursor.position_in(bounds) else {
+            return mouse::Interaction::default();
+        };
+        if state.dragging.is_some() && state.dragged {
+            return mouse::Interaction::Grabbing;
+        }
+        match self.node_at(&self.viewport(state, bounds.size()), bounds.size(), point) {
+            Some(node) if !node.mine => mouse::Interaction::Pointer,
+            _ => mouse::Interaction::Grab,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::device::DeviceMessage::ShowChannel;
+
+    const SIZE: Size = Size::new(512.0, 512.0);
+
+    fn position(latitude: f64, longitude: f64) -> MCPosition {
+        MCPosition {
+            latitude,
+            longitude,
+            ..Default::default()
+        }
+    }
+
+    fn node(node_id: u64, latitude: f64, longitude: f64, mine: bool) -> MapNode {
+        MapNode {
+            node_id: Some(NodeId::from(node_id)),
+            label: format!("Node {node_id}"),
+            position: WorldPoint::from(&position(latitude, longitude)),
+            mine,
+        }
+    }
+
+    fn select(conversation_id: ConversationId) -> Message {
+        DeviceViewEvent(ShowChannel(Some(conversation_id)))
+    }
+
+    fn tempdir() -> tempfile::TempDir {
+        tempfile::Builder::new()
+            .prefix("meshchat")
+            .tempdir()
+            .expect("Could not create a temp dir")
+    }
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    #[test]
+    fn test_world_point() {
+        let center = WorldPoint::from(&position(0.0, 0.0));
+        assert!(close(center.x, 0.5) && close(center.y, 0.5));
+        let north_west = WorldPoint::from(&position(MAX_LATITUDE, -180.0));
+        assert!(close(north_west.x, 0.0) && north_west.y.abs() < 1e-6);
+        // Beyond the edge of the map is clamped to it
+        let north_pole = WorldPoint::from(&position(90.0, 180.0));
+        assert!(close(north_pole.x, 1.0) && north_pole.y.abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_screen_and_world_points() {
+        let viewport = Viewport {
+            center: WorldPoint { x: 0.3, y: 0.6 },
+            zoom: 5.5,
+        };
+        assert_eq!(
+            viewport.screen_point(viewport.center, SIZE),
+            Point::new(256.0, 256.0)
+        );
+        let point = Point::new(10.0, 400.0);
+        let back = viewport.screen_point(viewport.world_point(point, SIZE), SIZE);
+        assert!(back.distance(point) < 0.01);
+    }
+
+    #[test]
+    fn test_pan() {
+        let mut viewport = Viewport {
+            center: WorldPoint { x: 0.5, y: 0.5 },
+            zoom: 1.0,
+        };
+        viewport.pan(Vector::new(128.0, -128.0));
+        assert!(close(viewport.center.x, 0.25) && close(viewport.center.y, 0.75));
+    }
+
+    #[test]
+    fn test_zoom_about_keeps_point_still() {
+        let mut viewport = Viewport {
+            center: WorldPoint { x: 0.5, y: 0.5 },
+            zoom: 3.0,
+        };
+        let point = Point::new(100.0, 50.0);
+        let before = viewport.world_point(point, SIZE);
+        viewport.zoom_about(point, 1.5, SIZE);
+        assert!(close(viewport.zoom, 4.5));
+        let after = viewport.world_point(point, SIZE);
+        assert!(close(before.x, after.x) && close(before.y, after.y));
+
+        viewport.zoom_about(point, 100.0, SIZE);
+        assert!(close(viewport.zoom, MAX_ZOOM));
+        viewport.zoom_about(point, -100.0, SIZE);
+        assert!(close(viewport.zoom, MIN_ZOOM));
+    }
+
+    #[test]
+    fn test_fit_nothing() {
+        let viewport = Viewport::fit(std::iter::empty(), SIZE);
+        assert_eq!(viewport.center, WorldPoint { x: 0.5, y: 0.5 });
+        assert!(close(viewport.zoom, MIN_ZOOM));
+    }
+
+    #[test]
+    fn test_fit_one_node() {
+        let point = WorldPoint::from(&position(46.5, 6.6));
+        let viewport = Viewport::fit(std::iter::once(point), SIZE);
+        assert_eq!(viewport.center, point);
+        assert!(close(viewport.zoom, CLOSE_ZOOM));
+    }
+
+    #[test]
+    fn test_fit_shows_all_nodes() {
+        let points = [
+            WorldPoint::from(&position(46.5, 6.6)),
+            WorldPoint::from(&position(47.4, 8.5)),
+            WorldPoint::from(&position(46.2, 6.1)),
+        ];
+        let viewport = Viewport::fit(points.into_iter(), SIZE);
+        for point in points {
+            let on_screen = viewport.screen_point(point, SIZE);
+            assert!(
+                Rectangle::with_size(SIZE).contains(on_screen),
+                "{on_screen:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_visible_tiles() {
+        let viewport = Viewport {
+            center: WorldPoint { x: 0.5, y: 0.5 },
+            zoom: 1.0,
+        };
+        let tiles = viewport.visible_tiles(SIZE);
+        assert_eq!(tiles.len(), 4);
+        assert!(tiles.contains(&(
+            TileId {
+                zoom: 1,
+                x: 1,
+                y: 1
+            },
+            Rectangle::new(Point::new(256.0, 256.0), Size::new(256.0, 256.0))
+        )));
+    }
+
+    #[test]
+    fn test_visible_tiles_between_levels() {
+        let viewport = Viewport {
+            center: WorldPoint { x: 0.5, y: 0.5 },
+            zoom: 10.5,
+        };
+        let tiles = viewport.visible_tiles(SIZE);
+        assert!(
+            tiles
+                .iter()
+                .all(|(tile, area)| tile.zoom == 10
+                    && (area.width - 256.0 * 2f32.sqrt()).abs() < 0.01)
+        );
+        // Tiles off the top and bottom of the world are not drawn
+        let edge = Viewport {
+            center: WorldPoint { x: 0.5, y: 0.0 },
+            zoom: 2.0,
+        };
+        assert!(edge.visible_tiles(SIZE).iter().all(|(tile, _)| tile.y < 4));
+    }
+
+    #[test]
+    fn test_tile_directory() {
+        let tempdir = tempdir();
+        let column = tempdir.path().join("3").join("2");
+        std::fs::create_dir_all(&column).expect("Could not create tile directory");
+        std::fs::write(column.join("5.jpg"), [1, 2, 3]).expect("Could not write tile");
+
+        let source = TileSource::open(tempdir.path()).expect("Could not open tiles");
+        assert_eq!(
+            source.tile(TileId {
+                zoom: 3,
+                x: 2,
+                y: 5
+            }),
+            Some(vec![1, 2, 3])
+        );
+        assert_eq!(
+            source.tile(TileId {
+                zoom: 3,
+                x: 2,
+                y: 6
+            }),
+            None
+        );
+    }
+
+    #[test]
+    fn test_mbtiles() {
+        let tempdir = tempdir();
+        let path = tempdir.path().join("map.mbtiles");
+        let connection = Connection::open(&path).expect("Could not create MBTiles");
+        connection
+            .execute_batch(
+                "CREATE TABLE tiles (zoom_level INTEGER, tile_column INTEGER, tile_row INTEGER, \
+                 tile_data BLOB); INSERT INTO tiles VALUES (2, 1, 0, x'0102');",
+            )
+            .expect("Could not add tile");
+        drop(connection);
+
+        let source = TileSource::open(&path).expect("Could not open tiles");
+        // Row 0 is the southernmost, which is y 3 at zoom level 2
+        assert_eq!(
+            source.tile(TileId {
+                zoom: 2,
+                x: 1,
+                y: 3
+            }),
+            Some(vec![1, 2])
+        );
+        assert_eq!(
+            source.tile(TileId {
+                zoom: 2,
+                x: 1,
+                y: 0
+            }),
+            None
+        );
+    }
+
+    #[test]
+    fn test_not_tiles() {
+        let tempdir = tempdir();
+        let path = tempdir.path().join("notes.txt");
+        std::fs::write(&path, "Not a database").expect("Could not write file");
+        assert!(TileSource::open(&path).is_err());
+        assert!(TileSource::open(&tempdir.path().join("missing.mbtiles")).is_err());
+    }
+
+    fn tile(zoom: u8, x: u32, y: u32) -> TileId {
+        TileId { zoom, x, y }
+    }
+
+    #[test]
+    fn test_missing_tiles_asked_for_once() {
+        let tempdir = tempdir();
+        let tiles = Tiles::new(
+            tempdir.path().to_path_buf(),
+            TileSource::open(tempdir.path()).expect("Could not open tiles"),
+        );
+        let wanted = [tile(1, 0, 0), tile(1, 1, 0)];
+        assert_eq!(tiles.missing(wanted.into_iter()), wanted.to_vec());
+        assert!(tiles.missing(wanted.into_iter()).is_empty());
+        assert!(tiles.image(wanted[0]).is_none());
+    }
+
+    #[test]
+    fn test_loaded_tiles_cached() {
+        let tempdir = tempdir();
+        let mut tiles = Tiles::new(
+            tempdir.path().to_path_buf(),
+            TileSource::open(tempdir.path()).expect("Could not open tiles"),
+        );
+        let image = Handle::from_bytes(vec![1, 2, 3]);
+        let _ = tiles.missing([tile(1, 0, 0), tile(1, 1, 0)].into_iter());
+        tiles.loaded(
+            tempdir.path(),
+            vec![(tile(1, 0, 0), Some(image.clone())), (tile(1, 1, 0), None)],
+        );
+        assert_eq!(tiles.image(tile(1, 0, 0)), Some(image));
+        assert!(tiles.image(tile(1, 1, 0)).is_none());
+        assert!(tiles.loading.borrow().is_empty());
+        // A tile without an image is not asked for again
+        assert!(tiles.missing([tile(1, 1, 0)].into_iter()).is_empty());
+
+        // Tiles read from tiles chosen before are dropped
+        tiles.loaded(
+            &tempdir.path().join("old.mbtiles"),
+            vec![(tile(1, 0, 1), Some(Handle::from_bytes(vec![4])))],
+        );
+        assert!(tiles.image(tile(1, 0, 1)).is_none());
+    }
+
+    #[test]
+    fn test_redraw_asks_for_missing_tiles() {
+        let tempdir = tempdir();
+        let tiles = Tiles::new(
+            tempdir.path().to_path_buf(),
+            TileSource::open(tempdir.path()).expect("Could not open tiles"),
+        );
+        let map = NodeMap::new(vec![node(1, 46.5, 6.6, false)], Some(&tiles), None, select);
+        let mut state = MapState::default();
+        let redraw = |state: &mut MapState| {
+            map.update(
+                state,
+                &Event::Window(window::Event::RedrawRequested(std::time::Instant::now())),
+                Rectangle::with_size(SIZE),
+                mouse::Cursor::Unavailable,
+            )
+            .and_then(|action| action.into_inner().0)
+        };
+
+        let visible = map.viewport(&state, SIZE).visible_tiles(SIZE);
+        assert!(matches!(
+            redraw(&mut state),
+            Some(DeviceViewEvent(LoadMapTiles(missing))) if missing.len() == visible.len()
+        ));
+        // They are being read, so are not asked for again
+        assert!(redraw(&mut state).is_none());
+    }
+
+    fn map_around(nodes: Vec<MapNode>) -> NodeMap<'static> {
+        NodeMap::new(nodes, None, None, select)
+    }
+
+    fn event(
+        map: &NodeMap,
+        state: &mut MapState,
+        event: mouse::Event,
+        at: Point,
+    ) -> Option<Action<Message>> {
+        map.update(
+            state,
+            &Event::Mouse(event),
+            Rectangle::with_size(SIZE),
+            mouse::Cursor::Available(at),
+        )
+    }
+
+    #[test]
+    fn test_click_node_selects_it() {
+        let map = map_around(vec![node(1, 46.5, 6.6, false), node(2, 47.4, 8.5, true)]);
+        let mut state = MapState::default();
+        let viewport = map.viewport(&state, SIZE);
+
+        let at = viewport.screen_point(map.nodes[0].position, SIZE);
+        let _ = event(
+            &map,
+            &mut state,
+            mouse::Event::ButtonPressed(mouse::Button::Left),
+            at,
+        );
+        let published = event(
+            &map,
+            &mut state,
+            mouse::Event::ButtonReleased(mouse::Button::Left),
+            at,
+        )
+        .and_then(|action| action.into_inner().0);
+        assert!(matches!(
+            published,
+            Some(DeviceViewEvent(ShowChannel(Some(Node(node_id))))) if node_id == NodeId::from(1u64)
+        ));
+
+        // Clicking my own node does not start a conversation
+        let at = viewport.screen_point(map.nodes[1].position, SIZE);
+        let _ = event(
+            &map,
+            &mut state,
+            mouse::Event::ButtonPressed(mouse::Button::Left),
+            at,
+        );
+        assert!(
+            event(
+                &map,
+                &mut state,
+                mouse::Event::ButtonReleased(mouse::Button::Left),
+                at
+            )
+            .is_none()
+        );
+    }
+
+    #[test]
+    fn test_drag_pans_and_does_not_select() {
+        let map = map_around(vec![node(1, 46.5, 6.6, false), node(2, 47.4, 8.5, false)]);
+        let mut state = MapState::default();
+        let fitted = map.viewport(&state, SIZE);
+
+        let at = fitted.screen_point(map.nodes[0].position, SIZE);
+        let _ = event(
+            &map,
+            &mut state,
+            mouse::Event::ButtonPressed(mouse::Button::Left),
+            at,
+        );
+        let _ = event(
+            &map,
+            &mut state,
+            mouse::Event::CursorMoved {
+                position: at + Vector::new(20.0, 0.0),
+            },
+            at + Vector::new(20.0, 0.0),
+        );
+        let released = event(
+            &map,
+            &mut state,
+            mouse::Event::ButtonReleased(mouse::Button::Left),
+            at + Vector::new(20.0, 0.0),
+        );
+        assert!(released.is_none());
+        let panned = state.viewport.expect("Viewport set by dragging");
+        assert!(panned.center.x < fitted.center.x);
+        assert!(state.dragging.is_none());
+    }
+
+    #[test]
+    fn test_scroll_zooms() {
+        let map = map_around(vec![node(1, 46.5, 6.6, false), node(2, 47.4, 8.5, false)]);
+        let mut state = MapState::default();
+        let fitted = map.viewport(&state, SIZE);
+        let _ = event(
+            &map,
+            &mut state,
+            mouse::Event::WheelScrolled {
+                delta: mouse::ScrollDelta::Lines { x: 0.0, y: 2.0 },
+            },
+            Point::new(256.0, 256.0),
+        );
+        let zoomed = state.viewport.expect("Viewport set by zooming");
+        assert!(close(zoomed.zoom, fitted.zoom + 1.0));
+    }
+
+    #[test]
+    fn test_node_at() {
+        let map = map_around(vec![node(1, 46.5, 6.6, false)]);
+        let viewport = map.viewport(&MapState::default(), SIZE);
+        let at = viewport.screen_point(map.nodes[0].position, SIZE);
+        assert!(
+            map.node_at(&viewport, SIZE, at + Vector::new(5.0, 5.0))
+                .is_some()
+        );
+        assert!(
+            map.node_at(&viewport, SIZE, at + Vector::new(50.0, 0.0))
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_focus_centres_map_until_moved() {
+        let focus = WorldPoint::from(&position(45.5, -73.5));
+        let nodes = vec![node(1, 46.5, 6.6, false), node(2, 47.4, 8.5, false)];
+        let map = NodeMap::new(nodes.clone(), None, Some(focus), select);
+        let mut state = MapState::default();
+        let centred = map.viewport(&state, SIZE);
+        assert_eq!(centred.center, focus);
+        assert!(close(centred.zoom, CLOSE_ZOOM));
+
+        let _ = event(
+            &map,
+            &mut state,
+            mouse::Event::WheelScrolled {
+                delta: mouse::ScrollDelta::Lines { x: 0.0, y: -2.0 },
+            },
+            Point::new(256.0, 256.0),
+        );
+        assert!(close(map.viewport(&state, SIZE).zoom, CLOSE_ZOOM - 1.0));
+
+        // Showing another position centres the map on it again
+        let other = WorldPoint::from(&position(10.0, 10.0));
+        let map = NodeMap::new(nodes, None, Some(other), select);
+        assert_eq!(map.viewport(&state, SIZE).center, other);
+    }
+}
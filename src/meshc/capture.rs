@@ -0,0 +1,534 @@
+//! [MeshCoreEvent] is not serializable, so these mirror the events (their types, payloads and
+//! attributes) that the subscription acts on, so they can be captured to a file and replayed from
+//! it. Events that the subscription ignores are not captured.
+
+use meshcore_rs::events::{
+    AdvertResponseData, AdvertisementData, BatteryInfo, ChannelInfoData, Contact, DeviceInfoData,
+    DiscoverEntry, EventPayload, Neighbour, NeighboursData, SelfInfo,
+};
+use meshcore_rs::{CHANNEL_SECRET_LEN, ChannelMessage, ContactMessage, EventType, MeshCoreEvent};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "Contact")]
+struct ContactDef {
+    public_key: [u8; 32],
+    contact_type: u8,
+    flags: u8,
+    path_len: i8,
+    out_path: Vec<u8>,
+    adv_name: String,
+    last_advert: u32,
+    adv_lat: i32,
+    adv_lon: i32,
+    last_modification_timestamp: u32,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "SelfInfo")]
+struct SelfInfoDef {
+    adv_type: u8,
+    tx_power: u8,
+    max_tx_power: u8,
+    public_key: [u8; 32],
+    adv_lat: i32,
+    adv_lon: i32,
+    multi_acks: u8,
+    adv_loc_policy: u8,
+    telemetry_mode_base: u8,
+    telemetry_mode_loc: u8,
+    telemetry_mode_env: u8,
+    manual_add_contacts: bool,
+    radio_freq: u32,
+    radio_bw: u32,
+    sf: u8,
+    cr: u8,
+    name: String,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "DeviceInfoData")]
+struct DeviceInfoDataDef {
+    fw_version_code: u8,
+    max_contacts: Option<u8>,
+    max_channels: Option<u8>,
+    ble_pin: Option<u32>,
+    fw_build: Option<String>,
+    model: Option<String>,
+    version: Option<String>,
+    repeat: Option<bool>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "BatteryInfo")]
+struct BatteryInfoDef {
+    battery_mv: u16,
+    used_kb: Option<u32>,
+    total_kb: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "ContactMessage")]
+struct ContactMessageDef {
+    sender_prefix: [u8; 6],
+    path_len: u8,
+    txt_type: u8,
+    sender_timestamp: u32,
+    text: String,
+    snr: Option<f32>,
+    signature: Option<[u8; 4]>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "ChannelMessage")]
+struct ChannelMessageDef {
+    channel_idx: u8,
+    path_len: u8,
+    txt_type: u8,
+    sender_timestamp: u32,
+    text: String,
+    snr: Option<f32>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "ChannelInfoData")]
+struct ChannelInfoDataDef {
+    channel_idx: u8,
+    name: String,
+    secret: [u8; CHANNEL_SECRET_LEN],
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "AdvertisementData")]
+struct AdvertisementDataDef {
+    prefix: [u8; 6],
+    name: String,
+    lat: i32,
+    lon: i32,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "Neighbour")]
+struct NeighbourDef {
+    pubkey: Vec<u8>,
+    secs_ago: i32,
+    snr: f32,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "DiscoverEntry")]
+struct DiscoverEntryDef {
+    pubkey: Vec<u8>,
+    name: String,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "AdvertResponseData")]
+struct AdvertResponseDataDef {
+    tag: [u8; 4],
+    pubkey: [u8; 32],
+    adv_type: u8,
+    node_name: String,
+    timestamp: u32,
+    flags: u8,
+    lat: Option<i32>,
+    lon: Option<i32>,
+    node_desc: Option<String>,
+}
+
+/// Wrappers so that lists of remote types can be serialized
+#[derive(Serialize, Deserialize)]
+pub struct CapturedContact(#[serde(with = "ContactDef")] Contact);
+
+#[derive(Serialize, Deserialize)]
+pub struct CapturedNeighbour(#[serde(with = "NeighbourDef")] Neighbour);
+
+#[derive(Serialize, Deserialize)]
+pub struct CapturedDiscoverEntry(#[serde(with = "DiscoverEntryDef")] DiscoverEntry);
+
+/// The type and payload of a [MeshCoreEvent] received from a radio
+#[derive(Serialize, Deserialize)]
+pub enum CapturedPayload {
+    Contacts(Vec<CapturedContact>),
+    NewContact(#[serde(with = "ContactDef")] Contact),
+    NextContact(#[serde(with = "ContactDef")] Contact),
+    SelfInfo(#[serde(with = "SelfInfoDef")] SelfInfo),
+    DeviceInfo(#[serde(with = "DeviceInfoDataDef")] DeviceInfoData),
+    Battery(#[serde(with = "BatteryInfoDef")] BatteryInfo),
+    ChannelInfo(#[serde(with = "ChannelInfoDataDef")] ChannelInfoData),
+    Advertisement(#[serde(with = "AdvertisementDataDef")] AdvertisementData),
+    NeighboursResponse {
+        total: u16,
+        neighbours: Vec<CapturedNeighbour>,
+    },
+    DiscoverResponse(Vec<CapturedDiscoverEntry>),
+    AdvertResponse(#[serde(with = "AdvertResponseDataDef")] AdvertResponseData),
+    ContactMsgRecv(#[serde(with = "ContactMessageDef")] ContactMessage),
+    ChannelMsgRecv(#[serde(with = "ChannelMessageDef")] ChannelMessage),
+    Ack {
+        tag: [u8; 4],
+    },
+    Error(String),
+}
+
+/// A [MeshCoreEvent] received from a radio, as it is stored in a capture file
+#[derive(Serialize, Deserialize)]
+pub struct CapturedEvent {
+    payload: CapturedPayload,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    attributes: HashMap<String, String>,
+}
+
+impl CapturedEvent {
+    /// The [CapturedEvent] for a [MeshCoreEvent], if it is one that is acted on
+    pub fn from_event(event: &MeshCoreEvent) -> Option<Self> {
+        let payload = match (event.event_type, &event.payload) {
+            (EventType::Contacts, EventPayload::Contacts(contacts)) => {
+                CapturedPayload::Contacts(contacts.iter().cloned().map(CapturedContact).collect())
+            }
+            (EventType::NewContact, EventPayload::Contact(contact)) => {
+                CapturedPayload::NewContact(contact.clone())
+            }
+            (EventType::NextContact, EventPayload::Contact(contact)) => {
+                CapturedPayload::NextContact(contact.clone())
+            }
+            (EventType::SelfInfo, EventPayload::SelfInfo(self_info)) => {
+                CapturedPayload::SelfInfo(self_info.clone())
+            }
+            (EventType::DeviceInfo, EventPayload::DeviceInfo(device_info)) => {
+                CapturedPayload::DeviceInfo(device_info.clone())
+            }
+            (EventType::Battery, EventPayload::Battery(battery_info)) => {
+                CapturedPayload::Battery(battery_info.clone())
+            }
+            // The channel's secret is blanked, so a capture can be shared without giving it away
+            (EventType::ChannelInfo, EventPayload::ChannelInfo(channel_info)) => {
+                CapturedPayload::ChannelInfo(ChannelInfoData {
+                    secret: [0; CHANNEL_SECRET_LEN],
+                    ..channel_info.clone()
+                })
+            }
+            (EventType::Advertisement, EventPayload::Advertisement(advertisement)) => {
+                CapturedPayload::Advertisement(advertisement.clone())
+            }
+            (EventType::NeighboursResponse, EventPayload::Neighbours(neighbours)) => {
+                CapturedPayload::NeighboursResponse {
+                    total: neighbours.total,
+                    neighbours: neighbours
+                        .neighbours
+                        .iter()
+                        .cloned()
+                        .map(CapturedNeighbour)
+                        .collect(),
+                }
+            }
+            (EventType::DiscoverResponse, EventPayload::DiscoverResponse(entries)) => {
+                CapturedPayload::DiscoverResponse(
+                    entries.iter().cloned().map(CapturedDiscoverEntry).collect(),
+                )
+            }
+            (EventType::AdvertResponse, EventPayload::AdvertResponse(advert_response)) => {
+                CapturedPayload::AdvertResponse(advert_response.clone())
+            }
+            (EventType::ContactMsgRecv, EventPayload::ContactMessage(contact_message)) => {
+                CapturedPayload::ContactMsgRecv(contact_message.clone())
+            }
+            (EventType::ChannelMsgRecv, EventPayload::ChannelMessage(channel_message)) => {
+                CapturedPayload::ChannelMsgRecv(channel_message.clone())
+            }
+            (EventType::Ack, EventPayload::Ack { tag }) => CapturedPayload::Ack { tag: *tag },
+            (EventType::Error, EventPayload::String(message)) => {
+                CapturedPayload::Error(message.clone())
+            }
+            _ => return None,
+        };
+        Some(CapturedEvent {
+            payload,
+            attributes: event.attributes.clone(),
+        })
+    }
+
+    /// Add an attribute to the event, as [MeshCoreEvent::with_attribute] does
+    pub fn with_attribute(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.attributes.insert(key.into(), value.into());
+        self
+    }
+}
+
+impl From<CapturedEvent> for MeshCoreEvent {
+    fn from(captured_event: CapturedEvent) -> Self {
+        let (event_type, payload) = match captured_event.payload {
+            CapturedPayload::Contacts(contacts) => (
+                EventType::Contacts,
+                EventPayload::Contacts(
+                    contacts
+                        .into_iter()
+                        .map(|CapturedContact(contact)| contact)
+                        .collect(),
+                ),
+            ),
+            CapturedPayload::NewContact(contact) => {
+                (EventType::NewContact, EventPayload::Contact(contact))
+            }
+            CapturedPayload::NextContact(contact) => {
+                (EventType::NextContact, EventPayload::Contact(contact))
+            }
+            CapturedPayload::SelfInfo(self_info) => {
+                (EventType::SelfInfo, EventPayload::SelfInfo(self_info))
+            }
+            CapturedPayload::DeviceInfo(device_info) => {
+                (EventType::DeviceInfo, EventPayload::DeviceInfo(device_info))
+            }
+            CapturedPayload::Battery(battery_info) => {
+                (EventType::Battery, EventPayload::Battery(battery_info))
+            }
+            CapturedPayload::ChannelInfo(channel_info) => (
+                EventType::ChannelInfo,
+                EventPayload::ChannelInfo(channel_info),
+            ),
+            CapturedPayload::Advertisement(advertisement) => (
+                EventType::Advertisement,
+                EventPayload::Advertisement(advertisement),
+            ),
+            CapturedPayload::NeighboursResponse { total, neighbours } => (
+                EventType::NeighboursResponse,
+                EventPayload::Neighbours(NeighboursData {
+                    total,
+                    neighbours: neighbours
+                        .into_iter()
+                        .map(|CapturedNeighbour(neighbour)| neighbour)
+                        .collect(),
+                }),
+            ),
+            CapturedPayload::DiscoverResponse(entries) => (
+                EventType::DiscoverResponse,
+                EventPayload::DiscoverResponse(
+                    entries
+                        .into_iter()
+                        .map(|CapturedDiscoverEntry(entry)| entry)
+                        .collect(),
+                ),
+            ),
+            CapturedPayload::AdvertResponse(advert_response) => (
+                EventType::AdvertResponse,
+                EventPayload::AdvertResponse(advert_response),
+            ),
+            CapturedPayload::ContactMsgRecv(contact_message) => (
+                EventType::ContactMsgRecv,
+                EventPayload::ContactMessage(contact_message),
+            ),
+            CapturedPayload::ChannelMsgRecv(channel_message) => (
+                EventType::ChannelMsgRecv,
+                EventPayload::ChannelMessage(channel_message),
+            ),
+            CapturedPayload::Ack { tag } => (EventType::Ack, EventPayload::Ack { tag }),
+            CapturedPayload::Error(message) => (EventType::Error, EventPayload::String(message)),
+        };
+        let mut event = MeshCoreEvent::new(event_type, payload);
+        event.attributes = captured_event.attributes;
+        event
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Serialize and deserialize a [MeshCoreEvent], as capturing it to a file and replaying does
+    fn roundtrip(event: MeshCoreEvent) -> MeshCoreEvent {
+        let captured = CapturedEvent::from_event(&event).expect("Event was not captured");
+        let json = serde_json::to_string(&captured).expect("Could not serialize event");
+        let captured: CapturedEvent =
+            serde_json::from_str(&json).expect("Could not deserialize event");
+        captured.into()
+    }
+
+    #[test]
+    fn contact_roundtrip() {
+        let event = MeshCoreEvent::new(
+            EventType::NextContact,
+            EventPayload::Contact(Contact {
+                public_key: [7u8; 32],
+                contact_type: 1,
+                flags: 2,
+                path_len: -1,
+                out_path: vec![1, 2, 3],
+                adv_name: "Alice".to_string(),
+                last_advert: 1000,
+                adv_lat: 37_774_900,
+                adv_lon: -122_419_400,
+                last_modification_timestamp: 2000,
+            }),
+        );
+
+        let replayed = roundtrip(event);
+        assert_eq!(replayed.event_type, EventType::NextContact);
+        let contact = match replayed.payload {
+            EventPayload::Contact(contact) => Some(contact),
+            _ => None,
+        }
+        .expect("Expected a contact");
+        assert_eq!(contact.public_key, [7u8; 32]);
+        assert_eq!(contact.path_len, -1);
+        assert_eq!(contact.out_path, vec![1, 2, 3]);
+        assert_eq!(contact.adv_name, "Alice");
+        assert_eq!(contact.adv_lon, -122_419_400);
+    }
+
+    #[test]
+    fn contacts_roundtrip() {
+        let event = MeshCoreEvent::new(
+            EventType::Contacts,
+            EventPayload::Contacts(vec![Contact {
+                public_key: [3u8; 32],
+                contact_type: 1,
+                flags: 0,
+                path_len: 0,
+                out_path: vec![],
+                adv_name: "Bob".to_string(),
+                last_advert: 0,
+                adv_lat: 0,
+                adv_lon: 0,
+                last_modification_timestamp: 0,
+            }]),
+        );
+
+        let replayed = roundtrip(event);
+        assert_eq!(replayed.event_type, EventType::Contacts);
+        let contacts = match replayed.payload {
+            EventPayload::Contacts(contacts) => Some(contacts),
+            _ => None,
+        }
+        .expect("Expected contacts");
+        assert_eq!(contacts.len(), 1);
+        assert_eq!(contacts[0].adv_name, "Bob");
+    }
+
+    #[test]
+    fn channel_message_roundtrip() {
+        let event = MeshCoreEvent::new(
+            EventType::ChannelMsgRecv,
+            EventPayload::ChannelMessage(ChannelMessage {
+                channel_idx: 2,
+                path_len: 1,
+                txt_type: 0,
+                sender_timestamp: 1234,
+                text: "Alice: Hello 👋".to_string(),
+                snr: Some(5.25),
+            }),
+        );
+
+        let replayed = roundtrip(event);
+        assert_eq!(replayed.event_type, EventType::ChannelMsgRecv);
+        let message = match replayed.payload {
+            EventPayload::ChannelMessage(message) => Some(message),
+            _ => None,
+        }
+        .expect("Expected a channel message");
+        assert_eq!(message.channel_idx, 2);
+        assert_eq!(message.sender_timestamp, 1234);
+        assert_eq!(message.text, "Alice: Hello 👋");
+        assert_eq!(message.snr, Some(5.25));
+    }
+
+    #[test]
+    fn channel_info_roundtrip_without_secret() {
+        let event = MeshCoreEvent::new(
+            EventType::ChannelInfo,
+            EventPayload::ChannelInfo(ChannelInfoData {
+                channel_idx: 1,
+                name: "Friends".to_string(),
+                secret: [9u8; CHANNEL_SECRET_LEN],
+            }),
+        );
+
+        let replayed = roundtrip(event);
+        let channel_info = match replayed.payload {
+            EventPayload::ChannelInfo(channel_info) => Some(channel_info),
+            _ => None,
+        }
+        .expect("Expected channel info");
+        assert_eq!(channel_info.channel_idx, 1);
+        assert_eq!(channel_info.name, "Friends");
+        assert_eq!(channel_info.secret, [0u8; CHANNEL_SECRET_LEN]);
+    }
+
+    #[test]
+    fn neighbours_roundtrip() {
+        let event = MeshCoreEvent::new(
+            EventType::NeighboursResponse,
+            EventPayload::Neighbours(NeighboursData {
+                total: 2,
+                neighbours: vec![
+                    Neighbour {
+                        pubkey: vec![1, 2, 3, 4, 5, 6],
+                        secs_ago: 30,
+                        snr: 2.5,
+                    },
+                    Neighbour {
+                        pubkey: vec![6, 5, 4, 3, 2, 1],
+                        secs_ago: 60,
+                        snr: -1.0,
+                    },
+                ],
+            }),
+        );
+
+        let replayed = roundtrip(event);
+        let neighbours = match replayed.payload {
+            EventPayload::Neighbours(neighbours) => Some(neighbours),
+            _ => None,
+        }
+        .expect("Expected neighbours");
+        assert_eq!(neighbours.total, 2);
+        assert_eq!(neighbours.neighbours.len(), 2);
+        assert_eq!(neighbours.neighbours[1].pubkey, vec![6, 5, 4, 3, 2, 1]);
+        assert_eq!(neighbours.neighbours[1].snr, -1.0);
+    }
+
+    #[test]
+    fn attributes_roundtrip() {
+        let event = MeshCoreEvent::new(
+            EventType::NeighboursResponse,
+            EventPayload::Neighbours(NeighboursData {
+                total: 0,
+                neighbours: vec![],
+            }),
+        )
+        .with_attribute("tag", "01020304");
+
+        let replayed = roundtrip(event);
+        assert_eq!(replayed.event_type, EventType::NeighboursResponse);
+        assert_eq!(
+            replayed.attributes.get("tag").map(String::as_str),
+            Some("01020304")
+        );
+    }
+
+    #[test]
+    fn ack_roundtrip() {
+        let event = MeshCoreEvent::new(EventType::Ack, EventPayload::Ack { tag: [1, 2, 3, 4] });
+        let replayed = roundtrip(event);
+        assert_eq!(replayed.event_type, EventType::Ack);
+        assert!(matches!(
+            replayed.payload,
+            EventPayload::Ack { tag } if tag == [1, 2, 3, 4]
+        ));
+    }
+
+    #[test]
+    fn ignored_events_not_captured() {
+        assert!(CapturedEvent::from_event(&MeshCoreEvent::ok()).is_none());
+        assert!(
+            CapturedEvent::from_event(&MeshCoreEvent::new(EventType::LogData, EventPayload::None))
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn mismatched_payload_not_captured() {
+        let event = MeshCoreEvent::new(EventType::SelfInfo, EventPayload::None);
+        assert!(CapturedEvent::from_event(&event).is_none());
+    }
+}
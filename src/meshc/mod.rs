@@ -1,3 +1,4 @@
+mod capture;
 pub mod subscription;
 
 pub const MESHCORE_SERVICE_UUID: Uuid = Uuid::from_u128(0x6e400001_b5a3_f393_e0a9_e50e24dcca9e);
@@ -7,7 +8,7 @@ use crate::conversation_id::{MessageId, NodeId};
 use crate::device::DeviceEvent;
 use crate::device::DeviceEvent::{MCMessageReceived, NewChannel};
 use crate::meshchat::{MCChannel, MCNodeInfo, MCPosition, MCUser};
-use crate::message::MCContent;
+use crate::message::{LinkQuality, MCContent};
 use crate::timestamp::TimeStamp;
 use meshcore_rs::ContactMessage;
 use meshcore_rs::commands::Destination;
@@ -49,6 +50,7 @@ impl From<&AdvertisementData> for MCNodeInfo {
                 ..Default::default()
             }),
             is_ignored: false,
+            hops_away: None,
         }
     }
 }
@@ -73,6 +75,8 @@ impl From<Contact> for MCNodeInfo {
                 ..Default::default()
             }),
             is_ignored: false,
+            // A negative path length means the path to the contact is not known, so it is flooded
+            hops_away: u32::try_from(contact.path_len).ok(),
         }
     }
 }
@@ -101,6 +105,7 @@ impl From<Neighbour> for MCNodeInfo {
             }),
             position: None,
             is_ignored: false,
+            hops_away: None,
         }
     }
 }
@@ -133,6 +138,7 @@ impl From<AdvertResponseData> for MCNodeInfo {
             }),
             position,
             is_ignored: false,
+            hops_away: None,
         }
     }
 }
@@ -152,10 +158,25 @@ impl From<DiscoverEntry> for MCNodeInfo {
             }),
             position: None,
             is_ignored: false,
+            hops_away: None,
         }
     }
 }
 
+/// The path length reported for messages that came by a direct route, not flooded, so the number
+/// of hops travelled is not known
+const DIRECT_ROUTE_PATH_LEN: u8 = 0xFF;
+
+/// How well a message was received, from the SNR and path length reported with it
+pub fn link_quality(snr: Option<f32>, path_len: u8) -> Option<LinkQuality> {
+    let hops = (path_len != DIRECT_ROUTE_PATH_LEN).then_some(u32::from(path_len));
+    (snr.is_some() || hops.is_some()).then_some(LinkQuality {
+        snr,
+        rssi: None,
+        hops,
+    })
+}
+
 impl From<ContactMessage> for DeviceEvent {
     fn from(contact_message: ContactMessage) -> Self {
         // jonesy:allow(bounds) via meshcore_rs sender_prefix .into()
@@ -166,6 +187,7 @@ impl From<ContactMessage> for DeviceEvent {
             node_id,
             MCContent::NewTextMessage(contact_message.text),
             TimeStamp::now(),
+            link_quality(contact_message.snr, contact_message.path_len),
         )
     }
 }
@@ -344,7 +366,9 @@ mod test {
         let event: DeviceEvent = message.into();
         let after_conversion = TimeStamp::now();
 
-        let MCMessageReceived(conversation_id, _msg_id, from, msg, event_timestamp) = event else {
+        let MCMessageReceived(conversation_id, _msg_id, from, msg, event_timestamp, link_quality) =
+            event
+        else {
             unreachable!("Expected MCMessageReceived event")
         };
 
@@ -358,6 +382,27 @@ mod test {
             "Event timestamp should be a local timestamp",
         );
         assert_eq!(msg.to_string(), "Direct message");
+        assert_eq!(
+            link_quality,
+            Some(LinkQuality {
+                snr: Some(10.5),
+                rssi: None,
+                hops: Some(0)
+            })
+        );
+    }
+
+    #[test]
+    fn link_quality_of_direct_route() {
+        assert_eq!(link_quality(None, DIRECT_ROUTE_PATH_LEN), None);
+        assert_eq!(
+            link_quality(Some(-4.0), DIRECT_ROUTE_PATH_LEN).and_then(|quality| quality.hops),
+            None
+        );
+        assert_eq!(
+            link_quality(None, 2).and_then(|quality| quality.hops),
+            Some(2)
+        );
     }
 
     // Tests for From<ChannelInfoData> for SubscriptionEvent
@@ -483,6 +528,7 @@ mod test {
 
         assert_eq!(node_info.node_id, NodeId::from(0xAABB_CCDD_EEFF_0000u64));
         assert!(!node_info.is_ignored);
+        assert_eq!(node_info.hops_away, Some(1));
 
         let user = node_info.user.expect("Expected user");
         assert_eq!(user.long_name, "ContactNode");
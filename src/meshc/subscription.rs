@@ -1,16 +1,20 @@
+use crate::capture;
+use crate::capture::{Capture, start_capture};
 use crate::conversation_id::ConversationId::{Channel, Node};
 use crate::conversation_id::{ChannelIndex, ConversationId, MessageId, NodeId};
 use crate::device::DeviceCommand::{
-    BatteryTick, Connect, Disconnect, MeshCoreRadioPacket, SendEmojiReply, SendPosition,
-    SendSelfInfo, SendText,
+    AckTick, BatteryTick, Connect, ConnectionLost, Disconnect, MeshCoreRadioPacket,
+    RequestNeighbours, ResendText, SendEmojiReply, SendPosition, SendSelfInfo, SendText,
 };
 use crate::device::DeviceEvent::{
-    ConnectedEvent, ConnectingEvent, ConnectionError, DeviceBatteryLevel, DisconnectedEvent,
-    MCMessageReceived, MessageACK, MyNodeNum, MyPosition, MyUserInfo, NewChannel, NewNode,
-    SendError,
+    AwaitingAck, ConnectedEvent, ConnectingEvent, ConnectionError, DeviceBatteryLevel,
+    DisconnectedEvent, MCMessageReceived, MessageACK, MessageFailed, MessageResent, MyNodeNum,
+    MyPosition, MyUserInfo, NeighbourInfo, NewChannel, NewNode, SendError,
 };
 use crate::device::{DeviceCommand, DeviceEvent, DeviceIdentifier};
 use crate::device_list::RadioType;
+use crate::meshc::capture::CapturedEvent;
+use crate::meshc::link_quality;
 use crate::meshc::subscription::DeviceState::{Connected, Disconnected};
 use futures::{SinkExt, Stream};
 use iced::stream;
@@ -18,13 +22,19 @@ use meshcore_rs::commands::Destination;
 use meshcore_rs::events::{
     BatteryInfo, ChannelInfoData, Contact, DeviceInfoData, EventPayload, NeighboursData, SelfInfo,
 };
-use meshcore_rs::{ChannelMessage, ContactMessage, Error, EventType, MeshCore, MeshCoreEvent};
+use meshcore_rs::parsing::hex_encode;
+use meshcore_rs::reader::MessageReader;
+use meshcore_rs::{
+    BinaryReqType, ChannelMessage, ContactMessage, Error, EventType, MeshCore, MeshCoreEvent,
+};
 use std::collections::{HashMap, HashSet};
+use std::path::Path;
 use std::pin::Pin;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
 use tokio::sync::mpsc::channel;
-use tokio_stream::StreamExt;
+use tokio_stream::{StreamExt, once};
 
-use crate::meshchat::{MCPosition, MCUser};
+use crate::meshchat::{MCNeighbour, MCPosition, MCUser};
 use crate::message::MCContent;
 use crate::timestamp::TimeStamp;
 use tokio::time::{Duration, interval, timeout};
@@ -36,6 +46,23 @@ enum DeviceState {
 }
 
 const BATTERY_INTERVAL: Duration = Duration::from_secs(30 * 60);
+/// How often to check for sent messages that have not been acknowledged in time
+const ACK_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+/// Bounds on how long to wait for an ACK, whatever timeout the radio suggests
+const MIN_ACK_TIMEOUT: Duration = Duration::from_secs(10);
+const MAX_ACK_TIMEOUT: Duration = Duration::from_secs(120);
+/// How many neighbours to ask a node for, and how long to wait for them
+const NEIGHBOURS_COUNT: u8 = 32;
+const NEIGHBOURS_TIMEOUT: Duration = Duration::from_secs(30);
+/// The attribute a captured response to a request for neighbours has the node asked in
+const REQUESTED_FROM: &str = "requested_from";
+/// Ask for the prefix of neighbours' keys that node ids are made from, so they match contacts
+const NEIGHBOURS_KEY_PREFIX_LENGTH: u8 = 6;
+/// The command that sends a binary request, such as for neighbours, to a contact. Sent by hand
+/// as meshcore_rs has no way to send it without waiting for the response, so this and
+/// [neighbours_request] must match `request_neighbours_with_timeout` in the meshcore-rs version
+/// pinned in Cargo.toml
+const CMD_SEND_BINARY_REQ: u8 = 0x32;
 
 #[derive(Debug, Default)]
 struct RadioCache {
@@ -45,8 +72,16 @@ struct RadioCache {
     known_channels: HashSet<u8>,
     /// Contact Name (String), Contact Node ID (NodeId)
     known_contacts: HashMap<String, NodeId>,
-    /// Messages that have been sent (by MessageId) that are pending an ACK (ChannelId for the message)
-    pending_ack: HashMap<MessageId, ConversationId>,
+    /// The full public key of each contact, needed to make requests to it
+    public_keys: HashMap<NodeId, [u8; 32]>,
+    /// Messages that have been sent (by MessageId) that are pending an ACK (ChannelId for the
+    /// message, and the time after which it is considered failed)
+    pending_ack: HashMap<MessageId, (ConversationId, Instant)>,
+    /// Requests for neighbours awaiting a response, by the tag the response will have, with the
+    /// node asked and the time after which no response is expected
+    neighbour_requests: HashMap<String, (NodeId, Instant)>,
+    /// The file events from the radio are being captured to, if capturing
+    capture: Option<Capture>,
 }
 
 impl RadioCache {
@@ -61,7 +96,66 @@ impl RadioCache {
             ..Default::default()
         }
     }
+
+    /// Remove and return the messages still pending an ACK whose deadline has passed at `now`
+    fn expire_pending_acks(&mut self, now: Instant) -> Vec<(MessageId, ConversationId)> {
+        let expired: Vec<(MessageId, ConversationId)> = self
+            .pending_ack
+            .iter()
+            .filter(|(_, (_, deadline))| *deadline <= now)
+            .map(|(message_id, (conversation_id, _))| (*message_id, *conversation_id))
+            .collect();
+        for (message_id, _) in &expired {
+            self.pending_ack.remove(message_id);
+        }
+        expired
+    }
+
+    /// Remember the request for neighbours that a replayed response was captured with, so the
+    /// response is for the node they were requested from, as it was when it was captured
+    fn replay_neighbour_request(&mut self, event: &MeshCoreEvent) {
+        if let Some(tag) = event.attributes.get("tag")
+            && let Some(node_id) = event
+                .attributes
+                .get(REQUESTED_FROM)
+                .and_then(|node_id| node_id.parse::<u64>().ok())
+        {
+            self.neighbour_requests.insert(
+                tag.clone(),
+                (NodeId::from(node_id), Instant::now() + NEIGHBOURS_TIMEOUT),
+            );
+        }
+    }
+
+    /// Forget the requests for neighbours whose response hasn't come by `now`
+    fn expire_neighbour_requests(&mut self, now: Instant) {
+        self.neighbour_requests
+            .retain(|_, (_, deadline)| *deadline > now);
+    }
+
+    /// Record an event from the radio in the capture file, if capturing. A response to a request
+    /// for neighbours is recorded with the node they were requested from, as no request is made
+    /// when the capture is replayed.
+    async fn record(&mut self, event: &MeshCoreEvent) {
+        if let Some(capture) = self.capture.as_mut()
+            && let Some(mut captured_event) = CapturedEvent::from_event(event)
+        {
+            if let Some((node_id, _)) = event
+                .attributes
+                .get("tag")
+                .and_then(|tag| self.neighbour_requests.get(tag))
+            {
+                captured_event =
+                    captured_event.with_attribute(REQUESTED_FROM, u64::from(node_id).to_string());
+            }
+            capture
+                .record(&captured_event)
+                .await
+                .unwrap_or_else(|e| eprintln!("Capture error: {e}"));
+        }
+    }
 }
+
 /// A stream of [DeviceEvent] for comms between the app and the radio
 pub fn subscribe() -> impl Stream<Item = DeviceEvent> {
     stream::channel(
@@ -90,16 +184,40 @@ pub fn subscribe() -> impl Stream<Item = DeviceEvent> {
                     Disconnected => {
                         // Wait for a message from the UI to request that we connect to a device
                         // No need to wait for any messages from a radio, as we are not connected to one
-                        if let Some(Connect(ble_device, _)) = gui_stream.next().await {
+                        if let Some(Connect(ble_device, _, capture_traffic)) =
+                            gui_stream.next().await
+                        {
                             gui_sender
                                 .send(ConnectingEvent(ble_device.clone()))
                                 .await
                                 .unwrap_or_else(|e| eprintln!("Send error: {e}"));
 
+                            if let DeviceIdentifier::Replay { path } = &ble_device {
+                                replay_capture(
+                                    path,
+                                    &ble_device,
+                                    &mut radio_cache,
+                                    &mut gui_stream,
+                                    &mut gui_sender,
+                                )
+                                .await;
+                                radio_cache = RadioCache::default();
+                                continue;
+                            }
+
                             match do_connect(&ble_device).await {
                                 Ok(meshcore) => {
                                     device_state = Connected(ble_device.clone(), meshcore);
 
+                                    if capture_traffic {
+                                        radio_cache.capture = start_capture(
+                                            &ble_device,
+                                            RadioType::MeshCore,
+                                            &mut gui_sender,
+                                        )
+                                        .await;
+                                    }
+
                                     gui_sender
                                         .send(ConnectedEvent(ble_device, RadioType::MeshCore))
                                         .await
@@ -121,35 +239,61 @@ pub fn subscribe() -> impl Stream<Item = DeviceEvent> {
                     Connected(ble_device, meshcore) => {
                         match initiate(&mut radio_cache, &meshcore, &mut gui_sender).await {
                             Ok(_) => {
-                                let from_radio_stream =
-                                    meshcore.event_stream().map(|from_radio_packet| {
-                                        Ok::<_, Error>(MeshCoreRadioPacket(Box::new(
-                                            from_radio_packet,
-                                        )))
-                                    });
+                                // The radio reports the link dropping as a Disconnected event
+                                let from_radio_stream = meshcore
+                                    .event_stream()
+                                    .map(|from_radio_packet| {
+                                        if from_radio_packet.event_type == EventType::Disconnected {
+                                            Ok::<_, Error>(ConnectionLost)
+                                        } else {
+                                            Ok::<_, Error>(MeshCoreRadioPacket(Box::new(
+                                                from_radio_packet,
+                                            )))
+                                        }
+                                    })
+                                    .chain(once(Ok::<_, Error>(ConnectionLost)));
 
                                 // Battery polling every 30 minutes
                                 let battery_interval = interval(BATTERY_INTERVAL);
                                 let battery_stream = IntervalStream::new(battery_interval)
                                     .map(|_| Ok::<_, Error>(BatteryTick));
 
+                                // Expire messages not acknowledged in time
+                                let ack_stream = IntervalStream::new(interval(ACK_CHECK_INTERVAL))
+                                    .map(|_| Ok::<_, Error>(AckTick));
+
                                 let gui_result_stream = (&mut gui_stream).map(Ok::<_, Error>);
                                 let mut merged_stream = from_radio_stream
                                     .merge(gui_result_stream)
-                                    .merge(battery_stream);
+                                    .merge(battery_stream)
+                                    .merge(ack_stream);
 
                                 loop {
                                     // jonesy:allow(unknown) via async poll
                                     match merged_stream.try_next().await {
                                         Ok(Some(message)) => {
                                             let result = match message {
-                                                Disconnect => break,
+                                                Disconnect | ConnectionLost => break,
                                                 SendText(
                                                     text,
                                                     conversation_id,
                                                     reply_to_message_id,
+                                                ) => send_text_message(
+                                                    &meshcore,
+                                                    &mut radio_cache,
+                                                    conversation_id,
+                                                    text,
+                                                    reply_to_message_id,
+                                                    &mut gui_sender,
+                                                )
+                                                .await
+                                                .map(drop),
+                                                ResendText(
+                                                    text,
+                                                    conversation_id,
+                                                    reply_to_message_id,
                                                 ) => {
-                                                    send_text_message(
+                                                    resend_text_message(
                                                         &meshcore,
                                                         &mut radio_cache,
                                                         conversation_id,
@@ -195,10 +339,11 @@ pub fn subscribe() -> impl Stream<Item = DeviceEvent> {
                                                     .await
                                                 }
                                                 MeshCoreRadioPacket(meshcore_event) => {
+                                                    radio_cache.record(&meshcore_event).await;
                                                     handle_radio_event(
                                                         &ble_device,
                                                         &mut radio_cache,
-                                                        &meshcore,
+                                                        Some(&meshcore),
                                                         meshcore_event,
                                                         &mut gui_sender,
                                                     )
@@ -206,6 +351,24 @@ pub fn subscribe() -> impl Stream<Item = DeviceEvent> {
                                                     .await
                                                 }
                                                 BatteryTick => request_battery(&meshcore).await,
+                                                RequestNeighbours(node_id) => {
+                                                    request_neighbours(
+                                                        &meshcore,
+                                                        &mut radio_cache,
+                                                        node_id,
+                                                    )
+                                                    .await
+                                                }
+                                                AckTick => {
+                                                    expire_pending_acks(
+                                                        &mut radio_cache,
+                                                        &mut gui_sender,
+                                                    )
+                                                    .await;
+                                                    radio_cache
+                                                        .expire_neighbour_requests(Instant::now());
+                                                    Ok(())
+                                                }
                                                 _ => Ok(()),
                                             };
 
@@ -264,17 +427,35 @@ pub fn subscribe() -> impl Stream<Item = DeviceEvent> {
 
 /// Connect to a specific BlueTooth device by name and return a [MeshCore] that receives messages
 /// from the radio and can be used to send messages to the radio.
-async fn do_connect(ble_device: &DeviceIdentifier) -> meshcore_rs::Result<MeshCore> {
-    timeout(
-        Duration::from_secs(10),
-        #[cfg(not(windows))]
-        MeshCore::ble_connect(&ble_device.name()),
-        #[cfg(windows)]
-        MeshCore::ble_connect(&ble_device.mac().unwrap_or_else(|| ble_device.name())),
-    )
-    // jonesy:allow(unknown) async state machine artifact
-    .await
-    .map_err(|_| Error::Timeout("Connect".to_string()))?
+async fn do_connect(device: &DeviceIdentifier) -> meshcore_rs::Result<MeshCore> {
+    let connect = async {
+        match device {
+            #[cfg(feature = "bluetooth")]
+            DeviceIdentifier::Ble { .. } => {
+                #[cfg(not(windows))]
+                let ble_name = device.name();
+                #[cfg(windows)]
+                let ble_name = device.mac().unwrap_or_else(|| device.name());
+                MeshCore::ble_connect(&ble_name).await
+            }
+            #[cfg(feature = "tcp")]
+            DeviceIdentifier::Tcp { host, port, .. } => MeshCore::tcp(host, *port).await,
+            #[cfg(feature = "serial")]
+            DeviceIdentifier::Serial { path, baud } => MeshCore::serial(path, *baud).await,
+            #[cfg(feature = "sim")]
+            DeviceIdentifier::Sim { .. } => Err(Error::connection(
+                "A simulated radio is not a MeshCore radio",
+            )),
+            DeviceIdentifier::Replay { .. } => {
+                Err(Error::connection("A capture is replayed, not connected to"))
+            }
+        }
+    };
+
+    timeout(Duration::from_secs(10), connect)
+        // jonesy:allow(unknown) async state machine artifact
+        .await
+        .map_err(|_| Error::Timeout("Connect".to_string()))?
 }
 
 /// Disconnect from the radio we are currently connected to using the [MeshCore]
@@ -292,16 +473,28 @@ async fn initiate(
     gui_sender: &mut futures_channel::mpsc::Sender<DeviceEvent>,
 ) -> meshcore_rs::Result<()> {
     let self_info = meshcore.commands().lock().await.send_appstart().await?;
+    radio_cache
+        .record(&MeshCoreEvent::new(
+            EventType::SelfInfo,
+            EventPayload::SelfInfo(self_info.clone()),
+        ))
+        .await;
     // jonesy:allow(bounds) via handle_self_info -> meshcore_rs .into()
     handle_self_info(radio_cache, self_info, gui_sender).await;
 
     let device_info = meshcore.commands().lock().await.send_device_query().await?;
+    radio_cache
+        .record(&MeshCoreEvent::new(
+            EventType::DeviceInfo,
+            EventPayload::DeviceInfo(device_info.clone()),
+        ))
+        .await;
     handle_device_info(radio_cache, device_info, gui_sender).await;
 
     // Add known contacts
     get_contacts(meshcore, radio_cache, gui_sender).await?;
 
-    get_channels(meshcore, gui_sender).await?;
+    get_channels(meshcore, radio_cache, gui_sender).await?;
 
     get_pending_messages(radio_cache, meshcore, gui_sender).await;
 
@@ -313,12 +506,19 @@ async fn initiate(
 /// Fetch all known channels from the radio and send them to the GUI
 async fn get_channels(
     meshcore: &MeshCore,
+    radio_cache: &mut RadioCache,
     gui_sender: &mut futures_channel::mpsc::Sender<DeviceEvent>,
 ) -> meshcore_rs::Result<()> {
     // jonesy:allow(unknown) async state machine artifact
     let mut index = 0;
     while let Ok(channel) = meshcore.commands().lock().await.get_channel(index).await {
         if !channel.name.is_empty() {
+            radio_cache
+                .record(&MeshCoreEvent::new(
+                    EventType::ChannelInfo,
+                    EventPayload::ChannelInfo(channel.clone()),
+                ))
+                .await;
             gui_sender
                 .send(NewChannel(channel.into()))
                 .await
@@ -350,6 +550,12 @@ async fn get_contacts(
         .await?;
 
     for contact in contacts {
+        radio_cache
+            .record(&MeshCoreEvent::new(
+                EventType::NewContact,
+                EventPayload::Contact(contact.clone()),
+            ))
+            .await;
         // jonesy:allow(bounds) via handle_new_contact -> Contact::prefix()
         handle_new_contact(radio_cache, contact, gui_sender).await;
     }
@@ -378,6 +584,7 @@ async fn get_pending_messages(
     gui_sender: &mut futures_channel::mpsc::Sender<DeviceEvent>,
 ) {
     while let Ok(Some(event)) = meshcore.commands().lock().await.get_msg().await {
+        radio_cache.record(&event).await;
         match event.event_type {
             EventType::ContactMsgRecv => {
                 if let EventPayload::ContactMessage(contact_message) = event.payload {
@@ -394,7 +601,7 @@ async fn get_pending_messages(
     }
 }
 
-/// Send a text message
+/// Send a text message, returning the id it was sent with
 async fn send_text_message(
     meshcore: &MeshCore,
     radio_cache: &mut RadioCache,
@@ -402,8 +609,8 @@ async fn send_text_message(
     text: String,
     _reply_to_message_id: Option<MessageId>,
     gui_sender: &mut futures_channel::mpsc::Sender<DeviceEvent>,
-) -> meshcore_rs::Result<()> {
-    match conversation_id {
+) -> meshcore_rs::Result<MessageId> {
+    let message_id = match conversation_id {
         Channel(channel_index) => {
             // No message sent info returned for a channel message
             meshcore
@@ -416,17 +623,21 @@ async fn send_text_message(
                 .await?;
 
             // Reflect the message back into the GUI
+            let message_id: MessageId = TimeStamp::now().into();
             let msg = MCContent::NewTextMessage(text);
             gui_sender
                 .send(MCMessageReceived(
                     conversation_id,
-                    TimeStamp::now().into(),
+                    message_id,
                     radio_cache.self_id,
                     msg,
                     TimeStamp::now(),
+                    None,
                 ))
                 .await
                 .unwrap_or_else(|e| eprintln!("Send error: {e}"));
+
+            message_id
         }
         Node(node_id) => {
             let message_sent_info = meshcore
@@ -439,8 +650,12 @@ async fn send_text_message(
 
             let message_id: MessageId = message_sent_info.expected_ack.into();
 
-            // Mark this sent message as pending an ACK
-            radio_cache.pending_ack.insert(message_id, Node(node_id));
+            // Mark this sent message as pending an ACK, until the timeout the radio suggests
+            let ack_timeout = Duration::from_millis(message_sent_info.suggested_timeout.into())
+                .clamp(MIN_ACK_TIMEOUT, MAX_ACK_TIMEOUT);
+            radio_cache
+                .pending_ack
+                .insert(message_id, (Node(node_id), Instant::now() + ack_timeout));
 
             // Reflect the message back into the GUI
             let msg = MCContent::NewTextMessage(text);
@@ -451,11 +666,47 @@ async fn send_text_message(
                     radio_cache.self_id,
                     msg,
                     TimeStamp::now(),
+                    None,
                 ))
                 .await
                 .unwrap_or_else(|e| eprintln!("Send error: {e}"));
+
+            gui_sender
+                .send(AwaitingAck(conversation_id, message_id, ack_timeout))
+                .await
+                .unwrap_or_else(|e| eprintln!("Send error: {e}"));
+
+            message_id
         }
-    }
+    };
+
+    Ok(message_id)
+}
+
+/// Send a text message again after it failed, and report the id it was sent with
+async fn resend_text_message(
+    meshcore: &MeshCore,
+    radio_cache: &mut RadioCache,
+    conversation_id: ConversationId,
+    text: String,
+    reply_to_message_id: Option<MessageId>,
+    gui_sender: &mut futures_channel::mpsc::Sender<DeviceEvent>,
+) -> meshcore_rs::Result<()> {
+    let message_id = send_text_message(
+        meshcore,
+        radio_cache,
+        conversation_id,
+        text,
+        reply_to_message_id,
+        gui_sender,
+    )
+    // jonesy:allow(unknown) async state machine artifact
+    .await?;
+
+    gui_sender
+        .send(MessageResent(message_id))
+        .await
+        .unwrap_or_else(|e| eprintln!("Send error: {e}"));
 
     Ok(())
 }
@@ -480,6 +731,7 @@ async fn send_emoji_reply(
     )
     // jonesy:allow(unknown) async state machine artifact
     .await
+    .map(drop)
 }
 
 /// Send a position message
@@ -505,6 +757,7 @@ async fn send_position(
     )
     // jonesy:allow(unknown) async state machine artifact
     .await
+    .map(drop)
 }
 
 /// Send SelfInfo to a channel or a node
@@ -526,6 +779,7 @@ async fn send_self_info(
     )
     // jonesy:allow(unknown) async state machine artifact
     .await
+    .map(drop)
 }
 
 /// Advertise my presence on the network to other nodes
@@ -588,12 +842,133 @@ async fn handle_new_contact(
     radio_cache
         .known_contacts
         .insert(contact.adv_name.clone(), node_id);
+    radio_cache.public_keys.insert(node_id, contact.public_key);
     gui_sender
         .send(NewNode(contact.into()))
         .await
         .unwrap_or_else(|e| eprintln!("Send error: {e}"));
 }
 
+/// The request for the nodes the contact with `public_key` hears directly, as meshcore_rs
+/// `request_neighbours_with_timeout` sends it, made unique by `nonce`
+fn neighbours_request(public_key: &[u8; 32], nonce: u32) -> Vec<u8> {
+    let mut request = vec![CMD_SEND_BINARY_REQ];
+    request.extend_from_slice(public_key);
+    request.push(BinaryReqType::Neighbours as u8);
+    request.push(0); // version
+    request.push(NEIGHBOURS_COUNT);
+    request.extend_from_slice(&0u16.to_le_bytes()); // offset
+    request.push(0); // order by
+    request.push(NEIGHBOURS_KEY_PREFIX_LENGTH);
+    request.extend_from_slice(&nonce.to_le_bytes());
+    request
+}
+
+/// Ask a contact for the nodes it hears directly. The response can take a while to come back
+/// over the mesh, so the commands lock is only held while the request is sent, and the response
+/// is not waited for here. It arrives as an event with the tag of the request, that
+/// [handle_radio_event] sends to the GUI.
+async fn request_neighbours(
+    meshcore: &MeshCore,
+    radio_cache: &mut RadioCache,
+    node_id: NodeId,
+) -> meshcore_rs::Result<()> {
+    // Only contacts can be asked, as the full public key is needed
+    let Some(public_key) = radio_cache.public_keys.get(&node_id).copied() else {
+        return Ok(());
+    };
+    let nonce = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|since| since.subsec_nanos())
+        .unwrap_or_default()
+        .max(1);
+
+    let event = meshcore
+        .commands()
+        .lock()
+        .await
+        .send(
+            &neighbours_request(&public_key, nonce),
+            Some(EventType::MsgSent),
+        )
+        .await?;
+    let EventPayload::MsgSent(sent) = event.payload else {
+        return Err(Error::protocol("Unexpected response to neighbours request"));
+    };
+
+    register_neighbours_request(
+        meshcore.reader(),
+        radio_cache,
+        node_id,
+        &public_key,
+        &sent.expected_ack,
+    )
+    .await;
+    Ok(())
+}
+
+/// Have `reader` turn the response tagged `tag`, from the contact with `public_key`, into a
+/// NeighboursResponse event, and remember the response is for `node_id`. The tag of that event
+/// is the key the request is remembered by in `radio_cache`
+async fn register_neighbours_request(
+    reader: &MessageReader,
+    radio_cache: &mut RadioCache,
+    node_id: NodeId,
+    public_key: &[u8; 32],
+    tag: &[u8],
+) {
+    reader
+        .register_binary_request(
+            tag,
+            BinaryReqType::Neighbours,
+            public_key.to_vec(),
+            NEIGHBOURS_TIMEOUT,
+            HashMap::from([(
+                "pubkey_prefix_length".to_string(),
+                NEIGHBOURS_KEY_PREFIX_LENGTH.to_string(),
+            )]),
+            false,
+        )
+        .await;
+    radio_cache.neighbour_requests.insert(
+        hex_encode(tag),
+        (node_id, Instant::now() + NEIGHBOURS_TIMEOUT),
+    );
+}
+
+/// The neighbours in a response, with when they were last heard relative to `now`
+fn neighbours_heard(neighbours: NeighboursData, now: TimeStamp) -> Vec<MCNeighbour> {
+    neighbours
+        .neighbours
+        .into_iter()
+        .map(|neighbour| MCNeighbour {
+            node_id: neighbour.pubkey.into(),
+            snr: Some(neighbour.snr),
+            last_heard: now
+                - TimeStamp::from(
+                    u64::from(neighbour.secs_ago.unsigned_abs()).saturating_mul(1000),
+                ),
+        })
+        .collect()
+}
+
+/// Report the sent messages that were not acknowledged before their deadline as failed
+async fn expire_pending_acks(
+    radio_cache: &mut RadioCache,
+    gui_sender: &mut futures_channel::mpsc::Sender<DeviceEvent>,
+) {
+    for (message_id, conversation_id) in radio_cache.expire_pending_acks(Instant::now()) {
+        gui_sender
+            .send(MessageFailed(
+                conversation_id,
+                message_id,
+                "No acknowledgement".to_string(),
+            ))
+            .await
+            .unwrap_or_else(|e| eprintln!("Send error: {e}"));
+    }
+}
+
 /// Request battery level from the device.
 /// The response comes through the event stream and is handled by handle_radio_event.
 async fn request_battery(meshcore: &MeshCore) -> meshcore_rs::Result<()> {
@@ -625,11 +1000,29 @@ async fn handle_new_channel(
         .unwrap_or_else(|e| eprintln!("Send error: {e}"));
 }
 
+/// Send the neighbours in the response tagged `tag` to the GUI, as the neighbours of the node
+/// they were requested from, and tell it about neighbours that are not already known as contacts
 async fn handle_neighbours(
+    radio_cache: &mut RadioCache,
+    tag: Option<&str>,
     neighbours: NeighboursData,
     gui_sender: &mut futures_channel::mpsc::Sender<DeviceEvent>,
 ) {
+    if let Some((node_id, _)) = tag.and_then(|tag| radio_cache.neighbour_requests.remove(tag)) {
+        gui_sender
+            .send(NeighbourInfo(
+                node_id,
+                neighbours_heard(neighbours.clone(), TimeStamp::now()),
+            ))
+            .await
+            .unwrap_or_else(|e| eprintln!("Send error: {e}"));
+    }
+
     for neighbour in neighbours.neighbours {
+        let node_id: NodeId = neighbour.pubkey.clone().into();
+        if radio_cache.public_keys.contains_key(&node_id) {
+            continue;
+        }
         gui_sender
             .send(NewNode(neighbour.into()))
             .await
@@ -661,6 +1054,7 @@ async fn handle_new_channel_message(
         node_id,
         MCContent::NewTextMessage(text.to_string()),
         TimeStamp::now(),
+        link_quality(channel_message.snr, channel_message.path_len),
     );
 
     gui_sender
@@ -679,18 +1073,21 @@ async fn handle_new_contact_message(
         .unwrap_or_else(|e| eprintln!("Send error: {e}"));
 }
 
+/// Handle an event from the radio. There is no [MeshCore] to make requests to the radio with
+/// when the event is being replayed from a capture.
 async fn handle_radio_event(
     connected_device_id: &DeviceIdentifier,
     radio_cache: &mut RadioCache,
-    meshcore: &MeshCore,
+    meshcore: Option<&MeshCore>,
     meshcore_event: Box<MeshCoreEvent>,
     gui_sender: &mut futures_channel::mpsc::Sender<DeviceEvent>,
 ) -> meshcore_rs::Result<()> {
     // jonesy:allow(misaligned_ptr) via meshcore_rs EventPayload enum matching (misaligned_ptr)
     match meshcore_event.event_type {
         EventType::NeighboursResponse => {
+            let tag = meshcore_event.attributes.get("tag").cloned();
             if let EventPayload::Neighbours(neighbours) = meshcore_event.payload {
-                handle_neighbours(neighbours, gui_sender).await;
+                handle_neighbours(radio_cache, tag.as_deref(), neighbours, gui_sender).await;
             }
         }
         EventType::Contacts => {
@@ -700,10 +1097,8 @@ async fn handle_radio_event(
         }
         EventType::NewContact | EventType::NextContact => {
             if let EventPayload::Contact(contact) = meshcore_event.payload {
-                gui_sender
-                    .send(NewNode(contact.into()))
-                    .await
-                    .unwrap_or_else(|e| eprintln!("Send error: {e}"));
+                // jonesy:allow(bounds) via handle_new_contact -> Contact::prefix()
+                handle_new_contact(radio_cache, contact, gui_sender).await;
             }
         }
         EventType::SelfInfo => {
@@ -737,7 +1132,10 @@ async fn handle_radio_event(
             }
         }
         EventType::MessagesWaiting => {
-            get_pending_messages(radio_cache, meshcore, gui_sender).await;
+            // When replaying, the messages fetched are in the capture after this event
+            if let Some(meshcore) = meshcore {
+                get_pending_messages(radio_cache, meshcore, gui_sender).await;
+            }
         }
         EventType::DiscoverResponse => {
             if let EventPayload::DiscoverResponse(discover_entry) = meshcore_event.payload {
@@ -770,7 +1168,7 @@ async fn handle_radio_event(
         EventType::Ack => {
             if let EventPayload::Ack { tag } = meshcore_event.payload {
                 let message_id: MessageId = tag.into();
-                if let Some(conversation_id) = radio_cache.pending_ack.remove(&message_id) {
+                if let Some((conversation_id, _)) = radio_cache.pending_ack.remove(&message_id) {
                     gui_sender
                         .send(MessageACK(conversation_id, message_id))
                         .await
@@ -804,6 +1202,66 @@ async fn handle_radio_event(
     Ok(())
 }
 
+/// Replay the events captured in the file at `path` through [handle_radio_event], as if the
+/// radio they were captured from was attached, until the GUI asks to disconnect
+async fn replay_capture(
+    path: &str,
+    device: &DeviceIdentifier,
+    radio_cache: &mut RadioCache,
+    gui_stream: &mut Pin<Box<dyn Stream<Item = DeviceCommand> + Send>>,
+    gui_sender: &mut futures_channel::mpsc::Sender<DeviceEvent>,
+) {
+    let records = match capture::load::<CapturedEvent>(Path::new(path)).await {
+        Ok((_header, records)) => records,
+        Err(e) => {
+            gui_sender
+                .send(ConnectionError(
+                    device.clone(),
+                    format!("Failed to replay {}", device.name()),
+                    e.to_string(),
+                ))
+                .await
+                .unwrap_or_else(|e| eprintln!("Send error: {e}"));
+            return;
+        }
+    };
+
+    gui_sender
+        .send(ConnectedEvent(device.clone(), RadioType::MeshCore))
+        .await
+        .unwrap_or_else(|e| eprintln!("Send error: {e}"));
+
+    let events = capture::replay(records)
+        .map(|captured_event| MeshCoreRadioPacket(Box::new(captured_event.into())));
+    let mut merged_stream = Box::pin(events.merge(gui_stream));
+
+    // jonesy:allow(unknown) via async poll
+    while let Some(message) = merged_stream.next().await {
+        let result = match message {
+            Disconnect => break,
+            MeshCoreRadioPacket(meshcore_event) => {
+                radio_cache.replay_neighbour_request(&meshcore_event);
+                handle_radio_event(device, radio_cache, None, meshcore_event, gui_sender).await
+            }
+            _ => Err(Error::connection(
+                "A capture being replayed can't be sent to",
+            )),
+        };
+
+        if let Err(e) = result {
+            gui_sender
+                .send(SendError("Subscription Error".to_string(), e.to_string()))
+                .await
+                .unwrap_or_else(|e| eprintln!("Send error: {e}"));
+        }
+    }
+
+    gui_sender
+        .send(DisconnectedEvent(device.clone()))
+        .await
+        .unwrap_or_else(|e| eprintln!("Send error: {e}"));
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -811,7 +1269,9 @@ mod tests {
     use crate::timestamp::TimeStamp;
     use futures::StreamExt;
     use futures::channel::mpsc;
-    use meshcore_rs::events::{BatteryInfo, SelfInfo};
+    use meshcore_rs::PacketType;
+    use meshcore_rs::events::{BatteryInfo, EventDispatcher, SelfInfo};
+    use std::sync::Arc;
 
     // Helper to create a test sender/receiver pair
     fn create_test_channel() -> (mpsc::Sender<DeviceEvent>, mpsc::Receiver<DeviceEvent>) {
@@ -1236,7 +1696,7 @@ mod tests {
             .await
             .expect("Expected MCMessageReceived event");
 
-        if let MCMessageReceived(_, _, _, _, timestamp) = event {
+        if let MCMessageReceived(_, _, _, _, timestamp, _) = event {
             assert!(
                 timestamp >= before && timestamp <= after,
                 "Timestamp should be local time",
@@ -1271,7 +1731,7 @@ mod tests {
             .await
             .expect("Expected MCMessageReceived event");
 
-        if let MCMessageReceived(_, _, _, _, timestamp) = event {
+        if let MCMessageReceived(_, _, _, _, timestamp, _) = event {
             assert!(
                 timestamp >= before && timestamp <= after,
                 "Timestamp should be local time",
@@ -1280,4 +1740,508 @@ mod tests {
             unreachable!("Expected MCMessageReceived event, got {:?}", event);
         }
     }
+
+    // Tests for neighbours
+
+    fn create_test_contact(name: &str, public_key: [u8; 32]) -> Contact {
+        Contact {
+            public_key,
+            contact_type: 2,
+            flags: 0,
+            path_len: -1,
+            out_path: vec![],
+            adv_name: name.to_string(),
+            last_advert: 0,
+            adv_lat: 0,
+            adv_lon: 0,
+            last_modification_timestamp: 0,
+        }
+    }
+
+    #[test]
+    fn neighbours_heard_ago() {
+        let neighbours = NeighboursData {
+            total: 1,
+            neighbours: vec![meshcore_rs::events::Neighbour {
+                pubkey: vec![1, 2, 3, 4, 5, 6],
+                secs_ago: 60,
+                snr: 7.25,
+            }],
+        };
+        let heard = neighbours_heard(neighbours, TimeStamp::from(100_000u64));
+        assert_eq!(
+            heard,
+            vec![MCNeighbour {
+                node_id: NodeId::from(0x0102_0304_0506_0000u64),
+                snr: Some(7.25),
+                last_heard: TimeStamp::from(40_000u64),
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn contact_public_key_kept() {
+        let mut radio_cache = RadioCache::default();
+        let (mut sender, _receiver) = create_test_channel();
+        let public_key = [0x33u8; 32];
+
+        handle_new_contact(
+            &mut radio_cache,
+            create_test_contact("Repeater", public_key),
+            &mut sender,
+        )
+        .await;
+
+        let node_id: NodeId = (&public_key).into();
+        let node_id = NodeId::from(u64::from(node_id) & 0xFFFF_FFFF_FFFF_0000);
+        assert_eq!(radio_cache.public_keys.get(&node_id), Some(&public_key));
+    }
+
+    #[tokio::test]
+    async fn neighbours_that_are_contacts_not_new_nodes() {
+        let mut radio_cache = RadioCache::default();
+        let (mut sender, mut receiver) = create_test_channel();
+        handle_new_contact(
+            &mut radio_cache,
+            create_test_contact("Repeater", [0x33u8; 32]),
+            &mut sender,
+        )
+        .await;
+        let _ = receiver.next().await;
+
+        let neighbours = NeighboursData {
+            total: 2,
+            neighbours: vec![
+                meshcore_rs::events::Neighbour {
+                    pubkey: vec![0x33; 6],
+                    secs_ago: 10,
+                    snr: 1.0,
+                },
+                meshcore_rs::events::Neighbour {
+                    pubkey: vec![0x44; 6],
+                    secs_ago: 10,
+                    snr: 1.0,
+                },
+            ],
+        };
+        handle_neighbours(&mut radio_cache, None, neighbours, &mut sender).await;
+        drop(sender);
+
+        let events: Vec<DeviceEvent> = receiver.collect().await;
+        assert_eq!(events.len(), 1);
+        assert!(matches!(events.first(), Some(NewNode(node))
+            if node.node_id == NodeId::from(0x4444_4444_4444_0000u64)));
+    }
+
+    #[test]
+    fn neighbours_request_wire_format() {
+        let request = neighbours_request(&[0x33u8; 32], 0x0102_0304);
+        assert_eq!(request.len(), 44);
+        assert_eq!(request[0], CMD_SEND_BINARY_REQ);
+        assert_eq!(&request[1..33], &[0x33u8; 32]);
+        assert_eq!(request[33], BinaryReqType::Neighbours as u8);
+        assert_eq!(
+            &request[34..],
+            &[
+                0,
+                NEIGHBOURS_COUNT,
+                0,
+                0,
+                0,
+                NEIGHBOURS_KEY_PREFIX_LENGTH,
+                4,
+                3,
+                2,
+                1
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn requested_neighbours_sent_for_node() {
+        let mut radio_cache = RadioCache::default();
+        let node_id = NodeId::from(0x3333_3333_3333_0000u64);
+        radio_cache
+            .neighbour_requests
+            .insert("0a0b0c0d".into(), (node_id, Instant::now()));
+        let (mut sender, receiver) = create_test_channel();
+
+        let neighbours = NeighboursData {
+            total: 1,
+            neighbours: vec![meshcore_rs::events::Neighbour {
+                pubkey: vec![0x44; 6],
+                secs_ago: 10,
+                snr: 1.0,
+            }],
+        };
+        handle_neighbours(&mut radio_cache, Some("0a0b0c0d"), neighbours, &mut sender).await;
+        drop(sender);
+
+        let events: Vec<DeviceEvent> = receiver.collect().await;
+        assert_eq!(events.len(), 2);
+        assert!(matches!(events.first(), Some(NeighbourInfo(from, heard))
+            if *from == node_id && heard.len() == 1));
+        assert!(radio_cache.neighbour_requests.is_empty());
+    }
+
+    #[tokio::test]
+    async fn neighbours_response_tag_matches_request() {
+        let dispatcher = Arc::new(EventDispatcher::new());
+        let reader = MessageReader::new(dispatcher.clone());
+        let mut events = dispatcher.receiver();
+        let mut radio_cache = RadioCache::default();
+        let node_id = NodeId::from(0x3333_3333_3333_0000u64);
+        let tag = [0x0a, 0x0b, 0x0c, 0x0d];
+        register_neighbours_request(&reader, &mut radio_cache, node_id, &[0x33; 32], &tag).await;
+
+        // The response as the radio sends it: subtype, tag, then one neighbour
+        let mut response = vec![PacketType::BinaryResponse as u8, 0];
+        response.extend_from_slice(&tag);
+        response.extend_from_slice(&1u16.to_le_bytes()); // total
+        response.extend_from_slice(&1u16.to_le_bytes()); // count
+        response.extend_from_slice(&[0x44; NEIGHBOURS_KEY_PREFIX_LENGTH as usize]);
+        response.extend_from_slice(&10i32.to_le_bytes()); // seconds ago
+        response.push(4); // SNR
+        reader
+            .handle_rx(response)
+            .await
+            .expect("Could not read the response");
+        let event = events.recv().await.expect("Expected a NeighboursResponse");
+        assert_eq!(event.event_type, EventType::NeighboursResponse);
+
+        let (mut sender, receiver) = create_test_channel();
+        handle_radio_event(
+            &DeviceIdentifier::from("device1"),
+            &mut radio_cache,
+            None,
+            Box::new(event),
+            &mut sender,
+        )
+        .await
+        .expect("Could not handle the NeighboursResponse event");
+        drop(sender);
+
+        let events: Vec<DeviceEvent> = receiver.collect().await;
+        assert!(matches!(events.first(), Some(NeighbourInfo(from, heard))
+            if *from == node_id && heard.len() == 1));
+        assert!(radio_cache.neighbour_requests.is_empty());
+    }
+
+    #[tokio::test]
+    async fn unrequested_neighbours_not_sent_for_node() {
+        let mut radio_cache = RadioCache::default();
+        let (mut sender, receiver) = create_test_channel();
+        let neighbours = NeighboursData {
+            total: 0,
+            neighbours: vec![],
+        };
+        handle_neighbours(&mut radio_cache, Some("0a0b0c0d"), neighbours, &mut sender).await;
+        drop(sender);
+
+        let events: Vec<DeviceEvent> = receiver.collect().await;
+        assert!(events.is_empty());
+    }
+
+    #[test]
+    fn expire_neighbour_requests_only_past_deadline() {
+        let mut radio_cache = RadioCache::default();
+        let now = Instant::now();
+        radio_cache
+            .neighbour_requests
+            .insert("old".into(), (NodeId::from(1u64), now));
+        radio_cache.neighbour_requests.insert(
+            "new".into(),
+            (NodeId::from(2u64), now + Duration::from_secs(30)),
+        );
+
+        radio_cache.expire_neighbour_requests(now);
+        assert_eq!(radio_cache.neighbour_requests.len(), 1);
+        assert!(radio_cache.neighbour_requests.contains_key("new"));
+    }
+
+    // Tests for pending ACKs
+
+    #[test]
+    fn expire_pending_acks_only_past_deadline() {
+        let mut radio_cache = RadioCache::default();
+        let now = Instant::now();
+        let conversation_id = Node(NodeId::from(42u64));
+        radio_cache
+            .pending_ack
+            .insert(MessageId::from(1u32), (conversation_id, now));
+        radio_cache.pending_ack.insert(
+            MessageId::from(2u32),
+            (conversation_id, now + Duration::from_secs(30)),
+        );
+
+        let expired = radio_cache.expire_pending_acks(now);
+        assert_eq!(expired, vec![(MessageId::from(1u32), conversation_id)]);
+        assert_eq!(radio_cache.pending_ack.len(), 1);
+
+        let expired = radio_cache.expire_pending_acks(now + Duration::from_secs(31));
+        assert_eq!(expired, vec![(MessageId::from(2u32), conversation_id)]);
+        assert!(radio_cache.pending_ack.is_empty());
+    }
+
+    #[tokio::test]
+    async fn expired_ack_sends_message_failed() {
+        let mut radio_cache = RadioCache::default();
+        let conversation_id = Node(NodeId::from(42u64));
+        radio_cache
+            .pending_ack
+            .insert(MessageId::from(1u32), (conversation_id, Instant::now()));
+        let (mut sender, mut receiver) = create_test_channel();
+
+        expire_pending_acks(&mut radio_cache, &mut sender).await;
+
+        let event = receiver.next().await.expect("Expected MessageFailed event");
+        assert!(matches!(event, MessageFailed(id, message_id, _)
+            if id == conversation_id && message_id == MessageId::from(1u32)));
+    }
+
+    #[tokio::test]
+    async fn ack_removes_pending_ack() {
+        let mut radio_cache = RadioCache::default();
+        let conversation_id = Node(NodeId::from(42u64));
+        radio_cache.pending_ack.insert(
+            MessageId::from(7u32),
+            (conversation_id, Instant::now() + MAX_ACK_TIMEOUT),
+        );
+        let (mut sender, mut receiver) = create_test_channel();
+
+        let event = MeshCoreEvent::new(
+            EventType::Ack,
+            EventPayload::Ack {
+                tag: 7u32.to_be_bytes(),
+            },
+        );
+        handle_radio_event(
+            &DeviceIdentifier::from("device1"),
+            &mut radio_cache,
+            None,
+            Box::new(event),
+            &mut sender,
+        )
+        .await
+        .expect("Could not handle the Ack event");
+
+        let event = receiver.next().await.expect("Expected MessageACK event");
+        assert!(matches!(event, MessageACK(id, message_id)
+            if id == conversation_id && message_id == MessageId::from(7u32)));
+        assert!(radio_cache.pending_ack.is_empty());
+        assert!(
+            radio_cache
+                .expire_pending_acks(Instant::now() + MAX_ACK_TIMEOUT)
+                .is_empty()
+        );
+    }
+
+    #[cfg(feature = "tcp")]
+    #[tokio::test]
+    async fn test_tcp_connect() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("Could not bind a listener");
+        let port = listener
+            .local_addr()
+            .expect("Could not get listener address")
+            .port();
+        let device = DeviceIdentifier::from(format!("tcp://127.0.0.1:{port}#MeshCore").as_str());
+
+        let accept = tokio::spawn(async move { listener.accept().await });
+        assert!(do_connect(&device).await.is_ok());
+        assert!(accept.await.expect("Accept task failed").is_ok());
+    }
+
+    #[cfg(feature = "tcp")]
+    #[tokio::test]
+    async fn test_tcp_connect_refused() {
+        // Bind then drop a listener to find a port that nothing is listening on
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .expect("Could not bind a listener")
+            .local_addr()
+            .expect("Could not get listener address")
+            .port();
+        let device = DeviceIdentifier::from(format!("tcp://127.0.0.1:{port}#MeshCore").as_str());
+
+        assert!(do_connect(&device).await.is_err());
+    }
+
+    #[cfg(feature = "serial")]
+    #[tokio::test]
+    async fn test_serial_connect_missing_port() {
+        let device = DeviceIdentifier::from("serial:///dev/does-not-exist?baud=115200");
+        assert!(do_connect(&device).await.is_err());
+    }
+
+    // Tests for capture and replay
+
+    /// Capture `events` as the radio cache does when connected to a radio, and return the path
+    /// of the capture file
+    async fn capture_events(dir: &Path, events: &[MeshCoreEvent]) -> String {
+        let device = DeviceIdentifier::from("AA:BB:CC:DD:EE:FF");
+        let capture = Capture::create(dir, &device, RadioType::MeshCore)
+            .await
+            .expect("Could not create capture");
+        let path = capture.path().to_string_lossy().to_string();
+        let mut radio_cache = RadioCache {
+            capture: Some(capture),
+            ..Default::default()
+        };
+        for event in events {
+            radio_cache.record(event).await;
+        }
+        path
+    }
+
+    /// Replay the capture at `path` until the GUI disconnects, returning the events sent to it
+    async fn replay_events(path: &str) -> Vec<DeviceEvent> {
+        let device = DeviceIdentifier::Replay {
+            path: path.to_string(),
+        };
+        let mut radio_cache = RadioCache::default();
+        let (mut sender, receiver) = create_test_channel();
+        let mut gui_stream = Box::pin(async_stream::stream! {
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            yield Disconnect;
+        }) as Pin<Box<dyn Stream<Item = DeviceCommand> + Send>>;
+
+        replay_capture(
+            path,
+            &device,
+            &mut radio_cache,
+            &mut gui_stream,
+            &mut sender,
+        )
+        .await;
+        drop(sender);
+        receiver.collect().await
+    }
+
+    #[tokio::test]
+    async fn capture_and_replay_channel_message() {
+        let tempdir = tempfile::Builder::new()
+            .prefix("meshchat")
+            .tempdir()
+            .expect("Could not create a temp dir");
+        let alice_key = [0x11u8; 32];
+        let path = capture_events(
+            tempdir.path(),
+            &[
+                MeshCoreEvent::new(
+                    EventType::SelfInfo,
+                    EventPayload::SelfInfo(create_test_self_info("Me", [0x22u8; 32], 0, 0)),
+                ),
+                MeshCoreEvent::ok(),
+                MeshCoreEvent::new(
+                    EventType::NewContact,
+                    EventPayload::Contact(Contact {
+                        public_key: alice_key,
+                        contact_type: 1,
+                        flags: 0,
+                        path_len: -1,
+                        out_path: vec![],
+                        adv_name: "Alice".to_string(),
+                        last_advert: 0,
+                        adv_lat: 0,
+                        adv_lon: 0,
+                        last_modification_timestamp: 0,
+                    }),
+                ),
+                MeshCoreEvent::new(
+                    EventType::ChannelMsgRecv,
+                    EventPayload::ChannelMessage(ChannelMessage {
+                        channel_idx: 0,
+                        path_len: 1,
+                        txt_type: 0,
+                        sender_timestamp: 1234,
+                        text: "Alice: Hello".to_string(),
+                        snr: None,
+                    }),
+                ),
+            ],
+        )
+        .await;
+
+        let (_header, records) = capture::load::<CapturedEvent>(Path::new(&path))
+            .await
+            .expect("Could not load capture");
+        assert_eq!(records.len(), 3, "Ignored events should not be captured");
+
+        let events = replay_events(&path).await;
+        assert!(matches!(
+            events.first(),
+            Some(ConnectedEvent(_, RadioType::MeshCore))
+        ));
+        assert!(events.iter().any(|event| matches!(event, MyNodeNum(_))));
+
+        // The contact replayed earlier is who the channel message is from
+        let alice_id: NodeId = (&[0x11u8; 6]).into();
+        let from = events
+            .iter()
+            .find_map(|event| match event {
+                MCMessageReceived(Channel(_), _, from, MCContent::NewTextMessage(text), _, _)
+                    if text == "Hello" =>
+                {
+                    Some(*from)
+                }
+                _ => None,
+            })
+            .expect("Expected the channel message to be replayed");
+        assert_eq!(from, alice_id);
+
+        assert!(matches!(events.last(), Some(DisconnectedEvent(_))));
+    }
+
+    #[tokio::test]
+    async fn capture_and_replay_neighbours() {
+        let tempdir = tempfile::Builder::new()
+            .prefix("meshchat")
+            .tempdir()
+            .expect("Could not create a temp dir");
+        let device = DeviceIdentifier::from("AA:BB:CC:DD:EE:FF");
+        let capture = Capture::create(tempdir.path(), &device, RadioType::MeshCore)
+            .await
+            .expect("Could not create capture");
+        let path = capture.path().to_string_lossy().to_string();
+        let node_id = NodeId::from(42u64);
+        let mut radio_cache = RadioCache {
+            capture: Some(capture),
+            ..Default::default()
+        };
+        radio_cache.neighbour_requests.insert(
+            "01020304".to_string(),
+            (node_id, Instant::now() + NEIGHBOURS_TIMEOUT),
+        );
+        radio_cache
+            .record(
+                &MeshCoreEvent::new(
+                    EventType::NeighboursResponse,
+                    EventPayload::Neighbours(NeighboursData {
+                        total: 1,
+                        neighbours: vec![meshcore_rs::events::Neighbour {
+                            pubkey: vec![0x33; 6],
+                            secs_ago: 10,
+                            snr: 1.5,
+                        }],
+                    }),
+                )
+                .with_attribute("tag", "01020304"),
+            )
+            .await;
+
+        // The response is for the node asked when it was captured, though none is asked now
+        let events = replay_events(&path).await;
+        assert!(events.iter().any(|event| matches!(
+            event,
+            NeighbourInfo(from, neighbours) if *from == node_id && neighbours.len() == 1
+        )));
+    }
+
+    #[tokio::test]
+    async fn replay_missing_capture_errors() {
+        let events = replay_events("/does/not/exist.jsonl").await;
+        assert_eq!(events.len(), 1);
+        assert!(matches!(events.first(), Some(ConnectionError(..))));
+    }
 }
@@ -1,15 +1,21 @@
 #[cfg(feature = "auto-update")]
 use crate::Message::UpdateChecked;
 use crate::Message::{
-    AddDeviceAlias, AddNodeAlias, AppError, AppNotification, CloseSettingsDialog, CloseShowUser,
-    ConfigLoaded, CopyToClipBoard, CriticalAppError, DeviceAndChannelConfigChange,
-    DeviceListViewEvent, DeviceViewEvent, Exit, HistoryLengthSelected, Navigation,
-    OpenSettingsDialog, OpenUrl, RemoveDeviceAlias, RemoveNodeAlias, RemoveNotification,
-    SetWindowPosition, SetWindowSize, ShowLocation, ShowUserInfo, ToggleAutoReconnect,
-    ToggleAutoUpdate, ToggleNodeFavourite, ToggleSaveWindowPosition, ToggleSaveWindowSize,
-    ToggleShowPositionUpdates, ToggleShowUserUpdates,
+    AddDeviceAlias, AddManualDevice, AddNodeAlias, AppError, AppNotification, ChooseMapTilesFile,
+    ChooseMapTilesFolder, CloseSettingsDialog, CloseShowUser, ConfigLoaded,
+    ConversationSettingsChanged, CopyToClipBoard, CriticalAppError, DeviceAndChannelConfigChange,
+    DeviceListViewEvent, DeviceViewEvent, Exit, ExportConversation, ExportPositions, ExportTrack,
+    HistoryLengthSelected, MapTilesSelected, MentionNotification, Navigation, NodeSortSelected,
+    OfflineAfterSelected, OnlineWithinSelected, OpenSettingsDialog, OpenUrl, RemoveDeviceAlias,
+    RemoveManualDevice, RemoveNodeAlias, RemoveNotification, ReplayCapture, SetWindowPosition,
+    SetWindowSize, ShowLocation, ShowUserInfo, ToggleAutoReconnect, ToggleAutoResend,
+    ToggleAutoUpdate, ToggleCaptureTraffic, ToggleNodeFavourite, ToggleSaveWindowPosition,
+    ToggleSaveWindowSize, ToggleShowPositionUpdates, ToggleShowUserUpdates, UnitsSelected,
+};
+use crate::capture;
+use crate::config::{
+    Config, ConversationSettings, HeardWithin, HistoryLength, NodeSort, Units, load_config,
 };
-use crate::config::{Config, HistoryLength, load_config};
 use crate::conversation_id::{ConversationId, NodeId};
 use crate::device::ConnectionState::Connected;
 use crate::device::DeviceMessage;
@@ -20,15 +26,24 @@ use crate::device::{Device, DeviceIdentifier};
 use crate::device_list::{DeviceList, DeviceListEvent, RadioType};
 #[cfg(feature = "bluetooth")]
 use crate::discovery::ble_discovery;
-#[cfg(feature = "tcp")]
+#[cfg(all(feature = "tcp", feature = "meshtastic"))]
 use crate::discovery::mdns_discovery;
+#[cfg(feature = "serial")]
+use crate::discovery::serial_discovery;
+#[cfg(feature = "sim")]
+use crate::discovery::sim_discovery;
+use crate::export::ExportFormat;
+use crate::map;
 #[cfg(feature = "meshcore")]
 use crate::meshc;
 #[cfg(feature = "meshtastic")]
 use crate::mesht;
 use crate::notification::{Notification, Notifications};
+#[cfg(feature = "sim")]
+use crate::sim;
 use crate::styles::{modal_style, picker_header_style, tooltip_style};
 use crate::timestamp::TimeStamp;
+use crate::tracks::TrackFormat;
 use iced::font::Weight;
 use iced::keyboard::key;
 use iced::widget::{Column, center, container, mouse_area, opaque, operation, stack, text};
@@ -36,14 +51,16 @@ use iced::{Center, Event, Font, Point, Size, Subscription, Task, clipboard, keyb
 use iced::{Element, Fill, event};
 #[cfg(feature = "auto-update")]
 use self_update::Status;
+use serde::{Deserialize, Serialize};
 use std::cmp::PartialEq;
 use std::fmt;
 use std::fmt::Formatter;
+use std::path::PathBuf;
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 
 /// A User as represented in the App, maybe a superset of User attributes from different meshes
-#[derive(Debug, Clone, Default, PartialEq)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 pub struct MCUser {
     pub id: String,
     pub long_name: String,
@@ -69,15 +86,28 @@ impl fmt::Display for MCUser {
 }
 
 /// A Node's Info as represented in the App, maybe a superset of User attributes from different meshes
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct MCNodeInfo {
     pub node_id: NodeId,
     pub user: Option<MCUser>,
     pub position: Option<MCPosition>,
     pub is_ignored: bool,
+    /// How many hops away the node is, if known
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hops_away: Option<u32>,
+}
+
+/// A node heard directly by another node, as reported by that node
+#[derive(Clone, Debug, PartialEq)]
+pub struct MCNeighbour {
+    pub node_id: NodeId,
+    /// The signal to noise ratio (dB) the neighbour is heard with, if reported
+    pub snr: Option<f32>,
+    /// When the neighbour was last heard
+    pub last_heard: TimeStamp,
 }
 
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct MCPosition {
     pub latitude: f64,
     pub longitude: f64,
@@ -111,6 +141,47 @@ impl fmt::Display for MCPosition {
     }
 }
 
+/// A named location shared on the mesh, that may expire
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct MCWaypoint {
+    /// Identifies the waypoint, so a later version of it replaces an earlier one
+    pub id: u32,
+    pub name: String,
+    pub description: String,
+    /// An emoji to show for the waypoint, if one was chosen
+    pub icon: Option<char>,
+    pub latitude: f64,
+    pub longitude: f64,
+    /// When the waypoint expires, or None if it never does
+    pub expire: Option<TimeStamp>,
+}
+
+impl MCWaypoint {
+    /// Return true if the waypoint has expired at `now`
+    pub fn is_expired(&self, now: TimeStamp) -> bool {
+        self.expire.is_some_and(|expire| expire <= now)
+    }
+
+    /// The position of the waypoint, so it can be shown like a node's position
+    pub fn position(&self) -> MCPosition {
+        MCPosition {
+            latitude: self.latitude,
+            longitude: self.longitude,
+            ..Default::default()
+        }
+    }
+}
+
+impl fmt::Display for MCWaypoint {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.icon.unwrap_or('🚩'), self.name)?;
+        if !self.description.is_empty() {
+            write!(f, ": {}", self.description)?;
+        }
+        Ok(())
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Default)]
 pub enum View {
     #[default]
@@ -124,6 +195,32 @@ pub struct MCChannel {
     pub name: String,
 }
 
+/// The settings of a channel needed to join it, as shared between radios
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MCChannelSettings {
+    /// The name of the channel, empty for the radio's default name
+    pub name: String,
+    /// The pre-shared key messages on the channel are encrypted with. A single byte is shorthand
+    /// for one of the well-known keys
+    pub psk: Vec<u8>,
+    pub uplink_enabled: bool,
+    pub downlink_enabled: bool,
+    /// How many bits of position sent on the channel are kept, 0 if position is not sent
+    pub position_precision: u32,
+}
+
+/// The channels shared in a channel URL, and how to join them
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MCChannelSet {
+    /// The channels, the primary channel first when they replace the channels on the radio
+    pub channels: Vec<MCChannelSettings>,
+    /// Add the channels to the slots not in use on the radio, instead of replacing its channels
+    pub add: bool,
+    /// The LoRa settings shared with the channels, set on the radio when its channels are replaced
+    #[cfg(feature = "meshtastic")]
+    pub lora_config: Option<meshtastic::protobufs::config::LoRaConfig>,
+}
+
 /// These are the messages that MeshChat responds to
 #[derive(Debug, Clone)]
 pub enum Message {
@@ -141,27 +238,46 @@ pub enum Message {
     CloseShowUser,
     OpenUrl(String),
     AppNotification(String, String, TimeStamp), // Message, detail, TimeStamp
-    AppError(String, String, TimeStamp),        // Message, detail, TimeStamp
+    /// A message mentioning the user, only notified of if the conversation's settings say so
+    MentionNotification(DeviceIdentifier, ConversationId, String, String, TimeStamp),
+    AppError(String, String, TimeStamp), // Message, detail, TimeStamp
     CriticalAppError(String, String, TimeStamp), // Message, detail, TimeStamp
     RemoveNotification(usize),
     ToggleNodeFavourite(NodeId),
+    /// Change how a conversation on a device counts unread messages and notifies of new ones
+    ConversationSettingsChanged(DeviceIdentifier, ConversationId, ConversationSettings),
     CopyToClipBoard(String),
     AddNodeAlias(NodeId, String),
     RemoveNodeAlias(NodeId),
     AddDeviceAlias(String, String),
     RemoveDeviceAlias(String),
+    AddManualDevice(DeviceIdentifier, RadioType),
+    RemoveManualDevice(String),
     Event(Event),
     OpenSettingsDialog,
     CloseSettingsDialog,
     ToggleShowPositionUpdates,
     ToggleShowUserUpdates,
     ToggleAutoReconnect,
+    ToggleAutoResend,
     ToggleAutoUpdate,
     ToggleSaveWindowSize,
     SetWindowSize(Size),
     SetWindowPosition(Option<Point>),
     ToggleSaveWindowPosition,
+    ToggleCaptureTraffic,
+    ReplayCapture,
     HistoryLengthSelected(HistoryLength),
+    NodeSortSelected(NodeSort),
+    OnlineWithinSelected(HeardWithin),
+    OfflineAfterSelected(HeardWithin),
+    UnitsSelected(Units),
+    ChooseMapTilesFile,
+    ChooseMapTilesFolder,
+    MapTilesSelected(Option<PathBuf>),
+    ExportConversation(ConversationId, ExportFormat),
+    ExportTrack(NodeId, TrackFormat),
+    ExportPositions(TrackFormat),
     #[cfg(feature = "auto-update")]
     UpdateChecked(Result<Status, String>),
     None,
@@ -207,6 +323,7 @@ impl MeshChat {
     /// Include the version number of the app and the number of unread messages, if any
     pub(crate) fn title(&self) -> String {
         let unread_count = self.device.unread_count(
+            &self.config,
             self.config.show_position_updates,
             self.config.show_user_updates,
         );
@@ -247,12 +364,42 @@ impl MeshChat {
     pub(crate) fn update(&mut self, message: Message) -> Task<Message> {
         match message {
             Navigation(view) => self.navigate(view),
+            // A radio type chosen by the user is kept over the one the device is discovered as
+            DeviceListViewEvent(DeviceListEvent::MeshRadioFound(device, radio_type)) => {
+                let radio_type = self
+                    .config
+                    .manual_devices
+                    .get(&String::from(&device))
+                    .copied()
+                    .unwrap_or(radio_type);
+                self.device_list
+                    .update(DeviceListEvent::MeshRadioFound(device, radio_type))
+            }
+            DeviceListViewEvent(DeviceListEvent::SelectRadioType(device, radio_type)) => {
+                self.config
+                    .manual_devices
+                    .insert(String::from(&device), radio_type);
+                Task::batch(vec![
+                    self.device_list
+                        .update(DeviceListEvent::SelectRadioType(device, radio_type)),
+                    self.config.save_config(),
+                ])
+            }
             DeviceListViewEvent(device_list_event) => self.device_list.update(device_list_event),
             DeviceViewEvent(device_event) => self.device.update(device_event),
             Exit => window::latest().and_then(window::close),
             AppNotification(summary, detail, timestamp) => self
                 .notifications
                 .add(Notification::Info(summary, detail, timestamp)),
+            MentionNotification(device, conversation_id, summary, detail, timestamp) => {
+                let settings = self.config.conversation_settings(&device, &conversation_id);
+                if settings.mentions_only && !settings.muted {
+                    self.notifications
+                        .add(Notification::Info(summary, detail, timestamp))
+                } else {
+                    Task::none()
+                }
+            }
             AppError(summary, detail, timestamp) => self
                 .notifications
                 .add(Notification::Error(summary, detail, timestamp)),
@@ -265,10 +412,21 @@ impl MeshChat {
                 self.device
                     .set_show_position_updates(config.show_position_updates);
                 self.device.set_show_user_updates(config.show_user_updates);
+                self.device.set_capture_traffic(config.capture_traffic);
+                self.device.set_auto_reconnect(config.auto_reconnect);
+                self.device.set_auto_resend(config.auto_resend);
+
+                let mut tasks = vec![self.device.set_map_tiles(config.map_tiles.as_deref())];
 
                 self.config = config;
 
-                let mut tasks = vec![];
+                // Devices added by hand are not discovered, so add them to the list of devices
+                for (device, radio_type) in &self.config.manual_devices {
+                    tasks.push(self.device_list.update(DeviceListEvent::MeshRadioFound(
+                        DeviceIdentifier::from(device.as_str()),
+                        *radio_type,
+                    )));
+                }
                 #[cfg(feature = "auto-update")]
                 if self.config.auto_update_startup {
                     tasks.push(Task::perform(check_for_update(), UpdateChecked));
@@ -303,6 +461,10 @@ impl MeshChat {
                 // jonesy:allow(overflow) via iced_runtime::task::Task::batch
                 Task::batch(tasks)
             }
+            // Don't remember a capture being replayed, to reconnect to it at startup
+            DeviceAndChannelConfigChange(Some((DeviceIdentifier::Replay { .. }, _)), _) => {
+                Task::none()
+            }
             DeviceAndChannelConfigChange(ble_device, conversation_id) => {
                 self.config.ble_device =
                     ble_device.map(|(device_id, radio_type)| (String::from(device_id), radio_type));
@@ -311,10 +473,7 @@ impl MeshChat {
                 self.config.save_config()
             }
             RemoveNotification(id) => self.notifications.remove(id),
-            ShowLocation(position) => {
-                let _ = webbrowser::open(&Self::location_url(&position));
-                Task::none()
-            }
+            ShowLocation(position) => self.device.show_location(&position),
             OpenUrl(url) => {
                 let _ = webbrowser::open(&url);
                 Task::none()
@@ -327,6 +486,11 @@ impl MeshChat {
                 // and save the config asynchronously, so that we don't block the GUI thread
                 self.config.save_config()
             }
+            ConversationSettingsChanged(device, conversation_id, settings) => {
+                self.config
+                    .set_conversation_settings(&device, conversation_id, settings);
+                self.config.save_config()
+            }
             CopyToClipBoard(string) => clipboard::write(string),
             AddNodeAlias(node_id, alias) => {
                 self.device.stop_editing_alias();
@@ -354,6 +518,28 @@ impl MeshChat {
                 self.config.device_aliases.remove(&ble_device);
                 self.config.save_config()
             }
+            AddManualDevice(device, radio_type) => {
+                #[cfg(feature = "tcp")]
+                self.device_list.clear_new_device();
+                self.config
+                    .manual_devices
+                    .insert(String::from(&device), radio_type);
+                Task::batch(vec![
+                    self.device_list
+                        .update(DeviceListEvent::MeshRadioFound(device, radio_type)),
+                    self.config.save_config(),
+                ])
+            }
+            RemoveManualDevice(ble_device) => {
+                if self.config.manual_devices.remove(&ble_device).is_some() {
+                    let _ = self.device_list.update(DeviceListEvent::MeshRadioLost(
+                        DeviceIdentifier::from(ble_device.as_str()),
+                    ));
+                    self.config.save_config()
+                } else {
+                    Task::none()
+                }
+            }
             Message::Event(event) => self.process_event(event),
             OpenSettingsDialog => {
                 self.showing_settings = true;
@@ -377,6 +563,12 @@ impl MeshChat {
             }
             ToggleAutoReconnect => {
                 self.config.auto_reconnect = !self.config.auto_reconnect;
+                self.device.set_auto_reconnect(self.config.auto_reconnect);
+                self.config.save_config()
+            }
+            ToggleAutoResend => {
+                self.config.auto_resend = !self.config.auto_resend;
+                self.device.set_auto_resend(self.config.auto_resend);
                 self.config.save_config()
             }
             ToggleAutoUpdate => {
@@ -403,13 +595,57 @@ impl MeshChat {
                     self.config.save_config()
                 }
             }
+            ToggleCaptureTraffic => {
+                self.config.capture_traffic = !self.config.capture_traffic;
+                self.device.set_capture_traffic(self.config.capture_traffic);
+                self.config.save_config()
+            }
+            ReplayCapture => capture::replay_capture(),
             HistoryLengthSelected(length) => {
                 self.config.history_length = length;
                 self.config.save_config()
             }
+            NodeSortSelected(node_sort) => {
+                self.config.node_sort = node_sort;
+                self.config.save_config()
+            }
+            // A node can't be offline before it stops being online, so move the other setting
+            // along with the one selected when they would cross
+            OnlineWithinSelected(online_within) => {
+                self.config.online_within = online_within;
+                self.config.offline_after = self.config.offline_after.max(online_within);
+                self.config.save_config()
+            }
+            OfflineAfterSelected(offline_after) => {
+                self.config.offline_after = offline_after;
+                self.config.online_within = self.config.online_within.min(offline_after);
+                self.config.save_config()
+            }
+            UnitsSelected(units) => {
+                self.config.units = units;
+                self.config.save_config()
+            }
+            ChooseMapTilesFile => map::choose_tiles(false),
+            ChooseMapTilesFolder => map::choose_tiles(true),
+            MapTilesSelected(path) => {
+                let open_task = self.device.set_map_tiles(path.as_deref());
+                // Only remember tiles that could be opened, to draw the map on next time
+                if self.device.map_tiles_path() != path.as_deref() {
+                    return open_task;
+                }
+                self.config.map_tiles = path;
+                Task::batch([open_task, self.config.save_config()])
+            }
+            ExportConversation(conversation_id, format) => {
+                self.device
+                    .export_conversation(conversation_id, format, &self.config)
+            }
+            ExportTrack(node_id, format) => self.device.export_track(node_id, format, &self.config),
+            ExportPositions(format) => self.device.export_positions(format, &self.config),
             ShowUserInfo(user) => {
+                let request_task = self.device.request_neighbours(&user);
                 self.show_user = Some(user);
-                Task::none()
+                request_task
             }
             CloseShowUser => {
                 self.show_user = None;
@@ -500,7 +736,10 @@ impl MeshChat {
         let state = self.device.connection_state();
 
         let header_view = match self.current_view {
-            View::DeviceListView => self.device_list.header(&self.config, state),
+            View::DeviceListView => {
+                self.device_list
+                    .header(&self.config, state, self.device.reconnecting())
+            }
             View::DeviceView(_) => self.device.header(&self.config, state, &self.device_list),
         };
 
@@ -525,6 +764,30 @@ impl MeshChat {
             return Self::modal(main_content_column, self.user(user), CloseShowUser);
         }
 
+        if let Some(traceroute) = self.device.traceroute_view(&self.config) {
+            return Self::modal(
+                main_content_column,
+                traceroute,
+                DeviceViewEvent(DeviceMessage::CloseTraceroute),
+            );
+        }
+
+        if let Some(waypoints) = self.device.waypoints_view(&self.config) {
+            return Self::modal(
+                main_content_column,
+                waypoints,
+                DeviceViewEvent(DeviceMessage::CloseWaypoints),
+            );
+        }
+
+        if let Some(channel_sharing) = self.device.channel_sharing_view() {
+            return Self::modal(
+                main_content_column,
+                channel_sharing,
+                DeviceViewEvent(DeviceMessage::CloseChannelSharing),
+            );
+        }
+
         main_content_column.into()
     }
 
@@ -539,7 +802,11 @@ impl MeshChat {
             .push(text(format!("Licensed: {}", user.is_licensed)))
             .push(text(format!("Role: {}", user.role_str)))
             .push(text(format!("Public Key: {:X?}", user.public_key)))
-            .push(text(format!("Unmessageable: {}", user.is_unmessagable)));
+            .push(text(format!("Unmessageable: {}", user.is_unmessagable)))
+            .push(self.device.distance_view(user, &self.config))
+            .push(self.device.neighbours_view(user, &self.config))
+            .push(self.device.telemetry_view(user))
+            .push(self.device.track_view(user));
 
         let inner = Column::new()
             .spacing(8)
@@ -579,28 +846,27 @@ impl MeshChat {
         .into()
     }
 
-    /// Convert a location tuple to a URL that can be opened in a browser.
-    pub fn location_url(position: &MCPosition) -> String {
-        format!(
-            "https://maps.google.com/?q={:.7},{:.7}",
-            position.latitude, position.longitude
-        )
-    }
-
     /// Subscribe to events from Discover and from Windows and from Devices (Radios)
     pub(crate) fn subscription(&self) -> Subscription<Message> {
         // jonesy:allow(misaligned_ptr) via alloc in subscription vec (misaligned_ptr)
         let subscriptions = vec![
             #[cfg(feature = "bluetooth")]
             Subscription::run(ble_discovery).map(DeviceListViewEvent),
-            #[cfg(feature = "tcp")]
+            #[cfg(all(feature = "tcp", feature = "meshtastic"))]
             Subscription::run(mdns_discovery).map(DeviceListViewEvent),
+            #[cfg(feature = "serial")]
+            Subscription::run(serial_discovery).map(DeviceListViewEvent),
             #[cfg(feature = "meshtastic")]
             Subscription::run(mesht::subscription::subscribe)
                 .map(|m| DeviceViewEvent(SubscriptionMessage(m))),
             #[cfg(feature = "meshcore")]
             Subscription::run(meshc::subscription::subscribe)
                 .map(|m| DeviceViewEvent(SubscriptionMessage(m))),
+            #[cfg(feature = "sim")]
+            Subscription::run(sim_discovery).map(DeviceListViewEvent),
+            #[cfg(feature = "sim")]
+            Subscription::run(sim::subscription::subscribe)
+                .map(|m| DeviceViewEvent(SubscriptionMessage(m))),
             event::listen().map(Message::Event),
         ];
 
@@ -665,40 +931,6 @@ pub(crate) mod tests {
         meshchat
     }
 
-    #[test]
-    fn test_location_url() {
-        let position = MCPosition {
-            latitude: 50.0,
-            longitude: 1.0,
-            altitude: None,
-            time: 0,
-            location_source: 0,
-            altitude_source: 0,
-            timestamp: TimeStamp::from(0u64),
-            timestamp_millis_adjust: 0,
-            altitude_hae: None,
-            altitude_geoidal_separation: None,
-            pdop: 0,
-            hdop: 0,
-            vdop: 0,
-            gps_accuracy: 0,
-            ground_speed: None,
-            ground_track: None,
-            fix_quality: 0,
-            fix_type: 0,
-            sats_in_view: 0,
-            sensor_id: 0,
-            next_update: 0,
-            seq_number: 0,
-            precision_bits: 0,
-        };
-
-        assert_eq!(
-            MeshChat::location_url(&position),
-            "https://maps.google.com/?q=50.0000000,1.0000000"
-        );
-    }
-
     #[test]
     fn test_default_view() {
         let meshchat = test_app();
@@ -844,37 +1076,30 @@ pub(crate) mod tests {
     }
 
     #[test]
-    fn test_location_url_negative_coordinates() {
-        let position = MCPosition {
-            latitude: -33.8688,
-            longitude: 151.2093,
-            altitude: None,
-            time: 0,
-            location_source: 0,
-            altitude_source: 0,
-            timestamp: TimeStamp::from(0u64),
-            timestamp_millis_adjust: 0,
-            altitude_hae: None,
-            altitude_geoidal_separation: None,
-            pdop: 0,
-            hdop: 0,
-            vdop: 0,
-            gps_accuracy: 0,
-            ground_speed: None,
-            ground_track: None,
-            fix_quality: 0,
-            fix_type: 0,
-            sats_in_view: 0,
-            sensor_id: 0,
-            next_update: 0,
-            seq_number: 0,
-            precision_bits: 0,
+    fn test_waypoint_expiry() {
+        let mut waypoint = MCWaypoint {
+            name: "Camp".into(),
+            ..Default::default()
         };
+        assert!(!waypoint.is_expired(TimeStamp::from(5000u64)));
+        waypoint.expire = Some(TimeStamp::from(5000u64));
+        assert!(!waypoint.is_expired(TimeStamp::from(4999u64)));
+        assert!(waypoint.is_expired(TimeStamp::from(5000u64)));
+    }
 
-        let url = MeshChat::location_url(&position);
-        assert!(url.contains("-33.8688"));
-        assert!(url.contains("151.2093"));
-        assert!(url.starts_with("https://maps.google.com"));
+    #[test]
+    fn test_waypoint_display() {
+        let mut waypoint = MCWaypoint {
+            name: "Camp".into(),
+            latitude: 50.0,
+            longitude: 1.0,
+            ..Default::default()
+        };
+        assert_eq!(waypoint.to_string(), "🚩 Camp");
+        waypoint.icon = Some('⛺');
+        waypoint.description = "By the river".into();
+        assert_eq!(waypoint.to_string(), "⛺ Camp: By the river");
+        assert_eq!(waypoint.position().latitude, 50.0);
     }
 
     #[test]
@@ -992,6 +1217,30 @@ pub(crate) mod tests {
         assert_eq!(meshchat.config.auto_reconnect, !initial);
     }
 
+    #[test]
+    fn test_toggle_auto_resend() {
+        let mut meshchat = test_app();
+        assert!(!meshchat.config.auto_resend);
+
+        let _ = meshchat.update(ToggleAutoResend);
+        assert!(meshchat.config.auto_resend);
+
+        let _ = meshchat.update(ToggleAutoResend);
+        assert!(!meshchat.config.auto_resend);
+    }
+
+    #[test]
+    fn test_toggle_capture_traffic() {
+        let mut meshchat = test_app();
+        assert!(!meshchat.config.capture_traffic);
+
+        let _ = meshchat.update(ToggleCaptureTraffic);
+        assert!(meshchat.config.capture_traffic);
+
+        let _ = meshchat.update(ToggleCaptureTraffic);
+        assert!(!meshchat.config.capture_traffic);
+    }
+
     #[test]
     fn test_toggle_auto_update() {
         let mut meshchat = test_app();
@@ -1013,6 +1262,82 @@ pub(crate) mod tests {
         );
     }
 
+    #[test]
+    fn test_node_sort_selected() {
+        let mut meshchat = test_app();
+        assert_eq!(meshchat.config.node_sort, NodeSort::FavouritesFirst);
+        let _ = meshchat.update(NodeSortSelected(NodeSort::Distance));
+        assert_eq!(meshchat.config.node_sort, NodeSort::Distance);
+    }
+
+    #[test]
+    fn test_node_status_thresholds_selected() {
+        let mut meshchat = test_app();
+        let _ = meshchat.update(OnlineWithinSelected(HeardWithin::ALL[0]));
+        let _ = meshchat.update(OfflineAfterSelected(HeardWithin::ALL[7]));
+        assert_eq!(meshchat.config.online_within, HeardWithin::ALL[0]);
+        assert_eq!(meshchat.config.offline_after, HeardWithin::ALL[7]);
+    }
+
+    #[test]
+    fn test_node_status_thresholds_clamped() {
+        let mut meshchat = test_app();
+        let _ = meshchat.update(OnlineWithinSelected(HeardWithin::ALL[2]));
+        let _ = meshchat.update(OfflineAfterSelected(HeardWithin::ALL[4]));
+
+        // Online for longer than nodes are offline after moves offline after along
+        let _ = meshchat.update(OnlineWithinSelected(HeardWithin::ALL[6]));
+        assert_eq!(meshchat.config.online_within, HeardWithin::ALL[6]);
+        assert_eq!(meshchat.config.offline_after, HeardWithin::ALL[6]);
+
+        // Offline after a shorter time than nodes are online within moves online within along
+        let _ = meshchat.update(OfflineAfterSelected(HeardWithin::ALL[1]));
+        assert_eq!(meshchat.config.online_within, HeardWithin::ALL[1]);
+        assert_eq!(meshchat.config.offline_after, HeardWithin::ALL[1]);
+    }
+
+    #[test]
+    fn test_map_tiles_cleared() {
+        let mut meshchat = test_app();
+        meshchat.config.map_tiles = Some(PathBuf::from("/no/such/tiles"));
+        let _ = meshchat.update(MapTilesSelected(None));
+        assert!(meshchat.config.map_tiles.is_none());
+    }
+
+    #[test]
+    fn test_map_tiles_not_opened_not_saved() {
+        let mut meshchat = test_app();
+        let tempdir = tempfile::Builder::new()
+            .prefix("meshchat")
+            .tempdir()
+            .expect("Could not create a temp dir");
+        let _ = meshchat.update(MapTilesSelected(Some(tempdir.path().to_path_buf())));
+        assert_eq!(meshchat.config.map_tiles.as_deref(), Some(tempdir.path()));
+
+        let _ = meshchat.update(MapTilesSelected(Some(
+            tempdir.path().join("missing.mbtiles"),
+        )));
+        assert_eq!(meshchat.config.map_tiles.as_deref(), Some(tempdir.path()));
+    }
+
+    #[test]
+    fn test_units_selected() {
+        let mut meshchat = test_app();
+        assert_eq!(meshchat.config.units, Units::Metric);
+        let _ = meshchat.update(UnitsSelected(Units::Imperial));
+        assert_eq!(meshchat.config.units, Units::Imperial);
+    }
+
+    #[test]
+    fn test_export_without_tracks() {
+        let mut meshchat = test_app();
+        let task = meshchat.update(ExportTrack(NodeId::from(1u64), TrackFormat::Gpx));
+        assert_eq!(task.units(), 0);
+        // Even with no positions, an empty file can be exported
+        let task = meshchat.update(ExportPositions(TrackFormat::Kml));
+        assert_eq!(task.units(), 1);
+    }
+
     #[test]
     fn test_show_user_info() {
         let mut meshchat = test_app();
@@ -1071,6 +1396,83 @@ pub(crate) mod tests {
         assert!(meshchat.show_user.is_none());
     }
 
+    #[test]
+    fn test_conversation_settings_changed() {
+        let mut meshchat = test_app();
+        let conversation_id = ConversationId::Channel(0.into());
+        let muted = ConversationSettings {
+            muted: true,
+            ..Default::default()
+        };
+        let device: DeviceIdentifier = "device1".into();
+        let _ = meshchat.update(ConversationSettingsChanged(
+            device.clone(),
+            conversation_id,
+            muted,
+        ));
+        assert_eq!(
+            meshchat
+                .config
+                .conversation_settings(&device, &conversation_id),
+            muted
+        );
+        assert!(
+            !meshchat
+                .config
+                .conversation_settings(&"device2".into(), &conversation_id)
+                .muted
+        );
+
+        let _ = meshchat.update(ConversationSettingsChanged(
+            device,
+            conversation_id,
+            ConversationSettings::default(),
+        ));
+        assert!(meshchat.config.conversation_settings.is_empty());
+    }
+
+    #[test]
+    fn test_mention_notification_follows_settings() {
+        let mut meshchat = test_app();
+        let device: DeviceIdentifier = "device1".into();
+        let conversation_id = ConversationId::Channel(0.into());
+        let mention = || {
+            MentionNotification(
+                "device1".into(),
+                ConversationId::Channel(0.into()),
+                "Bob mentioned you".to_string(),
+                "Hi @Alice".to_string(),
+                TimeStamp::now(),
+            )
+        };
+
+        // Not notified unless the conversation is set to notify of mentions only
+        let _ = meshchat.update(mention());
+        assert_eq!(meshchat.notifications.len(), 0);
+
+        let mentions_only = ConversationSettings {
+            mentions_only: true,
+            ..Default::default()
+        };
+        meshchat
+            .config
+            .set_conversation_settings(&device, conversation_id, mentions_only);
+        let _ = meshchat.update(mention());
+        assert_eq!(meshchat.notifications.len(), 1);
+
+        // Not notified when muted
+        meshchat.config.set_conversation_settings(
+            &device,
+            conversation_id,
+            ConversationSettings {
+                muted: true,
+                ..mentions_only
+            },
+        );
+        let _ = meshchat.update(mention());
+        assert_eq!(meshchat.notifications.len(), 1);
+    }
+
     #[test]
     fn test_config_loaded_sets_state() {
         let mut meshchat = test_app();
@@ -1089,6 +1491,15 @@ pub(crate) mod tests {
             fav_nodes: HashSet::new(),
             aliases: HashMap::new(),
             device_aliases: HashMap::new(),
+            manual_devices: HashMap::new(),
+            capture_traffic: false,
+            auto_resend: false,
+            conversation_settings: HashMap::new(),
+            node_sort: NodeSort::Name,
+            online_within: HeardWithin::ALL[3],
+            offline_after: HeardWithin::ALL[6],
+            units: Units::Imperial,
+            map_tiles: None,
         };
         let _ = meshchat.update(ConfigLoaded(config));
         assert_eq!(
@@ -1103,6 +1514,84 @@ pub(crate) mod tests {
         assert!(!meshchat.config.restore_window_position);
     }
 
+    #[cfg(feature = "tcp")]
+    #[test]
+    fn test_config_loaded_adds_manual_devices() {
+        let mut meshchat = test_app();
+        let mut manual_devices = HashMap::new();
+        manual_devices.insert("tcp://10.0.0.5:4403".to_string(), RadioType::default());
+        let config = Config {
+            manual_devices,
+            ..Config::default()
+        };
+        let _ = meshchat.update(ConfigLoaded(config));
+        assert!(
+            meshchat
+                .device_list
+                .devices
+                .contains_key(&DeviceIdentifier::from("tcp://10.0.0.5:4403"))
+        );
+    }
+
+    #[cfg(feature = "tcp")]
+    #[test]
+    fn test_add_and_remove_manual_device() {
+        let mut meshchat = test_app();
+        let device = DeviceIdentifier::from("tcp://10.0.0.5:4403");
+
+        let _ = meshchat.update(AddManualDevice(device.clone(), RadioType::default()));
+        assert_eq!(
+            meshchat.config.manual_devices.get("tcp://10.0.0.5:4403"),
+            Some(&RadioType::default())
+        );
+        assert!(meshchat.device_list.devices.contains_key(&device));
+
+        let _ = meshchat.update(RemoveManualDevice("tcp://10.0.0.5:4403".to_string()));
+        assert!(meshchat.config.manual_devices.is_empty());
+        assert!(!meshchat.device_list.devices.contains_key(&device));
+    }
+
+    #[cfg(all(feature = "tcp", feature = "meshtastic", feature = "meshcore"))]
+    #[test]
+    fn test_selected_radio_type_saved() {
+        let mut meshchat = test_app();
+        let device = DeviceIdentifier::from("tcp://10.0.0.5:4403");
+        let _ = meshchat.update(DeviceListViewEvent(DeviceListEvent::MeshRadioFound(
+            device.clone(),
+            RadioType::Meshtastic,
+        )));
+
+        let _ = meshchat.update(DeviceListViewEvent(DeviceListEvent::SelectRadioType(
+            device.clone(),
+            RadioType::MeshCore,
+        )));
+        assert_eq!(
+            meshchat.config.manual_devices.get("tcp://10.0.0.5:4403"),
+            Some(&RadioType::MeshCore)
+        );
+
+        // Rediscovering the device keeps the radio type chosen
+        let _ = meshchat.update(DeviceListViewEvent(DeviceListEvent::MeshRadioFound(
+            device.clone(),
+            RadioType::Meshtastic,
+        )));
+        assert_eq!(
+            meshchat
+                .device_list
+                .devices
+                .get(&device)
+                .map(|device_info| device_info.radio_type),
+            Some(RadioType::MeshCore)
+        );
+    }
+
+    #[test]
+    fn test_remove_unknown_manual_device() {
+        let mut meshchat = test_app();
+        let _ = meshchat.update(RemoveManualDevice("tcp://10.0.0.5:4403".to_string()));
+        assert!(meshchat.config.manual_devices.is_empty());
+    }
+
     #[test]
     fn test_config_loaded_with_auto_reconnect_sets_device() {
         let mut meshchat = test_app();
@@ -1135,6 +1624,22 @@ pub(crate) mod tests {
         );
     }
 
+    #[test]
+    fn test_replay_device_not_remembered() {
+        let mut meshchat = test_app();
+        let _ = meshchat.update(DeviceAndChannelConfigChange(
+            Some((
+                DeviceIdentifier::Replay {
+                    path: "/tmp/capture.jsonl".to_string(),
+                },
+                RadioType::default(),
+            )),
+            Some(ConversationId::Channel(1.into())),
+        ));
+        assert!(meshchat.config.ble_device.is_none());
+        assert!(meshchat.config.conversation_id.is_none());
+    }
+
     #[test]
     fn test_copy_to_clipboard_does_not_panic() {
         let mut meshchat = test_app();
@@ -0,0 +1,187 @@
+//! Meshtastic channel set URLs, like `https://meshtastic.org/e/#CgMSAQESCDgBQANIAVAe`,
+//! that share the channels on a radio so other radios can join them
+
+use crate::meshchat::{MCChannelSet, MCChannelSettings};
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use meshtastic::Message;
+use meshtastic::protobufs::{ChannelSet, ChannelSettings};
+
+/// The start of a channel set URL, the encoded channel set follows
+const CHANNEL_URL_PREFIX: &str = "https://meshtastic.org/e/#";
+
+/// The number of channel slots on a Meshtastic radio
+pub const MAX_CHANNELS: usize = 8;
+
+/// The URL that shares `channels`, the primary channel first. The LoRa settings are not
+/// included, so radios joining the channels keep theirs.
+pub fn channel_set_url(channels: &[MCChannelSettings]) -> String {
+    let channel_set = ChannelSet {
+        settings: channels.iter().map(ChannelSettings::from).collect(),
+        lora_config: None,
+    };
+    format!(
+        "{CHANNEL_URL_PREFIX}{}",
+        URL_SAFE_NO_PAD.encode(channel_set.encode_to_vec())
+    )
+}
+
+/// The channels shared in a channel set `url`, or why they can't be joined on a radio with
+/// `in_use` channel slots in use. A URL with `add=true` in its query adds the channels to the
+/// free slots on the radio, otherwise they replace all the channels on it.
+pub fn channels_in_url(url: &str, in_use: usize) -> Result<MCChannelSet, String> {
+    let (query, encoded) = url
+        .trim()
+        .split_once("meshtastic.org/e/")
+        .and_then(|(_, path)| path.split_once('#'))
+        .ok_or("Not a Meshtastic channel URL")?;
+    let add = query
+        .trim_start_matches('?')
+        .split('&')
+        .any(|param| param == "add=true");
+
+    // Some apps use the standard base64 alphabet, and padding, instead of the URL safe one
+    let encoded: String = encoded
+        .trim_end_matches('=')
+        .chars()
+        .map(|c| match c {
+            '+' => '-',
+            '/' => '_',
+            c => c,
+        })
+        .collect();
+    let bytes = URL_SAFE_NO_PAD
+        .decode(encoded)
+        .map_err(|e| format!("The channels in the URL could not be read: {e}"))?;
+    let channel_set = ChannelSet::decode(bytes.as_slice())
+        .map_err(|e| format!("The channels in the URL could not be read: {e}"))?;
+
+    if channel_set.settings.is_empty() {
+        return Err("There are no channels in the URL".into());
+    }
+    if channel_set.settings.len() > MAX_CHANNELS {
+        return Err(format!(
+            "There are {} channels in the URL, but a radio only has room for {MAX_CHANNELS}",
+            channel_set.settings.len()
+        ));
+    }
+    let free = MAX_CHANNELS.saturating_sub(in_use.max(1));
+    if add && channel_set.settings.len() > free {
+        return Err(format!(
+            "There are {} channels in the URL to add, but the radio only has room for {free} more",
+            channel_set.settings.len()
+        ));
+    }
+
+    Ok(MCChannelSet {
+        channels: channel_set
+            .settings
+            .iter()
+            .map(MCChannelSettings::from)
+            .collect(),
+        add,
+        lora_config: channel_set.lora_config,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn channel(name: &str, psk: Vec<u8>) -> MCChannelSettings {
+        MCChannelSettings {
+            name: name.into(),
+            psk,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let channels = vec![
+            channel("", vec![1]),
+            MCChannelSettings {
+                name: "Private".into(),
+                psk: (0..32).collect(),
+                uplink_enabled: true,
+                downlink_enabled: false,
+                position_precision: 13,
+            },
+        ];
+        let url = channel_set_url(&channels);
+        assert!(url.starts_with(CHANNEL_URL_PREFIX));
+        assert_eq!(
+            channels_in_url(&url, 1),
+            Ok(MCChannelSet {
+                channels,
+                add: false,
+                lora_config: None,
+            })
+        );
+    }
+
+    #[test]
+    fn test_default_channel_url() {
+        // The URL for the default LongFast channel, as shared by the Meshtastic apps
+        let channel_set =
+            channels_in_url("https://meshtastic.org/e/#CgMSAQESCDgBQANIAVAe", 1).expect("Channels");
+        assert_eq!(channel_set.channels, vec![channel("", vec![1])]);
+        assert!(!channel_set.add);
+        // The LoRa settings shared with it, for the US region
+        let lora_config = channel_set.lora_config.expect("LoRa config");
+        assert_eq!(lora_config.region, 1);
+        assert_eq!(lora_config.hop_limit, 3);
+        assert!(lora_config.tx_enabled);
+    }
+
+    #[test]
+    fn test_query_and_standard_alphabet() {
+        let channels = vec![channel("Team", vec![0xfb; 16])];
+        let url = channel_set_url(&channels);
+        let standard = url.replace('-', "+").replace('_', "/") + "==";
+        let with_query = standard.replace("/e/#", "/e/?add=true#");
+        assert_eq!(
+            channels_in_url(&with_query, 1),
+            Ok(MCChannelSet {
+                channels,
+                add: true,
+                lora_config: None,
+            })
+        );
+    }
+
+    #[test]
+    fn test_add_needs_room() {
+        let channels: Vec<MCChannelSettings> = (0..3)
+            .map(|index| channel(&format!("Channel {index}"), vec![1]))
+            .collect();
+        let url = channel_set_url(&channels).replace("/e/#", "/e/?add=true#");
+        assert!(channels_in_url(&url, 5).is_ok());
+        let error = channels_in_url(&url, 6).expect_err("No room");
+        assert!(error.contains("only has room for 2 more"));
+        // Replacing the channels doesn't need any free slots
+        assert!(channels_in_url(&channel_set_url(&channels), MAX_CHANNELS).is_ok());
+    }
+
+    #[test]
+    fn test_not_a_channel_url() {
+        assert!(channels_in_url("https://example.com/#CgMSAQE", 1).is_err());
+        assert!(channels_in_url("", 1).is_err());
+    }
+
+    #[test]
+    fn test_too_many_channels() {
+        let channels: Vec<MCChannelSettings> = (0..=MAX_CHANNELS)
+            .map(|index| channel(&format!("Channel {index}"), vec![1]))
+            .collect();
+        let error = channels_in_url(&channel_set_url(&channels), 1).expect_err("Too many channels");
+        assert!(error.contains("only has room for 8"));
+        assert!(channels_in_url(&channel_set_url(&channels[..MAX_CHANNELS]), 1).is_ok());
+    }
+
+    #[test]
+    fn test_bad_channel_set() {
+        assert!(channels_in_url("https://meshtastic.org/e/#!!!", 1).is_err());
+        assert!(channels_in_url("https://meshtastic.org/e/#", 1).is_err());
+    }
+}
@@ -1,11 +1,20 @@
 use crate::conversation_id::ConversationId::{Channel, Node};
 use crate::conversation_id::{ChannelIndex, ConversationId};
-use crate::meshchat::{MCChannel, MCNodeInfo, MCPosition, MCUser};
+use crate::meshchat::{MCChannel, MCChannelSettings, MCNodeInfo, MCPosition, MCUser, MCWaypoint};
+use crate::telemetry::{
+    MCDeviceMetrics, MCEnvironmentMetrics, MCPowerChannel, MCPowerMetrics, MCTelemetry,
+};
+use crate::timestamp::TimeStamp;
 use meshtastic::packet::PacketDestination;
-use meshtastic::protobufs::{NodeInfo, Position, User};
+use meshtastic::protobufs::telemetry::Variant;
+use meshtastic::protobufs::{
+    ChannelSettings, DeviceMetrics, EnvironmentMetrics, ModuleSettings, NodeInfo, Position,
+    PowerMetrics, Telemetry, User, Waypoint,
+};
 use meshtastic::types::{MeshChannel, NodeId};
 use uuid::Uuid;
 
+pub mod channel_url;
 pub mod subscription;
 
 pub const MESHTASTIC_SERVICE_UUID: Uuid = Uuid::from_u128(0x6ba1b218_15a8_461f_9fa8_5dcae273eafd);
@@ -53,6 +62,7 @@ impl From<&NodeInfo> for MCNodeInfo {
             user: node_info.user.as_ref().map(|u| u.into()),
             position: node_info.position.as_ref().map(|p| p.into()),
             is_ignored: node_info.is_ignored,
+            hops_away: node_info.hops_away,
         }
     }
 }
@@ -125,6 +135,109 @@ impl From<MCPosition> for Position {
     }
 }
 
+/// Conversions between [Waypoint] and MeshChat [MCWaypoint]
+impl From<&Waypoint> for MCWaypoint {
+    fn from(waypoint: &Waypoint) -> Self {
+        MCWaypoint {
+            id: waypoint.id,
+            name: waypoint.name.clone(),
+            description: waypoint.description.clone(),
+            icon: char::from_u32(waypoint.icon).filter(|icon| *icon != '\0'),
+            latitude: 0.0000001 * f64::from(waypoint.latitude_i.unwrap_or(0)),
+            longitude: 0.0000001 * f64::from(waypoint.longitude_i.unwrap_or(0)),
+            // expire is in seconds since the epoch, zero for never
+            expire: (waypoint.expire != 0)
+                .then(|| TimeStamp::from(u64::from(waypoint.expire).saturating_mul(1000))),
+        }
+    }
+}
+
+/// Conversions between MeshChat [MCWaypoint] and [Waypoint]
+impl From<MCWaypoint> for Waypoint {
+    fn from(waypoint: MCWaypoint) -> Self {
+        Waypoint {
+            id: waypoint.id,
+            latitude_i: Some((waypoint.latitude * 10_000_000.0).round() as i32),
+            longitude_i: Some((waypoint.longitude * 10_000_000.0).round() as i32),
+            expire: waypoint
+                .expire
+                .map(|expire| u32::try_from(u128::from(expire) / 1000).unwrap_or(u32::MAX))
+                .unwrap_or(0),
+            locked_to: 0,
+            name: waypoint.name,
+            description: waypoint.description,
+            icon: waypoint.icon.map(u32::from).unwrap_or(0),
+        }
+    }
+}
+
+/// Conversions between [DeviceMetrics] and MeshChat [MCDeviceMetrics]
+impl From<&DeviceMetrics> for MCDeviceMetrics {
+    fn from(metrics: &DeviceMetrics) -> Self {
+        MCDeviceMetrics {
+            battery_level: metrics.battery_level,
+            voltage: metrics.voltage,
+            channel_utilization: metrics.channel_utilization,
+            air_util_tx: metrics.air_util_tx,
+            uptime_seconds: metrics.uptime_seconds,
+        }
+    }
+}
+
+/// Conversions between [EnvironmentMetrics] and MeshChat [MCEnvironmentMetrics]
+impl From<&EnvironmentMetrics> for MCEnvironmentMetrics {
+    fn from(metrics: &EnvironmentMetrics) -> Self {
+        MCEnvironmentMetrics {
+            temperature: metrics.temperature,
+            relative_humidity: metrics.relative_humidity,
+            barometric_pressure: metrics.barometric_pressure,
+        }
+    }
+}
+
+/// Conversions between [PowerMetrics] and MeshChat [MCPowerMetrics]
+impl From<&PowerMetrics> for MCPowerMetrics {
+    fn from(metrics: &PowerMetrics) -> Self {
+        let readings = [
+            (metrics.ch1_voltage, metrics.ch1_current),
+            (metrics.ch2_voltage, metrics.ch2_current),
+            (metrics.ch3_voltage, metrics.ch3_current),
+            (metrics.ch4_voltage, metrics.ch4_current),
+            (metrics.ch5_voltage, metrics.ch5_current),
+            (metrics.ch6_voltage, metrics.ch6_current),
+            (metrics.ch7_voltage, metrics.ch7_current),
+            (metrics.ch8_voltage, metrics.ch8_current),
+        ];
+        MCPowerMetrics {
+            channels: (1..)
+                .zip(readings)
+                .filter(|(_, (voltage, current))| voltage.is_some() || current.is_some())
+                .map(|(channel, (voltage, current))| MCPowerChannel {
+                    channel,
+                    voltage,
+                    current,
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Conversions between [Telemetry] and MeshChat [MCTelemetry], for the kinds of metrics shown
+impl TryFrom<&Telemetry> for MCTelemetry {
+    type Error = ();
+
+    fn try_from(telemetry: &Telemetry) -> Result<Self, Self::Error> {
+        match &telemetry.variant {
+            Some(Variant::DeviceMetrics(metrics)) => Ok(MCTelemetry::Device(metrics.into())),
+            Some(Variant::EnvironmentMetrics(metrics)) => {
+                Ok(MCTelemetry::Environment(metrics.into()))
+            }
+            Some(Variant::PowerMetrics(metrics)) => Ok(MCTelemetry::Power(metrics.into())),
+            _ => Err(()),
+        }
+    }
+}
+
 impl From<&meshtastic::protobufs::Channel> for MCChannel {
     fn from(channel: &meshtastic::protobufs::Channel) -> Self {
         let name = match channel.settings {
@@ -145,6 +258,40 @@ impl From<&meshtastic::protobufs::Channel> for MCChannel {
     }
 }
 
+/// Conversions between [ChannelSettings] and MeshChat [MCChannelSettings]
+impl From<&ChannelSettings> for MCChannelSettings {
+    fn from(settings: &ChannelSettings) -> Self {
+        MCChannelSettings {
+            name: settings.name.clone(),
+            psk: settings.psk.clone(),
+            uplink_enabled: settings.uplink_enabled,
+            downlink_enabled: settings.downlink_enabled,
+            position_precision: settings
+                .module_settings
+                .as_ref()
+                .map(|module_settings| module_settings.position_precision)
+                .unwrap_or(0),
+        }
+    }
+}
+
+/// Conversions between MeshChat [MCChannelSettings] and [ChannelSettings]
+impl From<&MCChannelSettings> for ChannelSettings {
+    fn from(settings: &MCChannelSettings) -> Self {
+        ChannelSettings {
+            name: settings.name.clone(),
+            psk: settings.psk.clone(),
+            uplink_enabled: settings.uplink_enabled,
+            downlink_enabled: settings.downlink_enabled,
+            module_settings: Some(ModuleSettings {
+                position_precision: settings.position_precision,
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+}
+
 impl From<ChannelIndex> for MeshChannel {
     fn from(value: ChannelIndex) -> Self {
         MeshChannel::from(<ChannelIndex as Into<u32>>::into(value))
@@ -379,6 +526,38 @@ mod test {
         assert_eq!(mc_channel.name, "Default");
     }
 
+    #[test]
+    fn test_channel_settings_conversion() {
+        let settings = ChannelSettings {
+            name: "Private".to_string(),
+            psk: vec![0xab; 16],
+            uplink_enabled: true,
+            downlink_enabled: false,
+            module_settings: Some(ModuleSettings {
+                position_precision: 14,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        let mc_settings: MCChannelSettings = (&settings).into();
+        assert_eq!(mc_settings.name, "Private");
+        assert_eq!(mc_settings.psk, vec![0xab; 16]);
+        assert!(mc_settings.uplink_enabled);
+        assert!(!mc_settings.downlink_enabled);
+        assert_eq!(mc_settings.position_precision, 14);
+
+        let back: ChannelSettings = (&mc_settings).into();
+        assert_eq!(back, settings);
+    }
+
+    #[test]
+    fn test_channel_settings_without_module_settings() {
+        let mc_settings: MCChannelSettings = (&ChannelSettings::default()).into();
+        assert_eq!(mc_settings.position_precision, 0);
+        assert!(mc_settings.psk.is_empty());
+    }
+
     #[test]
     fn test_node_info_conversion() {
         let user = User {
@@ -430,6 +609,7 @@ mod test {
             position: Some(position),
             channel: 1,
             is_ignored: true,
+            hops_away: Some(2),
             ..Default::default()
         };
 
@@ -442,5 +622,121 @@ mod test {
         assert!(mc_node_info.user.is_none());
         assert!(mc_node_info.position.is_some());
         assert!(mc_node_info.is_ignored);
+        assert_eq!(mc_node_info.hops_away, Some(2));
+    }
+
+    #[test]
+    fn test_device_metrics_conversion() {
+        let telemetry = Telemetry {
+            variant: Some(Variant::DeviceMetrics(DeviceMetrics {
+                battery_level: Some(87),
+                voltage: Some(3.9),
+                channel_utilization: Some(12.5),
+                air_util_tx: Some(1.5),
+                uptime_seconds: Some(3600),
+            })),
+            ..Default::default()
+        };
+
+        let mc_telemetry = MCTelemetry::try_from(&telemetry).expect("Device metrics expected");
+        assert_eq!(
+            mc_telemetry,
+            MCTelemetry::Device(MCDeviceMetrics {
+                battery_level: Some(87),
+                voltage: Some(3.9),
+                channel_utilization: Some(12.5),
+                air_util_tx: Some(1.5),
+                uptime_seconds: Some(3600),
+            })
+        );
+    }
+
+    #[test]
+    fn test_environment_metrics_conversion() {
+        let telemetry = Telemetry {
+            variant: Some(Variant::EnvironmentMetrics(EnvironmentMetrics {
+                temperature: Some(21.5),
+                relative_humidity: Some(45.0),
+                barometric_pressure: Some(1013.0),
+                ..Default::default()
+            })),
+            ..Default::default()
+        };
+
+        let Ok(MCTelemetry::Environment(metrics)) = MCTelemetry::try_from(&telemetry) else {
+            panic!("Environment metrics expected");
+        };
+        assert_eq!(metrics.temperature, Some(21.5));
+        assert_eq!(metrics.relative_humidity, Some(45.0));
+        assert_eq!(metrics.barometric_pressure, Some(1013.0));
+    }
+
+    #[test]
+    fn test_power_metrics_only_channels_with_readings() {
+        let metrics = PowerMetrics {
+            ch1_voltage: Some(5.0),
+            ch3_current: Some(120.0),
+            ..Default::default()
+        };
+
+        let mc_metrics: MCPowerMetrics = (&metrics).into();
+        assert_eq!(
+            mc_metrics.channels,
+            vec![
+                MCPowerChannel {
+                    channel: 1,
+                    voltage: Some(5.0),
+                    current: None,
+                },
+                MCPowerChannel {
+                    channel: 3,
+                    voltage: None,
+                    current: Some(120.0),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_other_telemetry_not_converted() {
+        assert!(MCTelemetry::try_from(&Telemetry::default()).is_err());
+    }
+
+    #[test]
+    fn test_waypoint_conversion() {
+        let waypoint = Waypoint {
+            id: 42,
+            latitude_i: Some(505_000_000),
+            longitude_i: Some(-12_500_000),
+            expire: 1_700_000_000,
+            locked_to: 0,
+            name: "Camp".into(),
+            description: "By the river".into(),
+            icon: u32::from('⛺'),
+        };
+
+        let mc_waypoint: MCWaypoint = (&waypoint).into();
+        assert_eq!(mc_waypoint.id, 42);
+        assert_eq!(mc_waypoint.icon, Some('⛺'));
+        assert!((mc_waypoint.latitude - 50.5).abs() < 0.0000001);
+        assert!((mc_waypoint.longitude + 1.25).abs() < 0.0000001);
+        assert_eq!(
+            mc_waypoint.expire,
+            Some(TimeStamp::from(1_700_000_000_000u64))
+        );
+
+        let back: Waypoint = mc_waypoint.into();
+        assert_eq!(back, waypoint);
+    }
+
+    #[test]
+    fn test_waypoint_without_icon_or_expiry() {
+        let mc_waypoint: MCWaypoint = (&Waypoint::default()).into();
+        assert!(mc_waypoint.icon.is_none());
+        assert!(mc_waypoint.expire.is_none());
+
+        let waypoint: Waypoint = mc_waypoint.into();
+        assert_eq!(waypoint.icon, 0);
+        assert_eq!(waypoint.expire, 0);
     }
 }
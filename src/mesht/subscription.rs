@@ -1,55 +1,89 @@
 use crate::conversation_id::ConversationId;
 use crate::conversation_id::ConversationId::Node;
 use crate::device::DeviceCommand::{
-    Connect, Disconnect, MeshTasticRadioPacket, SendEmojiReply, SendPosition, SendSelfInfo,
-    SendText,
+    Connect, ConnectionLost, Disconnect, MeshTasticRadioPacket, ResendText, SendEmojiReply,
+    SendPosition, SendSelfInfo, SendText, SendTraceroute, SendWaypoint, SetChannels,
+    SetNodeIgnored,
 };
+use crate::mesht::channel_url::MAX_CHANNELS;
 use crate::mesht::subscription::DeviceState::{Connected, Disconnected};
 use crate::message::MCContent::{AlertMessage, EmojiReply, NewTextMessage, TextMessageReply};
+use crate::message::{LinkQuality, MCContent};
 
+use crate::capture;
+use crate::capture::{Capture, start_capture};
 use crate::conversation_id;
 use crate::device::DeviceEvent::{
-    ChannelName, ConnectedEvent, ConnectingEvent, ConnectionError, DeviceBatteryLevel,
-    DisconnectedEvent, MCMessageReceived, MessageACK, MyNodeNum, NewChannel, NewNode, NewNodeInfo,
-    NewNodePosition, RadioNotification, SendError,
+    AwaitingAck, ChannelName, ConnectedEvent, ConnectingEvent, ConnectionError, DeviceBatteryLevel,
+    DisconnectedEvent, MCMessageReceived, MessageACK, MessageFailed, MessageResent, MyNodeNum,
+    NeighbourInfo, NewChannel, NewChannelSettings, NewNode, NewNodeInfo, NewNodePosition,
+    NodeTelemetry, RadioNotification, SendError, TracerouteResponse,
 };
 use crate::device::{DeviceCommand, DeviceEvent, DeviceIdentifier};
 use crate::device_list::RadioType;
-use crate::meshchat::{MCChannel, MCNodeInfo, MCPosition};
+use crate::meshchat::{
+    MCChannel, MCChannelSet, MCChannelSettings, MCNeighbour, MCNodeInfo, MCPosition,
+};
+use crate::telemetry::MCTelemetry;
 use crate::timestamp::TimeStamp;
+use crate::traceroute::{Hop, Route};
 use futures::SinkExt;
 use futures::executor::block_on;
 use iced::stream;
 use meshtastic::Message;
+#[cfg(feature = "serial")]
+use meshtastic::api::StreamHandle;
 use meshtastic::api::{ConnectedStreamApi, StreamApi};
 use meshtastic::errors::Error;
-use meshtastic::packet::{PacketReceiver, PacketRouter};
-use meshtastic::protobufs::config::PayloadVariant::Lora;
+use meshtastic::packet::{PacketDestination, PacketReceiver, PacketRouter};
+use meshtastic::protobufs::admin_message::PayloadVariant::{RemoveIgnoredNode, SetIgnoredNode};
+use meshtastic::protobufs::channel::Role;
+use meshtastic::protobufs::config::PayloadVariant::{Lora, Security};
 use meshtastic::protobufs::from_radio::PayloadVariant::{
     Channel, ClientNotification, Config, MyInfo, NodeInfo, Packet,
 };
 use meshtastic::protobufs::mesh_packet::PayloadVariant::Decoded;
+use meshtastic::protobufs::routing::Variant::ErrorReason;
 use meshtastic::protobufs::telemetry::Variant::DeviceMetrics;
-use meshtastic::protobufs::{FromRadio, MeshPacket, PortNum, Position, Telemetry, User};
-use meshtastic::types::NodeId;
+use meshtastic::protobufs::{
+    AdminMessage, Channel as ProtoChannel, ChannelSettings, FromRadio, MeshPacket, NeighborInfo,
+    PortNum, Position, RouteDiscovery, Routing, Telemetry, User, Waypoint, routing,
+};
+use meshtastic::types::{MeshChannel, NodeId};
 use meshtastic::utils;
 #[cfg(feature = "bluetooth")]
 use meshtastic::utils::stream::BleId;
+use std::path::Path;
 use std::pin::Pin;
 use std::time::Duration;
 use tokio::sync::mpsc::channel;
 use tokio::time::timeout;
+#[cfg(feature = "serial")]
+use tokio_serial::{SerialPort, SerialPortBuilderExt, SerialStream};
 use tokio_stream::wrappers::UnboundedReceiverStream;
-use tokio_stream::{Stream, StreamExt};
+use tokio_stream::{Stream, StreamExt, once, pending};
+
+/// How long to wait for a direct message to be acknowledged before it is considered failed.
+/// The radio retries delivery itself, and reports when it gives up, so this is a backstop.
+const ACK_TIMEOUT: Duration = Duration::from_secs(90);
+
+/// How far the time a node says it took a telemetry reading can be from when it was received,
+/// before the node's clock is assumed to be wrong and the time it was received is used instead
+const MAX_TELEMETRY_CLOCK_SKEW: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// The packets received from a radio, or replayed from a capture of them
+type FromRadioStream = Pin<Box<dyn Stream<Item = FromRadio> + Send>>;
 
 enum DeviceState {
     Disconnected,
-    Connected(DeviceIdentifier, PacketReceiver),
+    Connected(DeviceIdentifier, FromRadioStream),
 }
 
 struct MyRouter {
     gui_sender: futures_channel::mpsc::Sender<DeviceEvent>,
     my_node_num: Option<u32>,
+    /// The text message being sent is being sent again automatically, so report its id
+    resending: bool,
 }
 
 impl MyRouter {
@@ -60,6 +94,7 @@ impl MyRouter {
         MyRouter {
             gui_sender,
             my_node_num: None,
+            resending: false,
         }
     }
 
@@ -129,6 +164,15 @@ impl MyRouter {
                         .send(NewChannel(MCChannel::from(channel)))
                         .await
                         .unwrap_or_else(|e| eprintln!("Send error: {e}"));
+                    if let Some(settings) = &channel.settings {
+                        self.gui_sender
+                            .send(NewChannelSettings(
+                                channel.index,
+                                MCChannelSettings::from(settings),
+                            ))
+                            .await
+                            .unwrap_or_else(|e| eprintln!("Send error: {e}"));
+                    }
                 }
             }
             Some(ClientNotification(notification)) => {
@@ -168,7 +212,7 @@ impl MyRouter {
         if let Some(Decoded(data)) = &mesh_packet.payload_variant {
             match PortNum::try_from(data.portnum) {
                 Ok(PortNum::RoutingApp) => {
-                    // An ACK
+                    // An ACK, or a NAK with the reason delivery failed
                     let conversation_id = if mesh_packet.from == mesh_packet.to {
                         // To a channel broadcast message
                         ConversationId::Channel(mesh_packet.channel.into())
@@ -177,9 +221,19 @@ impl MyRouter {
                         Node(conversation_id::NodeId::from(mesh_packet.from))
                     };
 
+                    let event = match Routing::decode(&data.payload as &[u8]).map(|r| r.variant) {
+                        Ok(Some(ErrorReason(reason))) if reason != routing::Error::None as i32 => {
+                            let reason = routing::Error::try_from(reason)
+                                .map(|error| error.as_str_name().replace('_', " ").to_lowercase())
+                                .unwrap_or_else(|_| format!("Error {reason}"));
+                            MessageFailed(conversation_id, data.request_id.into(), reason)
+                        }
+                        _ => MessageACK(conversation_id, data.request_id.into()),
+                    };
+
                     self.gui_sender
                         // jonesy:allow(unknown) async state machine artifact
-                        .send(MessageACK(conversation_id, data.request_id.into()))
+                        .send(event)
                         .await
                         .unwrap_or_else(|e| eprintln!("Send error: {e}"));
                 }
@@ -194,6 +248,7 @@ impl MyRouter {
                                 mesh_packet.from.into(),
                                 AlertMessage(message),
                                 TimeStamp::now(),
+                                link_quality(mesh_packet),
                             ))
                             .await
                             .unwrap_or_else(|e| eprintln!("Send error: {e}"));
@@ -220,9 +275,33 @@ impl MyRouter {
                                 mesh_packet.from.into(),
                                 mcmessage,
                                 TimeStamp::now(),
+                                link_quality(mesh_packet),
                             ))
                             .await
                             .unwrap_or_else(|e| eprintln!("Send error: {e}"));
+
+                        if Some(mesh_packet.from) == self.my_node_num && self.resending {
+                            self.resending = false;
+                            self.gui_sender
+                                .send(MessageResent(mesh_packet.id.into()))
+                                .await
+                                .unwrap_or_else(|e| eprintln!("Send error: {e}"));
+                        }
+
+                        // A DM I sent that the radio will try to get acknowledged
+                        if mesh_packet.want_ack
+                            && mesh_packet.to != u32::MAX
+                            && Some(mesh_packet.from) == self.my_node_num
+                        {
+                            self.gui_sender
+                                .send(AwaitingAck(
+                                    conversation_id,
+                                    mesh_packet.id.into(),
+                                    ACK_TIMEOUT,
+                                ))
+                                .await
+                                .unwrap_or_else(|e| eprintln!("Send error: {e}"));
+                        }
                     }
                 }
                 Ok(PortNum::PositionApp) => {
@@ -243,19 +322,73 @@ impl MyRouter {
                     }
                 }
                 Ok(PortNum::TelemetryApp) => {
-                    if let Ok(telemetry) = Telemetry::decode(&data.payload as &[u8])
-                        && Some(mesh_packet.from) == self.my_node_num
-                        && let Some(DeviceMetrics(metrics)) = telemetry.variant
+                    if let Ok(telemetry) = Telemetry::decode(&data.payload as &[u8]) {
+                        if Some(mesh_packet.from) == self.my_node_num
+                            && let Some(DeviceMetrics(metrics)) = &telemetry.variant
+                        {
+                            self.gui_sender
+                                .send(DeviceBatteryLevel(
+                                    metrics.battery_level.map(|level| level as u8),
+                                ))
+                                .await
+                                .unwrap_or_else(|e| eprintln!("Send error: {e}"));
+                        }
+
+                        if let Ok(mc_telemetry) = MCTelemetry::try_from(&telemetry) {
+                            self.gui_sender
+                                .send(NodeTelemetry(
+                                    mesh_packet.from.into(),
+                                    mc_telemetry,
+                                    telemetry_time(telemetry.time, TimeStamp::now()),
+                                ))
+                                .await
+                                .unwrap_or_else(|e| eprintln!("Send error: {e}"));
+                        }
+                    }
+                }
+                Ok(PortNum::TracerouteApp) => {
+                    // Only the response to a traceroute I sent, not requests to or through me
+                    if data.request_id != 0
+                        && Some(mesh_packet.to) == self.my_node_num
+                        && let Ok(discovery) = RouteDiscovery::decode(&data.payload as &[u8])
                     {
                         self.gui_sender
-                            .send(DeviceBatteryLevel(
-                                metrics.battery_level.map(|level| level as u8),
+                            .send(TracerouteResponse(traced_route(
+                                mesh_packet.to,
+                                mesh_packet.from,
+                                &discovery,
+                            )))
+                            .await
+                            .unwrap_or_else(|e| eprintln!("Send error: {e}"));
+                    }
+                }
+                Ok(PortNum::WaypointApp) => {
+                    if let Ok(waypoint) = Waypoint::decode(&data.payload as &[u8]) {
+                        let conversation_id = self.conversation_id_from_packet(mesh_packet);
+                        self.gui_sender
+                            .send(MCMessageReceived(
+                                conversation_id,
+                                mesh_packet.id.into(),
+                                mesh_packet.from.into(),
+                                MCContent::Waypoint((&waypoint).into()),
+                                TimeStamp::now(),
+                                link_quality(mesh_packet),
+                            ))
+                            .await
+                            .unwrap_or_else(|e| eprintln!("Send error: {e}"));
+                    }
+                }
+                Ok(PortNum::NeighborinfoApp) => {
+                    if let Ok(neighbor_info) = NeighborInfo::decode(&data.payload as &[u8]) {
+                        self.gui_sender
+                            .send(NeighbourInfo(
+                                neighbor_info.node_id.into(),
+                                neighbours(&neighbor_info, TimeStamp::now()),
                             ))
                             .await
                             .unwrap_or_else(|e| eprintln!("Send error: {e}"));
                     }
                 }
-                Ok(PortNum::NeighborinfoApp) => println!("Neighbor Info payload"),
                 Ok(PortNum::NodeinfoApp) => {
                     if let Ok(user) = User::decode(&data.payload as &[u8]) {
                         let conversation_id = self.conversation_id_from_packet(mesh_packet);
@@ -302,6 +435,7 @@ pub fn subscribe() -> impl Stream<Item = DeviceEvent> {
         move |mut gui_sender: futures_channel::mpsc::Sender<DeviceEvent>| async move {
             let mut device_state = Disconnected;
             let mut stream_api: Option<ConnectedStreamApi> = None;
+            let mut capture: Option<Capture> = None;
             let mut my_router = MyRouter::new(gui_sender.clone());
             let (subscriber_sender, mut subscriber_receiver) = channel::<DeviceCommand>(100);
 
@@ -324,16 +458,43 @@ pub fn subscribe() -> impl Stream<Item = DeviceEvent> {
                     Disconnected => {
                         // Wait for a message from the UI to request that we connect to a device
                         // No need to wait for any messages from a radio, as we are not connected to one
-                        if let Some(Connect(ble_device, _)) = gui_stream.next().await {
+                        if let Some(Connect(ble_device, _, capture_traffic)) =
+                            gui_stream.next().await
+                        {
                             gui_sender
                                 .send(ConnectingEvent(ble_device.clone()))
                                 .await
                                 .unwrap_or_else(|e| eprintln!("Send error: {e}"));
 
-                            match do_connect(&ble_device).await {
-                                Ok((packet_receiver, stream)) => {
-                                    device_state = Connected(ble_device.clone(), packet_receiver);
-                                    stream_api = Some(stream);
+                            // A replayed capture has no radio to send to, so no stream api
+                            let connection = if let DeviceIdentifier::Replay { path } = &ble_device
+                            {
+                                replay_packets(path).await.map(|packets| (packets, None))
+                            } else {
+                                do_connect(&ble_device)
+                                    .await
+                                    .map(|(packet_receiver, stream)| {
+                                        (
+                                            Box::pin(UnboundedReceiverStream::from(packet_receiver))
+                                                as FromRadioStream,
+                                            Some(stream),
+                                        )
+                                    })
+                            };
+
+                            match connection {
+                                Ok((packets, stream)) => {
+                                    device_state = Connected(ble_device.clone(), packets);
+                                    stream_api = stream;
+
+                                    if capture_traffic {
+                                        capture = start_capture(
+                                            &ble_device,
+                                            RadioType::Meshtastic,
+                                            &mut gui_sender,
+                                        )
+                                        .await;
+                                    }
 
                                     gui_sender
                                         .send(ConnectedEvent(ble_device, RadioType::Meshtastic))
@@ -353,21 +514,24 @@ pub fn subscribe() -> impl Stream<Item = DeviceEvent> {
                             }
                         }
                     }
-                    Connected(ble_device, packet_receiver) => {
-                        let from_radio_stream = UnboundedReceiverStream::from(packet_receiver).map(
-                            |from_radio_packet| MeshTasticRadioPacket(Box::new(from_radio_packet)),
-                        );
+                    Connected(ble_device, packets) => {
+                        // When the link to the radio drops, the stream of packets from it ends
+                        let from_radio_stream = packets
+                            .map(|from_radio_packet| {
+                                MeshTasticRadioPacket(Box::new(from_radio_packet))
+                            })
+                            .chain(once(ConnectionLost));
 
                         let mut merged_stream = from_radio_stream.merge(&mut gui_stream);
 
                         // jonesy:allow(unknown) via async poll
                         while let Some(message) = StreamExt::next(&mut merged_stream).await {
                             let result = match message {
-                                Connect(_, _) => {
+                                Connect(..) => {
                                     eprintln!("Cannot connect while already connected");
                                     Ok(())
                                 }
-                                Disconnect => break,
+                                Disconnect | ConnectionLost => break,
                                 SendText(text, conversation_id, reply_to_id) => {
                                     if let Some(mut api) = stream_api.take() {
                                         let r = send_text_message(
@@ -381,13 +545,25 @@ pub fn subscribe() -> impl Stream<Item = DeviceEvent> {
                                         let _none = stream_api.replace(api);
                                         r
                                     } else {
-                                        Err(Error::StreamBuildError {
-                                            source: Box::new(std::io::Error::new(
-                                                std::io::ErrorKind::NotConnected,
-                                                "Stream API not available",
-                                            )),
-                                            description: "Subscription".to_string(),
-                                        })
+                                        Err(api_not_available())
+                                    }
+                                }
+                                ResendText(text, conversation_id, reply_to_id) => {
+                                    if let Some(mut api) = stream_api.take() {
+                                        my_router.resending = true;
+                                        let r = send_text_message(
+                                            &mut api,
+                                            &mut my_router,
+                                            conversation_id,
+                                            reply_to_id.map(u32::from),
+                                            text,
+                                        )
+                                        .await;
+                                        my_router.resending = false;
+                                        let _none = stream_api.replace(api);
+                                        r
+                                    } else {
+                                        Err(api_not_available())
                                     }
                                 }
                                 SendPosition(conversation_id, mcposition) => {
@@ -402,13 +578,7 @@ pub fn subscribe() -> impl Stream<Item = DeviceEvent> {
                                         let _none = stream_api.replace(api);
                                         r
                                     } else {
-                                        Err(Error::StreamBuildError {
-                                            source: Box::new(std::io::Error::new(
-                                                std::io::ErrorKind::NotConnected,
-                                                "Stream API not available",
-                                            )),
-                                            description: "Subscription".to_string(),
-                                        })
+                                        Err(api_not_available())
                                     }
                                 }
                                 SendSelfInfo(conversation_id, mcuser) => {
@@ -423,13 +593,62 @@ pub fn subscribe() -> impl Stream<Item = DeviceEvent> {
                                         let _none = stream_api.replace(api);
                                         r
                                     } else {
-                                        Err(Error::StreamBuildError {
-                                            source: Box::new(std::io::Error::new(
-                                                std::io::ErrorKind::NotConnected,
-                                                "Stream API not available",
-                                            )),
-                                            description: "Subscription".to_string(),
-                                        })
+                                        Err(api_not_available())
+                                    }
+                                }
+                                SendWaypoint(conversation_id, mcwaypoint) => {
+                                    if let Some(mut api) = stream_api.take() {
+                                        let r = send_waypoint(
+                                            &mut api,
+                                            &mut my_router,
+                                            conversation_id,
+                                            mcwaypoint.into(),
+                                        )
+                                        .await;
+                                        let _none = stream_api.replace(api);
+                                        r
+                                    } else {
+                                        Err(api_not_available())
+                                    }
+                                }
+                                SetChannels(channel_set, in_use) => {
+                                    if let Some(mut api) = stream_api.take() {
+                                        let r = set_channels(
+                                            &mut api,
+                                            &mut my_router,
+                                            &channel_set,
+                                            &in_use,
+                                        )
+                                        .await;
+                                        let _none = stream_api.replace(api);
+                                        r
+                                    } else {
+                                        Err(api_not_available())
+                                    }
+                                }
+                                SetNodeIgnored(node_id, ignored) => {
+                                    if let Some(mut api) = stream_api.take() {
+                                        let r = set_node_ignored(
+                                            &mut api,
+                                            &mut my_router,
+                                            node_id,
+                                            ignored,
+                                        )
+                                        .await;
+                                        let _none = stream_api.replace(api);
+                                        r
+                                    } else {
+                                        Err(api_not_available())
+                                    }
+                                }
+                                SendTraceroute(node_id) => {
+                                    if let Some(mut api) = stream_api.take() {
+                                        let r = send_traceroute(&mut api, &mut my_router, node_id)
+                                            .await;
+                                        let _none = stream_api.replace(api);
+                                        r
+                                    } else {
+                                        Err(api_not_available())
                                     }
                                 }
                                 SendEmojiReply(emoji, conversation_id, reply_to_id) => {
@@ -445,17 +664,17 @@ pub fn subscribe() -> impl Stream<Item = DeviceEvent> {
                                         let _none = stream_api.replace(api);
                                         r
                                     } else {
-                                        Err(Error::StreamBuildError {
-                                            source: Box::new(std::io::Error::new(
-                                                std::io::ErrorKind::NotConnected,
-                                                "Stream API not available",
-                                            )),
-                                            description: "Subscription".to_string(),
-                                        })
+                                        Err(api_not_available())
                                     }
                                 }
                                 // jonesy:allow(misaligned_ptr) via meshtastic handle_a_packet_from_radio (misaligned_ptr)
                                 MeshTasticRadioPacket(packet) => {
+                                    if let Some(capture) = capture.as_mut() {
+                                        capture
+                                            .record(&without_keys(&packet))
+                                            .await
+                                            .unwrap_or_else(|e| eprintln!("Capture error: {e}"));
+                                    }
                                     my_router.handle_a_packet_from_radio(packet).await;
                                     Ok(())
                                 }
@@ -478,6 +697,7 @@ pub fn subscribe() -> impl Stream<Item = DeviceEvent> {
                         if let Some(api) = stream_api.take() {
                             let _ = do_disconnect(api).await;
                         }
+                        capture = None;
                         device_state = Disconnected;
                         gui_sender
                             .send(DisconnectedEvent(ble_device))
@@ -490,6 +710,215 @@ pub fn subscribe() -> impl Stream<Item = DeviceEvent> {
     )
 }
 
+/// The neighbours in a [NeighborInfo], heard `now` if when they were last heard is not reported
+fn neighbours(neighbor_info: &NeighborInfo, now: TimeStamp) -> Vec<MCNeighbour> {
+    neighbor_info
+        .neighbors
+        .iter()
+        .map(|neighbor| MCNeighbour {
+            node_id: neighbor.node_id.into(),
+            snr: Some(neighbor.snr),
+            last_heard: if neighbor.last_rx_time == 0 {
+                now
+            } else {
+                TimeStamp::from(u64::from(neighbor.last_rx_time).saturating_mul(1000))
+            },
+        })
+        .collect()
+}
+
+/// How well `mesh_packet` was received, None for packets that were not received over the air,
+/// such as ones sent from my own node. Older firmware does not report the hops travelled.
+fn link_quality(mesh_packet: &MeshPacket) -> Option<LinkQuality> {
+    let snr = (mesh_packet.rx_snr != 0.0).then_some(mesh_packet.rx_snr);
+    let rssi = (mesh_packet.rx_rssi != 0).then_some(mesh_packet.rx_rssi);
+    let hops = (mesh_packet.hop_start != 0)
+        .then(|| mesh_packet.hop_start.saturating_sub(mesh_packet.hop_limit));
+    (snr.is_some() || rssi.is_some() || hops.is_some()).then_some(LinkQuality { snr, rssi, hops })
+}
+
+/// Node number used in a route for a relay that did not report itself
+const UNKNOWN_NODE_NUM: u32 = u32::MAX;
+/// SNR value used in a route when it is not known
+const UNKNOWN_SNR: i32 = i8::MIN as i32;
+
+/// Build the [Route] from `me` to `destination` and back from a traceroute response. The nodes
+/// in the [RouteDiscovery] are the ones in between, and SNRs are in dB scaled by 4.
+fn traced_route(me: u32, destination: u32, discovery: &RouteDiscovery) -> Route {
+    let path = |from: u32, via: &[u32], to: u32, snrs: &[i32]| -> Vec<Hop> {
+        std::iter::once(from)
+            .chain(via.iter().copied())
+            .chain(std::iter::once(to))
+            .enumerate()
+            .map(|(index, node_num)| {
+                let node_id = (node_num != UNKNOWN_NODE_NUM).then(|| node_num.into());
+                let snr = index
+                    .checked_sub(1)
+                    .and_then(|index| snrs.get(index))
+                    .filter(|snr| **snr != UNKNOWN_SNR)
+                    .map(|snr| *snr as f32 / 4.0);
+                Hop::new(node_id, snr)
+            })
+            .collect()
+    };
+
+    // Older firmware does not report the way back
+    let back = if discovery.snr_back.is_empty() {
+        vec![]
+    } else {
+        path(destination, &discovery.route_back, me, &discovery.snr_back)
+    };
+
+    Route {
+        destination: destination.into(),
+        towards: path(me, &discovery.route, destination, &discovery.snr_towards),
+        back,
+    }
+}
+
+/// The configuration of the channel slots on the radio to write, to join the channels in
+/// `channel_set`. To replace the radio's channels every slot is written: the first channel is
+/// the primary one, and slots beyond the channels given are disabled. To add the channels, only
+/// the secondary slots not `in_use` are written, one per channel.
+fn channel_configs(channel_set: &MCChannelSet, in_use: &[i32]) -> Vec<ProtoChannel> {
+    if channel_set.add {
+        return (1..MAX_CHANNELS as i32)
+            .filter(|index| !in_use.contains(index))
+            .zip(&channel_set.channels)
+            .map(|(index, settings)| ProtoChannel {
+                index,
+                settings: Some(ChannelSettings::from(settings)),
+                role: Role::Secondary as i32,
+            })
+            .collect();
+    }
+
+    (0..MAX_CHANNELS)
+        .map(|index| {
+            let (role, settings) = match channel_set.channels.get(index) {
+                Some(settings) if index == 0 => (Role::Primary, ChannelSettings::from(settings)),
+                Some(settings) => (Role::Secondary, ChannelSettings::from(settings)),
+                None => (Role::Disabled, ChannelSettings::default()),
+            };
+            ProtoChannel {
+                index: index as i32,
+                settings: Some(settings),
+                role: role as i32,
+            }
+        })
+        .collect()
+}
+
+/// Join the channels in `channel_set` using the Admin API, replacing the channels on the radio
+/// (and its LoRa settings, if the channel set has them) or adding to the slots not `in_use`.
+/// The changes are made in one transaction, so the radio applies them all together, and restarts
+/// once to do so
+async fn set_channels(
+    stream_api: &mut ConnectedStreamApi,
+    my_router: &mut MyRouter,
+    channel_set: &MCChannelSet,
+    in_use: &[i32],
+) -> Result<(), Error> {
+    // jonesy:allow(unknown) async state machine artifact
+    stream_api.start_config_transaction().await?;
+    if !channel_set.add
+        && let Some(lora_config) = &channel_set.lora_config
+    {
+        stream_api
+            .update_config(
+                my_router,
+                meshtastic::protobufs::Config {
+                    payload_variant: Some(Lora(lora_config.clone())),
+                },
+            )
+            // jonesy:allow(unknown) async state machine artifact
+            .await?;
+    }
+    for channel in channel_configs(channel_set, in_use) {
+        stream_api
+            .update_channel_config(my_router, channel)
+            // jonesy:allow(unknown) async state machine artifact
+            .await?;
+    }
+    // jonesy:allow(unknown) async state machine artifact
+    stream_api.commit_config_transaction().await
+}
+
+/// The admin message that adds a node to the radio's list of ignored nodes, or removes it
+fn ignore_admin_message(node_id: conversation_id::NodeId, ignored: bool) -> AdminMessage {
+    let node_num = u32::from(node_id);
+    AdminMessage {
+        payload_variant: Some(if ignored {
+            SetIgnoredNode(node_num)
+        } else {
+            RemoveIgnoredNode(node_num)
+        }),
+        session_passkey: Vec::new(),
+    }
+}
+
+/// Have my radio ignore a node, or stop ignoring it
+async fn set_node_ignored(
+    stream_api: &mut ConnectedStreamApi,
+    my_router: &mut MyRouter,
+    node_id: conversation_id::NodeId,
+    ignored: bool,
+) -> Result<(), Error> {
+    stream_api
+        .send_mesh_packet(
+            my_router,
+            ignore_admin_message(node_id, ignored)
+                .encode_to_vec()
+                .into(),
+            PortNum::AdminApp,
+            PacketDestination::Local,
+            MeshChannel::new(0)?,
+            true,
+            true,
+            true,
+            None,
+            None,
+        )
+        // jonesy:allow(unknown) async state machine artifact
+        .await
+}
+
+/// Send a traceroute request to a node, the response is handled by [MyRouter]
+async fn send_traceroute(
+    stream_api: &mut ConnectedStreamApi,
+    my_router: &mut MyRouter,
+    node_id: conversation_id::NodeId,
+) -> Result<(), Error> {
+    let (packet_destination, mesh_channel) = Node(node_id).to_destination();
+    stream_api
+        .send_mesh_packet(
+            my_router,
+            RouteDiscovery::default().encode_to_vec().into(),
+            PortNum::TracerouteApp,
+            packet_destination,
+            mesh_channel,
+            false,
+            true, // want_response - the route traced
+            false,
+            None,
+            None,
+        )
+        // jonesy:allow(unknown) async state machine artifact
+        .await
+}
+
+/// The time a telemetry reading was taken, from the `time` in seconds the node reports, or when
+/// it was `received` if the node didn't report one, or its clock is far off
+fn telemetry_time(time: u32, received: TimeStamp) -> TimeStamp {
+    let measured = TimeStamp::from(u64::from(time).saturating_mul(1000));
+    let skew = u128::from((measured - received).max(received - measured));
+    if time == 0 || skew > MAX_TELEMETRY_CLOCK_SKEW.as_millis() {
+        received
+    } else {
+        measured
+    }
+}
+
 /// Send a Text Message to the other node or the channel, which is possibly a reply
 async fn send_text_message(
     stream_api: &mut ConnectedStreamApi,
@@ -568,6 +997,31 @@ async fn send_position(
         .await
 }
 
+/// Send a [Waypoint] to the channel or other node
+async fn send_waypoint(
+    stream_api: &mut ConnectedStreamApi,
+    my_router: &mut MyRouter,
+    conversation_id: ConversationId,
+    waypoint: Waypoint,
+) -> Result<(), Error> {
+    let (packet_destination, mesh_channel) = conversation_id.to_destination();
+    stream_api
+        .send_mesh_packet(
+            my_router,
+            waypoint.encode_to_vec().into(),
+            PortNum::WaypointApp,
+            packet_destination,
+            mesh_channel,
+            true, // want_ack
+            false,
+            true, // echo_response - via PacketRouter
+            None,
+            None,
+        )
+        // jonesy:allow(unknown) async state machine artifact
+        .await
+}
+
 /// Send a [User] info "ping" message to the channel or other node
 async fn send_user(
     stream_api: &mut ConnectedStreamApi,
@@ -665,12 +1119,106 @@ async fn do_connect(
             })??;
             stream_api.connect(ble_stream).await
         }
+        #[cfg(feature = "serial")]
+        DeviceIdentifier::Serial { path, baud } => {
+            let serial_stream = build_serial_stream(path, *baud)?;
+            stream_api.connect(serial_stream).await
+        }
+        #[cfg(feature = "sim")]
+        DeviceIdentifier::Sim { .. } => {
+            return Err(Error::StreamBuildError {
+                source: Box::new(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    "Meshtastic subscription",
+                )),
+                description: "A simulated radio is not a Meshtastic radio".to_string(),
+            });
+        }
+        DeviceIdentifier::Replay { .. } => {
+            return Err(Error::StreamBuildError {
+                source: Box::new(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    "Meshtastic subscription",
+                )),
+                description: "A capture is replayed, not connected to".to_string(),
+            });
+        }
     };
     let config_id = utils::generate_rand_id();
     let stream_api = stream_api.configure(config_id).await?;
     Ok((packet_receiver, stream_api))
 }
 
+/// The error for a command that needs the stream API when there isn't one, as the radio is not
+/// connected or is a replayed capture
+fn api_not_available() -> Error {
+    Error::StreamBuildError {
+        source: Box::new(std::io::Error::new(
+            std::io::ErrorKind::NotConnected,
+            "Stream API not available",
+        )),
+        description: "Subscription".to_string(),
+    }
+}
+
+/// A copy of `packet` with the device's private key and the channels' PSKs blanked, so that a
+/// capture can be shared (e.g. attached to a bug report) without giving away any key material
+fn without_keys(packet: &FromRadio) -> FromRadio {
+    let mut packet = packet.clone();
+    match &mut packet.payload_variant {
+        Some(Config(config)) => {
+            if let Some(Security(security)) = &mut config.payload_variant {
+                security.private_key.clear();
+            }
+        }
+        Some(Channel(channel)) => {
+            if let Some(settings) = &mut channel.settings {
+                settings.psk.clear();
+            }
+        }
+        _ => {}
+    }
+    packet
+}
+
+/// Load the capture file at `path` and return a stream that replays the [FromRadio] packets in it
+/// with the same timing as they were captured
+async fn replay_packets(path: &str) -> Result<FromRadioStream, Error> {
+    let (_header, records) = capture::load::<FromRadio>(Path::new(path))
+        .await
+        .map_err(|e| Error::StreamBuildError {
+            source: Box::new(e),
+            description: format!("Error loading capture '{path}'"),
+        })?;
+    // The replayed radio stays attached after the last packet, until the GUI disconnects
+    Ok(Box::pin(capture::replay(records).chain(pending())))
+}
+
+/// Open the serial port at `path` for use by the [StreamApi], which handles the Meshtastic
+/// serial framing of packets. This is the same as meshtastic's `build_serial_stream` except that
+/// failing to set the DTR and RTS lines is not an error, as not all serial devices
+/// (such as pseudo-terminals) have them.
+#[cfg(feature = "serial")]
+fn build_serial_stream(path: &str, baud: u32) -> Result<StreamHandle<SerialStream>, Error> {
+    let mut serial_stream = tokio_serial::new(path, baud)
+        .flow_control(tokio_serial::FlowControl::None)
+        .timeout(Duration::from_millis(10))
+        .open_native_async()
+        .map_err(|e| Error::StreamBuildError {
+            source: Box::new(e),
+            description: format!("Error opening serial port '{path}'"),
+        })?;
+
+    serial_stream
+        .write_data_terminal_ready(utils::DEFAULT_DTR_PIN_STATE)
+        .unwrap_or_else(|e| eprintln!("Could not set DTR on serial port '{path}': {e}"));
+    serial_stream
+        .write_request_to_send(utils::DEFAULT_RTS_PIN_STATE)
+        .unwrap_or_else(|e| eprintln!("Could not set RTS on serial port '{path}': {e}"));
+
+    Ok(StreamHandle::from_stream(serial_stream))
+}
+
 /// Disconnect from the radio we are currently connected to using the [ConnectedStreamApi]
 async fn do_disconnect(stream_api: ConnectedStreamApi) -> Result<StreamApi, Error> {
     timeout(Duration::from_secs(1), stream_api.disconnect())
@@ -1108,6 +1656,100 @@ mod tests {
             .try_next()
             .expect("Failed to receive NewChannel event");
         assert!(matches!(event, Some(NewChannel(_))));
+
+        // Followed by the settings needed to share it
+        let event = receiver
+            .try_next()
+            .expect("Failed to receive NewChannelSettings event");
+        assert!(matches!(
+            event,
+            Some(NewChannelSettings(0, settings)) if settings.name == "TestChannel"
+        ));
+    }
+
+    #[test]
+    fn test_channel_configs_replaced() {
+        let channels = vec![
+            MCChannelSettings {
+                psk: vec![1],
+                ..Default::default()
+            },
+            MCChannelSettings {
+                name: "Private".into(),
+                psk: vec![7; 32],
+                ..Default::default()
+            },
+        ];
+        let channel_set = MCChannelSet {
+            channels,
+            ..Default::default()
+        };
+        let configs = channel_configs(&channel_set, &[0, 1, 2]);
+        assert_eq!(configs.len(), MAX_CHANNELS);
+
+        let roles: Vec<Role> = configs.iter().map(ProtoChannel::role).collect();
+        assert_eq!(roles[0], Role::Primary);
+        assert_eq!(roles[1], Role::Secondary);
+        assert!(roles[2..].iter().all(|role| *role == Role::Disabled));
+
+        let indexes: Vec<i32> = configs.iter().map(|config| config.index).collect();
+        assert_eq!(indexes, (0..MAX_CHANNELS as i32).collect::<Vec<_>>());
+        assert_eq!(
+            configs[1]
+                .settings
+                .as_ref()
+                .map(|settings| settings.name.as_str()),
+            Some("Private")
+        );
+    }
+
+    #[test]
+    fn test_channel_configs_added() {
+        let channel_set = MCChannelSet {
+            channels: vec![
+                MCChannelSettings {
+                    name: "Team".into(),
+                    psk: vec![7; 16],
+                    ..Default::default()
+                },
+                MCChannelSettings {
+                    name: "Family".into(),
+                    psk: vec![8; 16],
+                    ..Default::default()
+                },
+            ],
+            add: true,
+            ..Default::default()
+        };
+        // Only the free secondary slots are written, so the channels in use are kept
+        let configs = channel_configs(&channel_set, &[0, 1, 3]);
+        let indexes: Vec<i32> = configs.iter().map(|config| config.index).collect();
+        assert_eq!(indexes, vec![2, 4]);
+        assert!(
+            configs
+                .iter()
+                .all(|config| config.role() == Role::Secondary)
+        );
+        assert_eq!(
+            configs[1]
+                .settings
+                .as_ref()
+                .map(|settings| settings.name.as_str()),
+            Some("Family")
+        );
+    }
+
+    #[test]
+    fn test_ignore_admin_message() {
+        let node_id = conversation_id::NodeId::from(0x1234u32);
+        assert_eq!(
+            ignore_admin_message(node_id, true).payload_variant,
+            Some(SetIgnoredNode(0x1234))
+        );
+        assert_eq!(
+            ignore_admin_message(node_id, false).payload_variant,
+            Some(RemoveIgnoredNode(0x1234))
+        );
     }
 
     #[tokio::test]
@@ -1212,7 +1854,7 @@ mod tests {
             .try_recv()
             .expect("Failed to receive MCMessageReceived event for new text message");
         assert!(
-            matches!(&event, MCMessageReceived(conversation_id, id, from, msg, _timestamp)
+            matches!(&event, MCMessageReceived(conversation_id, id, from, msg, _timestamp, _)
                 if *conversation_id == ConversationId::Channel(0.into()) && *id == MessageId::from(123) && *from == conversation_id::NodeId::from(2000u64)
                 && matches!(msg, NewTextMessage(text) if text == "Hello world")),
             "Expected MCMessageReceived with channel 0, id 123, from 2000, NewTextMessage('Hello world'), got {:?}",
@@ -1221,29 +1863,79 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn test_handle_text_message_reply() {
+    async fn test_handle_text_message_link_quality() {
         let (sender, mut receiver) = mpsc::channel::<DeviceEvent>(10);
         let mut router = MyRouter::new(sender);
         router.my_node_num = Some(1000);
 
-        // Text reply (reply_id set, emoji = 0)
-        let packet = create_text_mesh_packet(2000, u32::MAX, 0, 123, "Reply text", 456, 0);
+        let mut packet = create_text_mesh_packet(2000, u32::MAX, 0, 123, "Hello world", 0, 0);
+        packet.rx_snr = 5.25;
+        packet.rx_rssi = -97;
+        packet.hop_start = 3;
+        packet.hop_limit = 1;
         router.handle_a_mesh_packet(&packet).await;
 
         let event = receiver
             .try_recv()
-            .expect("Failed to receive MCMessageReceived event for text reply");
+            .expect("Failed to receive MCMessageReceived event");
         assert!(
-            matches!(&event, MCMessageReceived(_, _, _, msg, _)
-                if matches!(msg, TextMessageReply(reply_id, text) if *reply_id == MessageId::from(456) && text == "Reply text")),
-            "Expected MCMessageReceived with TextMessageReply(456, 'Reply text'), got {:?}",
+            matches!(&event, MCMessageReceived(_, _, _, _, _, Some(link_quality))
+                if *link_quality == LinkQuality { snr: Some(5.25), rssi: Some(-97), hops: Some(2) }),
+            "Expected MCMessageReceived with link quality, got {:?}",
             event
         );
     }
 
-    #[tokio::test]
-    async fn test_handle_emoji_reply() {
-        let (sender, mut receiver) = mpsc::channel::<DeviceEvent>(10);
+    #[test]
+    fn test_link_quality() {
+        // Sent from my own node, so not received over the air
+        let packet = create_mesh_packet(1000, u32::MAX, 0, 1);
+        assert_eq!(link_quality(&packet), None);
+
+        // Older firmware that does not report hop_start
+        let mut packet = create_mesh_packet(2000, u32::MAX, 0, 1);
+        packet.rx_snr = -2.5;
+        assert_eq!(
+            link_quality(&packet),
+            Some(LinkQuality {
+                snr: Some(-2.5),
+                rssi: None,
+                hops: None
+            })
+        );
+
+        // Heard directly
+        packet.hop_start = 3;
+        assert_eq!(
+            link_quality(&packet).and_then(|quality| quality.hops),
+            Some(0)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_handle_text_message_reply() {
+        let (sender, mut receiver) = mpsc::channel::<DeviceEvent>(10);
+        let mut router = MyRouter::new(sender);
+        router.my_node_num = Some(1000);
+
+        // Text reply (reply_id set, emoji = 0)
+        let packet = create_text_mesh_packet(2000, u32::MAX, 0, 123, "Reply text", 456, 0);
+        router.handle_a_mesh_packet(&packet).await;
+
+        let event = receiver
+            .try_recv()
+            .expect("Failed to receive MCMessageReceived event for text reply");
+        assert!(
+            matches!(&event, MCMessageReceived(_, _, _, msg, _, _)
+                if matches!(msg, TextMessageReply(reply_id, text) if *reply_id == MessageId::from(456) && text == "Reply text")),
+            "Expected MCMessageReceived with TextMessageReply(456, 'Reply text'), got {:?}",
+            event
+        );
+    }
+
+    #[tokio::test]
+    async fn test_handle_emoji_reply() {
+        let (sender, mut receiver) = mpsc::channel::<DeviceEvent>(10);
         let mut router = MyRouter::new(sender);
         router.my_node_num = Some(1000);
 
@@ -1255,7 +1947,7 @@ mod tests {
             .try_recv()
             .expect("Failed to receive MCMessageReceived event for emoji reply");
         assert!(
-            matches!(&event, MCMessageReceived(_, _, _, msg, _)
+            matches!(&event, MCMessageReceived(_, _, _, msg, _, _)
                 if matches!(msg, EmojiReply(reply_id, emoji) if *reply_id == MessageId::from(456) && emoji == "👍")),
             "Expected MCMessageReceived with EmojiReply(456, '👍'), got {:?}",
             event
@@ -1304,6 +1996,478 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn test_handle_nak_dm() {
+        let (sender, mut receiver) = mpsc::channel::<DeviceEvent>(10);
+        let mut router = MyRouter::new(sender);
+        router.my_node_num = Some(1000);
+
+        let mut packet = create_ack_mesh_packet(2000, 1000, 0, 789);
+        if let Some(Decoded(data)) = &mut packet.payload_variant {
+            data.payload = Routing {
+                variant: Some(ErrorReason(routing::Error::MaxRetransmit as i32)),
+            }
+            .encode_to_vec();
+        }
+        router.handle_a_mesh_packet(&packet).await;
+
+        let event = receiver
+            .try_recv()
+            .expect("Failed to receive MessageFailed event for DM");
+        assert!(
+            matches!(&event, MessageFailed(conversation_id, request_id, reason)
+                if *conversation_id == Node(conversation_id::NodeId::from(2000u32))
+                && *request_id == MessageId::from(789) && reason == "max retransmit"),
+            "Expected MessageFailed(Node(2000), 789, \"max retransmit\"), got {:?}",
+            event
+        );
+    }
+
+    #[tokio::test]
+    async fn test_handle_routing_no_error_is_ack() {
+        let (sender, mut receiver) = mpsc::channel::<DeviceEvent>(10);
+        let mut router = MyRouter::new(sender);
+        router.my_node_num = Some(1000);
+
+        let mut packet = create_ack_mesh_packet(2000, 1000, 0, 789);
+        if let Some(Decoded(data)) = &mut packet.payload_variant {
+            data.payload = Routing {
+                variant: Some(ErrorReason(routing::Error::None as i32)),
+            }
+            .encode_to_vec();
+        }
+        router.handle_a_mesh_packet(&packet).await;
+
+        let event = receiver.try_recv().expect("Failed to receive MessageACK");
+        assert!(matches!(event, MessageACK(_, _)));
+    }
+
+    #[tokio::test]
+    async fn test_sent_dm_awaits_ack() {
+        let (sender, mut receiver) = mpsc::channel::<DeviceEvent>(10);
+        let mut router = MyRouter::new(sender);
+        router.my_node_num = Some(1000);
+
+        let mut packet = create_text_mesh_packet(1000, 2000, 0, 123, "Hello", 0, 0);
+        packet.want_ack = true;
+        router.handle_a_mesh_packet(&packet).await;
+
+        let event = receiver
+            .try_recv()
+            .expect("Failed to receive MCMessageReceived");
+        assert!(matches!(event, MCMessageReceived(_, _, _, _, _, _)));
+        let event = receiver.try_recv().expect("Failed to receive AwaitingAck");
+        assert!(
+            matches!(&event, AwaitingAck(conversation_id, id, timeout)
+                if *conversation_id == Node(conversation_id::NodeId::from(2000u32))
+                && *id == MessageId::from(123) && *timeout == ACK_TIMEOUT),
+            "Expected AwaitingAck(Node(2000), 123, ACK_TIMEOUT), got {:?}",
+            event
+        );
+    }
+
+    #[tokio::test]
+    async fn test_sent_broadcast_does_not_await_ack() {
+        let (sender, mut receiver) = mpsc::channel::<DeviceEvent>(10);
+        let mut router = MyRouter::new(sender);
+        router.my_node_num = Some(1000);
+
+        let mut packet = create_text_mesh_packet(1000, u32::MAX, 0, 123, "Hello", 0, 0);
+        packet.want_ack = true;
+        router.handle_a_mesh_packet(&packet).await;
+
+        let _ = receiver
+            .try_recv()
+            .expect("Failed to receive MCMessageReceived");
+        assert!(receiver.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_received_dm_does_not_await_ack() {
+        let (sender, mut receiver) = mpsc::channel::<DeviceEvent>(10);
+        let mut router = MyRouter::new(sender);
+        router.my_node_num = Some(1000);
+
+        let mut packet = create_text_mesh_packet(2000, 1000, 0, 123, "Hello", 0, 0);
+        packet.want_ack = true;
+        router.handle_a_mesh_packet(&packet).await;
+
+        let _ = receiver
+            .try_recv()
+            .expect("Failed to receive MCMessageReceived");
+        assert!(receiver.try_recv().is_err());
+    }
+
+    fn create_traceroute_mesh_packet(
+        from: u32,
+        to: u32,
+        request_id: u32,
+        discovery: &RouteDiscovery,
+    ) -> MeshPacket {
+        let mut packet = create_mesh_packet(from, to, 0, 2);
+        packet.payload_variant = Some(Decoded(Data {
+            portnum: PortNum::TracerouteApp as i32,
+            payload: discovery.encode_to_vec(),
+            want_response: false,
+            dest: 0,
+            source: 0,
+            request_id,
+            reply_id: 0,
+            emoji: 0,
+            bitfield: Some(0),
+        }));
+        packet
+    }
+
+    #[test]
+    fn test_traced_route() {
+        let discovery = RouteDiscovery {
+            route: vec![3000, UNKNOWN_NODE_NUM],
+            snr_towards: vec![10, UNKNOWN_SNR, -6],
+            route_back: vec![3000],
+            snr_back: vec![25, 4],
+        };
+        let route = traced_route(1000, 2000, &discovery);
+        assert_eq!(route.destination, conversation_id::NodeId::from(2000u32));
+
+        let towards: Vec<_> = route
+            .towards
+            .iter()
+            .map(|hop| (hop.node_id, hop.snr))
+            .collect();
+        assert_eq!(
+            towards,
+            vec![
+                (Some(conversation_id::NodeId::from(1000u32)), None),
+                (Some(conversation_id::NodeId::from(3000u32)), Some(2.5)),
+                (None, None),
+                (Some(conversation_id::NodeId::from(2000u32)), Some(-1.5)),
+            ]
+        );
+
+        let back: Vec<_> = route
+            .back
+            .iter()
+            .map(|hop| (hop.node_id, hop.snr))
+            .collect();
+        assert_eq!(
+            back,
+            vec![
+                (Some(conversation_id::NodeId::from(2000u32)), None),
+                (Some(conversation_id::NodeId::from(3000u32)), Some(6.25)),
+                (Some(conversation_id::NodeId::from(1000u32)), Some(1.0)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_traced_route_direct_without_back() {
+        let discovery = RouteDiscovery {
+            snr_towards: vec![8],
+            ..Default::default()
+        };
+        let route = traced_route(1000, 2000, &discovery);
+        assert_eq!(route.towards.len(), 2);
+        assert!(route.back.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_handle_traceroute_response() {
+        let (sender, mut receiver) = mpsc::channel::<DeviceEvent>(10);
+        let mut router = MyRouter::new(sender);
+        router.my_node_num = Some(1000);
+
+        let discovery = RouteDiscovery {
+            snr_towards: vec![8],
+            ..Default::default()
+        };
+        let packet = create_traceroute_mesh_packet(2000, 1000, 99, &discovery);
+        router.handle_a_mesh_packet(&packet).await;
+
+        let event = receiver
+            .try_recv()
+            .expect("Failed to receive TracerouteResponse");
+        assert!(
+            matches!(&event, TracerouteResponse(route)
+                if route.destination == conversation_id::NodeId::from(2000u32)),
+            "Expected TracerouteResponse to node 2000, got {:?}",
+            event
+        );
+    }
+
+    #[tokio::test]
+    async fn test_handle_traceroute_request_ignored() {
+        let (sender, mut receiver) = mpsc::channel::<DeviceEvent>(10);
+        let mut router = MyRouter::new(sender);
+        router.my_node_num = Some(1000);
+
+        // A request from another node, which the radio answers itself
+        let packet = create_traceroute_mesh_packet(2000, 1000, 0, &RouteDiscovery::default());
+        router.handle_a_mesh_packet(&packet).await;
+        // A response to another node's request
+        let packet = create_traceroute_mesh_packet(2000, 3000, 99, &RouteDiscovery::default());
+        router.handle_a_mesh_packet(&packet).await;
+
+        assert!(receiver.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_neighbours() {
+        let neighbor_info = NeighborInfo {
+            node_id: 2000,
+            neighbors: vec![
+                meshtastic::protobufs::Neighbor {
+                    node_id: 3000,
+                    snr: 5.5,
+                    last_rx_time: 1_700_000_000,
+                    node_broadcast_interval_secs: 0,
+                },
+                meshtastic::protobufs::Neighbor {
+                    node_id: 4000,
+                    snr: -2.0,
+                    last_rx_time: 0,
+                    node_broadcast_interval_secs: 0,
+                },
+            ],
+            ..Default::default()
+        };
+        let now = TimeStamp::from(1_800_000_000_000u64);
+        assert_eq!(
+            neighbours(&neighbor_info, now),
+            vec![
+                MCNeighbour {
+                    node_id: conversation_id::NodeId::from(3000u32),
+                    snr: Some(5.5),
+                    last_heard: TimeStamp::from(1_700_000_000_000u64),
+                },
+                MCNeighbour {
+                    node_id: conversation_id::NodeId::from(4000u32),
+                    snr: Some(-2.0),
+                    last_heard: now,
+                },
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_handle_neighbor_info() {
+        let (sender, mut receiver) = mpsc::channel::<DeviceEvent>(10);
+        let mut router = MyRouter::new(sender);
+        router.my_node_num = Some(1000);
+
+        let neighbor_info = NeighborInfo {
+            node_id: 2000,
+            neighbors: vec![meshtastic::protobufs::Neighbor {
+                node_id: 3000,
+                snr: 5.5,
+                last_rx_time: 0,
+                node_broadcast_interval_secs: 0,
+            }],
+            ..Default::default()
+        };
+        let mut packet = create_mesh_packet(2000, u32::MAX, 0, 5);
+        packet.payload_variant = Some(Decoded(Data {
+            portnum: PortNum::NeighborinfoApp as i32,
+            payload: neighbor_info.encode_to_vec(),
+            want_response: false,
+            dest: 0,
+            source: 0,
+            request_id: 0,
+            reply_id: 0,
+            emoji: 0,
+            bitfield: Some(0),
+        }));
+        router.handle_a_mesh_packet(&packet).await;
+
+        let event = receiver
+            .try_recv()
+            .expect("Failed to receive NeighbourInfo");
+        assert!(
+            matches!(&event, NeighbourInfo(node_id, neighbours)
+                if *node_id == conversation_id::NodeId::from(2000u32) && neighbours.len() == 1),
+            "Expected NeighbourInfo from node 2000, got {:?}",
+            event
+        );
+    }
+
+    #[tokio::test]
+    async fn test_handle_waypoint() {
+        let (sender, mut receiver) = mpsc::channel::<DeviceEvent>(10);
+        let mut router = MyRouter::new(sender);
+        router.my_node_num = Some(1000);
+
+        let waypoint = Waypoint {
+            id: 7,
+            latitude_i: Some(505_000_000),
+            longitude_i: Some(-12_500_000),
+            name: "Camp".into(),
+            ..Default::default()
+        };
+        let mut packet = create_mesh_packet(2000, u32::MAX, 0, 8);
+        packet.payload_variant = Some(Decoded(Data {
+            portnum: PortNum::WaypointApp as i32,
+            payload: waypoint.encode_to_vec(),
+            want_response: false,
+            dest: 0,
+            source: 0,
+            request_id: 0,
+            reply_id: 0,
+            emoji: 0,
+            bitfield: Some(0),
+        }));
+        router.handle_a_mesh_packet(&packet).await;
+
+        let event = receiver
+            .try_recv()
+            .expect("Failed to receive MCMessageReceived");
+        assert!(
+            matches!(&event, MCMessageReceived(ConversationId::Channel(_), _, from, MCContent::Waypoint(received), _, _)
+                if *from == conversation_id::NodeId::from(2000u32)
+                    && received.id == 7
+                    && received.name == "Camp"),
+            "Expected a Waypoint from node 2000, got {:?}",
+            event
+        );
+    }
+
+    fn telemetry_packet(from: u32, telemetry: &Telemetry) -> MeshPacket {
+        let mut packet = create_mesh_packet(from, u32::MAX, 0, 6);
+        packet.payload_variant = Some(Decoded(Data {
+            portnum: PortNum::TelemetryApp as i32,
+            payload: telemetry.encode_to_vec(),
+            want_response: false,
+            dest: 0,
+            source: 0,
+            request_id: 0,
+            reply_id: 0,
+            emoji: 0,
+            bitfield: Some(0),
+        }));
+        packet
+    }
+
+    #[tokio::test]
+    async fn test_handle_telemetry_from_other_node() {
+        let (sender, mut receiver) = mpsc::channel::<DeviceEvent>(10);
+        let mut router = MyRouter::new(sender);
+        router.my_node_num = Some(1000);
+
+        let telemetry = Telemetry {
+            variant: Some(
+                meshtastic::protobufs::telemetry::Variant::EnvironmentMetrics(
+                    meshtastic::protobufs::EnvironmentMetrics {
+                        temperature: Some(21.5),
+                        ..Default::default()
+                    },
+                ),
+            ),
+            ..Default::default()
+        };
+        router
+            .handle_a_mesh_packet(&telemetry_packet(2000, &telemetry))
+            .await;
+
+        let event = receiver
+            .try_recv()
+            .expect("Failed to receive NodeTelemetry");
+        assert!(
+            matches!(&event, NodeTelemetry(node_id, MCTelemetry::Environment(metrics), _)
+                if *node_id == conversation_id::NodeId::from(2000u32)
+                    && metrics.temperature == Some(21.5)),
+            "Expected NodeTelemetry from node 2000, got {:?}",
+            event
+        );
+        assert!(
+            receiver.try_recv().is_err(),
+            "No battery level from other nodes"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_handle_telemetry_uses_measured_time() {
+        let (sender, mut receiver) = mpsc::channel::<DeviceEvent>(10);
+        let mut router = MyRouter::new(sender);
+        router.my_node_num = Some(1000);
+
+        // A minute ago, in seconds
+        let measured = (u64::from(TimeStamp::now()) / 1000 - 60) as u32;
+        let telemetry = Telemetry {
+            time: measured,
+            variant: Some(
+                meshtastic::protobufs::telemetry::Variant::EnvironmentMetrics(
+                    meshtastic::protobufs::EnvironmentMetrics {
+                        temperature: Some(21.5),
+                        ..Default::default()
+                    },
+                ),
+            ),
+        };
+        router
+            .handle_a_mesh_packet(&telemetry_packet(2000, &telemetry))
+            .await;
+
+        let event = receiver
+            .try_recv()
+            .expect("Failed to receive NodeTelemetry");
+        assert!(
+            matches!(&event, NodeTelemetry(_, _, timestamp)
+                if *timestamp == TimeStamp::from(u64::from(measured) * 1000)),
+            "Expected NodeTelemetry with the measured time, got {:?}",
+            event
+        );
+    }
+
+    #[test]
+    fn test_telemetry_time_falls_back_to_received() {
+        let received = TimeStamp::from(1_700_000_000_000u64);
+        assert_eq!(telemetry_time(0, received), received);
+        // A node whose clock was never set, that counts from when it started
+        assert_eq!(telemetry_time(3_600, received), received);
+        assert_eq!(telemetry_time(1_800_000_000, received), received);
+        assert_eq!(
+            telemetry_time(1_699_999_000, received),
+            TimeStamp::from(1_699_999_000_000u64)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_handle_my_device_telemetry() {
+        let (sender, mut receiver) = mpsc::channel::<DeviceEvent>(10);
+        let mut router = MyRouter::new(sender);
+        router.my_node_num = Some(1000);
+
+        let telemetry = Telemetry {
+            variant: Some(DeviceMetrics(meshtastic::protobufs::DeviceMetrics {
+                battery_level: Some(75),
+                voltage: Some(3.8),
+                ..Default::default()
+            })),
+            ..Default::default()
+        };
+        router
+            .handle_a_mesh_packet(&telemetry_packet(1000, &telemetry))
+            .await;
+
+        let event = receiver
+            .try_recv()
+            .expect("Failed to receive DeviceBatteryLevel");
+        assert!(
+            matches!(event, DeviceBatteryLevel(Some(75))),
+            "Expected DeviceBatteryLevel, got {:?}",
+            event
+        );
+        let event = receiver
+            .try_recv()
+            .expect("Failed to receive NodeTelemetry");
+        assert!(
+            matches!(&event, NodeTelemetry(node_id, MCTelemetry::Device(metrics), _)
+                if *node_id == conversation_id::NodeId::from(1000u32)
+                    && metrics.voltage == Some(3.8)),
+            "Expected NodeTelemetry from my node, got {:?}",
+            event
+        );
+    }
+
     #[tokio::test]
     async fn test_handle_packet_no_payload() {
         let (mut sender, mut receiver) = mpsc::channel::<DeviceEvent>(10);
@@ -1369,7 +2533,7 @@ mod tests {
         let event = receiver
             .try_recv()
             .expect("Failed to receive MCMessageReceived event from PacketRouter");
-        assert!(matches!(event, MCMessageReceived(_, _, _, _, _)));
+        assert!(matches!(event, MCMessageReceived(_, _, _, _, _, _)));
     }
 
     // Tests for local timestamp usage (TimeStamp::now()()() instead of radio rx_time)
@@ -1388,7 +2552,7 @@ mod tests {
         let event = receiver
             .try_recv()
             .expect("Expected MCMessageReceived event");
-        if let MCMessageReceived(_, _, _, _, timestamp) = event {
+        if let MCMessageReceived(_, _, _, _, timestamp, _) = event {
             assert!(
                 timestamp >= before && timestamp <= after,
                 "Timestamp should be local time ",
@@ -1412,7 +2576,7 @@ mod tests {
         let event = receiver
             .try_recv()
             .expect("Expected MCMessageReceived event");
-        if let MCMessageReceived(_, _, _, _, timestamp) = event {
+        if let MCMessageReceived(_, _, _, _, timestamp, _) = event {
             assert!(
                 timestamp >= before && timestamp <= after,
                 "Timestamp should be local time",
@@ -1436,7 +2600,7 @@ mod tests {
         let event = receiver
             .try_recv()
             .expect("Expected MCMessageReceived event");
-        if let MCMessageReceived(_, _, _, _, timestamp) = event {
+        if let MCMessageReceived(_, _, _, _, timestamp, _) = event {
             assert!(
                 timestamp >= before && timestamp <= after,
                 "Timestamp should be local time",
@@ -1489,4 +2653,266 @@ mod tests {
             unreachable!("Expected NewNodeInfo event, got {:?}", event);
         }
     }
+
+    #[cfg(all(feature = "serial", target_os = "linux"))]
+    #[tokio::test]
+    async fn test_serial_connect_over_pty() {
+        use meshtastic::protobufs::ToRadio;
+        use meshtastic::protobufs::to_radio::PayloadVariant::WantConfigId;
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        // The "master" end of the pair acts as the radio, the "slave" end is the serial port
+        let (mut radio, port) = SerialStream::pair().expect("Could not create pty pair");
+        let path = port.name().expect("pty has no name");
+        drop(port);
+
+        let device = DeviceIdentifier::Serial {
+            path,
+            baud: 115_200,
+        };
+        let (mut packet_receiver, _stream_api) =
+            timeout(Duration::from_secs(5), do_connect(&device))
+                .await
+                .expect("Timed out connecting")
+                .expect("Could not connect over serial");
+
+        // Connecting asks the radio for its config, in a framed packet
+        let mut header = [0u8; 4];
+        timeout(Duration::from_secs(5), radio.read_exact(&mut header))
+            .await
+            .expect("Timed out reading header")
+            .expect("Could not read header");
+        assert_eq!(header[0..2], [0x94, 0xc3]);
+        let mut data = vec![0u8; usize::from(u16::from_be_bytes([header[2], header[3]]))];
+        timeout(Duration::from_secs(5), radio.read_exact(&mut data))
+            .await
+            .expect("Timed out reading packet")
+            .expect("Could not read packet");
+        let to_radio = ToRadio::decode(data.as_slice()).expect("Could not decode ToRadio");
+        assert!(matches!(to_radio.payload_variant, Some(WantConfigId(_))));
+
+        // A framed packet from the radio should be received
+        let from_radio = FromRadio {
+            id: 1,
+            payload_variant: Some(MyInfo(MyNodeInfo {
+                my_node_num: 1234,
+                ..Default::default()
+            })),
+        };
+        let encoded = from_radio.encode_to_vec();
+        let length = u16::try_from(encoded.len()).expect("Packet too long");
+        let mut framed = vec![0x94, 0xc3];
+        framed.extend_from_slice(&length.to_be_bytes());
+        framed.extend_from_slice(&encoded);
+        radio
+            .write_all(&framed)
+            .await
+            .expect("Could not write packet");
+
+        let received = timeout(Duration::from_secs(5), packet_receiver.recv())
+            .await
+            .expect("Timed out receiving packet")
+            .expect("Packet receiver closed");
+        assert!(matches!(
+            received.payload_variant,
+            Some(MyInfo(MyNodeInfo {
+                my_node_num: 1234,
+                ..
+            }))
+        ));
+    }
+
+    #[cfg(feature = "serial")]
+    #[test]
+    fn test_serial_open_missing_port_fails() {
+        assert!(build_serial_stream("/dev/no-such-meshtastic-port", 115_200).is_err());
+    }
+
+    // Tests for capture and replay
+
+    fn my_info_packet(my_node_num: u32) -> FromRadio {
+        FromRadio {
+            id: 1,
+            payload_variant: Some(MyInfo(MyNodeInfo {
+                my_node_num,
+                reboot_count: 0,
+                min_app_version: 0,
+                pio_env: String::new(),
+                device_id: vec![],
+                firmware_edition: 0,
+                nodedb_count: 0,
+            })),
+        }
+    }
+
+    fn text_packet(from: u32, to: u32, id: u32, text: &str) -> FromRadio {
+        FromRadio {
+            id: 2,
+            payload_variant: Some(Packet(create_text_mesh_packet(from, to, 0, id, text, 0, 0))),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_from_radio_capture_roundtrip() {
+        let tempdir = tempfile::Builder::new()
+            .prefix("meshchat")
+            .tempdir()
+            .expect("Could not create a temp dir");
+        let device = DeviceIdentifier::from("AA:BB:CC:DD:EE:FF");
+        let mut capture = Capture::create(tempdir.path(), &device, RadioType::Meshtastic)
+            .await
+            .expect("Could not create capture");
+        let packets = vec![
+            my_info_packet(1000),
+            text_packet(2000, u32::MAX, 42, "Hello 👋"),
+        ];
+        for packet in &packets {
+            capture
+                .record(packet)
+                .await
+                .expect("Could not record packet");
+        }
+
+        let (header, records) = capture::load::<FromRadio>(capture.path())
+            .await
+            .expect("Could not load capture");
+        assert_eq!(header.radio_type, RadioType::Meshtastic);
+        assert_eq!(
+            records
+                .into_iter()
+                .map(|record| record.packet)
+                .collect::<Vec<_>>(),
+            packets
+        );
+    }
+
+    #[allow(deprecated)]
+    #[tokio::test]
+    async fn test_capture_has_no_keys() {
+        let tempdir = tempfile::Builder::new()
+            .prefix("meshchat")
+            .tempdir()
+            .expect("Could not create a temp dir");
+        let device = DeviceIdentifier::from("AA:BB:CC:DD:EE:FF");
+        let mut capture = Capture::create(tempdir.path(), &device, RadioType::Meshtastic)
+            .await
+            .expect("Could not create capture");
+        let security = FromRadio {
+            id: 1,
+            payload_variant: Some(Config(meshtastic::protobufs::Config {
+                payload_variant: Some(Security(meshtastic::protobufs::config::SecurityConfig {
+                    public_key: vec![1; 32],
+                    private_key: vec![2; 32],
+                    ..Default::default()
+                })),
+            })),
+        };
+        let channel = FromRadio {
+            id: 2,
+            payload_variant: Some(Channel(ProtoChannel {
+                index: 0,
+                settings: Some(ChannelSettings {
+                    psk: vec![3; 16],
+                    name: "Secret".to_string(),
+                    ..Default::default()
+                }),
+                role: Role::Primary as i32,
+            })),
+        };
+        for packet in [&security, &channel] {
+            capture
+                .record(&without_keys(packet))
+                .await
+                .expect("Could not record packet");
+        }
+
+        let (_, records) = capture::load::<FromRadio>(capture.path())
+            .await
+            .expect("Could not load capture");
+        let packets: Vec<FromRadio> = records.into_iter().map(|record| record.packet).collect();
+        match &packets[0].payload_variant {
+            Some(Config(meshtastic::protobufs::Config {
+                payload_variant: Some(Security(security)),
+            })) => {
+                assert!(security.private_key.is_empty());
+                assert_eq!(security.public_key, vec![1; 32]);
+            }
+            other => panic!("Expected a security config, got {other:?}"),
+        }
+        match &packets[1].payload_variant {
+            Some(Channel(ProtoChannel {
+                settings: Some(settings),
+                ..
+            })) => {
+                assert!(settings.psk.is_empty());
+                assert_eq!(settings.name, "Secret");
+            }
+            other => panic!("Expected a channel, got {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_replay_missing_capture_fails() {
+        assert!(replay_packets("/does/not/exist.jsonl").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_replay_capture_through_subscription() {
+        let tempdir = tempfile::Builder::new()
+            .prefix("meshchat")
+            .tempdir()
+            .expect("Could not create a temp dir");
+        let device = DeviceIdentifier::from("AA:BB:CC:DD:EE:FF");
+        let mut capture = Capture::create(tempdir.path(), &device, RadioType::Meshtastic)
+            .await
+            .expect("Could not create capture");
+        // A DM to my node, which is only known to be a DM once my node number has been replayed
+        for packet in [my_info_packet(1000), text_packet(2000, 1000, 42, "Hello")] {
+            capture
+                .record(&packet)
+                .await
+                .expect("Could not record packet");
+        }
+        let replay_device = DeviceIdentifier::Replay {
+            path: capture.path().to_string_lossy().to_string(),
+        };
+
+        let mut events = Box::pin(subscribe());
+        let sender = match events.next().await {
+            Some(DeviceEvent::Ready(sender, RadioType::Meshtastic)) => Some(sender),
+            _ => None,
+        }
+        .expect("Expected the subscription to be ready");
+
+        assert!(
+            sender
+                .send(Connect(replay_device.clone(), RadioType::Meshtastic, false))
+                .await
+                .is_ok()
+        );
+        assert!(matches!(events.next().await, Some(ConnectingEvent(_))));
+        assert!(matches!(
+            events.next().await,
+            Some(ConnectedEvent(device, RadioType::Meshtastic)) if device == replay_device
+        ));
+        assert!(matches!(events.next().await, Some(MyNodeNum(_))));
+        match events.next().await {
+            Some(MCMessageReceived(conversation_id, message_id, from, content, _, _)) => {
+                assert_eq!(
+                    conversation_id,
+                    Node(conversation_id::NodeId::from(2000u32))
+                );
+                assert_eq!(message_id, MessageId::from(42u32));
+                assert_eq!(from, conversation_id::NodeId::from(2000u32));
+                assert_eq!(content.text(), Some("Hello"));
+            }
+            _ => panic!("Expected the text message to be replayed"),
+        }
+
+        assert!(sender.send(Disconnect).await.is_ok());
+        assert!(matches!(
+            events.next().await,
+            Some(DisconnectedEvent(device)) if device == replay_device
+        ));
+    }
 }
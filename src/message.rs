@@ -2,14 +2,17 @@ use crate::Message::{CopyToClipBoard, DeviceViewEvent, OpenUrl, ShowLocation};
 use crate::conversation::ChannelViewMessage;
 use crate::conversation::ChannelViewMessage::{MessageSeen, ReplyWithEmoji};
 use crate::conversation_id::{ConversationId, MessageId, NodeId};
-use crate::device::DeviceMessage::{ChannelMsg, ShowChannel, StartForwardingMessage};
+use crate::device::DeviceMessage::{
+    ChannelMsg, ResendMessage, ShowChannel, StartForwardingMessage,
+};
 use crate::device::{is_favourite_node, long_name, short_name};
-use crate::meshchat::{MCNodeInfo, MCPosition, MCUser};
+use crate::meshchat::{MCNodeInfo, MCPosition, MCUser, MCWaypoint};
 use crate::message::MCContent::{
     AlertMessage, EmojiReply, NewTextMessage, PositionMessage, TextMessageReply, UserMessage,
+    Waypoint,
 };
 use crate::styles::{
-    COLOR_DICTIONARY, COLOR_GREEN, TIME_TEXT_COLOR, TIME_TEXT_SIZE, TIME_TEXT_WIDTH,
+    COLOR_DICTIONARY, COLOR_GREEN, COLOR_RED, TIME_TEXT_COLOR, TIME_TEXT_SIZE, TIME_TEXT_WIDTH,
     alert_message_style, bubble_style, button_chip_style, menu_button_style, message_text_style,
     tooltip_style,
 };
@@ -27,6 +30,7 @@ use iced::{Bottom, Color, Element, Fill, Font, Left, Padding, Renderer, Right, T
 use iced_aw::menu::Menu;
 use iced_aw::{MenuBar, menu_bar, menu_items};
 use ringmap::RingMap;
+use serde::{Deserialize, Serialize};
 use std::cmp::Ordering;
 use std::collections::{HashMap, HashSet};
 use std::default::Default;
@@ -34,7 +38,7 @@ use std::fmt;
 use std::fmt::Formatter;
 use std::hash::{DefaultHasher, Hash, Hasher};
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum MCContent {
     AlertMessage(String),   // message_text
     NewTextMessage(String), // message_text
@@ -43,6 +47,7 @@ pub enum MCContent {
     EmojiReply(MessageId, String), // reply_message_id, reply_emoji_text
     PositionMessage(MCPosition), // position
     UserMessage(MCUser),    // user
+    Waypoint(MCWaypoint),   // waypoint
 }
 
 impl fmt::Display for MCContent {
@@ -54,6 +59,30 @@ impl fmt::Display for MCContent {
             EmojiReply(reply_id, text) => f.write_str(format!("{}: {}", text, reply_id).as_str()),
             PositionMessage(position) => f.write_str(format!("{}", position).as_str()),
             UserMessage(user) => f.write_str(format!("{}", user).as_str()),
+            Waypoint(waypoint) => f.write_str(format!("{}", waypoint).as_str()),
+        }
+    }
+}
+
+impl MCContent {
+    /// A short name for the kind of content, as used when exporting messages
+    pub fn kind(&self) -> &'static str {
+        match self {
+            AlertMessage(_) => "alert",
+            NewTextMessage(_) => "text",
+            TextMessageReply(_, _) => "reply",
+            EmojiReply(_, _) => "emoji",
+            PositionMessage(_) => "position",
+            UserMessage(_) => "user",
+            Waypoint(_) => "waypoint",
+        }
+    }
+
+    /// The text typed in by a user for the kinds of content that have text, used when searching
+    pub fn text(&self) -> Option<&str> {
+        match self {
+            AlertMessage(text) | NewTextMessage(text) | TextMessageReply(_, text) => Some(text),
+            EmojiReply(_, _) | PositionMessage(_) | UserMessage(_) | Waypoint(_) => None,
         }
     }
 }
@@ -64,9 +93,115 @@ impl Default for MCContent {
     }
 }
 
+/// How far a message sent by this device has got in being delivered
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum DeliveryState {
+    /// Sent, and waiting for an acknowledgement that it was received
+    Sending,
+    /// Sent, with no acknowledgement expected. Messages from others are in this state
+    #[default]
+    Sent,
+    /// Acknowledged as received by a receiver
+    Acked,
+    /// Not acknowledged in time, or the radio reported it could not be delivered
+    Failed,
+}
+
+impl DeliveryState {
+    /// A short name for the delivery state, as used when exporting messages
+    pub fn name(&self) -> &'static str {
+        match self {
+            DeliveryState::Sending => "sending",
+            DeliveryState::Sent => "sent",
+            DeliveryState::Acked => "acked",
+            DeliveryState::Failed => "failed",
+        }
+    }
+}
+
+/// How well a message was received, as reported by the radio that received it
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct LinkQuality {
+    /// Signal to noise ratio (dB) the message was received with, from the last node to relay it
+    pub snr: Option<f32>,
+    /// Received signal strength (dBm), from the last node to relay it
+    pub rssi: Option<i32>,
+    /// How many times the message was relayed on its way, 0 if heard directly from the sender
+    pub hops: Option<u32>,
+}
+
+impl LinkQuality {
+    /// The strength of the signal, as 0 to 4 bars. LoRa can be received below the noise floor,
+    /// hence the negative SNR thresholds. None if the radio did not report it.
+    pub fn bars(&self) -> Option<usize> {
+        self.snr.map(|snr| match snr {
+            snr if snr >= 5.0 => 4,
+            snr if snr >= 0.0 => 3,
+            snr if snr >= -7.5 => 2,
+            snr if snr >= -15.0 => 1,
+            _ => 0,
+        })
+    }
+
+    /// A short summary to show alongside a message, the signal bars and the hops travelled
+    pub fn summary(&self) -> String {
+        let signal = self
+            .bars()
+            .map(|bars| format!("{:░<4}", "▂▄▆█".chars().take(bars).collect::<String>()));
+        let hops = self.hops.map(|hops| match hops {
+            0 => "direct".to_string(),
+            1 => "1 hop".to_string(),
+            hops => format!("{hops} hops"),
+        });
+        [signal, hops]
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// All that is known about how the message was received, one value per line
+    pub fn detail(&self) -> String {
+        [
+            self.hops.map(|hops| match hops {
+                0 => "Heard directly from the sender".to_string(),
+                1 => "Relayed once".to_string(),
+                hops => format!("Relayed {hops} times"),
+            }),
+            self.snr.map(|snr| format!("SNR: {snr:.2} dB")),
+            self.rssi.map(|rssi| format!("RSSI: {rssi} dBm")),
+        ]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>()
+        .join("\n")
+    }
+}
+
+/// Messages saved before [DeliveryState] existed have an `acked` bool instead
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SavedDelivery {
+    State(DeliveryState),
+    Acked(bool),
+}
+
+/// Nothing waits for the acknowledgement of a message loaded from disk, so one saved while it was
+/// [DeliveryState::Sending] is loaded as [DeliveryState::Failed], and can be resent
+fn deserialize_delivery<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<DeliveryState, D::Error> {
+    Ok(match SavedDelivery::deserialize(deserializer)? {
+        SavedDelivery::State(DeliveryState::Sending) => DeliveryState::Failed,
+        SavedDelivery::State(state) => state,
+        SavedDelivery::Acked(true) => DeliveryState::Acked,
+        SavedDelivery::Acked(false) => DeliveryState::Sent,
+    })
+}
+
 /// An entry in the Channel View that represents some type of message sent to either this user on
 /// this device or to a channel this device can read. Can be any of [MCContent] types.
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct MCMessage {
     /// NodeId of the node that sent this message
     from: NodeId,
@@ -77,11 +212,24 @@ pub struct MCMessage {
     message: MCContent,
     /// Has the user of the app seen this message?
     seen: bool,
-    /// Has the entry been acknowledged as received by a receiver?
-    acked: bool,
+    /// How far this message has got in being delivered
+    #[serde(default, alias = "acked", deserialize_with = "deserialize_delivery")]
+    delivery: DeliveryState,
     /// Map of emojis and for each emoji there is the string for it and a number of node ids
     /// who sent that emoji
     emoji_reply: HashMap<String, Vec<NodeId>>,
+    /// How well the message was received, if the radio reported it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    link_quality: Option<LinkQuality>,
+}
+
+/// Return true if `words` appear in `text` with no letters or digits joined on either side
+fn contains_words(text: &str, words: &str) -> bool {
+    text.match_indices(words).any(|(start, _)| {
+        let before = text[..start].chars().next_back();
+        let after = text[start + words.len()..].chars().next();
+        !before.is_some_and(char::is_alphanumeric) && !after.is_some_and(char::is_alphanumeric)
+    })
 }
 
 impl MCMessage {
@@ -118,8 +266,43 @@ impl MCMessage {
     }
 
     /// Mark the Entry as acknowledgeMd
+    #[cfg(test)]
     pub fn ack(&mut self) {
-        self.acked = true;
+        self.delivery = DeliveryState::Acked;
+    }
+
+    /// Set how far this message has got in being delivered
+    pub fn set_delivery(&mut self, delivery: DeliveryState) {
+        self.delivery = delivery;
+    }
+
+    /// Return how far this message has got in being delivered
+    pub fn delivery(&self) -> DeliveryState {
+        self.delivery
+    }
+
+    /// Set how well the message was received, as reported by the radio
+    pub fn set_link_quality(&mut self, link_quality: Option<LinkQuality>) {
+        self.link_quality = link_quality;
+    }
+
+    /// Return how well the message was received, if the radio reported it
+    pub fn link_quality(&self) -> Option<&LinkQuality> {
+        self.link_quality.as_ref()
+    }
+
+    /// Return true if the text of the message mentions `user`, by their long name or their short
+    /// name, such as "@ABCD", as whole words and ignoring case
+    pub fn mentions(&self, user: &MCUser) -> bool {
+        let Some(text) = self.message.text() else {
+            return false;
+        };
+        let text = text.to_lowercase();
+
+        [&user.long_name, &user.short_name]
+            .into_iter()
+            .map(|name| name.trim().to_lowercase())
+            .any(|name| !name.is_empty() && contains_words(&text, &name))
     }
 
     /// Add an emoji reply to this entry
@@ -133,7 +316,7 @@ impl MCMessage {
 
     /// Return true if the radio has acknowledged this message
     pub fn acked(&self) -> bool {
-        self.acked
+        self.delivery == DeliveryState::Acked
     }
 
     /// Return true if the user has seen this message already
@@ -333,6 +516,11 @@ impl MCMessage {
                 .style(button_chip_style)
                 .on_press(ShowLocation(position.clone()))
                 .into(),
+            Waypoint(waypoint) => button(text(message_text))
+                .padding([1, 5])
+                .style(button_chip_style)
+                .on_press(ShowLocation(waypoint.position()))
+                .into(),
             EmojiReply(_, _) => text(message_text).into(),
         };
 
@@ -360,8 +548,44 @@ impl MCMessage {
             .push(Self::time_to_text(self.time()))
             .align_y(Bottom);
 
-        if self.acked() {
-            text_and_time_row = text_and_time_row.push(text("✓").size(14).color(COLOR_GREEN))
+        if !mine && let Some(link_quality) = &self.link_quality {
+            text_and_time_row = text_and_time_row.push(Space::new().width(6.0)).push(
+                tooltip(
+                    text(link_quality.summary())
+                        .size(TIME_TEXT_SIZE)
+                        .color(TIME_TEXT_COLOR),
+                    text(link_quality.detail()),
+                    tooltip::Position::Left,
+                )
+                .style(tooltip_style),
+            );
+        }
+
+        text_and_time_row = match self.delivery {
+            DeliveryState::Sending => text_and_time_row.push(
+                tooltip(
+                    text("…").size(14).color(TIME_TEXT_COLOR),
+                    text("Waiting for an acknowledgement"),
+                    tooltip::Position::Left,
+                )
+                .style(tooltip_style),
+            ),
+            DeliveryState::Sent => text_and_time_row,
+            DeliveryState::Acked => text_and_time_row.push(text("✓").size(14).color(COLOR_GREEN)),
+            DeliveryState::Failed => text_and_time_row.push(Space::new().width(4.0)).push(
+                tooltip(
+                    button(text("Resend").size(TIME_TEXT_SIZE).color(COLOR_RED))
+                        .padding([0, 4])
+                        .style(button_chip_style)
+                        .on_press(DeviceViewEvent(ResendMessage(
+                            *conversation_id,
+                            self.message_id,
+                        ))),
+                    text("Not acknowledged, click to send it again"),
+                    tooltip::Position::Left,
+                )
+                .style(tooltip_style),
+            ),
         };
 
         // Add the message text and time row
@@ -557,7 +781,7 @@ impl MCMessage {
     }
 }
 
-fn menu_button(
+pub fn menu_button(
     label: String,
     message: Message,
 ) -> button::Button<'static, Message, Theme, Renderer> {
@@ -568,7 +792,7 @@ fn menu_button(
         .width(Fill)
 }
 
-fn menu_root_button(label: &str) -> button::Button<'_, Message, Theme, Renderer> {
+pub fn menu_root_button(label: &str) -> button::Button<'_, Message, Theme, Renderer> {
     button(text(label).size(14))
         .padding([0, 4])
         .style(button_chip_style)
@@ -1178,6 +1402,7 @@ mod tests {
                 }),
                 position: None,
                 is_ignored: false,
+                hops_away: None,
             },
         );
         let sources = vec![NodeId::from(100u64)];
@@ -1207,6 +1432,7 @@ mod tests {
                 }),
                 position: None,
                 is_ignored: false,
+                hops_away: None,
             },
         );
         nodes.insert(
@@ -1227,6 +1453,7 @@ mod tests {
                 }),
                 position: None,
                 is_ignored: false,
+                hops_away: None,
             },
         );
         let sources = vec![NodeId::from(1u64), NodeId::from(2u64)];
@@ -1266,6 +1493,7 @@ mod tests {
                 }),
                 position: None,
                 is_ignored: false,
+                hops_away: None,
             },
         );
         // sources include both known (1) and unknown (999) nodes
@@ -1285,6 +1513,7 @@ mod tests {
                 user: None, // No user info
                 position: None,
                 is_ignored: false,
+                hops_away: None,
             },
         );
         let sources = vec![NodeId::from(50u64)];
@@ -1524,6 +1753,7 @@ mod tests {
                 }),
                 position: None,
                 is_ignored: false,
+                hops_away: None,
             },
         );
         let column: Column<Message> = Column::new();
@@ -1742,6 +1972,7 @@ mod tests {
                 }),
                 position: None,
                 is_ignored: false,
+                hops_away: None,
             },
         );
         let conversation_id = ConversationId::Channel(0.into());
@@ -2105,6 +2336,7 @@ mod tests {
                 }),
                 position: None,
                 is_ignored: false,
+                hops_away: None,
             },
         );
         let conversation_id = ConversationId::Channel(0.into());
@@ -2156,6 +2388,7 @@ mod tests {
                 }),
                 position: None,
                 is_ignored: false,
+                hops_away: None,
             },
         );
         let conversation_id = ConversationId::Channel(0.into());
@@ -2238,6 +2471,7 @@ mod tests {
                 }),
                 position: None,
                 is_ignored: false,
+                hops_away: None,
             },
         );
         let conversation_id = ConversationId::Channel(0.into());
@@ -2292,6 +2526,7 @@ mod tests {
                 }),
                 position: None,
                 is_ignored: false,
+                hops_away: None,
             },
         );
         let conversation_id = ConversationId::Channel(0.into());
@@ -2377,6 +2612,7 @@ mod tests {
                 }),
                 position: None,
                 is_ignored: false,
+                hops_away: None,
             },
         );
         let conversation_id = ConversationId::Channel(0.into());
@@ -2444,4 +2680,181 @@ mod tests {
         assert!(!MCMessage::show_inline_menu(true, true)); // my message -> no menu
         assert!(!MCMessage::show_inline_menu(true, false)); // my message -> no menu
     }
+
+    #[test]
+    fn test_delivery_roundtrip() {
+        let mut message = MCMessage::new(
+            MessageId::from(1u64),
+            NodeId::from(2u64),
+            NewTextMessage("Hello".into()),
+            TimeStamp::from(0u64),
+        );
+        message.set_delivery(DeliveryState::Failed);
+        let json = serde_json::to_string(&message).expect("Could not serialize message");
+        let loaded: MCMessage = serde_json::from_str(&json).expect("Could not load message");
+        assert_eq!(loaded.delivery(), DeliveryState::Failed);
+    }
+
+    #[test]
+    fn test_saved_sending_loads_as_failed() {
+        let mut message = MCMessage::new(
+            MessageId::from(1u64),
+            NodeId::from(2u64),
+            NewTextMessage("Hello".into()),
+            TimeStamp::from(0u64),
+        );
+        message.set_delivery(DeliveryState::Sending);
+        let json = serde_json::to_string(&message).expect("Could not serialize message");
+        let loaded: MCMessage = serde_json::from_str(&json).expect("Could not load message");
+        assert_eq!(loaded.delivery(), DeliveryState::Failed);
+    }
+
+    #[test]
+    fn test_old_acked_flag_loads() {
+        let mut json = serde_json::to_value(MCMessage::new(
+            MessageId::from(1u64),
+            NodeId::from(2u64),
+            NewTextMessage("Hello".into()),
+            TimeStamp::from(0u64),
+        ))
+        .expect("Could not serialize message");
+        let fields = json.as_object_mut().expect("Message is not an object");
+        fields.remove("delivery");
+        fields.insert("acked".into(), true.into());
+        let loaded: MCMessage = serde_json::from_value(json).expect("Could not load message");
+        assert!(loaded.acked());
+
+        let mut json = serde_json::to_value(loaded).expect("Could not serialize message");
+        let fields = json.as_object_mut().expect("Message is not an object");
+        fields.remove("delivery");
+        fields.insert("acked".into(), false.into());
+        let loaded: MCMessage = serde_json::from_value(json).expect("Could not load message");
+        assert_eq!(loaded.delivery(), DeliveryState::Sent);
+    }
+
+    #[test]
+    fn test_link_quality_summary() {
+        let link_quality = LinkQuality {
+            snr: Some(6.25),
+            rssi: Some(-90),
+            hops: Some(0),
+        };
+        assert_eq!(link_quality.bars(), Some(4));
+        assert_eq!(link_quality.summary(), "▂▄▆█ direct");
+
+        let relayed = LinkQuality {
+            snr: Some(-10.0),
+            rssi: None,
+            hops: Some(3),
+        };
+        assert_eq!(relayed.summary(), "▂░░░ 3 hops");
+        assert_eq!(relayed.detail(), "Relayed 3 times\nSNR: -10.00 dB");
+    }
+
+    #[test]
+    fn test_link_quality_unknown() {
+        let link_quality = LinkQuality::default();
+        assert_eq!(link_quality.bars(), None);
+        assert_eq!(link_quality.summary(), "");
+        assert_eq!(link_quality.detail(), "");
+
+        let hops_only = LinkQuality {
+            hops: Some(1),
+            ..Default::default()
+        };
+        assert_eq!(hops_only.summary(), "1 hop");
+    }
+
+    #[test]
+    fn test_link_quality_bars() {
+        let bars = |snr: f32| {
+            LinkQuality {
+                snr: Some(snr),
+                ..Default::default()
+            }
+            .bars()
+        };
+        assert_eq!(bars(12.0), Some(4));
+        assert_eq!(bars(0.0), Some(3));
+        assert_eq!(bars(-5.0), Some(2));
+        assert_eq!(bars(-12.0), Some(1));
+        assert_eq!(bars(-20.0), Some(0));
+    }
+
+    #[test]
+    fn test_link_quality_roundtrip() {
+        let mut message = MCMessage::new(
+            MessageId::from(1u64),
+            NodeId::from(2u64),
+            NewTextMessage("Hello".into()),
+            TimeStamp::from(0u64),
+        );
+        let json = serde_json::to_value(&message).expect("Could not serialize message");
+        assert!(json.get("link_quality").is_none());
+
+        let link_quality = LinkQuality {
+            snr: Some(-3.5),
+            rssi: Some(-110),
+            hops: Some(2),
+        };
+        message.set_link_quality(Some(link_quality));
+        let json = serde_json::to_string(&message).expect("Could not serialize message");
+        let loaded: MCMessage = serde_json::from_str(&json).expect("Could not load message");
+        assert_eq!(loaded.link_quality(), Some(&link_quality));
+    }
+
+    fn named_user(long_name: &str, short_name: &str) -> MCUser {
+        MCUser {
+            long_name: long_name.into(),
+            short_name: short_name.into(),
+            ..Default::default()
+        }
+    }
+
+    fn text_message(text: &str) -> MCMessage {
+        MCMessage::new(
+            MessageId::from(1u64),
+            NodeId::from(2u64),
+            NewTextMessage(text.into()),
+            TimeStamp::from(0u64),
+        )
+    }
+
+    #[test]
+    fn test_mentions_long_name() {
+        let me = named_user("Base Camp", "BC");
+        assert!(text_message("Anyone at base camp?").mentions(&me));
+        assert!(!text_message("Anyone at the base?").mentions(&me));
+    }
+
+    #[test]
+    fn test_no_mention_inside_word() {
+        let me = named_user("Bob", "BB");
+        assert!(!text_message("Look, a bobcat!").mentions(&me));
+        assert!(!text_message("Kabob for dinner").mentions(&me));
+        assert!(text_message("Kabob for dinner, Bob?").mentions(&me));
+        assert!(text_message("@bob").mentions(&me));
+    }
+
+    #[test]
+    fn test_mentions_short_name() {
+        let me = named_user("Base Camp", "BC");
+        assert!(text_message("@bc are you there").mentions(&me));
+        assert!(text_message("hi BC!").mentions(&me));
+        assert!(!text_message("abc def").mentions(&me));
+    }
+
+    #[test]
+    fn test_no_mention_without_names_or_text() {
+        let nameless = named_user("", " ");
+        assert!(!text_message("anything at all").mentions(&nameless));
+
+        let position = MCMessage::new(
+            MessageId::from(1u64),
+            NodeId::from(2u64),
+            PositionMessage(MCPosition::default()),
+            TimeStamp::from(0u64),
+        );
+        assert!(!position.mentions(&named_user("Base Camp", "BC")));
+    }
 }
@@ -130,6 +130,12 @@ impl Notifications {
         self.inner.retain(|item| item.0 != id);
         Task::none()
     }
+
+    /// The number of notifications being shown
+    #[cfg(test)]
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
 }
 
 #[cfg(test)]
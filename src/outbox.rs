@@ -0,0 +1,427 @@
+use crate::Message;
+use crate::conversation_id::{ConversationId, MessageId};
+use crate::device::DeviceCommand::{ResendText, SendPosition, SendSelfInfo, SendText};
+use crate::device::DeviceMessage::OutboxLoaded;
+use crate::device::{DeviceCommand, DeviceIdentifier};
+use crate::history::device_dir_name;
+use crate::meshchat::{MCPosition, MCUser};
+use crate::message::MCContent::{NewTextMessage, PositionMessage, TextMessageReply, UserMessage};
+use crate::message::{MCContent, MCMessage};
+use crate::store::{read_json, write_json};
+use crate::styles::{TIME_TEXT_COLOR, TIME_TEXT_SIZE, bubble_style, tooltip_style};
+use crate::timestamp::TimeStamp;
+use directories::ProjectDirs;
+use iced::alignment::Horizontal::Right;
+use iced::widget::{Column, Container, Row, Space, text, tooltip};
+use iced::{Bottom, Element, Fill, Task, Theme};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+const OUTBOX_DIR: &str = "outbox";
+const OUTBOX_EXTENSION: &str = "json";
+
+/// What is to be sent to a conversation once the radio is connected
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Outgoing {
+    Text(String, Option<MessageId>), // optional reply to message id
+    /// A text message sent again automatically after it failed
+    Resend(String, Option<MessageId>),
+    Position(MCPosition),
+    SelfInfo(MCUser),
+}
+
+/// A message waiting in the [Outbox] to be sent
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueuedMessage {
+    conversation_id: ConversationId,
+    outgoing: Outgoing,
+    queued: TimeStamp,
+}
+
+impl QueuedMessage {
+    pub fn new(conversation_id: ConversationId, outgoing: Outgoing) -> Self {
+        QueuedMessage {
+            conversation_id,
+            outgoing,
+            queued: TimeStamp::now(),
+        }
+    }
+
+    /// The [DeviceCommand] that sends this message to the radio
+    pub fn command(self) -> DeviceCommand {
+        match self.outgoing {
+            Outgoing::Text(text, reply_to_id) => SendText(text, self.conversation_id, reply_to_id),
+            Outgoing::Resend(text, reply_to_id) => {
+                ResendText(text, self.conversation_id, reply_to_id)
+            }
+            Outgoing::Position(position) => SendPosition(self.conversation_id, position),
+            Outgoing::SelfInfo(user) => SendSelfInfo(self.conversation_id, user),
+        }
+    }
+
+    /// The content of the message, as it will be shown once sent
+    fn content(&self) -> MCContent {
+        match &self.outgoing {
+            Outgoing::Text(text, None) | Outgoing::Resend(text, None) => {
+                NewTextMessage(text.clone())
+            }
+            Outgoing::Text(text, Some(reply_to_id)) | Outgoing::Resend(text, Some(reply_to_id)) => {
+                TextMessageReply(*reply_to_id, text.clone())
+            }
+            Outgoing::Position(position) => PositionMessage(position.clone()),
+            Outgoing::SelfInfo(user) => UserMessage(user.clone()),
+        }
+    }
+
+    /// Show the message on the right, like my other messages, with a marker that it is pending
+    pub fn view<'a>(&self) -> Element<'a, Message> {
+        let pending = tooltip(
+            text("⏳").size(TIME_TEXT_SIZE),
+            text("Waiting to be sent once the radio is connected"),
+            tooltip::Position::Left,
+        )
+        .style(tooltip_style);
+
+        let content_row = Row::new()
+            .align_y(Bottom)
+            .push(text(self.content().to_string()).size(18))
+            .push(Space::new().width(10.0))
+            .push(
+                text(
+                    MCMessage::datetime_local(self.queued)
+                        .format("%H:%M")
+                        .to_string(),
+                )
+                .color(TIME_TEXT_COLOR)
+                .size(TIME_TEXT_SIZE),
+            )
+            .push(Space::new().width(4.0))
+            .push(pending);
+
+        let bubble = Container::new(content_row)
+            .padding([6, 8])
+            .style(|theme: &Theme| bubble_style(theme, true));
+
+        Column::new()
+            .width(Fill)
+            .align_x(Right)
+            .push(
+                Row::new()
+                    .padding(6)
+                    .push(Space::new().width(100.0))
+                    .push(bubble),
+            )
+            .into()
+    }
+}
+
+/// Messages typed while the radio is not connected, kept per device in the order they were sent,
+/// to be sent once the radio they were sent on is connected again
+#[derive(Debug, Default)]
+pub struct Outbox {
+    queued: HashMap<DeviceIdentifier, Vec<QueuedMessage>>,
+}
+
+impl Outbox {
+    /// Queue `outgoing` to be sent to `conversation_id` on `device`
+    pub fn queue(
+        &mut self,
+        device: &DeviceIdentifier,
+        conversation_id: ConversationId,
+        outgoing: Outgoing,
+    ) {
+        self.queued
+            .entry(device.clone())
+            .or_default()
+            .push(QueuedMessage::new(conversation_id, outgoing));
+    }
+
+    /// Queue `queued` again for `device`, after it could not be sent, in the order it was
+    /// first queued
+    pub fn requeue(&mut self, device: &DeviceIdentifier, queued: QueuedMessage) {
+        let device_queue = self.queued.entry(device.clone()).or_default();
+        let index = device_queue
+            .iter()
+            .position(|other| other.queued > queued.queued)
+            .unwrap_or(device_queue.len());
+        device_queue.insert(index, queued);
+    }
+
+    /// The messages waiting to be sent to `conversation_id` on `device`, in the order they
+    /// were queued
+    pub fn conversation(
+        &self,
+        device: &DeviceIdentifier,
+        conversation_id: ConversationId,
+    ) -> impl Iterator<Item = &QueuedMessage> {
+        self.queued
+            .get(device)
+            .into_iter()
+            .flatten()
+            .filter(move |queued| queued.conversation_id == conversation_id)
+    }
+
+    /// Return true if the messages for `device` are known, because messages have been queued
+    /// for it or the saved ones restored
+    pub fn has_device(&self, device: &DeviceIdentifier) -> bool {
+        self.queued.contains_key(device)
+    }
+
+    /// Restore the messages queued for `device` that were saved, unless messages have been queued
+    /// for it since, as they include the saved ones
+    pub fn restore(&mut self, device: &DeviceIdentifier, queued: Vec<QueuedMessage>) {
+        self.queued.entry(device.clone()).or_insert(queued);
+    }
+
+    /// Take all the messages to send now that `device` is connected, in the order they were
+    /// queued. Messages queued for other devices are kept.
+    pub fn take(&mut self, device: &DeviceIdentifier) -> Vec<QueuedMessage> {
+        self.queued
+            .get_mut(device)
+            .map(std::mem::take)
+            .unwrap_or_default()
+    }
+
+    /// The number of messages waiting to be sent, on all devices
+    #[cfg(test)]
+    pub fn len(&self) -> usize {
+        self.queued.values().map(Vec::len).sum()
+    }
+
+    /// Return true if there are no messages waiting to be sent on any device
+    #[cfg(test)]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Return the file the messages queued for `device` are saved in, if there is one
+fn outbox_file(device: &DeviceIdentifier) -> Option<PathBuf> {
+    ProjectDirs::from("net", "Mackenzie Serres", "meshchat").map(|proj_dirs| {
+        proj_dirs
+            .data_dir()
+            .join(OUTBOX_DIR)
+            .join(format!("{}.{OUTBOX_EXTENSION}", device_dir_name(device)))
+    })
+}
+
+/// Use `load_outbox` to load the messages saved as queued for `device`, when they were queued
+/// before the app was last closed
+pub fn load_outbox(device: &DeviceIdentifier) -> Task<Message> {
+    let device = device.clone();
+    let Some(outbox_file) = outbox_file(&device) else {
+        return Task::done(Message::DeviceViewEvent(OutboxLoaded(device, vec![])));
+    };
+
+    Task::future(async move { read_json(&outbox_file).await.map_err(|e| (outbox_file, e)) }).then(
+        move |result| match result {
+            Ok(queued) => Task::done(Message::DeviceViewEvent(OutboxLoaded(
+                device.clone(),
+                queued.unwrap_or_default(),
+            ))),
+            // The messages can't be sent, but new ones can still be queued
+            Err((outbox_file, e)) => Task::batch([
+                Task::done(Message::AppError(
+                    format!(
+                        "Error loading queued messages: '{}'",
+                        outbox_file.to_string_lossy()
+                    ),
+                    e.to_string(),
+                    TimeStamp::now(), // jonesy:allow(expect)
+                )),
+                Task::done(Message::DeviceViewEvent(OutboxLoaded(
+                    device.clone(),
+                    vec![],
+                ))),
+            ]),
+        },
+    )
+}
+
+/// Use `save_outbox` to save the messages queued in `outbox` for `device`, so they are not lost
+/// if the app is closed before they are sent
+pub fn save_outbox(device: &DeviceIdentifier, outbox: &Outbox) -> Task<Message> {
+    if let Some(outbox_file) = outbox_file(device) {
+        let queued = outbox.queued.get(device).cloned().unwrap_or_default();
+        Task::perform(
+            async move { write_json(&outbox_file, &queued).await },
+            move |result| match result {
+                Ok(_) => Message::None,
+                Err(e) => Message::AppError(
+                    "Error saving queued messages".to_string(),
+                    e.to_string(),
+                    TimeStamp::now(), // jonesy:allow(expect)
+                ),
+            },
+        )
+    } else {
+        Task::none()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::conversation_id::NodeId;
+
+    fn channel(index: u8) -> ConversationId {
+        ConversationId::Channel(index.into())
+    }
+
+    fn device() -> DeviceIdentifier {
+        DeviceIdentifier::from("device1")
+    }
+
+    #[test]
+    fn test_new_outbox_is_empty() {
+        let outbox = Outbox::default();
+        assert!(outbox.is_empty());
+        assert_eq!(outbox.len(), 0);
+    }
+
+    #[test]
+    fn test_take_in_order() {
+        let mut outbox = Outbox::default();
+        outbox.queue(&device(), channel(0), Outgoing::Text("one".into(), None));
+        outbox.queue(
+            &device(),
+            ConversationId::Node(NodeId::from(42u64)),
+            Outgoing::Text("two".into(), None),
+        );
+        outbox.queue(&device(), channel(0), Outgoing::Text("three".into(), None));
+        assert_eq!(outbox.len(), 3);
+
+        let texts: Vec<String> = outbox
+            .take(&device())
+            .into_iter()
+            .filter_map(|queued| match queued.command() {
+                SendText(text, _, _) => Some(text),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(texts, vec!["one", "two", "three"]);
+        assert!(outbox.is_empty());
+    }
+
+    #[test]
+    fn test_conversation_filters() {
+        let mut outbox = Outbox::default();
+        outbox.queue(&device(), channel(0), Outgoing::Text("one".into(), None));
+        outbox.queue(&device(), channel(1), Outgoing::Text("two".into(), None));
+        assert_eq!(outbox.conversation(&device(), channel(0)).count(), 1);
+        assert_eq!(outbox.conversation(&device(), channel(1)).count(), 1);
+        assert_eq!(outbox.conversation(&device(), channel(2)).count(), 0);
+        assert_eq!(
+            outbox
+                .conversation(&DeviceIdentifier::from("device2"), channel(0))
+                .count(),
+            0
+        );
+    }
+
+    #[test]
+    fn test_other_device_keeps_queued() {
+        let mut outbox = Outbox::default();
+        let device2 = DeviceIdentifier::from("device2");
+        outbox.queue(&device(), channel(0), Outgoing::Text("one".into(), None));
+        outbox.queue(&device2, channel(0), Outgoing::Text("two".into(), None));
+        assert_eq!(outbox.len(), 2);
+        assert_eq!(outbox.take(&device()).len(), 1);
+        assert_eq!(outbox.take(&device2).len(), 1);
+        assert!(outbox.is_empty());
+    }
+
+    #[test]
+    fn test_take_for_other_device_keeps_queued() {
+        let mut outbox = Outbox::default();
+        outbox.queue(&device(), channel(0), Outgoing::Text("one".into(), None));
+        assert!(outbox.take(&DeviceIdentifier::from("device2")).is_empty());
+        assert_eq!(outbox.len(), 1);
+    }
+
+    #[test]
+    fn test_requeue_in_order() {
+        let mut outbox = Outbox::default();
+        let first = QueuedMessage {
+            conversation_id: channel(0),
+            outgoing: Outgoing::Text("one".into(), None),
+            queued: TimeStamp::from(100u64),
+        };
+        let second = QueuedMessage {
+            conversation_id: channel(0),
+            outgoing: Outgoing::Text("two".into(), None),
+            queued: TimeStamp::from(200u64),
+        };
+        outbox.restore(&device(), vec![second]);
+        outbox.requeue(&device(), first);
+
+        let texts: Vec<String> = outbox
+            .take(&device())
+            .into_iter()
+            .filter_map(|queued| match queued.command() {
+                SendText(text, _, _) => Some(text),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(texts, vec!["one", "two"]);
+    }
+
+    #[test]
+    fn test_restore_saved() {
+        let mut outbox = Outbox::default();
+        let saved = vec![QueuedMessage::new(
+            channel(0),
+            Outgoing::Text("saved".into(), None),
+        )];
+        assert!(!outbox.has_device(&device()));
+        outbox.restore(&device(), saved.clone());
+        assert!(outbox.has_device(&device()));
+        assert_eq!(outbox.len(), 1);
+
+        // Messages queued since include the saved ones
+        outbox.queue(&device(), channel(0), Outgoing::Text("new".into(), None));
+        outbox.restore(&device(), saved);
+        assert_eq!(outbox.len(), 2);
+    }
+
+    #[test]
+    fn test_queued_saved_and_loaded() {
+        let queued = vec![
+            QueuedMessage::new(channel(0), Outgoing::Text("hi".into(), None)),
+            QueuedMessage::new(channel(1), Outgoing::Position(MCPosition::default())),
+        ];
+        let json = serde_json::to_string(&queued).expect("Could not serialize");
+        let loaded: Vec<QueuedMessage> = serde_json::from_str(&json).expect("Could not load");
+        assert_eq!(loaded.len(), 2);
+        assert!(matches!(&loaded[0].outgoing, Outgoing::Text(text, None) if text == "hi"));
+        assert_eq!(loaded[1].conversation_id, channel(1));
+    }
+
+    #[test]
+    fn test_commands() {
+        let reply = QueuedMessage::new(
+            channel(0),
+            Outgoing::Text("hi".into(), Some(MessageId::from(7u32))),
+        );
+        assert!(matches!(reply.content(), TextMessageReply(_, _)));
+        assert!(matches!(
+            reply.command(),
+            SendText(_, _, Some(id)) if id == MessageId::from(7u32)
+        ));
+
+        let position = QueuedMessage::new(channel(1), Outgoing::Position(MCPosition::default()));
+        assert!(matches!(position.content(), PositionMessage(_)));
+        assert!(matches!(position.command(), SendPosition(_, _)));
+
+        let user = QueuedMessage::new(channel(2), Outgoing::SelfInfo(MCUser::default()));
+        assert!(matches!(user.content(), UserMessage(_)));
+        assert!(matches!(user.command(), SendSelfInfo(_, _)));
+    }
+
+    #[test]
+    fn test_view() {
+        let queued = QueuedMessage::new(channel(0), Outgoing::Text("hi".into(), None));
+        let _element = queued.view();
+    }
+}
@@ -0,0 +1,593 @@
+//! A simulated radio, that invents channels and nodes and the traffic between them, so that the
+//! app can be used for demos and developed without a physical radio
+
+use crate::conversation_id::ConversationId::{Channel, Node};
+use crate::conversation_id::{MessageId, NodeId};
+use crate::device::DeviceCommand;
+use crate::device::DeviceCommand::{
+    ResendText, SendEmojiReply, SendPosition, SendSelfInfo, SendText, SendTraceroute, SendWaypoint,
+};
+use crate::device::DeviceEvent;
+use crate::device::DeviceEvent::{
+    DeviceBatteryLevel, MCMessageReceived, MessageACK, MessageResent, MyNodeNum, NewChannel,
+    NewNode, NewNodeInfo, NewNodePosition, TracerouteResponse,
+};
+use crate::meshchat::{MCChannel, MCNodeInfo, MCPosition, MCUser};
+use crate::message::LinkQuality;
+use crate::message::MCContent::{EmojiReply, NewTextMessage, TextMessageReply, Waypoint};
+use crate::timestamp::TimeStamp;
+use crate::traceroute::{Hop, Route};
+
+pub mod subscription;
+
+/// The name the simulated radio is discovered with
+pub const SIM_RADIO_NAME: &str = "Simulated Radio";
+
+/// The node number of the simulated radio itself
+const SIM_MY_NODE_NUM: u32 = 0x5117_0001;
+
+/// How long after sending a message the simulated radio reports it as ACKed, in ms
+const SIM_ACK_DELAY_MS: u128 = 1_500;
+
+/// How long after we send a DM to a simulated node it replies, in ms
+const SIM_REPLY_DELAY_MS: u128 = 3_000;
+
+/// How long after we send a traceroute to a simulated node the route is reported, in ms
+const SIM_TRACEROUTE_DELAY_MS: u128 = 2_000;
+
+/// One in this many ticks some simulated node will do something
+const SIM_ACTIVITY_ODDS: u64 = 6;
+
+/// The channels on the simulated radio
+const SIM_CHANNELS: [&str; 2] = ["LongFast", "Sim Friends"];
+
+/// The simulated nodes: node number, long name, short name, latitude, longitude
+const SIM_NODES: [(u32, &str, &str, f64, f64); 4] = [
+    (0x5117_00a1, "Alpine Relay", "ALPR", 46.5197, 6.6323),
+    (0x5117_00b2, "Harbour Base", "HARB", 46.5045, 6.6267),
+    (0x5117_00c3, "Trail Walker", "TRLW", 46.5302, 6.6581),
+    (0x5117_00d4, "Rooftop Node", "ROOF", 46.5163, 6.6401),
+];
+
+/// The messages simulated nodes send to channels
+const SIM_CHATTER: [&str; 8] = [
+    "Good morning mesh!",
+    "Anyone hearing me from the valley?",
+    "Signal is great up here today",
+    "Testing, testing, 1 2 3",
+    "Battery swap done, back online",
+    "Heading out on the trail, will check in later",
+    "Copy that, loud and clear",
+    "Nice weather for some range tests",
+];
+
+/// A simulated radio. It holds no connection, it just produces the [DeviceEvent]s a real radio
+/// would produce, in response to [DeviceCommand]s and the passing of time
+pub struct SimRadio {
+    rng_state: u64,
+    next_message_id: u32,
+    /// Events that will be produced at a later time, such as ACKs and replies
+    pending: Vec<(TimeStamp, DeviceEvent)>,
+}
+
+impl SimRadio {
+    /// Create a new [SimRadio], the `seed` determines the random activity it will produce
+    pub fn new(seed: u64) -> Self {
+        // xorshift needs a non-zero state
+        let rng_state = seed.max(1);
+        SimRadio {
+            rng_state,
+            next_message_id: (rng_state & 0xFFFF) as u32 + 1,
+            pending: vec![],
+        }
+    }
+
+    /// A simple xorshift pseudo-random number generator, good enough for inventing traffic
+    fn random(&mut self) -> u64 {
+        let mut x = self.rng_state;
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.rng_state = x;
+        x
+    }
+
+    /// Pick a random number in `0..n`
+    fn random_below(&mut self, n: u64) -> u64 {
+        self.random() % n.max(1) // jonesy:allow(div_zero)
+    }
+
+    fn new_message_id(&mut self) -> MessageId {
+        self.next_message_id = self.next_message_id.wrapping_add(1);
+        MessageId::from(self.next_message_id)
+    }
+
+    /// The node number of the simulated radio
+    pub fn my_node_id() -> NodeId {
+        NodeId::from(SIM_MY_NODE_NUM)
+    }
+
+    /// Return true if `node_id` is one of the simulated nodes
+    fn is_sim_node(node_id: NodeId) -> bool {
+        SIM_NODES
+            .iter()
+            .any(|(num, ..)| NodeId::from(*num) == node_id)
+    }
+
+    fn sim_user(num: u32, long_name: &str, short_name: &str) -> MCUser {
+        MCUser {
+            id: format!("!{num:08x}"),
+            long_name: long_name.to_string(),
+            short_name: short_name.to_string(),
+            hw_model_str: "Simulated".to_string(),
+            role_str: "Client".to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn sim_position(latitude: f64, longitude: f64) -> MCPosition {
+        MCPosition {
+            latitude,
+            longitude,
+            ..Default::default()
+        }
+    }
+
+    /// The events a radio sends when we connect to it, describing itself, its channels and the
+    /// nodes it knows about
+    pub fn connect_events(&self) -> Vec<DeviceEvent> {
+        let mut events = vec![MyNodeNum(Self::my_node_id())];
+
+        events.push(NewNode(MCNodeInfo {
+            node_id: Self::my_node_id(),
+            user: Some(Self::sim_user(SIM_MY_NODE_NUM, SIM_RADIO_NAME, "SIM")),
+            position: Some(Self::sim_position(46.5191, 6.6335)),
+            is_ignored: false,
+            hops_away: None,
+        }));
+
+        for (index, name) in SIM_CHANNELS.iter().enumerate() {
+            events.push(NewChannel(MCChannel {
+                index: index as i32,
+                name: name.to_string(),
+            }));
+        }
+
+        // The first simulated node is heard directly, and relays for the others
+        for (index, (num, long_name, short_name, latitude, longitude)) in
+            SIM_NODES.into_iter().enumerate()
+        {
+            events.push(NewNode(MCNodeInfo {
+                node_id: NodeId::from(num),
+                user: Some(Self::sim_user(num, long_name, short_name)),
+                position: Some(Self::sim_position(latitude, longitude)),
+                is_ignored: false,
+                hops_away: Some(u32::from(index > 0)),
+            }));
+        }
+
+        events.push(DeviceBatteryLevel(Some(87)));
+        events
+    }
+
+    /// Handle a [DeviceCommand] from the GUI at time `now`. What we send is echoed back, as real
+    /// radios do, and ACKs and replies are scheduled to be produced later by [SimRadio::tick]
+    pub fn command(&mut self, command: DeviceCommand, now: TimeStamp) -> Vec<DeviceEvent> {
+        if let ResendText(text, conversation_id, reply_to_id) = command {
+            let mut events = self.command(SendText(text, conversation_id, reply_to_id), now);
+            if let Some(MCMessageReceived(_, message_id, ..)) = events.first() {
+                events.push(MessageResent(*message_id));
+            }
+            return events;
+        }
+
+        let me = Self::my_node_id();
+        let message_id = self.new_message_id();
+
+        match command {
+            SendText(text, conversation_id, reply_to_id) => {
+                let content = match reply_to_id {
+                    Some(reply_to_id) => TextMessageReply(reply_to_id, text.clone()),
+                    None => NewTextMessage(text.clone()),
+                };
+                self.schedule(now, SIM_ACK_DELAY_MS, |_| {
+                    MessageACK(conversation_id, message_id)
+                });
+                if let Node(node_id) = conversation_id
+                    && Self::is_sim_node(node_id)
+                {
+                    let reply_id = self.new_message_id();
+                    self.schedule(now, SIM_REPLY_DELAY_MS, |time| {
+                        MCMessageReceived(
+                            conversation_id,
+                            reply_id,
+                            node_id,
+                            TextMessageReply(message_id, format!("Echo: {text}")),
+                            time,
+                            None,
+                        )
+                    });
+                }
+                vec![MCMessageReceived(
+                    conversation_id,
+                    message_id,
+                    me,
+                    content,
+                    now,
+                    None,
+                )]
+            }
+            SendEmojiReply(emoji, conversation_id, reply_to_id) => vec![MCMessageReceived(
+                conversation_id,
+                message_id,
+                me,
+                EmojiReply(reply_to_id, emoji),
+                now,
+                None,
+            )],
+            SendPosition(conversation_id, position) => {
+                vec![NewNodePosition(
+                    conversation_id,
+                    message_id,
+                    me,
+                    position,
+                    now,
+                )]
+            }
+            SendSelfInfo(conversation_id, user) => {
+                vec![NewNodeInfo(conversation_id, message_id, me, user, now)]
+            }
+            SendWaypoint(conversation_id, waypoint) => vec![MCMessageReceived(
+                conversation_id,
+                message_id,
+                me,
+                Waypoint(waypoint),
+                now,
+                None,
+            )],
+            SendTraceroute(node_id) if Self::is_sim_node(node_id) => {
+                let route = self.sim_route(node_id);
+                self.schedule(now, SIM_TRACEROUTE_DELAY_MS, |_| TracerouteResponse(route));
+                vec![]
+            }
+            _ => vec![],
+        }
+    }
+
+    /// Schedule an event to be produced `delay_ms` after `now`
+    fn schedule(
+        &mut self,
+        now: TimeStamp,
+        delay_ms: u128,
+        event: impl FnOnce(TimeStamp) -> DeviceEvent,
+    ) {
+        let due = TimeStamp::from(u128::from(now).saturating_add(delay_ms));
+        self.pending.push((due, event(due)));
+    }
+
+    /// Invent the route to a simulated node, relayed by the first simulated node unless that
+    /// is where it is going
+    fn sim_route(&mut self, destination: NodeId) -> Route {
+        let relay = NodeId::from(SIM_NODES[0].0);
+        let mut snr = || Some((self.random_below(40) as f32 - 10.0) / 4.0);
+        let me = Self::my_node_id();
+        let (towards, back) = if destination == relay {
+            (
+                vec![Hop::new(Some(me), None), Hop::new(Some(destination), snr())],
+                vec![Hop::new(Some(destination), None), Hop::new(Some(me), snr())],
+            )
+        } else {
+            (
+                vec![
+                    Hop::new(Some(me), None),
+                    Hop::new(Some(relay), snr()),
+                    Hop::new(Some(destination), snr()),
+                ],
+                vec![
+                    Hop::new(Some(destination), None),
+                    Hop::new(Some(relay), snr()),
+                    Hop::new(Some(me), snr()),
+                ],
+            )
+        };
+        Route {
+            destination,
+            towards,
+            back,
+        }
+    }
+
+    /// Produce the events that are due at time `now`, plus any random activity on the mesh
+    pub fn tick(&mut self, now: TimeStamp) -> Vec<DeviceEvent> {
+        let (due, pending): (Vec<_>, Vec<_>) =
+            self.pending.drain(..).partition(|(time, _)| *time <= now);
+        self.pending = pending;
+        let mut events: Vec<DeviceEvent> = due.into_iter().map(|(_, event)| event).collect();
+
+        if self.random_below(SIM_ACTIVITY_ODDS) == 0 {
+            events.push(self.random_activity(now));
+        }
+
+        events
+    }
+
+    /// Invent something a simulated node does: chat on a channel, or report its position
+    fn random_activity(&mut self, now: TimeStamp) -> DeviceEvent {
+        let (num, _, _, latitude, longitude) =
+            SIM_NODES[self.random_below(SIM_NODES.len() as u64) as usize];
+        let from = NodeId::from(num);
+        let message_id = self.new_message_id();
+        let conversation_id =
+            Channel((self.random_below(SIM_CHANNELS.len() as u64) as usize).into());
+
+        if self.random_below(4) == 0 {
+            // Wander a little from the starting position
+            let drift = |r: u64| (r % 200) as f64 / 100_000.0 - 0.001;
+            let position = Self::sim_position(
+                latitude + drift(self.random()),
+                longitude + drift(self.random()),
+            );
+            NewNodePosition(conversation_id, message_id, from, position, now)
+        } else {
+            let text = SIM_CHATTER[self.random_below(SIM_CHATTER.len() as u64) as usize];
+            // Somewhere between a weak signal relayed a few times, and a strong one heard directly
+            let link_quality = LinkQuality {
+                snr: Some(self.random_below(80) as f32 / 4.0 - 12.0),
+                rssi: Some(-(self.random_below(60) as i32) - 60),
+                hops: Some(self.random_below(4) as u32),
+            };
+            MCMessageReceived(
+                conversation_id,
+                message_id,
+                from,
+                NewTextMessage(text.to_string()),
+                now,
+                Some(link_quality),
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::conversation_id::{ChannelIndex, ConversationId};
+    use crate::meshchat::MCWaypoint;
+
+    fn channel_0() -> ConversationId {
+        Channel(ChannelIndex::from(0u8))
+    }
+
+    fn sim_node() -> NodeId {
+        NodeId::from(SIM_NODES[0].0)
+    }
+
+    #[test]
+    fn connect_events_describe_radio() {
+        let radio = SimRadio::new(1);
+        let events = radio.connect_events();
+
+        assert!(matches!(events.first(), Some(MyNodeNum(id)) if *id == SimRadio::my_node_id()));
+        let channels = events
+            .iter()
+            .filter(|event| matches!(event, NewChannel(_)))
+            .count();
+        assert_eq!(channels, SIM_CHANNELS.len());
+        let nodes = events
+            .iter()
+            .filter(|event| matches!(event, NewNode(_)))
+            .count();
+        assert_eq!(nodes, SIM_NODES.len() + 1);
+        assert!(matches!(events.last(), Some(DeviceBatteryLevel(Some(_)))));
+    }
+
+    #[test]
+    fn sent_text_is_echoed_then_acked() {
+        let mut radio = SimRadio::new(1);
+        let now = TimeStamp::from(10_000u64);
+
+        let events = radio.command(SendText("Hello".to_string(), channel_0(), None), now);
+        let message_id = match events.as_slice() {
+            [
+                MCMessageReceived(conversation_id, message_id, from, NewTextMessage(text), time, _),
+            ] => {
+                assert_eq!(*conversation_id, channel_0());
+                assert_eq!(*from, SimRadio::my_node_id());
+                assert_eq!(text, "Hello");
+                assert_eq!(*time, now);
+                Some(*message_id)
+            }
+            _ => None,
+        }
+        .expect("Expected the sent message to be echoed");
+
+        // Not acked yet
+        let early = radio.pending.len();
+        assert_eq!(early, 1);
+
+        let acked = radio
+            .tick(TimeStamp::from(10_000u64 + SIM_ACK_DELAY_MS as u64))
+            .into_iter()
+            .any(|event| matches!(event, MessageACK(id, ack) if id == channel_0() && ack == message_id));
+        assert!(acked);
+        assert!(radio.pending.is_empty());
+    }
+
+    #[test]
+    fn resent_text_reports_its_id() {
+        let mut radio = SimRadio::new(1);
+        let now = TimeStamp::from(10_000u64);
+
+        let events = radio.command(ResendText("Hello".to_string(), channel_0(), None), now);
+        assert!(matches!(
+            events.as_slice(),
+            [MCMessageReceived(_, echoed_id, ..), MessageResent(resent_id)] if echoed_id == resent_id
+        ));
+    }
+
+    #[test]
+    fn sent_reply_is_echoed_as_reply() {
+        let mut radio = SimRadio::new(1);
+        let reply_to = MessageId::from(42u32);
+        let events = radio.command(
+            SendText("Re".to_string(), channel_0(), Some(reply_to)),
+            TimeStamp::from(0u64),
+        );
+        assert!(matches!(
+            events.as_slice(),
+            [MCMessageReceived(_, _, _, TextMessageReply(id, text), _, _)] if *id == reply_to && text == "Re"
+        ));
+    }
+
+    #[test]
+    fn dm_to_sim_node_gets_echo_reply() {
+        let mut radio = SimRadio::new(1);
+        let now = TimeStamp::from(0u64);
+        let conversation_id = Node(sim_node());
+        let _ = radio.command(SendText("Ping".to_string(), conversation_id, None), now);
+        assert_eq!(radio.pending.len(), 2);
+
+        let events = radio.tick(TimeStamp::from(SIM_REPLY_DELAY_MS as u64));
+        let reply = events.iter().any(|event| {
+            matches!(
+                event,
+                MCMessageReceived(id, _, from, TextMessageReply(_, text), _, _)
+                    if *id == conversation_id && *from == sim_node() && text == "Echo: Ping"
+            )
+        });
+        assert!(reply);
+    }
+
+    #[test]
+    fn dm_to_unknown_node_gets_no_reply() {
+        let mut radio = SimRadio::new(1);
+        let _ = radio.command(
+            SendText("Ping".to_string(), Node(NodeId::from(7u32)), None),
+            TimeStamp::from(0u64),
+        );
+        // Only the ACK is pending
+        assert_eq!(radio.pending.len(), 1);
+    }
+
+    #[test]
+    fn emoji_position_and_user_echoed() {
+        let mut radio = SimRadio::new(1);
+        let now = TimeStamp::from(0u64);
+        let reply_to = MessageId::from(5u32);
+
+        let events = radio.command(SendEmojiReply("👍".to_string(), channel_0(), reply_to), now);
+        assert!(matches!(
+            events.as_slice(),
+            [MCMessageReceived(_, _, _, EmojiReply(id, emoji), _, _)] if *id == reply_to && emoji == "👍"
+        ));
+
+        let events = radio.command(
+            SendPosition(channel_0(), SimRadio::sim_position(1.0, 2.0)),
+            now,
+        );
+        assert!(matches!(
+            events.as_slice(),
+            [NewNodePosition(_, _, from, position, _)] if *from == SimRadio::my_node_id() && position.latitude == 1.0
+        ));
+
+        let user = SimRadio::sim_user(SIM_MY_NODE_NUM, "Me", "ME");
+        let events = radio.command(SendSelfInfo(channel_0(), user), now);
+        assert!(matches!(
+            events.as_slice(),
+            [NewNodeInfo(_, _, _, user, _)] if user.long_name == "Me"
+        ));
+    }
+
+    #[test]
+    fn waypoint_echoed() {
+        let mut radio = SimRadio::new(1);
+        let waypoint = MCWaypoint {
+            id: 7,
+            name: "Camp".into(),
+            ..Default::default()
+        };
+        let events = radio.command(
+            SendWaypoint(channel_0(), waypoint.clone()),
+            TimeStamp::from(0u64),
+        );
+        assert!(matches!(
+            events.as_slice(),
+            [MCMessageReceived(_, _, from, Waypoint(echoed), _, _)] if *from == SimRadio::my_node_id() && *echoed == waypoint
+        ));
+    }
+
+    #[test]
+    fn message_ids_are_unique() {
+        let mut radio = SimRadio::new(99);
+        let first = radio.new_message_id();
+        let second = radio.new_message_id();
+        assert_ne!(first, second);
+    }
+
+    #[test]
+    fn random_activity_comes_from_sim_nodes() {
+        let mut radio = SimRadio::new(12345);
+        for tick in 0..200u64 {
+            for event in radio.tick(TimeStamp::from(tick * 1000)) {
+                let from = match event {
+                    MCMessageReceived(Channel(_), _, from, NewTextMessage(_), _, _)
+                    | NewNodePosition(Channel(_), _, from, _, _) => Some(from),
+                    _ => None,
+                };
+                assert!(from.is_some_and(SimRadio::is_sim_node));
+            }
+        }
+    }
+
+    #[test]
+    fn same_seed_same_activity() {
+        let mut radio1 = SimRadio::new(7);
+        let mut radio2 = SimRadio::new(7);
+        for tick in 0..50u64 {
+            let now = TimeStamp::from(tick * 1000);
+            assert_eq!(radio1.tick(now).len(), radio2.tick(now).len());
+        }
+    }
+
+    #[test]
+    fn zero_seed_still_random() {
+        let mut radio = SimRadio::new(0);
+        assert_ne!(radio.random(), 0);
+    }
+
+    #[test]
+    fn traceroute_to_sim_node_is_reported() {
+        let mut radio = SimRadio::new(1);
+        let now = TimeStamp::from(10_000u64);
+        let destination = NodeId::from(SIM_NODES[1].0);
+
+        assert!(radio.command(SendTraceroute(destination), now).is_empty());
+        let events = radio.tick(TimeStamp::from(10_000u64 + SIM_TRACEROUTE_DELAY_MS as u64));
+        let route = events
+            .into_iter()
+            .find_map(|event| match event {
+                TracerouteResponse(route) => Some(route),
+                _ => None,
+            })
+            .expect("Expected the route to be reported");
+        assert_eq!(route.destination, destination);
+        assert_eq!(route.towards.len(), 3);
+        assert_eq!(route.back.len(), 3);
+        assert_eq!(
+            route.towards.first().and_then(|hop| hop.node_id),
+            Some(SimRadio::my_node_id())
+        );
+        assert_eq!(
+            route.back.last().and_then(|hop| hop.node_id),
+            Some(SimRadio::my_node_id())
+        );
+    }
+
+    #[test]
+    fn traceroute_to_unknown_node_not_reported() {
+        let mut radio = SimRadio::new(1);
+        let events = radio.command(SendTraceroute(NodeId::from(1u32)), TimeStamp::from(0u64));
+        assert!(events.is_empty());
+        assert!(radio.pending.is_empty());
+    }
+}
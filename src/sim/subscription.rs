@@ -0,0 +1,167 @@
+use crate::device::DeviceCommand::{Connect, Disconnect};
+use crate::device::DeviceEvent::{ConnectedEvent, ConnectingEvent, DisconnectedEvent};
+use crate::device::{DeviceCommand, DeviceEvent};
+use crate::device_list::RadioType;
+use crate::sim::SimRadio;
+use crate::timestamp::TimeStamp;
+use futures::SinkExt;
+use iced::stream;
+use std::time::Duration;
+use tokio::sync::mpsc::{Receiver, channel};
+use tokio::time::{Instant, timeout_at};
+use tokio_stream::Stream;
+
+/// How often the simulated radio checks for scheduled events and invents new activity
+const SIM_TICK: Duration = Duration::from_secs(1);
+
+/// A stream of [DeviceEvent] for comms between the app and the simulated radio. It follows the
+/// same [DeviceEvent]/[DeviceCommand] contract as the subscriptions to real radios
+pub fn subscribe() -> impl Stream<Item = DeviceEvent> {
+    stream::channel(
+        100,
+        move |mut gui_sender: futures_channel::mpsc::Sender<DeviceEvent>| async move {
+            let (subscriber_sender, mut subscriber_receiver) = channel::<DeviceCommand>(100);
+
+            //Inform the GUI the subscription is ready to receive messages, so it can send messages
+            // jonesy:allow(unknown) async state machine artifact
+            let _ = gui_sender
+                .send(DeviceEvent::Ready(subscriber_sender, RadioType::Sim))
+                .await;
+
+            // Wait for a message from the UI to request that we connect to a device
+            while let Some(command) = subscriber_receiver.recv().await {
+                if let Connect(sim_device, _, _) = command {
+                    gui_sender
+                        .send(ConnectingEvent(sim_device.clone()))
+                        .await
+                        .unwrap_or_else(|e| eprintln!("Send error: {e}"));
+
+                    let mut radio = SimRadio::new(u64::from(TimeStamp::now()));
+
+                    gui_sender
+                        .send(ConnectedEvent(sim_device.clone(), RadioType::Sim))
+                        .await
+                        .unwrap_or_else(|e| eprintln!("Send error: {e}"));
+
+                    for event in radio.connect_events() {
+                        gui_sender
+                            .send(event)
+                            .await
+                            .unwrap_or_else(|e| eprintln!("Send error: {e}"));
+                    }
+
+                    run_connected(&mut radio, &mut subscriber_receiver, &mut gui_sender).await;
+
+                    gui_sender
+                        .send(DisconnectedEvent(sim_device))
+                        .await
+                        .unwrap_or_else(|e| eprintln!("Send error: {e}"));
+                }
+            }
+        },
+    )
+}
+
+/// While connected, pass commands from the GUI to the [SimRadio] and give it regular ticks,
+/// sending all the events it produces to the GUI. Returns when the GUI asks to disconnect.
+async fn run_connected(
+    radio: &mut SimRadio,
+    subscriber_receiver: &mut Receiver<DeviceCommand>,
+    gui_sender: &mut futures_channel::mpsc::Sender<DeviceEvent>,
+) {
+    let mut next_tick = Instant::now() + SIM_TICK;
+
+    loop {
+        let events = match timeout_at(next_tick, subscriber_receiver.recv()).await {
+            Ok(Some(Disconnect)) | Ok(None) => return,
+            Ok(Some(Connect(..))) => {
+                eprintln!("Cannot connect while already connected");
+                vec![]
+            }
+            Ok(Some(command)) => radio.command(command, TimeStamp::now()),
+            Err(_elapsed) => {
+                next_tick += SIM_TICK;
+                radio.tick(TimeStamp::now())
+            }
+        };
+
+        for event in events {
+            gui_sender
+                .send(event)
+                .await
+                .unwrap_or_else(|e| eprintln!("Send error: {e}"));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::conversation_id::{ChannelIndex, ConversationId};
+    use crate::device::DeviceCommand::SendText;
+    use crate::device::DeviceEvent::{MCMessageReceived, MyNodeNum, Ready};
+    use crate::device::DeviceIdentifier;
+    use crate::sim::SIM_RADIO_NAME;
+    use tokio_stream::StreamExt;
+
+    fn sim_device() -> DeviceIdentifier {
+        DeviceIdentifier::Sim {
+            name: SIM_RADIO_NAME.to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_connect_send_disconnect() {
+        let mut events = Box::pin(subscribe());
+
+        let sender = match events.next().await {
+            Some(Ready(sender, RadioType::Sim)) => Some(sender),
+            _ => None,
+        }
+        .expect("Expected the subscription to be ready");
+
+        assert!(
+            sender
+                .send(Connect(sim_device(), RadioType::Sim, false))
+                .await
+                .is_ok()
+        );
+        assert!(matches!(events.next().await, Some(ConnectingEvent(_))));
+        assert!(matches!(
+            events.next().await,
+            Some(ConnectedEvent(device, RadioType::Sim)) if device == sim_device()
+        ));
+        assert!(matches!(events.next().await, Some(MyNodeNum(_))));
+
+        let conversation_id = ConversationId::Channel(ChannelIndex::from(0u8));
+        assert!(
+            sender
+                .send(SendText("Hello sim".to_string(), conversation_id, None))
+                .await
+                .is_ok()
+        );
+
+        // Skip the rest of the connect events until our message is echoed back
+        let mut echoed = false;
+        while let Some(event) = events.next().await {
+            if let MCMessageReceived(_, _, from, content, _, _) = event
+                && from == SimRadio::my_node_id()
+            {
+                assert_eq!(content.text(), Some("Hello sim"));
+                echoed = true;
+                break;
+            }
+        }
+        assert!(echoed);
+
+        assert!(sender.send(Disconnect).await.is_ok());
+        let mut disconnected = false;
+        while let Some(event) = events.next().await {
+            if matches!(event, DisconnectedEvent(_)) {
+                disconnected = true;
+                break;
+            }
+        }
+        assert!(disconnected);
+    }
+}
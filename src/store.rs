@@ -0,0 +1,242 @@
+use crate::Message;
+use crate::timestamp::TimeStamp;
+use iced::Task;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use std::io;
+use std::path::{Path, PathBuf};
+use tokio::fs::{DirBuilder, File};
+use tokio::io::AsyncWriteExt;
+use uuid::Uuid;
+
+/// Write `value` as JSON to the file at `path`, creating its directory if needed.
+///
+/// Saves can overlap, and the app can stop part way through one, so it is written to a temporary
+/// file that is then renamed into place, and the file at `path` is always complete
+pub async fn write_json<T: Serialize>(path: &Path, value: &T) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        DirBuilder::new()
+            .recursive(true)
+            // jonesy:allow(unknown) async state machine artifact
+            .create(parent)
+            .await?;
+    }
+
+    let temp_path = path.with_extension(format!("{}.tmp", Uuid::new_v4()));
+    let result = write(&temp_path, value).await;
+    match result {
+        Ok(()) => tokio::fs::rename(&temp_path, path).await,
+        Err(e) => {
+            let _ = tokio::fs::remove_file(&temp_path).await;
+            Err(e)
+        }
+    }
+}
+
+async fn write<T: Serialize>(path: &Path, value: &T) -> io::Result<()> {
+    let mut file = File::create(path).await?;
+    let json = serde_json::to_string(value).map_err(io::Error::other)?;
+    file.write_all(json.as_bytes()).await?;
+    file.sync_all().await
+}
+
+/// Read the JSON file at `path`, or None if there is no such file
+pub async fn read_json<T: DeserializeOwned>(path: &Path) -> io::Result<Option<T>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let json = tokio::fs::read_to_string(path).await?;
+    serde_json::from_str(&json)
+        .map(Some)
+        .map_err(io::Error::other)
+}
+
+/// The values read from the files in a directory, and the files that could not be read
+pub struct DirContents<T> {
+    pub values: Vec<T>,
+    /// The path of each file that could not be read, and why
+    pub bad_files: Vec<(PathBuf, String)>,
+}
+
+/// Read all the JSON files with `extension` in `dir`, which is empty if there is no such
+/// directory. A file that can't be read is skipped and returned in [DirContents::bad_files], so
+/// that one bad file doesn't stop the rest being read
+pub async fn read_json_dir<T: DeserializeOwned>(
+    dir: &Path,
+    extension: &str,
+) -> io::Result<DirContents<T>> {
+    let mut contents = DirContents {
+        values: vec![],
+        bad_files: vec![],
+    };
+
+    if !dir.exists() {
+        return Ok(contents);
+    }
+
+    let mut entries = tokio::fs::read_dir(dir).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let path = entry.path();
+        if path.extension().and_then(|ext| ext.to_str()) == Some(extension) {
+            match read_json(&path).await {
+                Ok(Some(value)) => contents.values.push(value),
+                Ok(None) => {}
+                Err(e) => contents.bad_files.push((path, e.to_string())),
+            }
+        }
+    }
+
+    Ok(contents)
+}
+
+/// Send `loaded` with what was read, then an [Message::AppError] for each of the `bad_files`
+/// holding `what` that could not be read
+pub fn report_bad_files(
+    loaded: Message,
+    what: &'static str,
+    bad_files: Vec<(PathBuf, String)>,
+) -> Task<Message> {
+    Task::batch(
+        std::iter::once(Task::done(loaded)).chain(bad_files.into_iter().map(|(path, e)| {
+            Task::done(Message::AppError(
+                format!("Skipped unreadable {what}: '{}'", path.to_string_lossy()),
+                e,
+                TimeStamp::now(), // jonesy:allow(expect)
+            ))
+        })),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Thing {
+        name: String,
+    }
+
+    fn thing(name: &str) -> Thing {
+        Thing { name: name.into() }
+    }
+
+    fn tempdir() -> tempfile::TempDir {
+        tempfile::Builder::new()
+            .prefix("meshchat")
+            .tempdir()
+            .expect("Could not create a temp dir")
+    }
+
+    async fn file_names(dir: &Path) -> Vec<String> {
+        let mut entries = tokio::fs::read_dir(dir).await.expect("Could not read dir");
+        let mut names = vec![];
+        while let Some(entry) = entries.next_entry().await.expect("Could not read entry") {
+            names.push(entry.file_name().to_string_lossy().to_string());
+        }
+        names.sort();
+        names
+    }
+
+    #[tokio::test]
+    async fn read_missing_file_is_none() {
+        let tempdir = tempdir();
+        let read: Option<Thing> = read_json(&tempdir.path().join("missing.json"))
+            .await
+            .expect("Could not read");
+        assert!(read.is_none());
+    }
+
+    #[tokio::test]
+    async fn write_creates_dir_and_leaves_no_temp_files() {
+        let tempdir = tempdir();
+        let dir = tempdir.path().join("a").join("b");
+        let path = dir.join("thing.json");
+
+        write_json(&path, &thing("first"))
+            .await
+            .expect("Could not write");
+        write_json(&path, &thing("second"))
+            .await
+            .expect("Could not write");
+
+        let read: Option<Thing> = read_json(&path).await.expect("Could not read");
+        assert_eq!(read, Some(thing("second")));
+        assert_eq!(file_names(&dir).await, vec!["thing.json"]);
+    }
+
+    #[tokio::test]
+    async fn overlapping_writes_leave_a_complete_file() {
+        let tempdir = tempdir();
+        let path = tempdir.path().join("thing.json");
+        let names: Vec<String> = (0..20).map(|i| "x".repeat(i * 1000)).collect();
+
+        let writes: Vec<_> = names
+            .iter()
+            .map(|name| {
+                let path = path.clone();
+                let thing = thing(name);
+                tokio::spawn(async move { write_json(&path, &thing).await })
+            })
+            .collect();
+        for write in writes {
+            write
+                .await
+                .expect("Write panicked")
+                .expect("Could not write");
+        }
+
+        let read: Option<Thing> = read_json(&path).await.expect("Could not read");
+        assert!(read.is_some_and(|read| names.contains(&read.name)));
+    }
+
+    #[tokio::test]
+    async fn read_missing_dir_is_empty() {
+        let tempdir = tempdir();
+        let contents: DirContents<Thing> = read_json_dir(&tempdir.path().join("missing"), "json")
+            .await
+            .expect("Could not read dir");
+        assert!(contents.values.is_empty());
+        assert!(contents.bad_files.is_empty());
+    }
+
+    #[tokio::test]
+    async fn read_dir_skips_bad_and_other_files() {
+        let tempdir = tempdir();
+        write_json(&tempdir.path().join("good.json"), &thing("good"))
+            .await
+            .expect("Could not write");
+        write_json(&tempdir.path().join("other.txt"), &thing("other"))
+            .await
+            .expect("Could not write");
+        let mut corrupt = File::create(tempdir.path().join("bad.json"))
+            .await
+            .expect("Could not create file");
+        corrupt
+            .write_all(b"{ not json")
+            .await
+            .expect("Could not write file");
+
+        let contents: DirContents<Thing> = read_json_dir(tempdir.path(), "json")
+            .await
+            .expect("Could not read dir");
+        assert_eq!(contents.values, vec![thing("good")]);
+        assert_eq!(contents.bad_files.len(), 1);
+        assert_eq!(contents.bad_files[0].0, tempdir.path().join("bad.json"));
+    }
+
+    #[test]
+    fn bad_files_are_reported() {
+        let task = report_bad_files(
+            Message::None,
+            "history",
+            vec![(PathBuf::from("bad.json"), "oops".into())],
+        );
+        assert_eq!(task.units(), 2);
+        assert_eq!(
+            report_bad_files(Message::None, "history", vec![]).units(),
+            1
+        );
+    }
+}
@@ -526,6 +526,20 @@ pub fn reply_to_style(_theme: &Theme) -> Style {
     REPLY_TO_STYLE
 }
 
+const HIGHLIGHT_BORDER: Border = Border {
+    radius: RADIUS_12, // rounded corners
+    width: 2.0,
+    color: CYAN,
+};
+
+/// Style for a message that has been highlighted, such as one found by searching
+pub fn highlight_style(_theme: &Theme) -> Style {
+    Style {
+        border: HIGHLIGHT_BORDER,
+        ..Default::default()
+    }
+}
+
 pub fn channel_row_style(theme: &Theme, status: Status) -> button::Style {
     let palette = theme.palette();
 
@@ -0,0 +1,399 @@
+use crate::Message;
+use crate::message::MCMessage;
+use crate::styles::{TIME_TEXT_COLOR, TIME_TEXT_SIZE};
+use crate::timestamp::TimeStamp;
+use iced::Element;
+use iced::widget::{Column, Row, text};
+use std::collections::VecDeque;
+use std::fmt;
+use std::fmt::Formatter;
+
+/// How many readings of telemetry to keep for each node
+const HISTORY_LENGTH: usize = 20;
+/// How many of the most recent readings to show in the history of a node's telemetry
+const HISTORY_SHOWN: usize = 5;
+
+/// Metrics about the radio device itself
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MCDeviceMetrics {
+    /// Battery level in percent, over 100 means powered externally
+    pub battery_level: Option<u32>,
+    /// Voltage (V) of the battery or power supply
+    pub voltage: Option<f32>,
+    /// Percent of the channel heard to be in use, by this node and others
+    pub channel_utilization: Option<f32>,
+    /// Percent of the last hour this node has been transmitting
+    pub air_util_tx: Option<f32>,
+    /// How long (secs) the device has been running for
+    pub uptime_seconds: Option<u32>,
+}
+
+/// Metrics from sensors of the environment the device is in
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MCEnvironmentMetrics {
+    /// Temperature in °C
+    pub temperature: Option<f32>,
+    /// Relative humidity in percent
+    pub relative_humidity: Option<f32>,
+    /// Barometric pressure in hPa
+    pub barometric_pressure: Option<f32>,
+}
+
+/// Voltage and current measured on one channel of a power sensor
+#[derive(Clone, Debug, PartialEq)]
+pub struct MCPowerChannel {
+    /// The channel number of the sensor, starting at 1
+    pub channel: u8,
+    /// Voltage (V)
+    pub voltage: Option<f32>,
+    /// Current (mA)
+    pub current: Option<f32>,
+}
+
+/// Metrics from a power sensor attached to the device, only the channels with readings
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MCPowerMetrics {
+    pub channels: Vec<MCPowerChannel>,
+}
+
+/// A reading of telemetry reported by a node
+#[derive(Clone, Debug, PartialEq)]
+pub enum MCTelemetry {
+    Device(MCDeviceMetrics),
+    Environment(MCEnvironmentMetrics),
+    Power(MCPowerMetrics),
+}
+
+/// Format an optional value with its unit, or nothing if there is no value
+fn value<T: fmt::Display>(name: &str, value: Option<T>, unit: &str) -> Option<String> {
+    value.map(|value| format!("{name}{value}{unit}"))
+}
+
+/// Format an optional float value with one decimal place and its unit
+fn decimal(name: &str, value: Option<f32>, unit: &str) -> Option<String> {
+    value.map(|value| format!("{name}{value:.1}{unit}"))
+}
+
+/// Format a duration in seconds as days, hours and minutes
+fn uptime(seconds: u32) -> String {
+    let days = seconds / 86_400;
+    let hours = (seconds % 86_400) / 3_600;
+    let minutes = (seconds % 3_600) / 60;
+    if days > 0 {
+        format!("{days}d {hours}h {minutes}m")
+    } else if hours > 0 {
+        format!("{hours}h {minutes}m")
+    } else {
+        format!("{minutes}m")
+    }
+}
+
+impl MCDeviceMetrics {
+    /// The values reported, one line each
+    fn lines(&self) -> Vec<String> {
+        [
+            value("Battery: ", self.battery_level, "%"),
+            self.voltage
+                .map(|voltage| format!("Voltage: {voltage:.2} V")),
+            decimal("Channel Utilization: ", self.channel_utilization, "%"),
+            decimal("Airtime: ", self.air_util_tx, "%"),
+            self.uptime_seconds
+                .map(|seconds| format!("Uptime: {}", uptime(seconds))),
+        ]
+        .into_iter()
+        .flatten()
+        .collect()
+    }
+}
+
+impl MCEnvironmentMetrics {
+    /// The values reported, one line each
+    fn lines(&self) -> Vec<String> {
+        [
+            decimal("Temperature: ", self.temperature, " °C"),
+            decimal("Humidity: ", self.relative_humidity, "%"),
+            decimal("Pressure: ", self.barometric_pressure, " hPa"),
+        ]
+        .into_iter()
+        .flatten()
+        .collect()
+    }
+}
+
+impl MCPowerMetrics {
+    /// The values reported, one line for each channel
+    fn lines(&self) -> Vec<String> {
+        self.channels
+            .iter()
+            .map(|channel| {
+                let readings: Vec<String> = [
+                    channel.voltage.map(|voltage| format!("{voltage:.2} V")),
+                    decimal("", channel.current, " mA"),
+                ]
+                .into_iter()
+                .flatten()
+                .collect();
+                format!("Power {}: {}", channel.channel, readings.join(" "))
+            })
+            .collect()
+    }
+}
+
+impl MCTelemetry {
+    /// The values reported, one line each
+    fn lines(&self) -> Vec<String> {
+        match self {
+            MCTelemetry::Device(metrics) => metrics.lines(),
+            MCTelemetry::Environment(metrics) => metrics.lines(),
+            MCTelemetry::Power(metrics) => metrics.lines(),
+        }
+    }
+}
+
+impl fmt::Display for MCTelemetry {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.lines().join(", "))
+    }
+}
+
+/// The telemetry readings from a node, most recent last, with when each was received
+#[derive(Debug, Default)]
+pub struct TelemetryHistory {
+    readings: VecDeque<(TimeStamp, MCTelemetry)>,
+}
+
+impl TelemetryHistory {
+    /// Add a reading in time order, as readings can arrive late, dropping the oldest if there
+    /// are then more than [HISTORY_LENGTH] readings
+    pub fn add(&mut self, timestamp: TimeStamp, telemetry: MCTelemetry) {
+        let index = self
+            .readings
+            .partition_point(|(time, _)| *time <= timestamp);
+        self.readings.insert(index, (timestamp, telemetry));
+        if self.readings.len() > HISTORY_LENGTH {
+            self.readings.pop_front();
+        }
+    }
+
+    /// The number of readings kept
+    #[cfg(test)]
+    pub fn len(&self) -> usize {
+        self.readings.len()
+    }
+
+    /// The most recent device metrics reported
+    pub fn latest_device(&self) -> Option<&MCDeviceMetrics> {
+        self.readings
+            .iter()
+            .rev()
+            .find_map(|(_, telemetry)| match telemetry {
+                MCTelemetry::Device(metrics) => Some(metrics),
+                _ => None,
+            })
+    }
+
+    /// The most recent environment metrics reported
+    pub fn latest_environment(&self) -> Option<&MCEnvironmentMetrics> {
+        self.readings
+            .iter()
+            .rev()
+            .find_map(|(_, telemetry)| match telemetry {
+                MCTelemetry::Environment(metrics) => Some(metrics),
+                _ => None,
+            })
+    }
+
+    /// The most recent power metrics reported
+    pub fn latest_power(&self) -> Option<&MCPowerMetrics> {
+        self.readings
+            .iter()
+            .rev()
+            .find_map(|(_, telemetry)| match telemetry {
+                MCTelemetry::Power(metrics) => Some(metrics),
+                _ => None,
+            })
+    }
+
+    /// The latest value of each metric reported, followed by the most recent readings
+    pub fn view<'a>(&self) -> Element<'a, Message> {
+        let latest = self
+            .latest_device()
+            .map(MCDeviceMetrics::lines)
+            .into_iter()
+            .chain(self.latest_environment().map(MCEnvironmentMetrics::lines))
+            .chain(self.latest_power().map(MCPowerMetrics::lines))
+            .flatten()
+            .fold(
+                Column::new().spacing(2).push(text("Telemetry")),
+                |column, line| column.push(text(format!("  {line}"))),
+            );
+
+        self.readings
+            .iter()
+            .rev()
+            .take(HISTORY_SHOWN)
+            .fold(
+                latest.push(text("History")),
+                |column, (timestamp, telemetry)| {
+                    column.push(
+                        Row::new()
+                            .spacing(8)
+                            .push(
+                                text(
+                                    MCMessage::datetime_local(*timestamp)
+                                        .format("  %H:%M")
+                                        .to_string(),
+                                )
+                                .size(TIME_TEXT_SIZE)
+                                .color(TIME_TEXT_COLOR),
+                            )
+                            .push(text(telemetry.to_string()).size(TIME_TEXT_SIZE)),
+                    )
+                },
+            )
+            .into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn device(battery_level: u32) -> MCTelemetry {
+        MCTelemetry::Device(MCDeviceMetrics {
+            battery_level: Some(battery_level),
+            ..Default::default()
+        })
+    }
+
+    fn environment(temperature: f32) -> MCTelemetry {
+        MCTelemetry::Environment(MCEnvironmentMetrics {
+            temperature: Some(temperature),
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_empty_history() {
+        let history = TelemetryHistory::default();
+        assert_eq!(history.len(), 0);
+        assert!(history.latest_device().is_none());
+        assert!(history.latest_environment().is_none());
+        assert!(history.latest_power().is_none());
+    }
+
+    #[test]
+    fn test_latest_of_each_kind() {
+        let mut history = TelemetryHistory::default();
+        history.add(TimeStamp::from(1000u64), device(90));
+        history.add(TimeStamp::from(2000u64), environment(20.5));
+        history.add(TimeStamp::from(3000u64), device(80));
+
+        assert_eq!(
+            history
+                .latest_device()
+                .and_then(|metrics| metrics.battery_level),
+            Some(80)
+        );
+        assert_eq!(
+            history
+                .latest_environment()
+                .and_then(|metrics| metrics.temperature),
+            Some(20.5)
+        );
+        assert!(history.latest_power().is_none());
+    }
+
+    #[test]
+    fn test_late_reading_is_not_latest() {
+        let mut history = TelemetryHistory::default();
+        history.add(TimeStamp::from(3000u64), device(80));
+        history.add(TimeStamp::from(1000u64), device(90));
+
+        assert_eq!(
+            history
+                .latest_device()
+                .and_then(|metrics| metrics.battery_level),
+            Some(80)
+        );
+        assert_eq!(
+            history.readings.front().map(|(timestamp, _)| *timestamp),
+            Some(TimeStamp::from(1000u64))
+        );
+    }
+
+    #[test]
+    fn test_history_is_bounded() {
+        let mut history = TelemetryHistory::default();
+        for level in 0..(HISTORY_LENGTH as u32 + 5) {
+            history.add(TimeStamp::from(u64::from(level)), device(level));
+        }
+        assert_eq!(history.len(), HISTORY_LENGTH);
+        assert_eq!(
+            history.readings.front().map(|(timestamp, _)| *timestamp),
+            Some(TimeStamp::from(5u64))
+        );
+    }
+
+    #[test]
+    fn test_uptime() {
+        assert_eq!(uptime(59), "0m");
+        assert_eq!(uptime(3_660), "1h 1m");
+        assert_eq!(uptime(90_061), "1d 1h 1m");
+    }
+
+    #[test]
+    fn test_device_lines() {
+        let metrics = MCDeviceMetrics {
+            battery_level: Some(87),
+            voltage: Some(3.912),
+            channel_utilization: Some(12.34),
+            air_util_tx: Some(1.24),
+            uptime_seconds: Some(3_600),
+        };
+        assert_eq!(
+            metrics.lines(),
+            vec![
+                "Battery: 87%",
+                "Voltage: 3.91 V",
+                "Channel Utilization: 12.3%",
+                "Airtime: 1.2%",
+                "Uptime: 1h 0m"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_display_skips_missing_values() {
+        let telemetry = MCTelemetry::Environment(MCEnvironmentMetrics {
+            temperature: Some(21.0),
+            relative_humidity: None,
+            barometric_pressure: Some(1013.2),
+        });
+        assert_eq!(
+            telemetry.to_string(),
+            "Temperature: 21.0 °C, Pressure: 1013.2 hPa"
+        );
+    }
+
+    #[test]
+    fn test_power_lines() {
+        let metrics = MCPowerMetrics {
+            channels: vec![MCPowerChannel {
+                channel: 2,
+                voltage: Some(5.0),
+                current: Some(120.0),
+            }],
+        };
+        assert_eq!(metrics.lines(), vec!["Power 2: 5.00 V 120.0 mA"]);
+    }
+
+    #[test]
+    fn test_view() {
+        let mut history = TelemetryHistory::default();
+        let _empty = history.view();
+        history.add(TimeStamp::from(1000u64), device(90));
+        history.add(TimeStamp::from(2000u64), environment(20.5));
+        let _element = history.view();
+    }
+}
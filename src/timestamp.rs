@@ -1,8 +1,9 @@
+use serde::{Deserialize, Serialize};
 use std::ops::Sub;
 use std::time::{SystemTime, UNIX_EPOCH};
 
 /// Time in EPOC in seconds timestamp
-#[derive(PartialEq, PartialOrd, Ord, Eq, Debug, Default, Clone, Copy)]
+#[derive(PartialEq, PartialOrd, Ord, Eq, Debug, Default, Clone, Copy, Serialize, Deserialize)]
 pub struct TimeStamp(u128);
 
 impl From<u128> for TimeStamp {
@@ -0,0 +1,233 @@
+use crate::Message;
+use crate::conversation_id::NodeId;
+use crate::styles::{COLOR_RED, TIME_TEXT_COLOR, picker_header_style, tooltip_style};
+use iced::font::Weight;
+use iced::widget::{Column, Row, Space, container, text};
+use iced::{Center, Element, Fill, Font};
+
+/// A node a packet passed through on its way along a route
+#[derive(Debug, Clone, PartialEq)]
+pub struct Hop {
+    /// The node, or None if it did not report itself, as some relays don't
+    pub node_id: Option<NodeId>,
+    /// The signal to noise ratio (dB) the node received the packet with, if known
+    pub snr: Option<f32>,
+}
+
+impl Hop {
+    pub fn new(node_id: Option<NodeId>, snr: Option<f32>) -> Self {
+        Hop { node_id, snr }
+    }
+}
+
+/// The route packets took from my node to another node and back again
+#[derive(Debug, Clone, PartialEq)]
+pub struct Route {
+    /// The node the route was traced to
+    pub destination: NodeId,
+    /// Every node from mine to the destination, including both
+    pub towards: Vec<Hop>,
+    /// Every node from the destination back to mine, including both. Empty if not reported.
+    pub back: Vec<Hop>,
+}
+
+/// A traceroute to a node, that is waiting for a response until the route is known, or it
+/// failed
+#[derive(Debug, Clone)]
+pub struct Traceroute {
+    destination: NodeId,
+    route: Option<Route>,
+    /// Why there is no route, if the traceroute failed or no response came in time
+    failure: Option<String>,
+}
+
+impl Traceroute {
+    pub fn new(destination: NodeId) -> Self {
+        Traceroute {
+            destination,
+            route: None,
+            failure: None,
+        }
+    }
+
+    /// Record the route if it is the one this traceroute is waiting for, returning true if so
+    pub fn set_route(&mut self, route: Route) -> bool {
+        if route.destination == self.destination {
+            self.route = Some(route);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Record why the traceroute to `destination` failed, if it is the one this traceroute is
+    /// waiting for and the route is not known already, returning true if so
+    pub fn fail(&mut self, destination: NodeId, reason: String) -> bool {
+        if destination == self.destination && self.route.is_none() {
+            self.failure = Some(reason);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// The route, once the response has been received
+    #[cfg(test)]
+    pub fn route(&self) -> Option<&Route> {
+        self.route.as_ref()
+    }
+
+    /// Why there is no route, if the traceroute failed
+    #[cfg(test)]
+    pub fn failure(&self) -> Option<&str> {
+        self.failure.as_deref()
+    }
+
+    /// A dialog showing the route traced, or that we are waiting for it, naming nodes using `name`
+    pub fn view<'a>(&self, name: impl Fn(NodeId) -> String) -> Element<'a, Message> {
+        let destination = name(self.destination);
+
+        let route_column = match (&self.route, &self.failure) {
+            (None, None) => Column::new()
+                .padding(8)
+                .push(text(format!("Waiting for a response from {destination}…"))),
+            (None, Some(failure)) => Column::new()
+                .padding(8)
+                .push(text(failure.clone()).color(COLOR_RED)),
+            (Some(route), _) => {
+                let back: Element<'a, Message> = if route.back.is_empty() {
+                    text("The return path was not reported")
+                        .color(TIME_TEXT_COLOR)
+                        .into()
+                } else {
+                    Self::path(&route.back, &name)
+                };
+                Column::new()
+                    .padding(8)
+                    .spacing(8)
+                    .push(text(format!("Towards {destination}")).size(16))
+                    .push(Self::path(&route.towards, &name))
+                    .push(text("Back").size(16))
+                    .push(back)
+            }
+        };
+
+        let inner = Column::new()
+            .spacing(8)
+            .width(420)
+            .push(
+                container(
+                    text(format!("Traceroute to {destination}"))
+                        .size(18)
+                        .width(Fill)
+                        .font(Font {
+                            weight: Weight::Bold,
+                            ..Default::default()
+                        })
+                        .align_x(Center),
+                )
+                .padding(12)
+                .style(picker_header_style)
+                .padding(4),
+            )
+            .push(route_column);
+        container(inner).style(tooltip_style).into()
+    }
+
+    /// The hops along a path, one per line, with the SNR each was received with
+    fn path<'a>(hops: &[Hop], name: &impl Fn(NodeId) -> String) -> Element<'a, Message> {
+        hops.iter()
+            .enumerate()
+            .fold(Column::new().spacing(2), |column, (index, hop)| {
+                let node_name = hop.node_id.map(name).unwrap_or("Unknown".into());
+                let arrow = if index == 0 { "" } else { "→ " };
+                column.push(
+                    Row::new()
+                        .push(text(format!("{arrow}{node_name}")))
+                        .push(Space::new().width(Fill))
+                        .push(text(Self::snr(index, hop)).color(TIME_TEXT_COLOR)),
+                )
+            })
+            .into()
+    }
+
+    /// The SNR a hop was received with, there is none for where the path starts
+    fn snr(index: usize, hop: &Hop) -> String {
+        match (index, hop.snr) {
+            (0, _) => String::new(),
+            (_, Some(snr)) => format!("{snr:.2} dB"),
+            (_, None) => "? dB".into(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(id: u32) -> NodeId {
+        NodeId::from(id)
+    }
+
+    fn route(destination: u32) -> Route {
+        Route {
+            destination: node(destination),
+            towards: vec![
+                Hop::new(Some(node(1)), None),
+                Hop::new(None, Some(2.5)),
+                Hop::new(Some(node(destination)), Some(-4.0)),
+            ],
+            back: vec![],
+        }
+    }
+
+    #[test]
+    fn test_new_traceroute_is_waiting() {
+        let traceroute = Traceroute::new(node(42));
+        assert!(traceroute.route().is_none());
+    }
+
+    #[test]
+    fn test_set_route() {
+        let mut traceroute = Traceroute::new(node(42));
+        assert!(traceroute.set_route(route(42)));
+        assert_eq!(traceroute.route(), Some(&route(42)));
+    }
+
+    #[test]
+    fn test_set_route_other_destination_ignored() {
+        let mut traceroute = Traceroute::new(node(42));
+        assert!(!traceroute.set_route(route(43)));
+        assert!(traceroute.route().is_none());
+    }
+
+    #[test]
+    fn test_fail() {
+        let mut traceroute = Traceroute::new(node(42));
+        assert!(!traceroute.fail(node(43), "No response".into()));
+        assert!(traceroute.failure().is_none());
+        assert!(traceroute.fail(node(42), "No response".into()));
+        assert_eq!(traceroute.failure(), Some("No response"));
+
+        // A response that comes in late is still shown
+        assert!(traceroute.set_route(route(42)));
+        assert!(!traceroute.fail(node(42), "No response".into()));
+    }
+
+    #[test]
+    fn test_snr() {
+        assert_eq!(Traceroute::snr(0, &Hop::new(None, Some(1.0))), "");
+        assert_eq!(Traceroute::snr(1, &Hop::new(None, Some(6.25))), "6.25 dB");
+        assert_eq!(Traceroute::snr(2, &Hop::new(None, None)), "? dB");
+    }
+
+    #[test]
+    fn test_view() {
+        let mut traceroute = Traceroute::new(node(42));
+        let _waiting = traceroute.view(|node_id| node_id.to_string());
+        traceroute.fail(node(42), "No response".into());
+        let _failed = traceroute.view(|node_id| node_id.to_string());
+        traceroute.set_route(route(42));
+        let _traced = traceroute.view(|node_id| node_id.to_string());
+    }
+}
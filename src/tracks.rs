@@ -0,0 +1,616 @@
+use crate::Message;
+use crate::conversation_id::NodeId;
+use crate::device::DeviceIdentifier;
+use crate::device::DeviceMessage::TracksLoaded;
+use crate::history::history_dir;
+use crate::meshchat::MCPosition;
+use crate::store::{read_json_dir, report_bad_files, write_json};
+use crate::timestamp::TimeStamp;
+use chrono::{DateTime, SecondsFormat, Utc};
+use iced::Task;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::fmt::Formatter;
+use std::io;
+use std::path::PathBuf;
+
+/// The directory, in the history of a device, the tracks of its nodes are stored in
+const TRACKS_DIR: &str = "tracks";
+const TRACKS_EXTENSION: &str = "json";
+/// How many positions to keep in the track of each node
+const TRACK_LENGTH: usize = 1000;
+
+/// The file formats tracks and positions can be exported to, for mapping tools
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrackFormat {
+    Gpx,
+    Kml,
+}
+
+impl TrackFormat {
+    pub const ALL: [TrackFormat; 2] = [TrackFormat::Gpx, TrackFormat::Kml];
+
+    /// The file extension used for files of this format
+    pub fn extension(&self) -> &'static str {
+        match self {
+            TrackFormat::Gpx => "gpx",
+            TrackFormat::Kml => "kml",
+        }
+    }
+}
+
+impl fmt::Display for TrackFormat {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            TrackFormat::Gpx => f.write_str("GPX"),
+            TrackFormat::Kml => f.write_str("KML"),
+        }
+    }
+}
+
+/// A position of a node, and when it was received
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrackPoint {
+    pub timestamp: TimeStamp,
+    pub position: MCPosition,
+}
+
+/// The positions reported by a node, oldest first
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Track {
+    points: VecDeque<TrackPoint>,
+}
+
+impl Track {
+    /// Add a position in time order, dropping the oldest if there are already [TRACK_LENGTH].
+    /// Return false if there already is a position at that time.
+    pub fn add(&mut self, timestamp: TimeStamp, position: &MCPosition) -> bool {
+        let index = self
+            .points
+            .partition_point(|point| point.timestamp <= timestamp);
+        if index > 0 && self.points[index - 1].timestamp == timestamp {
+            return false;
+        }
+
+        self.points.insert(
+            index,
+            TrackPoint {
+                timestamp,
+                position: position.clone(),
+            },
+        );
+        if self.points.len() > TRACK_LENGTH {
+            self.points.pop_front();
+        }
+        true
+    }
+
+    pub fn points(&self) -> impl Iterator<Item = &TrackPoint> {
+        self.points.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.points.len()
+    }
+
+    /// The most recent position
+    pub fn last(&self) -> Option<&TrackPoint> {
+        self.points.back()
+    }
+}
+
+/// The tracks of all the nodes on a device, and which have changed since they were saved
+#[derive(Debug, Default)]
+pub struct Tracks {
+    tracks: HashMap<NodeId, Track>,
+    unsaved: HashSet<NodeId>,
+}
+
+impl Tracks {
+    /// Add a position of `node_id` to its track. Return true if the track changed, and so needs
+    /// saving.
+    pub fn add(&mut self, node_id: NodeId, timestamp: TimeStamp, position: &MCPosition) -> bool {
+        let added = self
+            .tracks
+            .entry(node_id)
+            .or_default()
+            .add(timestamp, position);
+        if added {
+            self.unsaved.insert(node_id);
+        }
+        added
+    }
+
+    /// Take the tracks that have changed since they were last taken, to be saved
+    pub fn take_unsaved(&mut self) -> Vec<(NodeId, Track)> {
+        self.unsaved
+            .drain()
+            .filter_map(|node_id| {
+                self.tracks
+                    .get(&node_id)
+                    .map(|track| (node_id, track.clone()))
+            })
+            .collect()
+    }
+
+    pub fn get(&self, node_id: &NodeId) -> Option<&Track> {
+        self.tracks.get(node_id)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&NodeId, &Track)> {
+        self.tracks.iter()
+    }
+
+    /// Forget all tracks, when disconnecting from the device
+    pub fn clear(&mut self) {
+        self.tracks.clear();
+        self.unsaved.clear();
+    }
+
+    /// Add the positions of tracks loaded from disk to the ones received since connecting
+    pub fn merge(&mut self, loaded: HashMap<NodeId, Track>) {
+        for (node_id, loaded_track) in loaded {
+            let track = self.tracks.entry(node_id).or_default();
+            for point in loaded_track.points {
+                track.add(point.timestamp, &point.position);
+            }
+        }
+    }
+}
+
+/// The time as GPX and KML want it: in UTC, in RFC 3339 format
+fn utc_time(timestamp: TimeStamp) -> String {
+    DateTime::<Utc>::from_timestamp_millis(timestamp.into())
+        .unwrap_or_default()
+        .to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+/// Escape the characters that are special in XML text and attributes
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+/// Render the `track` of a node called `name` in the requested [TrackFormat]
+pub fn render_track(name: &str, track: &Track, format: TrackFormat) -> String {
+    let name = xml_escape(name);
+    match format {
+        TrackFormat::Gpx => {
+            let points: String = track
+                .points()
+                .map(|point| {
+                    format!(
+                        "      <trkpt lat=\"{}\" lon=\"{}\">{}<time>{}</time></trkpt>\n",
+                        point.position.latitude,
+                        point.position.longitude,
+                        gpx_elevation(&point.position),
+                        utc_time(point.timestamp)
+                    )
+                })
+                .collect();
+            gpx(&format!(
+                "  <trk>\n    <name>{name}</name>\n    <trkseg>\n{points}    </trkseg>\n  </trk>\n"
+            ))
+        }
+        TrackFormat::Kml => {
+            let whens: String = track
+                .points()
+                .map(|point| format!("        <when>{}</when>\n", utc_time(point.timestamp)))
+                .collect();
+            let coords: String = track
+                .points()
+                .map(|point| {
+                    format!(
+                        "        <gx:coord>{}</gx:coord>\n",
+                        kml_coordinates(&point.position, ' ')
+                    )
+                })
+                .collect();
+            kml(
+                &name,
+                &format!(
+                    "    <Placemark>\n      <name>{name}</name>\n      <gx:Track>\n{whens}{coords}      </gx:Track>\n    </Placemark>\n"
+                ),
+            )
+        }
+    }
+}
+
+/// Render the last position of each of the named `nodes` in the requested [TrackFormat]
+pub fn render_positions(nodes: &[(String, &TrackPoint)], format: TrackFormat) -> String {
+    match format {
+        TrackFormat::Gpx => {
+            let waypoints: String = nodes
+                .iter()
+                .map(|(name, point)| {
+                    format!(
+                        "  <wpt lat=\"{}\" lon=\"{}\">{}<time>{}</time><name>{}</name></wpt>\n",
+                        point.position.latitude,
+                        point.position.longitude,
+                        gpx_elevation(&point.position),
+                        utc_time(point.timestamp),
+                        xml_escape(name)
+                    )
+                })
+                .collect();
+            gpx(&waypoints)
+        }
+        TrackFormat::Kml => {
+            let placemarks: String = nodes
+                .iter()
+                .map(|(name, point)| {
+                    format!(
+                        "    <Placemark>\n      <name>{}</name>\n      <TimeStamp><when>{}</when></TimeStamp>\n      <Point><coordinates>{}</coordinates></Point>\n    </Placemark>\n",
+                        xml_escape(name),
+                        utc_time(point.timestamp),
+                        kml_coordinates(&point.position, ',')
+                    )
+                })
+                .collect();
+            kml("Node positions", &placemarks)
+        }
+    }
+}
+
+/// The elevation of a position as a GPX element, if its altitude is known
+fn gpx_elevation(position: &MCPosition) -> String {
+    position
+        .altitude
+        .map(|altitude| format!("<ele>{altitude}</ele>"))
+        .unwrap_or_default()
+}
+
+/// The longitude, latitude and any altitude of a position, as KML lists them
+fn kml_coordinates(position: &MCPosition, separator: char) -> String {
+    match position.altitude {
+        Some(altitude) => format!(
+            "{}{separator}{}{separator}{altitude}",
+            position.longitude, position.latitude
+        ),
+        None => format!("{}{separator}{}", position.longitude, position.latitude),
+    }
+}
+
+fn gpx(contents: &str) -> String {
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<gpx version=\"1.1\" creator=\"MeshChat\" xmlns=\"http://www.topografix.com/GPX/1/1\">\n{contents}</gpx>\n"
+    )
+}
+
+fn kml(name: &str, contents: &str) -> String {
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<kml xmlns=\"http://www.opengis.net/kml/2.2\" xmlns:gx=\"http://www.google.com/kml/ext/2.2\">\n  <Document>\n    <name>{name}</name>\n{contents}  </Document>\n</kml>\n"
+    )
+}
+
+/// Return the directory the tracks of nodes on `device` are stored in, with its history
+fn tracks_dir(device: &DeviceIdentifier) -> Option<PathBuf> {
+    history_dir(device).map(|history_dir| history_dir.join(TRACKS_DIR))
+}
+
+/// The name of the file the track of a node is stored in
+fn track_file_name(node_id: NodeId) -> String {
+    format!("node_{node_id}.{TRACKS_EXTENSION}")
+}
+
+/// The on-disk format of the track of one node
+#[derive(Debug, Serialize, Deserialize)]
+struct NodeTrack {
+    node_id: NodeId,
+    track: Track,
+}
+
+/// The saved tracks, and the files that could not be read
+type LoadedTracks = (HashMap<NodeId, Track>, Vec<(PathBuf, String)>);
+
+// Private methods for async reading and writing of track files
+async fn load(tracks_dir: PathBuf) -> io::Result<LoadedTracks> {
+    let contents = read_json_dir::<NodeTrack>(&tracks_dir, TRACKS_EXTENSION).await?;
+    let tracks = contents
+        .values
+        .into_iter()
+        .map(|node_track| (node_track.node_id, node_track.track))
+        .collect();
+    Ok((tracks, contents.bad_files))
+}
+
+async fn save(tracks_dir: PathBuf, node_track: NodeTrack) -> io::Result<()> {
+    let track_path = tracks_dir.join(track_file_name(node_track.node_id));
+    write_json(&track_path, &node_track).await
+}
+
+/// Use `load_tracks` to load the tracks of all the nodes previously saved for `device` from disk.
+/// A track file that can't be read is skipped and reported, and the rest are loaded.
+pub fn load_tracks(device: &DeviceIdentifier) -> Task<Message> {
+    if let Some(tracks_dir) = tracks_dir(device) {
+        let device = device.clone();
+        Task::future(load(tracks_dir.clone())).then(move |result| match result {
+            Ok((tracks, bad_files)) => report_bad_files(
+                Message::DeviceViewEvent(TracksLoaded(device.clone(), tracks)),
+                "node track",
+                bad_files,
+            ),
+            // Positions heard from now on are still tracked and saved
+            Err(e) => Task::batch([
+                Task::done(Message::AppError(
+                    format!(
+                        "Error loading node tracks: '{}'",
+                        tracks_dir.to_string_lossy()
+                    ),
+                    e.to_string(),
+                    TimeStamp::now(), // jonesy:allow(expect)
+                )),
+                Task::done(Message::DeviceViewEvent(TracksLoaded(
+                    device.clone(),
+                    HashMap::new(),
+                ))),
+            ]),
+        })
+    } else {
+        Task::none()
+    }
+}
+
+/// Use `save_track` to save the track of a node on `device` to disk
+pub fn save_track(device: &DeviceIdentifier, node_id: NodeId, track: Track) -> Task<Message> {
+    if let Some(tracks_dir) = tracks_dir(device) {
+        Task::perform(save(tracks_dir.clone(), NodeTrack { node_id, track }), {
+            move |result| match result {
+                Ok(_) => Message::None,
+                Err(e) => Message::AppError(
+                    format!(
+                        "Error saving node track: '{}'",
+                        tracks_dir.to_string_lossy()
+                    ),
+                    e.to_string(),
+                    TimeStamp::now(), // jonesy:allow(expect)
+                ),
+            }
+        })
+    } else {
+        Task::none()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::fs::File;
+    use tokio::io::AsyncWriteExt;
+
+    fn position(latitude: f64, longitude: f64, altitude: Option<i32>) -> MCPosition {
+        MCPosition {
+            latitude,
+            longitude,
+            altitude,
+            ..Default::default()
+        }
+    }
+
+    fn track_of(points: &[(u64, f64)]) -> Track {
+        let mut track = Track::default();
+        for (timestamp, latitude) in points {
+            track.add(TimeStamp::from(*timestamp), &position(*latitude, 0.0, None));
+        }
+        track
+    }
+
+    fn latitudes(track: &Track) -> Vec<f64> {
+        track
+            .points()
+            .map(|point| point.position.latitude)
+            .collect()
+    }
+
+    #[test]
+    fn test_track_in_time_order() {
+        let track = track_of(&[(3000, 3.0), (1000, 1.0), (2000, 2.0)]);
+        assert_eq!(latitudes(&track), vec![1.0, 2.0, 3.0]);
+        assert_eq!(track.last().map(|point| point.position.latitude), Some(3.0));
+    }
+
+    #[test]
+    fn test_track_duplicate_time_ignored() {
+        let mut track = track_of(&[(1000, 1.0)]);
+        assert!(!track.add(TimeStamp::from(1000u64), &position(5.0, 0.0, None)));
+        assert_eq!(latitudes(&track), vec![1.0]);
+    }
+
+    #[test]
+    fn test_track_bounded() {
+        let mut track = Track::default();
+        for timestamp in 0..(TRACK_LENGTH as u64 + 10) {
+            track.add(TimeStamp::from(timestamp), &position(0.0, 0.0, None));
+        }
+        assert_eq!(track.len(), TRACK_LENGTH);
+        assert_eq!(
+            track.points().next().map(|point| point.timestamp),
+            Some(TimeStamp::from(10u64))
+        );
+    }
+
+    #[test]
+    fn test_tracks_add_marks_changed_track_unsaved() {
+        let mut tracks = Tracks::default();
+        let node_id = NodeId::from(1u64);
+        let here = position(1.0, 2.0, None);
+        assert!(tracks.add(node_id, TimeStamp::from(1000u64), &here));
+        assert!(!tracks.add(node_id, TimeStamp::from(1000u64), &here));
+        assert_eq!(tracks.iter().count(), 1);
+
+        let unsaved = tracks.take_unsaved();
+        assert_eq!(unsaved.len(), 1);
+        assert_eq!(
+            unsaved.first().map(|(id, track)| (*id, track.len())),
+            Some((node_id, 1))
+        );
+        assert!(tracks.take_unsaved().is_empty());
+
+        tracks.add(node_id, TimeStamp::from(2000u64), &here);
+        tracks.clear();
+        assert!(tracks.get(&node_id).is_none());
+        assert!(tracks.take_unsaved().is_empty());
+    }
+
+    #[test]
+    fn test_tracks_merge() {
+        let mut tracks = Tracks::default();
+        let node_id = NodeId::from(1u64);
+        tracks.add(node_id, TimeStamp::from(2000u64), &position(2.0, 0.0, None));
+        tracks.merge(HashMap::from([
+            (node_id, track_of(&[(1000, 1.0), (2000, 9.0)])),
+            (NodeId::from(2u64), track_of(&[(1000, 1.0)])),
+        ]));
+
+        assert_eq!(tracks.get(&node_id).map(latitudes), Some(vec![1.0, 2.0]));
+        assert_eq!(tracks.iter().count(), 2);
+    }
+
+    #[test]
+    fn test_utc_time() {
+        assert_eq!(utc_time(TimeStamp::from(0u64)), "1970-01-01T00:00:00Z");
+        assert_eq!(
+            utc_time(TimeStamp::from(1_700_000_000_500u64)),
+            "2023-11-14T22:13:20Z"
+        );
+    }
+
+    #[test]
+    fn test_xml_escape() {
+        assert_eq!(
+            xml_escape("<Tom & \"Jerry's\">"),
+            "&lt;Tom &amp; &quot;Jerry&apos;s&quot;&gt;"
+        );
+    }
+
+    #[test]
+    fn test_render_track_gpx() {
+        let mut track = Track::default();
+        track.add(TimeStamp::from(0u64), &position(46.5, 6.6, Some(372)));
+        track.add(TimeStamp::from(60_000u64), &position(46.6, 6.7, None));
+        let gpx = render_track("Team <1>", &track, TrackFormat::Gpx);
+
+        assert!(gpx.starts_with("<?xml"));
+        assert!(gpx.contains("<name>Team &lt;1&gt;</name>"));
+        assert!(gpx.contains(
+            "<trkpt lat=\"46.5\" lon=\"6.6\"><ele>372</ele><time>1970-01-01T00:00:00Z</time></trkpt>"
+        ));
+        assert!(
+            gpx.contains(
+                "<trkpt lat=\"46.6\" lon=\"6.7\"><time>1970-01-01T00:01:00Z</time></trkpt>"
+            )
+        );
+        assert!(gpx.trim_end().ends_with("</gpx>"));
+    }
+
+    #[test]
+    fn test_render_track_kml() {
+        let mut track = Track::default();
+        track.add(TimeStamp::from(0u64), &position(46.5, 6.6, Some(372)));
+        track.add(TimeStamp::from(60_000u64), &position(46.6, 6.7, None));
+        let kml = render_track("Team", &track, TrackFormat::Kml);
+
+        assert!(kml.contains("<gx:Track>"));
+        assert!(kml.contains("<when>1970-01-01T00:00:00Z</when>"));
+        // KML lists longitude first
+        assert!(kml.contains("<gx:coord>6.6 46.5 372</gx:coord>"));
+        assert!(kml.contains("<gx:coord>6.7 46.6</gx:coord>"));
+        assert!(kml.trim_end().ends_with("</kml>"));
+    }
+
+    #[test]
+    fn test_render_positions() {
+        let point = TrackPoint {
+            timestamp: TimeStamp::from(0u64),
+            position: position(46.5, 6.6, None),
+        };
+        let nodes = vec![("Alpha & Co".to_string(), &point)];
+
+        let gpx = render_positions(&nodes, TrackFormat::Gpx);
+        assert!(gpx.contains(
+            "<wpt lat=\"46.5\" lon=\"6.6\"><time>1970-01-01T00:00:00Z</time><name>Alpha &amp; Co</name></wpt>"
+        ));
+
+        let kml = render_positions(&nodes, TrackFormat::Kml);
+        assert!(kml.contains("<name>Alpha &amp; Co</name>"));
+        assert!(kml.contains("<coordinates>6.6,46.5</coordinates>"));
+    }
+
+    #[test]
+    fn test_track_format() {
+        assert_eq!(TrackFormat::Gpx.to_string(), "GPX");
+        assert_eq!(TrackFormat::Kml.extension(), "kml");
+    }
+
+    #[tokio::test]
+    async fn load_missing_dir_is_empty() {
+        let tempdir = tempfile::Builder::new()
+            .prefix("meshchat")
+            .tempdir()
+            .expect("Could not create a temp dir");
+        let (tracks, bad_files) = load(tempdir.path().join(TRACKS_DIR))
+            .await
+            .expect("Could not load tracks");
+        assert!(tracks.is_empty());
+        assert!(bad_files.is_empty());
+    }
+
+    #[tokio::test]
+    async fn save_and_load_roundtrip() {
+        let tempdir = tempfile::Builder::new()
+            .prefix("meshchat")
+            .tempdir()
+            .expect("Could not create a temp dir");
+        let tracks_dir = tempdir.path().join(TRACKS_DIR);
+        let node_id = NodeId::from(42u64);
+
+        save(
+            tracks_dir.clone(),
+            NodeTrack {
+                node_id,
+                track: track_of(&[(1000, 1.0), (2000, 2.0)]),
+            },
+        )
+        .await
+        .expect("Could not save track");
+        assert!(tracks_dir.join(track_file_name(node_id)).exists());
+
+        let (loaded, _) = load(tracks_dir).await.expect("Could not load tracks");
+        assert_eq!(loaded.get(&node_id).map(latitudes), Some(vec![1.0, 2.0]));
+    }
+
+    #[tokio::test]
+    async fn load_skips_corrupt_file() {
+        let tempdir = tempfile::Builder::new()
+            .prefix("meshchat")
+            .tempdir()
+            .expect("Could not create a temp dir");
+        let node_id = NodeId::from(42u64);
+        save(
+            tempdir.path().to_path_buf(),
+            NodeTrack {
+                node_id,
+                track: track_of(&[(1000, 1.0)]),
+            },
+        )
+        .await
+        .expect("Could not save track");
+        let mut corrupt = File::create(tempdir.path().join(track_file_name(NodeId::from(1u64))))
+            .await
+            .expect("Could not create file");
+        corrupt
+            .write_all(b"{ not json")
+            .await
+            .expect("Could not write file");
+
+        let (tracks, bad_files) = load(tempdir.path().to_path_buf())
+            .await
+            .expect("Could not load tracks");
+        assert_eq!(tracks.get(&node_id).map(Track::len), Some(1));
+        assert_eq!(bad_files.len(), 1);
+    }
+}
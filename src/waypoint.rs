@@ -0,0 +1,371 @@
+use crate::Message;
+use crate::Message::{DeviceViewEvent, ShowLocation};
+use crate::conversation_id::ConversationId;
+use crate::device::DeviceMessage::{SendWaypointMessage, WaypointMsg};
+use crate::meshchat::{MCPosition, MCWaypoint};
+use crate::message::MCMessage;
+use crate::styles::{
+    COLOR_RED, TIME_TEXT_COLOR, TIME_TEXT_SIZE, button_chip_style, text_input_style,
+};
+use crate::timestamp::TimeStamp;
+use crate::waypoint::WaypointMessage::{
+    DescriptionInput, ExpirySelected, IconInput, LatitudeInput, LongitudeInput, NameInput,
+};
+use crate::widgets::dialog::dialog;
+use iced::widget::{Column, Row, Space, button, pick_list, text, text_input};
+use iced::{Center, Element, Fill};
+use std::fmt;
+use std::fmt::Formatter;
+use uuid::Uuid;
+
+/// The longest name a waypoint can have, in characters
+const MAX_NAME_LENGTH: usize = 30;
+/// The longest description a waypoint can have, in characters
+const MAX_DESCRIPTION_LENGTH: usize = 100;
+
+/// How long a waypoint sent is valid for
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum WaypointExpiry {
+    Hour,
+    #[default]
+    Day,
+    Week,
+    Never,
+}
+
+impl WaypointExpiry {
+    pub const ALL: [WaypointExpiry; 4] = [
+        WaypointExpiry::Hour,
+        WaypointExpiry::Day,
+        WaypointExpiry::Week,
+        WaypointExpiry::Never,
+    ];
+
+    /// When a waypoint sent at `now` expires, None if it never does
+    fn expire(&self, now: TimeStamp) -> Option<TimeStamp> {
+        let hours: u64 = match self {
+            WaypointExpiry::Hour => 1,
+            WaypointExpiry::Day => 24,
+            WaypointExpiry::Week => 7 * 24,
+            WaypointExpiry::Never => return None,
+        };
+        Some(TimeStamp::from(
+            u128::from(now).saturating_add(u128::from(hours * 60 * 60 * 1000)),
+        ))
+    }
+}
+
+impl fmt::Display for WaypointExpiry {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            WaypointExpiry::Hour => write!(f, "Expires in an hour"),
+            WaypointExpiry::Day => write!(f, "Expires in a day"),
+            WaypointExpiry::Week => write!(f, "Expires in a week"),
+            WaypointExpiry::Never => write!(f, "Never expires"),
+        }
+    }
+}
+
+/// Changes to the waypoint being composed
+#[derive(Debug, Clone)]
+pub enum WaypointMessage {
+    NameInput(String),
+    DescriptionInput(String),
+    IconInput(String),
+    LatitudeInput(String),
+    LongitudeInput(String),
+    ExpirySelected(WaypointExpiry),
+}
+
+/// A new id for a waypoint, random so it will not clash with other nodes' waypoints
+pub fn new_waypoint_id() -> u32 {
+    let [a, b, c, d, ..] = Uuid::new_v4().into_bytes();
+    // zero is not a valid waypoint id
+    u32::from_le_bytes([a, b, c, d]).max(1)
+}
+
+/// A waypoint being composed in a dialog, to be sent to a conversation
+#[derive(Debug, Clone)]
+pub struct WaypointEditor {
+    conversation_id: ConversationId,
+    name: String,
+    description: String,
+    icon: String,
+    latitude: String,
+    longitude: String,
+    expiry: WaypointExpiry,
+}
+
+impl WaypointEditor {
+    /// Compose a waypoint to send to `conversation_id`, at `position` if known
+    pub fn new(conversation_id: ConversationId, position: Option<&MCPosition>) -> Self {
+        let (latitude, longitude) = position
+            .map(|position| {
+                (
+                    format!("{:.5}", position.latitude),
+                    format!("{:.5}", position.longitude),
+                )
+            })
+            .unwrap_or_default();
+        WaypointEditor {
+            conversation_id,
+            name: String::new(),
+            description: String::new(),
+            icon: String::new(),
+            latitude,
+            longitude,
+            expiry: WaypointExpiry::default(),
+        }
+    }
+
+    /// The conversation the waypoint will be sent to
+    pub fn conversation_id(&self) -> ConversationId {
+        self.conversation_id
+    }
+
+    /// Update the waypoint being composed, limiting the name and description to what can be sent
+    pub fn update(&mut self, message: WaypointMessage) {
+        match message {
+            NameInput(name) => self.name = name.chars().take(MAX_NAME_LENGTH).collect(),
+            DescriptionInput(description) => {
+                self.description = description.chars().take(MAX_DESCRIPTION_LENGTH).collect()
+            }
+            IconInput(icon) => {
+                self.icon = icon.chars().last().map(String::from).unwrap_or_default()
+            }
+            LatitudeInput(latitude) => self.latitude = latitude,
+            LongitudeInput(longitude) => self.longitude = longitude,
+            ExpirySelected(expiry) => self.expiry = expiry,
+        }
+    }
+
+    /// The waypoint composed, with `id`, sent at `now`, or why it cannot be sent
+    pub fn waypoint(&self, id: u32, now: TimeStamp) -> Result<MCWaypoint, String> {
+        if self.name.trim().is_empty() {
+            return Err("Enter a name".into());
+        }
+        let latitude = self
+            .latitude
+            .trim()
+            .parse::<f64>()
+            .ok()
+            .filter(|latitude| (-90.0..=90.0).contains(latitude))
+            .ok_or("Latitude must be between -90 and 90")?;
+        let longitude = self
+            .longitude
+            .trim()
+            .parse::<f64>()
+            .ok()
+            .filter(|longitude| (-180.0..=180.0).contains(longitude))
+            .ok_or("Longitude must be between -180 and 180")?;
+
+        Ok(MCWaypoint {
+            id,
+            name: self.name.trim().to_string(),
+            description: self.description.trim().to_string(),
+            icon: self.icon.chars().next(),
+            latitude,
+            longitude,
+            expire: self.expiry.expire(now),
+        })
+    }
+
+    /// A dialog to compose the waypoint, to send to the conversation called `destination`
+    pub fn view<'a>(&self, destination: String) -> Element<'a, Message> {
+        let input = |placeholder: &str, value: &str, on_input: fn(String) -> WaypointMessage| {
+            text_input(placeholder, value)
+                .on_input(move |s| DeviceViewEvent(WaypointMsg(on_input(s))))
+                .style(text_input_style)
+        };
+
+        let mut send_button = button(text("Send")).style(button_chip_style);
+        let problem = match self.waypoint(0, TimeStamp::now()) {
+            Ok(_) => {
+                send_button = send_button.on_press(DeviceViewEvent(SendWaypointMessage));
+                None
+            }
+            Err(problem) => Some(problem),
+        };
+
+        let fields = Column::new()
+            .padding(8)
+            .spacing(8)
+            .push(
+                Row::new()
+                    .spacing(8)
+                    .push(input("🚩", &self.icon, IconInput).width(50))
+                    .push(input("Name", &self.name, NameInput)),
+            )
+            .push(input("Description", &self.description, DescriptionInput))
+            .push(
+                Row::new()
+                    .spacing(8)
+                    .push(input("Latitude", &self.latitude, LatitudeInput))
+                    .push(input("Longitude", &self.longitude, LongitudeInput)),
+            )
+            .push(pick_list(
+                WaypointExpiry::ALL,
+                Some(self.expiry),
+                |expiry| DeviceViewEvent(WaypointMsg(ExpirySelected(expiry))),
+            ))
+            .push(
+                Row::new()
+                    .align_y(Center)
+                    .push(text(problem.unwrap_or_default()).color(COLOR_RED))
+                    .push(Space::new().width(Fill))
+                    .push(send_button),
+            );
+
+        dialog(format!("Waypoint to {destination}"), fields)
+    }
+}
+
+/// A dialog listing `waypoints`, with a button to show where each one is
+pub fn waypoints_view<'a>(waypoints: &[&MCWaypoint]) -> Element<'a, Message> {
+    let mut list = Column::new().padding(8).spacing(8);
+    if waypoints.is_empty() {
+        list = list.push(text("No waypoints have been shared").color(TIME_TEXT_COLOR));
+    }
+
+    for waypoint in waypoints {
+        let expire = waypoint
+            .expire
+            .map(|expire| {
+                MCMessage::datetime_local(expire)
+                    .format("Expires %d %b %H:%M")
+                    .to_string()
+            })
+            .unwrap_or("Never expires".into());
+        let details = Column::new()
+            .width(Fill)
+            .push(text(format!(
+                "{} {}",
+                waypoint.icon.unwrap_or('🚩'),
+                waypoint.name
+            )))
+            .push(text(waypoint.description.clone()).size(TIME_TEXT_SIZE))
+            .push(text(expire).size(TIME_TEXT_SIZE).color(TIME_TEXT_COLOR));
+        list = list.push(
+            Row::new().align_y(Center).push(details).push(
+                button(text("📌"))
+                    .style(button_chip_style)
+                    .on_press(ShowLocation(waypoint.position())),
+            ),
+        );
+    }
+
+    dialog("Waypoints".into(), list)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn editor() -> WaypointEditor {
+        let mut editor = WaypointEditor::new(ConversationId::Channel(0.into()), None);
+        editor.update(NameInput("Camp".into()));
+        editor.update(LatitudeInput("50.5".into()));
+        editor.update(LongitudeInput("-1.25".into()));
+        editor
+    }
+
+    #[test]
+    fn test_new_at_position() {
+        let position = MCPosition {
+            latitude: 50.123456,
+            longitude: -1.5,
+            ..Default::default()
+        };
+        let editor = WaypointEditor::new(ConversationId::Channel(0.into()), Some(&position));
+        assert_eq!(editor.latitude, "50.12346");
+        assert_eq!(editor.longitude, "-1.50000");
+    }
+
+    #[test]
+    fn test_waypoint() {
+        let mut editor = editor();
+        editor.update(IconInput("⛺".into()));
+        editor.update(DescriptionInput("By the river ".into()));
+        editor.update(ExpirySelected(WaypointExpiry::Hour));
+
+        let waypoint = editor
+            .waypoint(7, TimeStamp::from(1000u64))
+            .expect("Waypoint expected");
+        assert_eq!(
+            waypoint,
+            MCWaypoint {
+                id: 7,
+                name: "Camp".into(),
+                description: "By the river".into(),
+                icon: Some('⛺'),
+                latitude: 50.5,
+                longitude: -1.25,
+                expire: Some(TimeStamp::from(3_601_000u64)),
+            }
+        );
+    }
+
+    #[test]
+    fn test_never_expires() {
+        let mut editor = editor();
+        editor.update(ExpirySelected(WaypointExpiry::Never));
+        let waypoint = editor
+            .waypoint(7, TimeStamp::now())
+            .expect("Waypoint expected");
+        assert!(waypoint.expire.is_none());
+    }
+
+    #[test]
+    fn test_name_needed() {
+        let mut editor = editor();
+        editor.update(NameInput("  ".into()));
+        assert!(editor.waypoint(7, TimeStamp::now()).is_err());
+    }
+
+    #[test]
+    fn test_coordinates_checked() {
+        let mut editor = editor();
+        editor.update(LatitudeInput("91".into()));
+        assert!(editor.waypoint(7, TimeStamp::now()).is_err());
+        editor.update(LatitudeInput("north".into()));
+        assert!(editor.waypoint(7, TimeStamp::now()).is_err());
+        editor.update(LatitudeInput("-90".into()));
+        editor.update(LongitudeInput("180.5".into()));
+        assert!(editor.waypoint(7, TimeStamp::now()).is_err());
+    }
+
+    #[test]
+    fn test_lengths_limited() {
+        let mut editor = editor();
+        editor.update(NameInput("n".repeat(40)));
+        editor.update(DescriptionInput("d".repeat(120)));
+        assert_eq!(editor.name.chars().count(), MAX_NAME_LENGTH);
+        assert_eq!(editor.description.chars().count(), MAX_DESCRIPTION_LENGTH);
+    }
+
+    #[test]
+    fn test_icon_is_one_character() {
+        let mut editor = editor();
+        editor.update(IconInput("⛺".into()));
+        editor.update(IconInput("⛺🏠".into()));
+        assert_eq!(editor.icon, "🏠");
+        editor.update(IconInput(String::new()));
+        assert!(editor.icon.is_empty());
+    }
+
+    #[test]
+    fn test_new_waypoint_id_not_zero() {
+        assert_ne!(new_waypoint_id(), 0);
+    }
+
+    #[test]
+    fn test_views() {
+        let editor = editor();
+        let _editor = editor.view("Channel 0".into());
+        let waypoint = editor
+            .waypoint(7, TimeStamp::now())
+            .expect("Waypoint expected");
+        let _list = waypoints_view(&[&waypoint]);
+        let _empty = waypoints_view(&[]);
+    }
+}
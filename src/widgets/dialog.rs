@@ -0,0 +1,30 @@
+//! A dialog with a title, shown in place of the device view's content
+use crate::Message;
+use crate::styles::{picker_header_style, tooltip_style};
+use iced::font::Weight;
+use iced::widget::{Column, container, text};
+use iced::{Center, Element, Fill, Font};
+
+/// A dialog with a `title` above the `content`
+pub fn dialog<'a>(title: String, content: Column<'a, Message>) -> Element<'a, Message> {
+    let inner = Column::new()
+        .spacing(8)
+        .width(420)
+        .push(
+            container(
+                text(title)
+                    .size(18)
+                    .width(Fill)
+                    .font(Font {
+                        weight: Weight::Bold,
+                        ..Default::default()
+                    })
+                    .align_x(Center),
+            )
+            .padding(12)
+            .style(picker_header_style)
+            .padding(4),
+        )
+        .push(content);
+    container(inner).style(tooltip_style).into()
+}
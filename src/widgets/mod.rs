@@ -1,4 +1,5 @@
 pub mod battery;
+pub mod dialog;
 pub mod easing;
 pub mod emoji_picker;
 pub mod linear;